@@ -0,0 +1,177 @@
+use criterion::{Criterion, criterion_group, criterion_main};
+use rpov::{
+    any_shapes::AnyShape,
+    colors::Color,
+    floats::Float,
+    lighting::point_light,
+    shapes::Intersectable,
+    spheres::Sphere,
+    transformations::{scaling, translation},
+    tuples::point,
+    world::WorldBuilder,
+};
+
+// A light just in front of the point being shaded, with a wall of spheres
+// behind the light that a shadow ray never needs to consider.
+fn crowded_world() -> WorldBuilder {
+    let mut w =
+        WorldBuilder::with_light(point_light(point(0.0, 0.0, 2.0), Color::new(1.0, 1.0, 1.0)));
+    for i in 0..20 {
+        for j in 0..20 {
+            let x = i as Float;
+            let y = j as Float;
+            w.objects.push(
+                Sphere::with_transform(translation(x * 0.1 - 1.0, y * 0.1 - 1.0, 5.0 + x)).unwrap(),
+            );
+        }
+    }
+    w
+}
+
+fn shadow_rays(c: &mut Criterion) {
+    let w = crowded_world();
+    let p = point(0.0, 0.0, 0.0);
+    let mut g = c.benchmark_group("shadow_ray");
+    g.bench_function("unbounded", |b| {
+        b.iter(|| {
+            let light = w.light.as_ref().unwrap();
+            let v = light.position - p;
+            let r = rpov::rays::Ray::new(p, v.normalize());
+            std::hint::black_box(w.intersect(r))
+        })
+    });
+    g.bench_function("bounded", |b| {
+        b.iter(|| std::hint::black_box(w.is_shadowed(p)))
+    });
+}
+// The same primary rays traced through the mutable world and the built scene,
+// which caches inverse transforms.
+fn frozen_scene(c: &mut Criterion) {
+    let w = crowded_world();
+    let scene = crowded_world().build();
+    let rays: Vec<_> = (0..20)
+        .map(|i| {
+            let target = point(i as Float * 0.1 - 1.0, 0.0, 6.0);
+            rpov::rays::Ray::new(
+                point(0.0, 0.0, -5.0),
+                (target - point(0.0, 0.0, -5.0)).normalize(),
+            )
+        })
+        .collect();
+    let mut g = c.benchmark_group("primary_rays");
+    g.bench_function("world", |b| {
+        b.iter(|| rays.iter().map(|r| w.intersect(*r).len()).sum::<usize>())
+    });
+    g.bench_function("scene", |b| {
+        b.iter(|| {
+            rays.iter()
+                .map(|r| scene.intersect(*r).len())
+                .sum::<usize>()
+        })
+    });
+}
+
+// Spheres stored as boxed trait objects against the same spheres in AnyShape.
+fn shape_stores(c: &mut Criterion) {
+    let mut boxed = WorldBuilder::new();
+    let mut inline: WorldBuilder<Vec<AnyShape>> = WorldBuilder::default();
+    for s in crowded_world().objects {
+        inline.shapes.push(s.clone().into());
+        boxed.shapes.push(Box::new(s));
+    }
+    let r = rpov::rays::Ray::new(point(0.0, 0.0, -5.0), rpov::tuples::vector(0.0, 0.0, 1.0));
+    let mut g = c.benchmark_group("shape_store");
+    g.bench_function("boxed", |b| b.iter(|| boxed.intersect(r).len()));
+    g.bench_function("any_shape", |b| b.iter(|| inline.intersect(r).len()));
+}
+
+// 500 small spheres scattered across the view. Most primary rays miss most
+// spheres' bounding spheres, so the world skips their full intersect.
+fn many_small_spheres(c: &mut Criterion) {
+    let mut w = WorldBuilder::new();
+    for i in 0..500 {
+        let x = (i % 25) as Float;
+        let y = (i / 25) as Float;
+        w.objects.push(
+            Sphere::with_transform(
+                translation(x * 0.4 - 5.0, y * 0.4 - 4.0, 5.0) * scaling(0.1, 0.1, 0.1),
+            )
+            .unwrap(),
+        );
+    }
+    let rays: Vec<_> = (0..100)
+        .map(|i| {
+            let target = point(i as Float * 0.1 - 5.0, (i as Float * 0.37).sin() * 4.0, 5.0);
+            rpov::rays::Ray::new(
+                point(0.0, 0.0, -5.0),
+                (target - point(0.0, 0.0, -5.0)).normalize(),
+            )
+        })
+        .collect();
+    let mut g = c.benchmark_group("many_small_spheres");
+    g.bench_function("every_sphere", |b| {
+        b.iter(|| {
+            rays.iter()
+                .map(|r| {
+                    w.objects
+                        .iter()
+                        .map(|s| s.intersect(*r).len())
+                        .sum::<usize>()
+                })
+                .sum::<usize>()
+        })
+    });
+    g.bench_function("bounding_sphere_rejection", |b| {
+        b.iter(|| rays.iter().map(|r| w.intersect(*r).len()).sum::<usize>())
+    });
+}
+
+// The sphere solver on its own. Compare runs with and without the
+// precise-intersections feature to see what solving in f64 costs.
+fn sphere_solver(c: &mut Criterion) {
+    let s = Sphere::with_transform(translation(0.0, 0.0, 5.0)).unwrap();
+    let rays: Vec<_> = (0..1000)
+        .map(|i| {
+            let y = i as Float / 500.0 - 1.0;
+            rpov::rays::Ray::new(point(0.0, y, 0.0), rpov::tuples::vector(0.0, 0.0, 1.0))
+        })
+        .collect();
+    c.bench_function("sphere_solver", |b| {
+        b.iter(|| rays.iter().map(|r| s.intersect(*r).len()).sum::<usize>())
+    });
+}
+
+// One transformed sphere against a row of rays, one at a time and as a batch
+// that inverts the transform once.
+fn sphere_batch(c: &mut Criterion) {
+    let s = Sphere::with_transform(translation(0.0, 0.0, 5.0) * scaling(2.0, 1.0, 1.0)).unwrap();
+    let rays: Vec<_> = (0..1000)
+        .map(|i| {
+            let x = i as Float / 250.0 - 2.0;
+            rpov::rays::Ray::new(point(x, 0.3, 0.0), rpov::tuples::vector(0.0, 0.0, 1.0))
+        })
+        .collect();
+    let mut g = c.benchmark_group("sphere_batch");
+    g.bench_function("loop", |b| {
+        b.iter(|| rays.iter().map(|r| s.intersect(*r).len()).sum::<usize>())
+    });
+    g.bench_function("batch", |b| {
+        let mut out = Vec::with_capacity(2 * rays.len());
+        b.iter(|| {
+            out.clear();
+            s.intersect_batch(&rays, &mut out);
+            out.len()
+        })
+    });
+}
+
+criterion_group!(
+    benches,
+    shadow_rays,
+    frozen_scene,
+    shape_stores,
+    many_small_spheres,
+    sphere_solver,
+    sphere_batch
+);
+criterion_main!(benches);
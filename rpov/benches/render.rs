@@ -0,0 +1,212 @@
+use criterion::{BatchSize, Criterion, criterion_group, criterion_main};
+use rpov::{
+    camera::Camera,
+    colors::Color,
+    floats::{Float, PI},
+    intersections::hit_for,
+    lighting::point_light,
+    materials::Material,
+    rays::{Ray, RayKind},
+    shapes::prefab::{checkered_floor, matte_plane},
+    spheres::{Sphere, glass_sphere},
+    transformations::{rotation_x, rotation_y, scaling, translation, view_transform},
+    tuples::{point, vector},
+    world::{WorldBuilder, default_world, render_pixels},
+};
+
+fn camera(from: (Float, Float, Float), to: (Float, Float, Float)) -> Camera {
+    let mut c = Camera::new(100, 50, PI / 3.0);
+    c.set_transform(view_transform(
+        point(from.0, from.1, from.2),
+        point(to.0, to.1, to.2),
+        vector(0.0, 1.0, 0.0),
+    ));
+    c
+}
+
+fn sphere(transform: rpov::matrices::Matrix4, color: Color) -> Sphere {
+    let mut s = Sphere::with_transform(transform).unwrap();
+    s.material = Material {
+        color,
+        diffuse: 0.7,
+        specular: 0.3,
+        ..Material::new()
+    };
+    s
+}
+
+// The three spheres in a corner from chapter 7.
+fn ch7_world() -> WorldBuilder {
+    let mut w = WorldBuilder::with_light(point_light(
+        point(-10.0, 10.0, -10.0),
+        Color::new(1.0, 1.0, 1.0),
+    ));
+    let wall_color = Color::new(1.0, 0.9, 0.9);
+    let mut left_wall = matte_plane(wall_color);
+    left_wall
+        .set_transform(translation(0.0, 0.0, 5.0) * rotation_y(-PI / 4.0) * rotation_x(PI / 2.0))
+        .unwrap();
+    let mut right_wall = matte_plane(wall_color);
+    right_wall
+        .set_transform(translation(0.0, 0.0, 5.0) * rotation_y(PI / 4.0) * rotation_x(PI / 2.0))
+        .unwrap();
+    w.planes = vec![matte_plane(wall_color), left_wall, right_wall];
+    w.objects = vec![
+        sphere(translation(-0.5, 1.0, 0.5), Color::new(0.1, 1.0, 0.5)),
+        sphere(
+            translation(1.5, 0.5, -0.5) * scaling(0.5, 0.5, 0.5),
+            Color::new(0.5, 1.0, 0.1),
+        ),
+        sphere(
+            translation(-1.5, 0.33, -0.75) * scaling(0.33, 0.33, 0.33),
+            Color::new(1.0, 0.8, 0.1),
+        ),
+    ];
+    w
+}
+
+// A glass ball and a mirror ball over a reflective checkered floor.
+fn glass_world() -> WorldBuilder {
+    let mut w = WorldBuilder::with_light(point_light(
+        point(-10.0, 10.0, -10.0),
+        Color::new(1.0, 1.0, 1.0),
+    ));
+    w.planes.push(checkered_floor(
+        Color::new(0.9, 0.9, 0.9),
+        Color::new(0.1, 0.1, 0.1),
+    ));
+    let mut glass = glass_sphere();
+    glass.set_transform(translation(-0.6, 1.0, 0.0)).unwrap();
+    glass.material.reflective = 0.9;
+    glass.material.diffuse = 0.1;
+    let mut mirror = rpov::shapes::prefab::mirror_sphere();
+    mirror
+        .set_transform(translation(1.2, 0.7, 1.0) * scaling(0.7, 0.7, 0.7))
+        .unwrap();
+    w.objects = vec![glass, mirror];
+    w
+}
+
+// A 10x10 grid of small spheres over a floor, lit from high above so that
+// most of the floor needs a shadow ray through the grid.
+fn shadow_world() -> WorldBuilder {
+    let mut w = WorldBuilder::with_light(point_light(
+        point(0.0, 20.0, 0.0),
+        Color::new(1.0, 1.0, 1.0),
+    ));
+    w.planes.push(matte_plane(Color::new(0.8, 0.8, 0.8)));
+    for i in 0..10 {
+        for j in 0..10 {
+            let (x, z) = (i as Float * 0.5 - 2.25, j as Float * 0.5 - 2.25);
+            w.objects.push(sphere(
+                translation(x, 1.0, z) * scaling(0.2, 0.2, 0.2),
+                Color::new(0.8, 0.3, 0.3),
+            ));
+        }
+    }
+    w
+}
+
+// Each scene is rendered from the plain world, which render_pixels builds
+// into a scene on every call (the baseline), and from a scene built once
+// outside the timing, so only the cached rendering is measured.
+fn bench_scene(c: &mut Criterion, name: &str, world: fn() -> WorldBuilder, cam: Camera) {
+    let mut g = c.benchmark_group(name);
+    g.sample_size(10);
+    g.bench_function("world_baseline", |b| {
+        b.iter_batched(world, |w| render_pixels(&cam, &w), BatchSize::SmallInput)
+    });
+    g.bench_function("scene_cached_inverses", |b| {
+        b.iter_batched(
+            || world().build(),
+            |scene| scene.render(&cam),
+            BatchSize::SmallInput,
+        )
+    });
+    g.finish();
+}
+
+fn renders(c: &mut Criterion) {
+    bench_scene(
+        c,
+        "render_default_world_100x50",
+        default_world,
+        camera((0.0, 0.0, -5.0), (0.0, 0.0, 0.0)),
+    );
+    bench_scene(
+        c,
+        "render_ch7_100x50",
+        ch7_world,
+        camera((0.0, 1.5, -5.0), (0.0, 1.0, 0.0)),
+    );
+    bench_scene(
+        c,
+        "render_glass_100x50",
+        glass_world,
+        camera((0.0, 2.0, -6.0), (0.0, 0.8, 0.0)),
+    );
+    bench_scene(
+        c,
+        "render_shadows_100_spheres_100x50",
+        shadow_world,
+        camera((0.0, 4.0, -7.0), (0.0, 0.0, 0.0)),
+    );
+}
+
+// Every primary ray of a 100x50 image, without tracing them. The camera
+// caches its inverse transform and eye, so this is one matrix-vector
+// multiply and a normalize per ray.
+fn camera_rays(c: &mut Criterion) {
+    let cam = camera((0.0, 1.5, -5.0), (0.0, 1.0, 0.0));
+    c.bench_function("ray_for_pixel_100x50", |b| {
+        b.iter(|| {
+            let mut sum = 0.0;
+            for y in 0..cam.vsize {
+                for x in 0..cam.hsize {
+                    sum += cam.ray_for_pixel(x, y).direction.x;
+                }
+            }
+            sum
+        })
+    });
+}
+
+// Shadow rays from a 50x50 grid on the ch7 floor to the light. Sorting every
+// intersection to find the nearest hit (the baseline) against stopping at the
+// first shape in the way.
+fn shadow_queries(c: &mut Criterion) {
+    let w = ch7_world();
+    let light = w.light.as_ref().unwrap().position;
+    let rays: Vec<Ray> = (0..50 * 50)
+        .map(|i| {
+            let p = point(
+                (i % 50) as Float * 0.2 - 5.0,
+                0.0,
+                (i / 50) as Float * 0.2 - 5.0,
+            );
+            Ray::new(p, (light - p).normalize()).with_kind(RayKind::Shadow)
+        })
+        .collect();
+    let distance = |r: &Ray| (light - r.origin).magnitude();
+    let mut g = c.benchmark_group("shadow_queries_ch7_50x50");
+    g.bench_function("nearest_hit_baseline", |b| {
+        b.iter(|| {
+            rays.iter()
+                .filter(|r| {
+                    hit_for(&w.intersect_bounded(**r, distance(r)), RayKind::Shadow).is_some()
+                })
+                .count()
+        })
+    });
+    g.bench_function("intersect_any", |b| {
+        b.iter(|| {
+            rays.iter()
+                .filter(|r| w.intersect_any(**r, distance(r)))
+                .count()
+        })
+    });
+    g.finish();
+}
+
+criterion_group!(benches, renders, camera_rays, shadow_queries);
+criterion_main!(benches);
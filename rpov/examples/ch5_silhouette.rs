@@ -0,0 +1,32 @@
+// Chapter 5: the silhouettes of a sphere under five transforms, side by side.
+//
+//     cargo run --example ch5_silhouette -- [--size 100] [-o ch5.ppm]
+#[path = "common.rs"]
+mod common;
+
+use rpov::{
+    canvas::compose_grid,
+    error::RpovError,
+    floats::PI,
+    matrices::Matrix4,
+    transformations::{rotation_z, scaling, shearing},
+    world::demo::silhouette,
+};
+
+fn main() -> Result<(), RpovError> {
+    let args = common::parse_args(100, 100, "ch5.ppm");
+    let pixels = args.width.min(args.height);
+    let sx = scaling(0.5, 1.0, 1.0);
+    let transforms = [
+        Matrix4::identity(),
+        scaling(1.0, 0.5, 1.0),
+        sx,
+        rotation_z(PI / 4.0) * sx,
+        shearing(1.0, 0.0, 0.0, 0.0, 0.0, 0.0) * sx,
+    ];
+    let tiles = transforms
+        .into_iter()
+        .map(|t| silhouette(t, pixels))
+        .collect::<Result<_, _>>()?;
+    common::write(&compose_grid(tiles, 5), &args.output)
+}
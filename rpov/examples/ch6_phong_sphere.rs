@@ -0,0 +1,13 @@
+// Chapter 6: a striped sphere lit with the Phong reflection model.
+//
+//     cargo run --release --example ch6_phong_sphere -- [--size 400] [-o ch6.ppm]
+#[path = "common.rs"]
+mod common;
+
+use rpov::{error::RpovError, world::demo::phong_sphere};
+
+fn main() -> Result<(), RpovError> {
+    let args = common::parse_args(400, 400, "ch6.ppm");
+    let canvas = phong_sphere(args.width.min(args.height));
+    common::write(&canvas, &args.output)
+}
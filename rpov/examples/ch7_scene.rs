@@ -0,0 +1,16 @@
+// Chapter 7: three spheres in a corner, seen through a camera.
+//
+//     cargo run --release --example ch7_scene -- [--size 400x200] [-o ch7.ppm]
+#[path = "common.rs"]
+mod common;
+
+use rpov::{
+    error::RpovError,
+    world::{demo::chapter7_scene, render},
+};
+
+fn main() -> Result<(), RpovError> {
+    let args = common::parse_args(400, 200, "ch7.ppm");
+    let (world, camera) = chapter7_scene(args.width, args.height);
+    common::write(&render(camera, world), &args.output)
+}
@@ -0,0 +1,117 @@
+// Argument handling shared by the examples:
+//
+//     --size WIDTHxHEIGHT   or --size N for N x N
+//     -o PATH               written as PNG for a .png path, PPM otherwise
+//
+// plus whatever flags and valued options an example declares for itself.
+// Each example uses only part of this.
+#![allow(dead_code)]
+
+use std::{
+    collections::{HashMap, HashSet},
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use rpov::{canvas::Canvas, error::RpovError};
+
+pub struct Args {
+    pub width: usize,
+    pub height: usize,
+    pub output: PathBuf,
+    flags: HashSet<String>,
+    options: HashMap<String, String>,
+}
+
+impl Args {
+    pub fn flag(&self, name: &str) -> bool {
+        self.flags.contains(name)
+    }
+
+    // The value given for a declared option, or `default` when it was left
+    // out. Exits with the usage message if the value doesn't parse.
+    pub fn option<T: FromStr>(&self, name: &str, default: T) -> T {
+        match self.options.get(name) {
+            Some(value) => value
+                .parse()
+                .unwrap_or_else(|_| usage(&format!("bad value `{value}` for {name}"))),
+            None => default,
+        }
+    }
+}
+
+// Reads the arguments, falling back to the given size and output path.
+pub fn parse_args(width: usize, height: usize, output: &str) -> Args {
+    parse_args_with(width, height, output, &[], &[])
+}
+
+// Like parse_args, but also accepts the given `--flag`s and the `--option
+// VALUE` pairs named in `options`.
+pub fn parse_args_with(
+    width: usize,
+    height: usize,
+    output: &str,
+    flags: &[&str],
+    options: &[&str],
+) -> Args {
+    let mut args = Args {
+        width,
+        height,
+        output: PathBuf::from(output),
+        flags: HashSet::new(),
+        options: HashMap::new(),
+    };
+    let mut argv = std::env::args().skip(1);
+    while let Some(arg) = argv.next() {
+        match arg.as_str() {
+            "--size" => {
+                let size = argv.next().unwrap_or_default();
+                let Some((w, h)) = parse_size(&size) else {
+                    usage(&format!("bad size `{size}`"));
+                };
+                (args.width, args.height) = (w, h);
+            }
+            "-o" => match argv.next() {
+                Some(path) => args.output = PathBuf::from(path),
+                None => usage("-o needs a path"),
+            },
+            flag if flags.contains(&flag) => {
+                args.flags.insert(arg);
+            }
+            option if options.contains(&option) => match argv.next() {
+                Some(value) => {
+                    args.options.insert(arg, value);
+                }
+                None => usage(&format!("{arg} needs a value")),
+            },
+            _ => usage(&format!("unknown argument `{arg}`")),
+        }
+    }
+    args
+}
+
+fn parse_size(size: &str) -> Option<(usize, usize)> {
+    let (w, h) = size.split_once('x').unwrap_or((size, size));
+    match (w.parse(), h.parse()) {
+        (Ok(w), Ok(h)) if w > 0 && h > 0 => Some((w, h)),
+        _ => None,
+    }
+}
+
+fn usage(problem: &str) -> ! {
+    eprintln!("{problem}\nusage: [--size WIDTHxHEIGHT] [-o PATH]");
+    std::process::exit(2);
+}
+
+pub fn write(canvas: &Canvas, path: &Path) -> Result<(), RpovError> {
+    if path
+        .extension()
+        .is_some_and(|e| e.eq_ignore_ascii_case("png"))
+    {
+        canvas.write_png(path)?;
+    } else {
+        canvas.write_ppm(path)?;
+    }
+    println!("wrote {}", path.display());
+    Ok(())
+}
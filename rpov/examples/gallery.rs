@@ -0,0 +1,150 @@
+// Renders every material swatch, labels each tile with its name and writes
+// them as one grid image.
+//
+//     cargo run --release --example gallery [--size TILE] [--columns N] [-o PATH]
+//     cargo run --example gallery --describe
+//     cargo run --release --example gallery --auto-frame
+//     cargo run --release --example gallery --ascii-preview
+//     cargo run --release --example gallery --hue-preserving-clamp
+#[path = "common.rs"]
+mod common;
+
+use rpov::{
+    canvas::{Canvas, compose_grid},
+    colors::Color,
+    error::RpovError,
+    options::{OutputOptions, ToneMapper},
+    tuples::vector,
+    world::{
+        gallery::{material_swatches, swatch_camera},
+        render,
+    },
+};
+
+// A 3x5 pixel font, one row of three bits per line. Anything missing is
+// drawn as a space.
+const GLYPHS: [(char, [u8; 5]); 37] = [
+    ('a', [0b010, 0b101, 0b111, 0b101, 0b101]),
+    ('b', [0b110, 0b101, 0b110, 0b101, 0b110]),
+    ('c', [0b011, 0b100, 0b100, 0b100, 0b011]),
+    ('d', [0b110, 0b101, 0b101, 0b101, 0b110]),
+    ('e', [0b111, 0b100, 0b110, 0b100, 0b111]),
+    ('f', [0b111, 0b100, 0b110, 0b100, 0b100]),
+    ('g', [0b011, 0b100, 0b101, 0b101, 0b011]),
+    ('h', [0b101, 0b101, 0b111, 0b101, 0b101]),
+    ('i', [0b111, 0b010, 0b010, 0b010, 0b111]),
+    ('j', [0b001, 0b001, 0b001, 0b101, 0b010]),
+    ('k', [0b101, 0b101, 0b110, 0b101, 0b101]),
+    ('l', [0b100, 0b100, 0b100, 0b100, 0b111]),
+    ('m', [0b101, 0b111, 0b111, 0b101, 0b101]),
+    ('n', [0b110, 0b101, 0b101, 0b101, 0b101]),
+    ('o', [0b010, 0b101, 0b101, 0b101, 0b010]),
+    ('p', [0b110, 0b101, 0b110, 0b100, 0b100]),
+    ('q', [0b010, 0b101, 0b101, 0b110, 0b011]),
+    ('r', [0b110, 0b101, 0b110, 0b101, 0b101]),
+    ('s', [0b011, 0b100, 0b010, 0b001, 0b110]),
+    ('t', [0b111, 0b010, 0b010, 0b010, 0b010]),
+    ('u', [0b101, 0b101, 0b101, 0b101, 0b111]),
+    ('v', [0b101, 0b101, 0b101, 0b101, 0b010]),
+    ('w', [0b101, 0b101, 0b111, 0b111, 0b101]),
+    ('x', [0b101, 0b101, 0b010, 0b101, 0b101]),
+    ('y', [0b101, 0b101, 0b010, 0b010, 0b010]),
+    ('z', [0b111, 0b001, 0b010, 0b100, 0b111]),
+    ('0', [0b111, 0b101, 0b101, 0b101, 0b111]),
+    ('1', [0b010, 0b110, 0b010, 0b010, 0b111]),
+    ('2', [0b110, 0b001, 0b010, 0b100, 0b111]),
+    ('3', [0b110, 0b001, 0b010, 0b001, 0b110]),
+    ('4', [0b101, 0b101, 0b111, 0b001, 0b001]),
+    ('5', [0b111, 0b100, 0b110, 0b001, 0b110]),
+    ('6', [0b011, 0b100, 0b111, 0b101, 0b111]),
+    ('7', [0b111, 0b001, 0b010, 0b010, 0b010]),
+    ('8', [0b111, 0b101, 0b111, 0b101, 0b111]),
+    ('9', [0b111, 0b101, 0b111, 0b001, 0b110]),
+    ('.', [0b000, 0b000, 0b000, 0b000, 0b010]),
+];
+
+// The text in white on a black strip, each font pixel drawn as a
+// scale x scale block with a one-block margin all round.
+fn label(text: &str, scale: usize) -> Canvas {
+    let chars: Vec<char> = text.to_lowercase().chars().collect();
+    let mut strip = Canvas::new((chars.len() * 4 + 1) * scale, 7 * scale);
+    let white = Color::new(1.0, 1.0, 1.0);
+    for (i, c) in chars.iter().enumerate() {
+        let Some((_, rows)) = GLYPHS.iter().find(|(g, _)| g == c) else {
+            continue;
+        };
+        for (row, bits) in rows.iter().enumerate() {
+            for col in 0..3 {
+                if bits & (0b100 >> col) == 0 {
+                    continue;
+                }
+                let (x, y) = ((1 + i * 4 + col) * scale, (1 + row) * scale);
+                for dy in 0..scale {
+                    for dx in 0..scale {
+                        strip.write_pixel(x + dx, y + dy, white);
+                    }
+                }
+            }
+        }
+    }
+    strip
+}
+
+fn main() -> Result<(), RpovError> {
+    let args = common::parse_args_with(
+        100,
+        100,
+        "gallery.png",
+        &[
+            "--describe",
+            "--auto-frame",
+            "--ascii-preview",
+            "--hue-preserving-clamp",
+        ],
+        &["--columns"],
+    );
+    if args.flag("--describe") {
+        for swatch in material_swatches() {
+            println!("{}:\n{}\n", swatch.name, swatch.world().describe());
+        }
+        return Ok(());
+    }
+    let output = if args.flag("--hue-preserving-clamp") {
+        OutputOptions::new().with_tone_mapper(ToneMapper::HuePreservingClamp { desaturation: 0.0 })
+    } else {
+        OutputOptions::new()
+    };
+    let size = args.width;
+    let columns = args.option("--columns", 4).max(1);
+    let scale = (size / 100).max(1);
+
+    let swatches = material_swatches();
+    let mut tiles = vec![];
+    for (i, swatch) in swatches.iter().enumerate() {
+        println!(
+            "{}: row {}, column {}",
+            swatch.name,
+            i / columns,
+            i % columns
+        );
+        let mut camera = swatch_camera(size);
+        let world = swatch.world();
+        if args.flag("--auto-frame") {
+            let (bounds, unbounded) = world.bounds();
+            if unbounded > 0 {
+                eprintln!("warning: {unbounded} unbounded shapes left out of the framing");
+            }
+            camera.frame(&bounds, vector(0.0, -0.4, 1.0));
+        }
+        let mut tile = render(camera, world);
+        let name = label(&swatch.name, scale);
+        tile.blit(&name, 0, size.saturating_sub(name.height));
+        tiles.push(tile);
+    }
+    let grid = output.apply(&compose_grid(tiles, columns));
+    common::write(&grid, &args.output)?;
+    if args.flag("--ascii-preview") {
+        print!("{}", grid.to_ascii(80));
+    }
+    Ok(())
+}
@@ -0,0 +1,53 @@
+// Renders two blended metaballs above a floor, found by sphere tracing.
+//
+//     cargo run --release --example metaballs [width] [height]
+use rpov::{
+    camera::Camera,
+    colors::Color,
+    error::RpovError,
+    floats::PI,
+    lighting::point_light,
+    planes::Plane,
+    sdf_shapes::{SdfShape, sdf_metaballs},
+    transformations::{translation, view_transform},
+    tuples::{point, vector},
+    world::{WorldBuilder, render},
+};
+
+fn main() -> Result<(), RpovError> {
+    let mut args = std::env::args().skip(1);
+    let width = args.next().and_then(|a| a.parse().ok()).unwrap_or(200);
+    let height = args.next().and_then(|a| a.parse().ok()).unwrap_or(100);
+
+    let mut blob = SdfShape::new(sdf_metaballs(
+        point(-0.7, 0.0, 0.0),
+        point(0.7, 0.0, 0.0),
+        0.8,
+        0.5,
+    ));
+    blob.set_transform(translation(0.0, 1.0, 0.0))?;
+    blob.material.color = Color::new(0.9, 0.4, 0.2);
+    blob.material.reflective = 0.2;
+
+    let mut floor = Plane::new();
+    floor.material.color = Color::new(0.6, 0.6, 0.6);
+    floor.material.specular = 0.0;
+
+    let mut w = WorldBuilder::with_light(point_light(
+        point(-5.0, 8.0, -6.0),
+        Color::new(1.0, 1.0, 1.0),
+    ));
+    w.planes.push(floor);
+    w.shapes.push(Box::new(blob));
+
+    let mut c = Camera::new(width, height, PI / 3.0);
+    c.set_transform(view_transform(
+        point(0.0, 2.0, -5.0),
+        point(0.0, 0.8, 0.0),
+        vector(0.0, 1.0, 0.0),
+    ));
+    c.validate()?;
+    render(c, w).write_ppm("metaballs.ppm")?;
+    println!("wrote metaballs.ppm");
+    Ok(())
+}
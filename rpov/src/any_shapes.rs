@@ -0,0 +1,289 @@
+use std::collections::HashSet;
+
+use crate::{
+    bounds::{BoundingSphere, may_hit},
+    floats::Float,
+    intersections::{Intersection, Shape},
+    materials::Material,
+    matrices::Matrix4,
+    planes::{Plane, WavyPlane},
+    rays::Ray,
+    rectangles::Rectangle,
+    shapes::{Intersectable, ShapeFunctions},
+    spheres::Sphere,
+    tuples::Tuple4,
+    world::{Intersections, ShapeStore},
+};
+
+// A closed set of the built-in shapes. A Vec<AnyShape> stores them inline and
+// dispatches with a match rather than through a boxed trait object, so a
+// world can be filled without a heap allocation per shape.
+#[derive(Debug, Clone)]
+pub enum AnyShape {
+    Sphere(Sphere),
+    Plane(Plane),
+    Rectangle(Rectangle),
+    WavyPlane(WavyPlane),
+}
+
+macro_rules! dispatch {
+    ($self:expr, $s:ident => $body:expr) => {
+        match $self {
+            AnyShape::Sphere($s) => $body,
+            AnyShape::Plane($s) => $body,
+            AnyShape::Rectangle($s) => $body,
+            AnyShape::WavyPlane($s) => $body,
+        }
+    };
+}
+
+impl AnyShape {
+    // The wrapped shape, which is what intersections and the world's shape
+    // lists refer to.
+    pub fn as_shape(&self) -> &dyn Shape {
+        dispatch!(self, s => s)
+    }
+
+    pub fn as_shape_mut(&mut self) -> &mut dyn Shape {
+        dispatch!(self, s => s)
+    }
+}
+
+impl From<Sphere> for AnyShape {
+    fn from(s: Sphere) -> Self {
+        AnyShape::Sphere(s)
+    }
+}
+
+impl From<Plane> for AnyShape {
+    fn from(p: Plane) -> Self {
+        AnyShape::Plane(p)
+    }
+}
+
+impl From<Rectangle> for AnyShape {
+    fn from(r: Rectangle) -> Self {
+        AnyShape::Rectangle(r)
+    }
+}
+
+impl From<WavyPlane> for AnyShape {
+    fn from(p: WavyPlane) -> Self {
+        AnyShape::WavyPlane(p)
+    }
+}
+
+impl ShapeFunctions for AnyShape {
+    fn normal_at(&self, world_point: &Tuple4) -> Tuple4 {
+        dispatch!(self, s => s.normal_at(world_point))
+    }
+
+    fn local_normal_at(&self, local_point: &Tuple4) -> Tuple4 {
+        dispatch!(self, s => s.local_normal_at(local_point))
+    }
+
+    fn local_tangent_at(&self, local_point: &Tuple4) -> Option<Tuple4> {
+        dispatch!(self, s => s.local_tangent_at(local_point))
+    }
+
+    fn tangent_at(&self, world_point: &Tuple4, world_normal: Tuple4) -> Tuple4 {
+        dispatch!(self, s => s.tangent_at(world_point, world_normal))
+    }
+
+    fn material(&self) -> &Material {
+        dispatch!(self, s => s.material())
+    }
+
+    fn id(&self) -> u64 {
+        dispatch!(self, s => s.id())
+    }
+
+    fn tags(&self) -> &HashSet<String> {
+        dispatch!(self, s => s.tags())
+    }
+
+    fn resolved_material(&self) -> &Material {
+        dispatch!(self, s => s.resolved_material())
+    }
+
+    fn transform(&self) -> Matrix4 {
+        dispatch!(self, s => ShapeFunctions::transform(s))
+    }
+
+    fn transform_inverse(&self) -> Matrix4 {
+        dispatch!(self, s => s.transform_inverse())
+    }
+
+    fn bounding_sphere(&self) -> Option<BoundingSphere> {
+        dispatch!(self, s => s.bounding_sphere())
+    }
+}
+
+// Intersections refer to the wrapped shape, so shading never goes back
+// through the enum.
+impl Intersectable<AnyShape> for AnyShape {
+    fn intersect<'a>(&'a self, ray: Ray) -> Vec<Intersection<'a>> {
+        dispatch!(self, s => s.intersect(ray))
+    }
+
+    fn intersect_bounded<'a>(&'a self, ray: Ray, t_max: Float) -> Vec<Intersection<'a>> {
+        dispatch!(self, s => s.intersect_bounded(ray, t_max))
+    }
+
+    fn local_intersect<'a>(&'a self, local_ray: Ray) -> Vec<Intersection<'a>> {
+        dispatch!(self, s => s.local_intersect(local_ray))
+    }
+
+    fn local_intersect_bounded<'a>(
+        &'a self,
+        local_ray: Ray,
+        t_max: Float,
+    ) -> Vec<Intersection<'a>> {
+        dispatch!(self, s => s.local_intersect_bounded(local_ray, t_max))
+    }
+
+    fn intersect_batch<'a>(&'a self, rays: &[Ray], out: &mut Vec<(usize, Intersection<'a>)>) {
+        dispatch!(self, s => s.intersect_batch(rays, out))
+    }
+
+    fn local_intersect_batch<'a>(
+        &'a self,
+        local_rays: &[Ray],
+        out: &mut Vec<(usize, Intersection<'a>)>,
+    ) {
+        dispatch!(self, s => s.local_intersect_batch(local_rays, out))
+    }
+
+    fn hits_before(&self, ray: Ray, t_max: Float) -> bool {
+        dispatch!(self, s => s.hits_before(ray, t_max))
+    }
+
+    fn intersects_before(&self, local_ray: Ray, t_max: Float) -> bool {
+        dispatch!(self, s => s.intersects_before(local_ray, t_max))
+    }
+}
+
+impl ShapeStore for Vec<AnyShape> {
+    fn intersect_into<'a>(&'a self, r: Ray, t_max: Float, xs: &mut Intersections<'a>) -> usize {
+        let mut tested = 0;
+        for shape in self.iter().filter(|s| may_hit(*s, r)) {
+            xs.append(&mut shape.intersect_bounded(r, t_max));
+            tested += 1;
+        }
+        tested
+    }
+
+    fn any_hit(&self, r: Ray, hits: &dyn Fn(&dyn Shape) -> bool) -> bool {
+        self.iter()
+            .filter(|s| may_hit(*s, r))
+            .any(|s| hits(s.as_shape()))
+    }
+
+    fn iter_shapes(&self) -> Box<dyn Iterator<Item = &dyn Shape> + '_> {
+        Box::new(self.iter().map(AnyShape::as_shape))
+    }
+
+    fn shape_mut(&mut self, id: u64) -> Option<&mut dyn Shape> {
+        let shape = self.iter_mut().find(|s| s.id() == id)?;
+        Some(shape.as_shape_mut())
+    }
+
+    fn remove_shape(&mut self, id: u64) -> bool {
+        let before = self.len();
+        self.retain(|s| s.id() != id);
+        self.len() < before
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        assert_same_object,
+        camera::Camera,
+        colors::Color,
+        floats::PI,
+        intersections::hit,
+        patterns::{Pattern, stripe_pattern},
+        rays::ray,
+        spheres::glass_sphere,
+        transformations::{scaling, translation, view_transform},
+        tuples::{point, vector},
+        world::{WorldBuilder, default_world},
+    };
+
+    fn camera() -> Camera {
+        let mut c = Camera::new(11, 11, PI / 2.0);
+        c.set_transform(view_transform(
+            point(0.0, 0.0, -5.0),
+            point(0.0, 0.0, 0.0),
+            vector(0.0, 1.0, 0.0),
+        ));
+        c
+    }
+
+    #[test]
+    fn both_stores_render_the_default_world_identically() {
+        let mut boxed = default_world();
+        let mut inline: WorldBuilder<Vec<AnyShape>> = WorldBuilder {
+            light: boxed.light.clone(),
+            ..WorldBuilder::default()
+        };
+        for s in std::mem::take(&mut boxed.objects) {
+            inline.shapes.push(AnyShape::from(s.clone()));
+            boxed.shapes.push(Box::new(s));
+        }
+        assert_eq!(
+            boxed.build().render(&camera()).to_ppm(),
+            inline.build().render(&camera()).to_ppm()
+        );
+    }
+
+    #[test]
+    fn intersections_refer_to_the_wrapped_shape() {
+        let shape = AnyShape::from(Sphere::with_transform(scaling(2.0, 2.0, 2.0)).unwrap());
+        let xs = shape.intersect(ray(point(0.0, 0.0, -5.0), vector(0.0, 0.0, 1.0)));
+        let AnyShape::Sphere(inner) = &shape else {
+            unreachable!()
+        };
+        assert_eq!(xs[0].t, 3.0);
+        assert_same_object!(xs[0].object, inner);
+    }
+
+    #[test]
+    fn refraction_indices_work_through_the_enum() {
+        let mut w: WorldBuilder<Vec<AnyShape>> = WorldBuilder::default();
+        let mut a = glass_sphere();
+        a.set_transform(scaling(2.0, 2.0, 2.0)).unwrap();
+        a.material.refractive_index = 1.5;
+        let mut b = glass_sphere();
+        b.set_transform(translation(0.0, 0.0, -0.25)).unwrap();
+        b.material.refractive_index = 2.0;
+        w.shapes.push(a.into());
+        w.shapes.push(b.into());
+        let r = ray(point(0.0, 0.0, -4.0), vector(0.0, 0.0, 1.0));
+        let xs = w.intersect(r);
+        let comps = hit(&xs).unwrap().prepare_computations(r, Some(xs.clone()));
+        assert_eq!((comps.n1(), comps.n2()), (1.0, 1.5));
+        let comps = xs[1].prepare_computations(r, Some(xs.clone()));
+        assert_eq!((comps.n1(), comps.n2()), (1.5, 2.0));
+    }
+
+    #[test]
+    fn object_patterns_work_through_the_enum() {
+        let stripes = stripe_pattern(Color::new(1.0, 1.0, 1.0), Color::new(0.0, 0.0, 0.0));
+        let sphere = Sphere::with_transform(scaling(2.0, 2.0, 2.0)).unwrap();
+        let shape = AnyShape::from(sphere.clone());
+        for x in [0.5, 1.5, 2.5, 3.5] {
+            let p = point(x, 0.0, 0.0);
+            assert_eq!(
+                stripes.pattern_at_shape(&shape, p),
+                stripes.pattern_at_shape(&sphere, p)
+            );
+        }
+        assert_ne!(
+            stripes.pattern_at_shape(&shape, point(1.5, 0.0, 0.0)),
+            stripes.pattern_at_shape(&shape, point(2.5, 0.0, 0.0))
+        );
+    }
+}
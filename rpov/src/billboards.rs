@@ -0,0 +1,194 @@
+use std::collections::HashSet;
+
+use crate::{
+    bounds::BoundingSphere,
+    floats::Float,
+    intersections::Intersection,
+    materials::Material,
+    matrices::{Matrix4, TransformError, validate_transform},
+    rays::Ray,
+    shapes::{Intersectable, ShapeFunctions, next_shape_id},
+    tuples::{Tuple4, point, vector},
+};
+
+// A flat disc of `radius` around the origin that turns to face every ray, so
+// it looks the same from any side. Meant for glow and fog sprites: give it a
+// transparent, thin_surface material whose pattern fades out from the center.
+#[derive(Debug, Clone)]
+pub struct Billboard {
+    pub id: u64,
+    // Set with set_transform, which also caches its inverse.
+    transform: Matrix4,
+    transform_inverse: Matrix4,
+    pub material: Material,
+    pub tags: HashSet<String>,
+    pub radius: Float,
+}
+
+impl Billboard {
+    pub fn new(radius: Float) -> Self {
+        Self {
+            id: next_shape_id(),
+            transform: Matrix4::identity(),
+            transform_inverse: Matrix4::identity(),
+            material: Material::new(),
+            tags: HashSet::new(),
+            radius,
+        }
+    }
+
+    pub fn transform(&self) -> Matrix4 {
+        self.transform
+    }
+
+    // Leaves the transform as it was when the new one can't be inverted.
+    pub fn set_transform(&mut self, transform: Matrix4) -> Result<(), TransformError> {
+        validate_transform("Billboard", transform)?;
+        self.transform = transform;
+        self.transform_inverse = transform.inverse();
+        Ok(())
+    }
+}
+
+impl ShapeFunctions for Billboard {
+    fn id(&self) -> u64 {
+        self.id
+    }
+
+    fn tags(&self) -> &HashSet<String> {
+        &self.tags
+    }
+
+    fn transform(&self) -> Matrix4 {
+        self.transform
+    }
+
+    fn transform_inverse(&self) -> Matrix4 {
+        self.transform_inverse
+    }
+
+    fn material(&self) -> &Material {
+        &self.material
+    }
+
+    // The normal depends on the ray, so hits carry their own. Without a ray,
+    // this is the normal seen by rays along +z.
+    fn local_normal_at(&self, _local_point: &Tuple4) -> Tuple4 {
+        vector(0.0, 0.0, -1.0)
+    }
+
+    fn bounding_sphere(&self) -> Option<BoundingSphere> {
+        Some(BoundingSphere::around_origin(self.transform, self.radius))
+    }
+}
+
+impl Intersectable<Billboard> for Billboard {
+    // The disc lies in the plane through the origin perpendicular to the ray,
+    // which the ray crosses where it comes closest to the origin.
+    fn local_intersect<'a>(&'a self, local_ray: Ray) -> Vec<Intersection<'a>> {
+        let d = local_ray.direction;
+        let to_origin = point(0.0, 0.0, 0.0) - local_ray.origin;
+        let t = to_origin.dot(d) / d.dot(d);
+        let offset = local_ray.position(t) - point(0.0, 0.0, 0.0);
+        if offset.magnitude() > self.radius {
+            return vec![];
+        }
+        let mut normal = self.transform_inverse().transpose() * -d;
+        normal.w = 0.0;
+        vec![Intersection::with_normal(t, self, normal.normalize())]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        colors::Color,
+        floats::PI,
+        lighting::point_light,
+        options::RenderOptions,
+        transformations::{rotation_y, scaling, translation},
+        tuples::check_tuple,
+        world::WorldBuilder,
+    };
+
+    fn directions() -> Vec<Tuple4> {
+        vec![
+            vector(0.0, 0.0, 1.0),
+            vector(1.0, 0.0, 0.0),
+            vector(0.0, -1.0, 0.0),
+            vector(-1.0, 2.0, 3.0).normalize(),
+            vector(0.3, -0.4, -0.8).normalize(),
+        ]
+    }
+
+    #[test]
+    fn a_billboard_is_hit_from_any_direction_aimed_at_its_center() {
+        let mut b = Billboard::new(0.5);
+        b.set_transform(translation(1.0, 2.0, 3.0)).unwrap();
+        for d in directions() {
+            let r = Ray::new(point(1.0, 2.0, 3.0) - d * 5.0, d);
+            let xs = b.intersect(r);
+            assert_eq!(xs.len(), 1, "{d:?}");
+            assert!((xs[0].t - 5.0).abs() < 1e-4, "{d:?}");
+            check_tuple(xs[0].normal_at(&r.position(xs[0].t)), -d);
+        }
+    }
+
+    #[test]
+    fn a_billboards_silhouette_is_the_same_from_every_side() {
+        let b = Billboard::new(2.0);
+        for d in directions() {
+            // Any vector perpendicular to d will do for the sideways offset.
+            let across = d.cross(vector(0.6, 0.0, 0.8)).normalize();
+            let hits = |offset: Float| {
+                let r = Ray::new(point(0.0, 0.0, 0.0) - d * 5.0 + across * offset, d);
+                !b.intersect(r).is_empty()
+            };
+            assert!(hits(1.99) && !hits(2.01), "{d:?}");
+        }
+    }
+
+    #[test]
+    fn a_scaled_billboard_still_faces_the_ray() {
+        let mut b = Billboard::new(1.0);
+        b.set_transform(rotation_y(PI / 5.0) * scaling(1.0, 3.0, 1.0))
+            .unwrap();
+        let d = vector(1.0, 0.0, 1.0).normalize();
+        let r = Ray::new(point(0.0, 0.0, 0.0) - d * 4.0, d);
+        let xs = b.intersect(r);
+        assert_eq!(xs.len(), 1);
+        assert!(xs[0].normal_at(&r.position(xs[0].t)).dot(-d) > 0.0);
+    }
+
+    #[test]
+    fn a_billboard_keeps_the_inverse_of_its_transform() {
+        let mut b = Billboard::new(1.0);
+        assert_eq!(b.transform_inverse(), Matrix4::identity());
+        let t = translation(1.0, 2.0, 3.0) * scaling(2.0, 2.0, 2.0);
+        b.set_transform(t).unwrap();
+        assert_eq!(b.transform_inverse(), t.inverse());
+        assert!(b.set_transform(scaling(0.0, 1.0, 1.0)).is_err());
+        assert_eq!(b.transform_inverse(), t.inverse());
+    }
+
+    #[test]
+    fn a_transparent_billboard_composites_over_the_background() {
+        let light = point_light(point(0.0, 0.0, -10.0), Color::new(1.0, 1.0, 1.0));
+        let mut w = WorldBuilder::with_light(light);
+        w.options = RenderOptions::new().with_background(Color::new(0.0, 0.0, 1.0));
+        let mut glow = Billboard::new(1.0);
+        glow.material.color = Color::new(1.0, 0.0, 0.0);
+        glow.material.ambient = 1.0;
+        glow.material.diffuse = 0.0;
+        glow.material.specular = 0.0;
+        glow.material.transparency = 0.5;
+        glow.material.thin_surface = true;
+        w.shapes.push(Box::new(glow));
+
+        let through = w.color_at(Ray::new(point(0.0, 0.0, -5.0), vector(0.0, 0.0, 1.0)));
+        assert_eq!(through, Color::new(1.0, 0.0, 0.5));
+        let beside = w.color_at(Ray::new(point(2.0, 0.0, -5.0), vector(0.0, 0.0, 1.0)));
+        assert_eq!(beside, Color::new(0.0, 0.0, 1.0));
+    }
+}
@@ -0,0 +1,306 @@
+use crate::{
+    floats::{EPSILON, Float},
+    matrices::Matrix4,
+    rays::Ray,
+    shapes::ShapeFunctions,
+    tuples::{Tuple4, point},
+};
+
+// Axis-aligned box. An empty box has min at +infinity and max at -infinity.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BoundingBox {
+    pub min: Tuple4,
+    pub max: Tuple4,
+}
+
+impl Default for BoundingBox {
+    fn default() -> Self {
+        Self::empty()
+    }
+}
+
+impl BoundingBox {
+    pub fn new(min: Tuple4, max: Tuple4) -> Self {
+        Self { min, max }
+    }
+
+    pub fn empty() -> Self {
+        let inf = Float::INFINITY;
+        Self {
+            min: point(inf, inf, inf),
+            max: point(-inf, -inf, -inf),
+        }
+    }
+
+    // Everywhere, for shapes such as planes that have no finite extent.
+    pub fn infinite() -> Self {
+        let inf = Float::INFINITY;
+        Self {
+            min: point(-inf, -inf, -inf),
+            max: point(inf, inf, inf),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.min.x > self.max.x || self.min.y > self.max.y || self.min.z > self.max.z
+    }
+
+    pub fn add_point(&mut self, p: Tuple4) {
+        self.min = point(
+            self.min.x.min(p.x),
+            self.min.y.min(p.y),
+            self.min.z.min(p.z),
+        );
+        self.max = point(
+            self.max.x.max(p.x),
+            self.max.y.max(p.y),
+            self.max.z.max(p.z),
+        );
+    }
+
+    pub fn merge(&mut self, other: &BoundingBox) {
+        if other.is_empty() {
+            return;
+        }
+        self.add_point(other.min);
+        self.add_point(other.max);
+    }
+
+    pub fn contains_point(&self, p: Tuple4) -> bool {
+        p.x >= self.min.x - EPSILON
+            && p.x <= self.max.x + EPSILON
+            && p.y >= self.min.y - EPSILON
+            && p.y <= self.max.y + EPSILON
+            && p.z >= self.min.z - EPSILON
+            && p.z <= self.max.z + EPSILON
+    }
+
+    pub fn center(&self) -> Tuple4 {
+        point(
+            (self.min.x + self.max.x) / 2.0,
+            (self.min.y + self.max.y) / 2.0,
+            (self.min.z + self.max.z) / 2.0,
+        )
+    }
+
+    pub fn corners(&self) -> [Tuple4; 8] {
+        let (a, b) = (self.min, self.max);
+        [
+            point(a.x, a.y, a.z),
+            point(a.x, a.y, b.z),
+            point(a.x, b.y, a.z),
+            point(a.x, b.y, b.z),
+            point(b.x, a.y, a.z),
+            point(b.x, a.y, b.z),
+            point(b.x, b.y, a.z),
+            point(b.x, b.y, b.z),
+        ]
+    }
+
+    // The box containing this box after it has been transformed.
+    pub fn transform(&self, m: Matrix4) -> BoundingBox {
+        if self.is_empty() {
+            return *self;
+        }
+        let mut result = BoundingBox::empty();
+        for corner in self.corners() {
+            result.add_point(m * corner);
+        }
+        result
+    }
+
+    // The range of t over which the ray is inside the box, using the slab method.
+    pub fn intersect(&self, ray: Ray) -> Option<(Float, Float)> {
+        let axes = [
+            (ray.origin.x, ray.direction.x, self.min.x, self.max.x),
+            (ray.origin.y, ray.direction.y, self.min.y, self.max.y),
+            (ray.origin.z, ray.direction.z, self.min.z, self.max.z),
+        ];
+        let mut tmin = Float::NEG_INFINITY;
+        let mut tmax = Float::INFINITY;
+        for (origin, direction, min, max) in axes {
+            if direction == 0.0 {
+                if origin < min || origin > max {
+                    return None;
+                }
+                continue;
+            }
+            let t0 = (min - origin) / direction;
+            let t1 = (max - origin) / direction;
+            tmin = tmin.max(t0.min(t1));
+            tmax = tmax.min(t0.max(t1));
+        }
+        if tmin > tmax {
+            None
+        } else {
+            Some((tmin, tmax))
+        }
+    }
+}
+
+// A world-space sphere enclosing a shape, used to reject rays cheaply before
+// a shape's own intersect transforms them.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BoundingSphere {
+    pub center: Tuple4,
+    pub radius: Float,
+}
+
+impl BoundingSphere {
+    pub fn new(center: Tuple4, radius: Float) -> Self {
+        Self { center, radius }
+    }
+
+    // Bounds a sphere of local_radius around the object-space origin once it
+    // has been transformed. The radius is stretched by an upper bound on the
+    // largest singular value of the linear part, so the bound stays
+    // conservative under anisotropic scaling and shearing; the column norms
+    // alone would underestimate a sheared transform.
+    pub fn around_origin(transform: Matrix4, local_radius: Float) -> Self {
+        let center = transform * point(0.0, 0.0, 0.0);
+        // Without shear, the largest scale factor is exactly that value. It
+        // is padded for the little shear decompose tolerates.
+        if let Some((_, _, s)) = transform.decompose() {
+            let stretch = s.x.abs().max(s.y.abs()).max(s.z.abs()) * (1.0 + EPSILON);
+            return Self::new(center, local_radius * stretch);
+        }
+        let mut frobenius = 0.0;
+        let mut max_column: Float = 0.0;
+        let mut max_row: Float = 0.0;
+        for i in 0..3 {
+            let (mut column, mut row) = (0.0, 0.0);
+            for j in 0..3 {
+                frobenius += transform[(i, j)] * transform[(i, j)];
+                column += transform[(j, i)].abs();
+                row += transform[(i, j)].abs();
+            }
+            max_column = max_column.max(column);
+            max_row = max_row.max(row);
+        }
+        let stretch = frobenius.sqrt().min((max_column * max_row).sqrt());
+        Self::new(center, local_radius * stretch)
+    }
+
+    // False only when the ray's line misses the sphere entirely. Hits behind
+    // the origin are not ruled out, so callers still see every intersection.
+    pub fn may_hit(&self, ray: Ray) -> bool {
+        let oc = ray.origin - self.center;
+        let a = ray.direction.dot(ray.direction);
+        let b = oc.dot(ray.direction);
+        let c = oc.dot(oc) - self.radius * self.radius;
+        // Leave some slack for rays that only graze the bound.
+        b * b - a * c >= -EPSILON * a
+    }
+}
+
+// Whether the shape must be intersected with the ray. Shapes without a bound
+// are always tested.
+pub fn may_hit<S: ShapeFunctions + ?Sized>(shape: &S, ray: Ray) -> bool {
+    shape.bounding_sphere().is_none_or(|b| b.may_hit(ray))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        planes::Plane,
+        rays::ray,
+        spheres::Sphere,
+        transformations::{rotation_y, rotation_z, scaling, shearing, translation},
+        tuples::vector,
+    };
+
+    #[test]
+    fn adding_points_grows_an_empty_box() {
+        let mut b = BoundingBox::empty();
+        assert!(b.is_empty());
+        b.add_point(point(-5.0, 2.0, 0.0));
+        b.add_point(point(7.0, 0.0, -3.0));
+        assert_eq!(b.min, point(-5.0, 0.0, -3.0));
+        assert_eq!(b.max, point(7.0, 2.0, 0.0));
+    }
+
+    #[test]
+    fn a_box_contains_points_inside_it() {
+        let b = BoundingBox::new(point(5.0, -2.0, 0.0), point(11.0, 4.0, 7.0));
+        assert!(b.contains_point(point(5.0, -2.0, 0.0)));
+        assert!(b.contains_point(point(8.0, 1.0, 3.0)));
+        assert!(!b.contains_point(point(3.0, 0.0, 3.0)));
+        assert!(!b.contains_point(point(8.0, 1.0, 8.0)));
+    }
+
+    #[test]
+    fn transforming_a_box_bounds_its_corners() {
+        let b = BoundingBox::new(point(-1.0, -1.0, -1.0), point(1.0, 1.0, 1.0));
+        let t = b.transform(rotation_y(crate::floats::PI / 4.0));
+        let s2 = crate::floats::SQRT_2;
+        crate::tuples::check_tuple(t.min, point(-s2, -1.0, -s2));
+        crate::tuples::check_tuple(t.max, point(s2, 1.0, s2));
+    }
+
+    #[test]
+    fn intersecting_a_ray_with_a_box() {
+        let b = BoundingBox::new(point(-1.0, -1.0, -1.0), point(1.0, 1.0, 1.0));
+        let r = ray(point(0.0, 0.5, -5.0), vector(0.0, 0.0, 1.0));
+        assert_eq!(b.intersect(r), Some((4.0, 6.0)));
+        let r = ray(point(0.0, 2.0, -5.0), vector(0.0, 0.0, 1.0));
+        assert_eq!(b.intersect(r), None);
+    }
+
+    #[test]
+    fn a_sheared_and_stretched_sphere_stays_inside_its_bound() {
+        let transform = translation(1.0, -2.0, 3.0)
+            * shearing(1.0, 0.5, 0.0, 2.0, 0.3, 0.0)
+            * rotation_z(0.7)
+            * scaling(0.5, 3.0, 1.5);
+        let b = Sphere::with_transform(transform)
+            .unwrap()
+            .bounding_sphere()
+            .unwrap();
+        for i in 0..40 {
+            for j in 0..20 {
+                let theta = i as Float / 40.0 * 2.0 * crate::floats::PI;
+                let phi = j as Float / 19.0 * crate::floats::PI;
+                let local = point(phi.sin() * theta.cos(), phi.cos(), phi.sin() * theta.sin());
+                let distance = (transform * local - b.center).magnitude();
+                assert!(distance <= b.radius + EPSILON, "{distance} > {}", b.radius);
+            }
+        }
+    }
+
+    #[test]
+    fn a_uniformly_scaled_sphere_has_a_tight_bound() {
+        let b = Sphere::with_transform(translation(0.0, 1.0, 0.0) * scaling(2.0, 2.0, 2.0))
+            .unwrap()
+            .bounding_sphere()
+            .unwrap();
+        assert_eq!(b.center, point(0.0, 1.0, 0.0));
+        assert!((b.radius - 2.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn a_rotated_stretched_sphere_is_bounded_by_its_largest_scale() {
+        let b = Sphere::with_transform(rotation_y(0.7) * rotation_z(0.3) * scaling(1.0, 4.0, 2.0))
+            .unwrap()
+            .bounding_sphere()
+            .unwrap();
+        assert!((b.radius - 4.0).abs() < 0.01, "{}", b.radius);
+    }
+
+    #[test]
+    fn a_ray_whose_line_misses_the_bound_is_rejected() {
+        let b = BoundingSphere::new(point(0.0, 0.0, 0.0), 1.0);
+        assert!(b.may_hit(ray(point(0.0, 0.5, -5.0), vector(0.0, 0.0, 1.0))));
+        assert!(!b.may_hit(ray(point(0.0, 2.0, -5.0), vector(0.0, 0.0, 1.0))));
+        // Hits behind the ray are kept, since intersect reports them too.
+        assert!(b.may_hit(ray(point(0.0, 0.0, 5.0), vector(0.0, 0.0, 1.0))));
+    }
+
+    #[test]
+    fn shapes_without_a_bound_are_always_tested() {
+        let r = ray(point(0.0, 100.0, -5.0), vector(1.0, 0.0, 0.0));
+        assert!(Plane::new().bounding_sphere().is_none());
+        assert!(may_hit(&Plane::new(), r));
+        assert!(!may_hit(&Sphere::new(), r));
+    }
+}
@@ -0,0 +1,173 @@
+// How much light a surface scatters toward the eye, split into the diffuse
+// and specular terms of the Phong reflection model. All directions are unit
+// vectors pointing away from the surface.
+use crate::{floats::Float, materials::SpecularModel, tuples::Tuple4};
+
+pub trait Brdf {
+    // Factors for the diffuse term, which scales the surface color, and the
+    // specular term, which scales the light's own color. Both are 0 when the
+    // light is behind the surface.
+    fn eval(&self, light_dir: Tuple4, eye_dir: Tuple4, normal: Tuple4) -> (Float, Float);
+}
+
+fn diffuse_factor(diffuse: Float, light_dir: Tuple4, normal: Tuple4) -> Option<Float> {
+    let light_dot_normal = light_dir.dot(normal);
+    (light_dot_normal >= 0.0).then_some(diffuse * light_dot_normal)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Phong {
+    pub diffuse: Float,
+    pub specular: Float,
+    pub shininess: Float,
+}
+
+impl Brdf for Phong {
+    fn eval(&self, light_dir: Tuple4, eye_dir: Tuple4, normal: Tuple4) -> (Float, Float) {
+        let Some(diffuse) = diffuse_factor(self.diffuse, light_dir, normal) else {
+            return (0.0, 0.0);
+        };
+        // The cosine of the angle between the reflected light and the eye.
+        // Negative when the light reflects away from the eye.
+        let reflect_dot_eye = (-light_dir).reflect(normal).dot(eye_dir);
+        if reflect_dot_eye <= 0.0 {
+            return (diffuse, 0.0);
+        }
+        (
+            diffuse,
+            self.specular * reflect_dot_eye.powf(self.shininess),
+        )
+    }
+}
+
+// Uses the half vector between the light and the eye. The shininess is
+// multiplied by 4 so highlights roughly match Phong's size.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BlinnPhong {
+    pub diffuse: Float,
+    pub specular: Float,
+    pub shininess: Float,
+}
+
+impl Brdf for BlinnPhong {
+    fn eval(&self, light_dir: Tuple4, eye_dir: Tuple4, normal: Tuple4) -> (Float, Float) {
+        let Some(diffuse) = diffuse_factor(self.diffuse, light_dir, normal) else {
+            return (0.0, 0.0);
+        };
+        // With the light and the eye exactly opposite, as for a grazing
+        // back-light, there is no half vector and no highlight.
+        let Some(half) = (light_dir + eye_dir).try_normalize() else {
+            return (diffuse, 0.0);
+        };
+        let normal_dot_half = normal.dot(half);
+        if normal_dot_half <= 0.0 {
+            return (diffuse, 0.0);
+        }
+        (
+            diffuse,
+            self.specular * normal_dot_half.powf(self.shininess * 4.0),
+        )
+    }
+}
+
+// The BRDF a material's specular_model picks, without boxing.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum AnyBrdf {
+    Phong(Phong),
+    BlinnPhong(BlinnPhong),
+}
+
+impl AnyBrdf {
+    pub(crate) fn new(
+        model: SpecularModel,
+        diffuse: Float,
+        specular: Float,
+        shininess: Float,
+    ) -> Self {
+        match model {
+            SpecularModel::Phong => AnyBrdf::Phong(Phong {
+                diffuse,
+                specular,
+                shininess,
+            }),
+            SpecularModel::BlinnPhong => AnyBrdf::BlinnPhong(BlinnPhong {
+                diffuse,
+                specular,
+                shininess,
+            }),
+        }
+    }
+}
+
+impl Brdf for AnyBrdf {
+    fn eval(&self, light_dir: Tuple4, eye_dir: Tuple4, normal: Tuple4) -> (Float, Float) {
+        match self {
+            AnyBrdf::Phong(b) => b.eval(light_dir, eye_dir, normal),
+            AnyBrdf::BlinnPhong(b) => b.eval(light_dir, eye_dir, normal),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{floats::FRAC_1_SQRT_2, tuples::vector};
+
+    const SHINY: Phong = Phong {
+        diffuse: 0.9,
+        specular: 0.9,
+        shininess: 200.0,
+    };
+
+    #[test]
+    fn light_behind_the_surface_contributes_nothing() {
+        let normal = vector(0.0, 0.0, -1.0);
+        let light = vector(0.0, 0.0, 1.0);
+        assert_eq!(SHINY.eval(light, normal, normal), (0.0, 0.0));
+        let blinn = BlinnPhong {
+            diffuse: 0.9,
+            specular: 0.9,
+            shininess: 200.0,
+        };
+        assert_eq!(blinn.eval(light, normal, normal), (0.0, 0.0));
+    }
+
+    #[test]
+    fn phong_peaks_with_the_eye_along_the_reflection() {
+        let normal = vector(0.0, 0.0, -1.0);
+        let (diffuse, specular) = SHINY.eval(normal, normal, normal);
+        crate::check_floats!(diffuse, 0.9);
+        crate::check_floats!(specular, 0.9);
+        let eye = vector(0.0, FRAC_1_SQRT_2, -FRAC_1_SQRT_2);
+        let (_, off_axis) = SHINY.eval(normal, eye, normal);
+        assert!(off_axis < 1e-6);
+    }
+
+    #[test]
+    fn blinn_phong_has_no_highlight_with_the_light_opposite_the_eye() {
+        let normal = vector(0.0, 0.0, -1.0);
+        let light = vector(0.0, 1.0, 0.0);
+        let blinn = BlinnPhong {
+            diffuse: 0.9,
+            specular: 0.9,
+            shininess: 200.0,
+        };
+        assert_eq!(blinn.eval(light, -light, normal), (0.0, 0.0));
+    }
+
+    #[test]
+    fn any_brdf_follows_the_specular_model() {
+        let normal = vector(0.0, 0.0, -1.0);
+        let light = vector(0.0, 1.0, -1.0).normalize();
+        let eye = vector(0.0, 0.0, -1.0);
+        let blinn = BlinnPhong {
+            diffuse: 0.9,
+            specular: 0.9,
+            shininess: 10.0,
+        };
+        let any = AnyBrdf::new(SpecularModel::BlinnPhong, 0.9, 0.9, 10.0);
+        assert_eq!(any.eval(light, eye, normal), blinn.eval(light, eye, normal));
+        let phong = AnyBrdf::new(SpecularModel::Phong, 0.9, 0.9, 10.0);
+        assert!(any.eval(light, eye, normal).1 > phong.eval(light, eye, normal).1);
+    }
+}
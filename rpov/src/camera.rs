@@ -1,29 +1,164 @@
+use std::fmt;
+
 use crate::{
-    floats::Float,
+    bounds::BoundingBox,
+    floats::{EPSILON, Float, PI},
     matrices::Matrix4,
+    quaternions::Quaternion,
     rays::{Ray, ray},
-    tuples::point,
+    transformations::view_transform,
+    tuples::{Tuple4, point, vector},
 };
 
+#[derive(Debug, Clone, PartialEq)]
+pub struct CameraError {
+    pub field: &'static str,
+    pub message: String,
+}
+
+impl fmt::Display for CameraError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid camera `{}`: {}", self.field, self.message)
+    }
+}
+
+impl std::error::Error for CameraError {}
+
+// The share of the image left clear around a framed scene.
+const FRAME_MARGIN: Float = 0.05;
+
+// Which extent of the image `field_of_view` spans.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum FovAxis {
+    // Whichever of width and height is larger, as in the book.
+    #[default]
+    Larger,
+    Horizontal,
+    Vertical,
+    Diagonal,
+}
+
+// A block of pixels from (x0, y0) up to but not including (x1, y1).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PixelRect {
+    pub x0: usize,
+    pub y0: usize,
+    pub x1: usize,
+    pub y1: usize,
+}
+
+impl PixelRect {
+    pub fn new(x0: usize, y0: usize, x1: usize, y1: usize) -> Self {
+        Self { x0, y0, x1, y1 }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.x0 >= self.x1 || self.y0 >= self.y1
+    }
+
+    pub fn area(&self) -> usize {
+        if self.is_empty() {
+            0
+        } else {
+            (self.x1 - self.x0) * (self.y1 - self.y0)
+        }
+    }
+
+    pub fn contains(&self, x: usize, y: usize) -> bool {
+        (self.x0..self.x1).contains(&x) && (self.y0..self.y1).contains(&y)
+    }
+
+    // Grown by `margin` pixels on every side, without leaving a width x
+    // height image.
+    pub fn expand(&self, margin: usize, width: usize, height: usize) -> PixelRect {
+        PixelRect::new(
+            self.x0.saturating_sub(margin),
+            self.y0.saturating_sub(margin),
+            (self.x1 + margin).min(width),
+            (self.y1 + margin).min(height),
+        )
+    }
+}
+
+#[derive(Debug, Clone)]
 pub struct Camera {
     pub hsize: usize,
     pub vsize: usize,
     pub field_of_view: Float,
-    pub transform: Matrix4,
+    pub fov_axis: FovAxis,
+    // Set with set_transform, which also caches its inverse and the eye.
+    transform: Matrix4,
+    transform_inverse: Matrix4,
+    origin: Tuple4,
     pub pixel_size: Float,
+    // Shifts the film across the optical axis, in the same units as the film
+    // at distance 1. Shifting up instead of tilting the camera keeps vertical
+    // lines vertical.
+    pub film_offset: (Float, Float),
+    // How far in front of the eye the film sits. The film grows with it, so
+    // the field of view is unchanged.
+    pub film_distance: Float,
+    // Primary rays only see geometry from `near` out to `far` along them, so
+    // a camera can look out of a skydome it sits in. Secondary rays aren't
+    // clipped.
+    pub near: Float,
+    pub far: Option<Float>,
     half_width: Float,
     half_height: Float,
 }
 
+// The camera of the book's first camera scenario.
+impl Default for Camera {
+    fn default() -> Self {
+        Self::new(160, 120, crate::floats::PI / 2.0)
+    }
+}
+
+// Cameras that produce the same rays. The transform is compared to within
+// EPSILON, so one rebuilt from a scene file still matches; the cached
+// inverse follows from it.
+impl PartialEq for Camera {
+    fn eq(&self, other: &Self) -> bool {
+        let close = |a: Float, b: Float| (a - b).abs() < EPSILON;
+        self.hsize == other.hsize
+            && self.vsize == other.vsize
+            && self.fov_axis == other.fov_axis
+            && close(self.field_of_view, other.field_of_view)
+            && close(self.film_offset.0, other.film_offset.0)
+            && close(self.film_offset.1, other.film_offset.1)
+            && close(self.film_distance, other.film_distance)
+            && close(self.near, other.near)
+            && self.far == other.far
+            && self.transform.approx_eq(&other.transform, EPSILON)
+    }
+}
+
 impl Camera {
     pub fn new(hsize: usize, vsize: usize, field_of_view: Float) -> Self {
+        Self::with_fov_axis(hsize, vsize, field_of_view, FovAxis::default())
+    }
+
+    pub fn with_fov_axis(
+        hsize: usize,
+        vsize: usize,
+        field_of_view: Float,
+        fov_axis: FovAxis,
+    ) -> Self {
         let half_view = (field_of_view / 2.0).tan();
         let aspect_ratio = hsize as Float / vsize as Float;
 
-        let (half_width, half_height) = if aspect_ratio >= 1.0 {
-            (half_view, half_view / aspect_ratio)
-        } else {
-            (half_view * aspect_ratio, half_view)
+        let (half_width, half_height) = match fov_axis {
+            FovAxis::Larger if aspect_ratio >= 1.0 => (half_view, half_view / aspect_ratio),
+            FovAxis::Larger => (half_view * aspect_ratio, half_view),
+            FovAxis::Horizontal => (half_view, half_view / aspect_ratio),
+            FovAxis::Vertical => (half_view * aspect_ratio, half_view),
+            FovAxis::Diagonal => {
+                let diagonal = (hsize as Float).hypot(vsize as Float);
+                (
+                    half_view * hsize as Float / diagonal,
+                    half_view * vsize as Float / diagonal,
+                )
+            }
         };
         let pixel_size = (half_width * 2.0) / hsize as Float;
 
@@ -31,25 +166,247 @@ impl Camera {
             hsize,
             vsize,
             field_of_view,
+            fov_axis,
             transform: Matrix4::identity(),
+            transform_inverse: Matrix4::identity(),
+            origin: point(0.0, 0.0, 0.0),
             pixel_size,
+            film_offset: (0.0, 0.0),
+            film_distance: 1.0,
+            near: EPSILON,
+            far: None,
             half_width,
             half_height,
         }
     }
 
+    // The same view at another image size. The pixel size follows, so the
+    // field of view still spans the image.
+    pub fn with_size(&self, hsize: usize, vsize: usize) -> Camera {
+        let mut c = Camera::with_fov_axis(hsize, vsize, self.field_of_view, self.fov_axis);
+        c.set_transform(self.transform);
+        c.film_offset = self.film_offset;
+        c.film_distance = self.film_distance;
+        c.near = self.near;
+        c.far = self.far;
+        c
+    }
+
+    pub fn transform(&self) -> Matrix4 {
+        self.transform
+    }
+
+    // Camera fields are set directly, so a renderer loading a camera from
+    // elsewhere should check it before rendering with it.
+    pub fn validate(&self) -> Result<(), CameraError> {
+        let invalid = |field, message: String| Err(CameraError { field, message });
+        if self.hsize == 0 || self.vsize == 0 {
+            let message = format!("must be at least 1x1, got {}x{}", self.hsize, self.vsize);
+            return invalid("size", message);
+        }
+        let fov = self.field_of_view;
+        if !(fov > 0.0 && fov < PI) {
+            return invalid(
+                "field_of_view",
+                format!("must be between 0 and π, got {fov}"),
+            );
+        }
+        let d = self.film_distance;
+        if !(d > 0.0 && d.is_finite()) {
+            return invalid("film_distance", format!("must be positive, got {d}"));
+        }
+        let near = self.near;
+        if !(near >= 0.0 && near.is_finite()) {
+            return invalid("near", format!("must be non-negative, got {near}"));
+        }
+        if let Some(far) = self.far
+            && (far.is_nan() || far <= near)
+        {
+            return invalid("far", format!("must be beyond near ({near}), got {far}"));
+        }
+        Ok(())
+    }
+
+    // The t range primary rays are clipped to.
+    pub fn clip_range(&self) -> (Float, Float) {
+        (self.near, self.far.unwrap_or(Float::INFINITY))
+    }
+
+    pub fn set_transform(&mut self, transform: Matrix4) {
+        self.transform = transform;
+        self.transform_inverse = transform.inverse();
+        self.origin = self.transform_inverse * point(0.0, 0.0, 0.0);
+    }
+
+    // Places the eye at `position`, turned by `rotation` from the default of
+    // looking down -z with +y up. Slerping the rotation between keyframes
+    // gives a smooth orbit without gimbal lock.
+    pub fn orient(&mut self, position: Tuple4, rotation: Quaternion) {
+        self.set_transform(
+            rotation.conjugate().to_matrix4()
+                * crate::transformations::translation(-position.x, -position.y, -position.z),
+        );
+    }
+
     pub fn ray_for_pixel(&self, px: usize, py: usize) -> Ray {
-        let xoffset = (px as Float + 0.5) * self.pixel_size;
-        let yoffset = (py as Float + 0.5) * self.pixel_size;
+        self.ray_for_pixel_offset(px, py, 0.5, 0.5)
+    }
+
+    // `dx` and `dy` are the position within the pixel, from 0 to 1.
+    pub fn ray_for_pixel_offset(&self, px: usize, py: usize, dx: Float, dy: Float) -> Ray {
+        let xoffset = (px as Float + dx) * self.pixel_size;
+        let yoffset = (py as Float + dy) * self.pixel_size;
+
+        let world_x = self.half_width - xoffset + self.film_offset.0;
+        let world_y = self.half_height - yoffset + self.film_offset.1;
+
+        let d = self.film_distance;
+        let pixel = self.transform_inverse * point(world_x * d, world_y * d, -d);
+        let direction = (pixel - self.origin).normalize();
+
+        ray(self.origin, direction)
+    }
 
-        let world_x = self.half_width - xoffset;
-        let world_y = self.half_height - yoffset;
+    // Points the camera along `direction` at the center of `bounds`, backed
+    // off just far enough for every corner of the box to be in view with a
+    // small margin. The field of view and image size are kept.
+    pub fn frame(&mut self, bounds: &BoundingBox, direction: Tuple4) {
+        if bounds.is_empty() {
+            return;
+        }
+        let center = bounds.center();
+        let direction = direction.normalize();
+        let up = if direction.y.abs() > 0.99 {
+            vector(0.0, 0.0, 1.0)
+        } else {
+            vector(0.0, 1.0, 0.0)
+        };
+        let origin = point(0.0, 0.0, 0.0);
+        let orientation = view_transform(origin, origin + direction, up);
+        let (tan_x, tan_y) = (
+            self.half_width / (1.0 + FRAME_MARGIN),
+            self.half_height / (1.0 + FRAME_MARGIN),
+        );
+        // In the oriented frame the camera sits at z = d looking down -z, so a
+        // corner q is at depth d - q.z and fits when |q.x| <= tan_x * depth.
+        let distance = bounds
+            .corners()
+            .iter()
+            .map(|&corner| orientation * (corner - center))
+            .map(|q| q.z + (q.x.abs() / tan_x).max(q.y.abs() / tan_y))
+            .fold(0.0, Float::max);
+        let eye = center - direction * distance;
+        self.set_transform(view_transform(eye, center, up));
+    }
+
+    // Left and right eye cameras, eye_separation apart along this camera's
+    // own x axis. The eyes stay parallel and their films are shifted instead,
+    // so points convergence_distance ahead line up in both images without the
+    // keystoning that toeing the eyes in would cause.
+    pub fn stereo(&self, eye_separation: Float, convergence_distance: Float) -> (Camera, Camera) {
+        let eye = |side: Float| {
+            // +x in camera space is the left of the image.
+            let shift = side * eye_separation / 2.0;
+            let mut c = self.clone();
+            c.set_transform(crate::transformations::translation(-shift, 0.0, 0.0) * self.transform);
+            c.film_offset.0 -= shift / convergence_distance;
+            c
+        };
+        (eye(1.0), eye(-1.0))
+    }
+
+    // The pixel a world point lands on, or None when world_to_pixel gives
+    // None.
+    pub fn project_point(&self, world_point: Tuple4) -> Option<(usize, usize)> {
+        let (px, py) = self.world_to_pixel(world_point)?;
+        // The far edges of the image belong to no pixel.
+        if px >= self.hsize as Float || py >= self.vsize as Float {
+            return None;
+        }
+        Some((px.floor() as usize, py.floor() as usize))
+    }
+
+    // Where a world point lands on the image, in fractional pixels from the
+    // top-left corner, so pixel (x, y) has its center at (x + 0.5, y + 0.5).
+    // The reverse of ray_for_pixel. None when the point is behind the camera,
+    // off the image, or outside the near..far range.
+    pub fn world_to_pixel(&self, world_point: Tuple4) -> Option<(Float, Float)> {
+        let (near, far) = self.clip_range();
+        if !(near..=far).contains(&self.depth_of(world_point)) {
+            return None;
+        }
+        let (px, py) = self.project_to_image(world_point)?;
+        if px < 0.0 || py < 0.0 || px > self.hsize as Float || py > self.vsize as Float {
+            return None;
+        }
+        Some((px, py))
+    }
+
+    // How far a world point is from the eye along the primary ray through
+    // it, which is the t that near and far clip. Negative behind the camera.
+    pub fn depth_of(&self, world_point: Tuple4) -> Float {
+        let distance = (world_point - self.origin).magnitude();
+        if (self.transform * world_point).z > 0.0 {
+            -distance
+        } else {
+            distance
+        }
+    }
 
-        let pixel = self.transform.inverse() * point(world_x, world_y, -1.0);
-        let origin = self.transform.inverse() * point(0.0, 0.0, 0.0);
-        let direction = (pixel - origin).normalize();
+    // Where a world point lands in pixel units, even off the image, or None
+    // when it is behind the camera.
+    fn project_to_image(&self, world_point: Tuple4) -> Option<(Float, Float)> {
+        let p = self.transform * world_point;
+        if p.z >= 0.0 {
+            return None;
+        }
+        // Where the line from the eye through p crosses the film at distance 1.
+        let film_x = p.x / -p.z;
+        let film_y = p.y / -p.z;
+        Some((
+            (self.half_width + self.film_offset.0 - film_x) / self.pixel_size,
+            (self.half_height + self.film_offset.1 - film_y) / self.pixel_size,
+        ))
+    }
 
-        ray(origin, direction)
+    // Every pixel the box could cover, or None when it is wholly off the image
+    // or behind the camera. A box reaching behind the camera or out to
+    // infinity could cover anything, so it gets the whole image.
+    pub fn project_bounds(&self, bounds: &BoundingBox) -> Option<PixelRect> {
+        if bounds.is_empty() {
+            return None;
+        }
+        let whole = PixelRect::new(0, 0, self.hsize, self.vsize);
+        let corners = bounds.corners();
+        if corners
+            .iter()
+            .any(|p| !(p.x.is_finite() && p.y.is_finite() && p.z.is_finite()))
+        {
+            return Some(whole);
+        }
+        let projected: Vec<_> = corners.iter().map(|&p| self.project_to_image(p)).collect();
+        if projected.iter().all(Option::is_none) {
+            return None;
+        }
+        if projected.iter().any(Option::is_none) {
+            return Some(whole);
+        }
+        let (mut x0, mut y0) = (Float::INFINITY, Float::INFINITY);
+        let (mut x1, mut y1) = (Float::NEG_INFINITY, Float::NEG_INFINITY);
+        for (x, y) in projected.into_iter().flatten() {
+            (x0, y0) = (x0.min(x), y0.min(y));
+            (x1, y1) = (x1.max(x), y1.max(y));
+        }
+        // A corner exactly on a pixel edge belongs to the pixel after it, as
+        // in project_point.
+        let clamp = |v: Float, size: usize| v.clamp(0.0, size as Float) as usize;
+        let rect = PixelRect::new(
+            clamp(x0.floor(), self.hsize),
+            clamp(y0.floor(), self.vsize),
+            clamp(x1.floor() + 1.0, self.hsize),
+            clamp(y1.floor() + 1.0, self.vsize),
+        );
+        (!rect.is_empty()).then_some(rect)
     }
 }
 
@@ -58,10 +415,13 @@ mod tests {
     use crate::floats::check_float;
     use crate::floats::consts::FRAC_1_SQRT_2;
     use crate::floats::consts::PI;
-    use crate::transformations::{rotation_y, translation};
+    use crate::quaternions::Quaternion;
+    use crate::transformations::{rotation_y, translation, view_transform};
     use crate::tuples::check_tuple;
     use crate::{
-        camera::Camera,
+        bounds::BoundingBox,
+        camera::{Camera, FovAxis, PixelRect},
+        floats::{EPSILON, Float},
         matrices::Matrix4,
         tuples::{point, vector},
     };
@@ -81,7 +441,7 @@ mod tests {
         assert_eq!(c.hsize, 160);
         assert_eq!(c.vsize, 120);
         assert_eq!(c.field_of_view, PI / 2.0);
-        assert_eq!(c.transform, Matrix4::identity());
+        assert_eq!(c.transform(), Matrix4::identity());
     }
 
     // Scenario: The pixel size for a horizontal canvas
@@ -138,9 +498,269 @@ mod tests {
     #[test]
     fn constructing_a_ray_when_the_camera_is_transformed() {
         let mut c = Camera::new(201, 101, PI / 2.0);
-        c.transform = rotation_y(PI / 4.0) * translation(0.0, -2.0, 5.0);
+        c.set_transform(rotation_y(PI / 4.0) * translation(0.0, -2.0, 5.0));
         let r = c.ray_for_pixel(100, 50);
         check_tuple(r.origin, point(0.0, 2.0, -5.0));
         check_tuple(r.direction, vector(FRAC_1_SQRT_2, 0.0, -FRAC_1_SQRT_2));
     }
+
+    #[test]
+    fn a_vertical_fov_keeps_the_same_height_in_portrait_and_landscape() {
+        let landscape = Camera::with_fov_axis(200, 100, PI / 3.0, FovAxis::Vertical);
+        let portrait = Camera::with_fov_axis(100, 200, PI / 3.0, FovAxis::Vertical);
+        check_float(landscape.half_height, portrait.half_height);
+        check_float(landscape.half_height, (PI / 6.0).tan());
+    }
+
+    #[test]
+    fn a_horizontal_fov_spans_the_width_of_a_portrait_image() {
+        let c = Camera::with_fov_axis(125, 200, PI / 2.0, FovAxis::Horizontal);
+        check_float(c.half_width, 1.0);
+        check_float(c.pixel_size, 2.0 / 125.0);
+    }
+
+    #[test]
+    fn a_diagonal_fov_spans_the_corners() {
+        let c = Camera::with_fov_axis(300, 400, PI / 2.0, FovAxis::Diagonal);
+        check_float(c.half_width.hypot(c.half_height), 1.0);
+        check_float(c.pixel_size * c.vsize as Float, c.half_height * 2.0);
+    }
+
+    #[test]
+    fn film_distance_leaves_rays_unchanged() {
+        let mut c = Camera::new(201, 101, PI / 2.0);
+        c.set_transform(rotation_y(PI / 4.0) * translation(0.0, -2.0, 5.0));
+        let expected = c.ray_for_pixel(30, 70);
+        c.film_distance = 3.5;
+        let r = c.ray_for_pixel(30, 70);
+        check_tuple(r.origin, expected.origin);
+        check_tuple(r.direction, expected.direction);
+    }
+
+    #[test]
+    fn a_film_offset_shifts_the_center_ray() {
+        let mut c = Camera::new(201, 101, PI / 2.0);
+        c.film_offset = (0.0, 0.5);
+        let r = c.ray_for_pixel(100, 50);
+        check_tuple(r.direction, vector(0.0, 0.5, -1.0).normalize());
+    }
+
+    // The column of the first lit pixel in each of the top and bottom rows.
+    fn left_edges(c: Camera) -> (usize, usize) {
+        use crate::{
+            colors::Color, lighting::point_light, rectangles::Rectangle,
+            transformations::rotation_x, world::WorldBuilder,
+        };
+        // A tall panel standing upright 10 units in front of the camera.
+        let mut panel = Rectangle::new(4.0, 30.0);
+        panel
+            .set_transform(translation(0.0, 5.0, 10.0) * rotation_x(-PI / 2.0))
+            .unwrap();
+        let mut w = WorldBuilder::with_light(point_light(
+            point(0.0, 10.0, -10.0),
+            Color::new(1.0, 1.0, 1.0),
+        ));
+        w.shapes.push(Box::new(panel));
+        let image = crate::world::render(c, w);
+        let edge = |y: usize| {
+            (0..image.width)
+                .find(|&x| image.pixel_at(x, y).red > 0.0)
+                .expect("the panel spans every row")
+        };
+        (edge(0), edge(image.height - 1))
+    }
+
+    #[test]
+    fn shifting_the_film_keeps_vertical_edges_vertical() {
+        let eye = point(0.0, 1.0, 0.0);
+        let mut shifted = Camera::new(60, 60, PI / 3.0);
+        shifted.set_transform(crate::transformations::view_transform(
+            eye,
+            point(0.0, 1.0, 10.0),
+            vector(0.0, 1.0, 0.0),
+        ));
+        shifted.film_offset = (0.0, 0.4);
+        let (top, bottom) = left_edges(shifted);
+        assert_eq!(top, bottom);
+
+        let mut tilted = Camera::new(60, 60, PI / 3.0);
+        tilted.set_transform(crate::transformations::view_transform(
+            eye,
+            point(0.0, 5.0, 10.0),
+            vector(0.0, 1.0, 0.0),
+        ));
+        let (top, bottom) = left_edges(tilted);
+        assert!(top > bottom, "{top} {bottom}");
+    }
+
+    #[test]
+    fn a_projected_point_lands_on_the_pixel_whose_ray_hits_it() {
+        let mut c = Camera::new(201, 101, PI / 2.0);
+        c.set_transform(rotation_y(PI / 4.0) * translation(0.0, -2.0, 5.0));
+        c.film_offset = (0.1, -0.2);
+        for (px, py) in [(30, 70), (100, 50), (0, 0), (200, 100)] {
+            let r = c.ray_for_pixel(px, py);
+            assert_eq!(c.project_point(r.position(7.5)), Some((px, py)));
+        }
+    }
+
+    #[test]
+    fn points_behind_the_camera_or_off_the_image_do_not_project() {
+        let c = Camera::new(11, 11, PI / 2.0);
+        assert_eq!(c.project_point(point(0.0, 0.0, 5.0)), None);
+        assert_eq!(c.project_point(point(100.0, 0.0, -1.0)), None);
+        assert_eq!(c.project_point(point(0.0, 0.0, -3.0)), Some((5, 5)));
+    }
+
+    #[test]
+    fn world_to_pixel_undoes_ray_for_pixel() {
+        let mut c = Camera::new(201, 101, PI / 2.0);
+        c.set_transform(rotation_y(PI / 4.0) * translation(0.0, -2.0, 5.0));
+        c.film_offset = (0.1, -0.2);
+        c.film_distance = 2.0;
+        for (px, py) in [(100, 50), (0, 0), (200, 0), (0, 100), (200, 100), (37, 81)] {
+            let r = c.ray_for_pixel(px, py);
+            for t in [0.5, 7.5, 300.0] {
+                let (x, y) = c.world_to_pixel(r.position(t)).unwrap();
+                assert!((x - (px as Float + 0.5)).abs() < 1e-3, "{x} {px}");
+                assert!((y - (py as Float + 0.5)).abs() < 1e-3, "{y} {py}");
+            }
+        }
+    }
+
+    #[test]
+    fn world_to_pixel_leaves_out_points_outside_the_view() {
+        let mut c = Camera::new(11, 11, PI / 2.0);
+        assert_eq!(c.world_to_pixel(point(0.0, 0.0, -3.0)), Some((5.5, 5.5)));
+        check_float(c.depth_of(point(1.0, 2.0, -3.0)), (14.0 as Float).sqrt());
+        check_float(c.depth_of(point(0.0, 0.0, 5.0)), -5.0);
+        assert_eq!(c.world_to_pixel(point(0.0, 0.0, 5.0)), None);
+        assert_eq!(c.world_to_pixel(point(100.0, 0.0, -1.0)), None);
+        // Near the image's left edge, as +x in camera space is to the left.
+        let (x, _) = c.world_to_pixel(point(2.7, 0.0, -3.0)).unwrap();
+        check_float(x, 0.55);
+        assert_eq!(c.world_to_pixel(point(3.3, 0.0, -3.0)), None);
+        c.near = 4.0;
+        assert_eq!(c.world_to_pixel(point(0.0, 0.0, -3.0)), None);
+        c.near = 1.0;
+        c.far = Some(2.0);
+        assert_eq!(c.world_to_pixel(point(0.0, 0.0, -3.0)), None);
+    }
+
+    #[test]
+    fn off_axis_points_are_clipped_where_their_pixels_ray_is() {
+        let mut c = Camera::new(11, 11, PI / 2.0);
+        c.set_transform(rotation_y(PI / 6.0) * translation(1.0, 0.0, 2.0));
+        c.far = Some(3.0);
+        let r = c.ray_for_pixel(1, 9);
+        // Well off axis, so these are nearer than far in view-space z too.
+        let inside = r.position(2.95);
+        let beyond = r.position(3.05);
+        assert!(-(c.transform() * beyond).z < 3.0);
+        check_float(c.depth_of(inside), 2.95);
+        check_float(c.depth_of(beyond), 3.05);
+        let (x, y) = c.world_to_pixel(inside).unwrap();
+        assert!((x - 1.5).abs() < 1e-3 && (y - 9.5).abs() < 1e-3, "{x} {y}");
+        assert_eq!(c.world_to_pixel(beyond), None);
+    }
+
+    #[test]
+    fn orienting_a_camera_matches_the_view_transform() {
+        let mut c = Camera::new(11, 11, PI / 2.0);
+        c.orient(
+            point(0.0, 0.0, -8.0),
+            Quaternion::from_axis_angle(vector(0.0, 1.0, 0.0), PI),
+        );
+        crate::matrices::check(
+            c.transform(),
+            view_transform(
+                point(0.0, 0.0, -8.0),
+                point(0.0, 0.0, 0.0),
+                vector(0.0, 1.0, 0.0),
+            ),
+        );
+        let r = c.ray_for_pixel(5, 5);
+        check_tuple(r.origin, point(0.0, 0.0, -8.0));
+        check_tuple(r.direction, vector(0.0, 0.0, 1.0));
+    }
+
+    #[test]
+    fn stereo_eyes_sit_apart_along_the_cameras_own_x_axis() {
+        let mut c = Camera::new(11, 11, PI / 2.0);
+        c.set_transform(view_transform(
+            point(0.0, 0.0, 0.0),
+            point(1.0, 0.0, 0.0),
+            vector(0.0, 1.0, 0.0),
+        ));
+        let (left, right) = c.stereo(0.2, 5.0);
+        let eye = |c: &Camera| c.transform().inverse() * point(0.0, 0.0, 0.0);
+        // The book's space is left-handed, so looking down +x puts +z on
+        // the left.
+        check_tuple(eye(&left), point(0.0, 0.0, 0.1));
+        check_tuple(eye(&right), point(0.0, 0.0, -0.1));
+        // Both centers look at the point on the axis 5 units ahead.
+        for e in [&left, &right] {
+            assert_eq!(e.project_point(point(5.0, 0.0, 0.0)), Some((5, 5)));
+        }
+    }
+
+    #[test]
+    fn cameras_that_differ_only_by_rounding_are_equal() {
+        let mut a = Camera::new(201, 101, PI / 2.0);
+        a.set_transform(rotation_y(PI / 4.0) * translation(0.0, -2.0, 5.0));
+        let mut b = Camera::new(201, 101, PI / 2.0 + 1e-6);
+        b.set_transform(rotation_y(PI / 4.0 + 1e-6) * translation(0.0, -2.0, 5.0));
+        assert_eq!(a, b);
+        assert_eq!(a.clone(), a);
+    }
+
+    #[test]
+    fn cameras_with_a_different_size_view_or_transform_differ() {
+        let a = Camera::default();
+        assert_eq!(a, Camera::new(160, 120, PI / 2.0));
+        assert_ne!(a, Camera::new(160, 100, PI / 2.0));
+        assert_ne!(a, Camera::new(160, 120, PI / 3.0));
+        let mut moved = Camera::default();
+        moved.set_transform(translation(0.0, 0.0, 0.01));
+        assert_ne!(a, moved);
+    }
+
+    #[test]
+    fn projecting_a_box_covers_every_pixel_its_corners_land_on() {
+        let c = Camera::default();
+        let b = BoundingBox::new(point(-0.5, -0.5, -3.5), point(0.5, 0.5, -2.5));
+        let rect = c.project_bounds(&b).unwrap();
+        for corner in b.corners() {
+            let (x, y) = c.project_point(corner).unwrap();
+            assert!(rect.contains(x, y), "{rect:?} {x} {y}");
+        }
+        assert!(rect.area() < c.hsize * c.vsize / 4);
+    }
+
+    #[test]
+    fn boxes_behind_the_camera_project_to_nothing_and_around_it_to_everything() {
+        let c = Camera::default();
+        let whole = Some(PixelRect::new(0, 0, c.hsize, c.vsize));
+        let behind = BoundingBox::new(point(-1.0, -1.0, 2.0), point(1.0, 1.0, 3.0));
+        assert_eq!(c.project_bounds(&behind), None);
+        let around = BoundingBox::new(point(-1.0, -1.0, -1.0), point(1.0, 1.0, 1.0));
+        assert_eq!(c.project_bounds(&around), whole);
+        assert_eq!(c.project_bounds(&BoundingBox::infinite()), whole);
+        assert_eq!(c.project_bounds(&BoundingBox::empty()), None);
+        let off_to_the_side = BoundingBox::new(point(50.0, 0.0, -3.0), point(51.0, 1.0, -2.0));
+        assert_eq!(c.project_bounds(&off_to_the_side), None);
+    }
+
+    #[test]
+    fn clipping_ranges_must_run_forwards() {
+        let mut c = Camera::default();
+        assert_eq!(c.clip_range(), (EPSILON, Float::INFINITY));
+        c.near = 2.0;
+        c.far = Some(1.0);
+        assert_eq!(c.validate().unwrap_err().field, "far");
+        c.far = Some(3.0);
+        assert!(c.validate().is_ok());
+        c.near = -1.0;
+        assert_eq!(c.validate().unwrap_err().field, "near");
+    }
 }
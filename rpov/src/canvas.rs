@@ -1,20 +1,42 @@
-use crate::{colors::Color, floats::Float};
+use std::{fmt, path::Path, path::PathBuf};
 
+use crate::{colors::Color, error::RpovError, floats::Float};
+
+#[derive(Clone)]
 pub struct Canvas {
     pub width: usize,
     pub height: usize,
     pixels: Vec<Color>,
+    // Coverage per pixel. Canvases without it are fully opaque.
+    alpha: Option<Vec<Float>>,
 }
 
 impl Canvas {
     pub fn new(width: usize, height: usize) -> Self {
+        let len = width
+            .checked_mul(height)
+            .unwrap_or_else(|| panic!("a {width}x{height} canvas is too large"));
         Canvas {
             width,
             height,
-            pixels: vec![Color::new(0.0, 0.0, 0.0); width * height],
+            pixels: vec![Color::new(0.0, 0.0, 0.0); len],
+            alpha: None,
+        }
+    }
+
+    // A canvas that starts fully transparent.
+    pub fn with_alpha(width: usize, height: usize) -> Self {
+        let canvas = Canvas::new(width, height);
+        Canvas {
+            alpha: Some(vec![0.0; canvas.pixels.len()]),
+            ..canvas
         }
     }
 
+    pub fn has_alpha(&self) -> bool {
+        self.alpha.is_some()
+    }
+
     fn check_xy(&self, x: usize, y: usize) -> usize {
         assert!(
             x < self.width && y < self.height,
@@ -52,23 +74,202 @@ impl Canvas {
         self.pixels[pos]
     }
 
+    // Adds an alpha channel on first use, with every other pixel opaque.
+    pub fn write_alpha(&mut self, x: usize, y: usize, alpha: Float) {
+        let pos = self.check_xy(x, y);
+        let len = self.pixels.len();
+        self.alpha.get_or_insert_with(|| vec![1.0; len])[pos] = alpha;
+    }
+
+    pub fn alpha_at(&self, x: usize, y: usize) -> Float {
+        let pos = self.check_xy(x, y);
+        self.alpha.as_ref().map_or(1.0, |alpha| alpha[pos])
+    }
+
+    // Adds other * weight to every pixel. Nothing is clamped, so canvases can
+    // be summed and then scaled into an average.
+    pub fn accumulate(&mut self, other: &Canvas, weight: Float) {
+        assert!(
+            self.width == other.width && self.height == other.height,
+            "cannot accumulate a {}x{} canvas into a {}x{} one",
+            other.width,
+            other.height,
+            self.width,
+            self.height
+        );
+        for (pixel, o) in self.pixels.iter_mut().zip(&other.pixels) {
+            *pixel = *pixel + *o * weight;
+        }
+        if let Some(alpha) = self.alpha.as_mut() {
+            for (i, a) in alpha.iter_mut().enumerate() {
+                *a += other.alpha.as_ref().map_or(1.0, |o| o[i]) * weight;
+            }
+        }
+    }
+
+    pub fn scale(&mut self, factor: Float) {
+        for pixel in self.pixels.iter_mut() {
+            *pixel = *pixel * factor;
+        }
+        if let Some(alpha) = self.alpha.as_mut() {
+            alpha.iter_mut().for_each(|a| *a *= factor);
+        }
+    }
+
+    // Stretched or shrunk to width x height. Colors are blended as they are
+    // stored, without gamma, and alpha is resized with them.
+    pub fn resize(&self, width: usize, height: usize, filter: Filter) -> Canvas {
+        if (width, height) == (self.width, self.height) {
+            return self.clone();
+        }
+        let mut resized = if self.has_alpha() {
+            Canvas::with_alpha(width, height)
+        } else {
+            Canvas::new(width, height)
+        };
+        // Bilinear samples skip source pixels when shrinking by more than 2,
+        // so it averages boxes instead.
+        let shrinking = self.width > 2 * width || self.height > 2 * height;
+        let filter = match filter {
+            Filter::Bilinear if shrinking => Filter::Box,
+            f => f,
+        };
+        for y in 0..height {
+            for x in 0..width {
+                let weights: Vec<(usize, usize, Float)> = match filter {
+                    Filter::Nearest => {
+                        let sx = (2 * x + 1) * self.width / (2 * width);
+                        let sy = (2 * y + 1) * self.height / (2 * height);
+                        vec![(sx, sy, 1.0)]
+                    }
+                    Filter::Bilinear => {
+                        let xs = linear_weights(x, self.width, width);
+                        let ys = linear_weights(y, self.height, height);
+                        cross_weights(&xs, &ys)
+                    }
+                    Filter::Box => {
+                        let xs = box_weights(x, self.width, width);
+                        let ys = box_weights(y, self.height, height);
+                        cross_weights(&xs, &ys)
+                    }
+                };
+                let mut color = Color::new(0.0, 0.0, 0.0);
+                let mut alpha = 0.0;
+                for (sx, sy, w) in weights {
+                    color = color + self.pixel_at(sx, sy) * w;
+                    alpha += self.alpha_at(sx, sy) * w;
+                }
+                resized.write_pixel(x, y, color);
+                if resized.has_alpha() {
+                    resized.write_alpha(x, y, alpha);
+                }
+            }
+        }
+        resized
+    }
+
+    // Copies src into this canvas with its top-left corner at (x, y). Anything
+    // falling outside this canvas is dropped.
+    pub fn blit(&mut self, src: &Canvas, x: usize, y: usize) {
+        for sy in 0..src.height.min(self.height.saturating_sub(y)) {
+            for sx in 0..src.width.min(self.width.saturating_sub(x)) {
+                self.write_pixel(x + sx, y + sy, src.pixel_at(sx, sy));
+                if src.has_alpha() {
+                    self.write_alpha(x + sx, y + sy, src.alpha_at(sx, sy));
+                }
+            }
+        }
+    }
+
+    // Like write_pixel, but points off the canvas are ignored, so shapes can
+    // be drawn partly outside it.
+    fn plot(&mut self, x: i64, y: i64, color: Color) {
+        if x >= 0 && y >= 0 && (x as usize) < self.width && (y as usize) < self.height {
+            self.write_pixel(x as usize, y as usize, color);
+        }
+    }
+
+    // Bresenham's line, both endpoints included.
+    pub fn draw_line(&mut self, x0: i64, y0: i64, x1: i64, y1: i64, color: Color) {
+        let dx = (x1 - x0).abs();
+        let dy = -(y1 - y0).abs();
+        let sx = if x0 < x1 { 1 } else { -1 };
+        let sy = if y0 < y1 { 1 } else { -1 };
+        let (mut x, mut y) = (x0, y0);
+        let mut err = dx + dy;
+        loop {
+            self.plot(x, y, color);
+            if x == x1 && y == y1 {
+                break;
+            }
+            let e2 = 2 * err;
+            if e2 >= dy {
+                err += dy;
+                x += sx;
+            }
+            if e2 <= dx {
+                err += dx;
+                y += sy;
+            }
+        }
+    }
+
+    // The outline of a circle, by the midpoint algorithm.
+    pub fn draw_circle(&mut self, cx: i64, cy: i64, r: i64, color: Color) {
+        let (mut x, mut y) = (r, 0);
+        let mut err = 1 - r;
+        while x >= y {
+            for (px, py) in [
+                (x, y),
+                (y, x),
+                (-y, x),
+                (-x, y),
+                (-x, -y),
+                (-y, -x),
+                (y, -x),
+                (x, -y),
+            ] {
+                self.plot(cx + px, cy + py, color);
+            }
+            y += 1;
+            if err < 0 {
+                err += 2 * y + 1;
+            } else {
+                x -= 1;
+                err += 2 * (y - x) + 1;
+            }
+        }
+    }
+
+    // The outline of a w x h rectangle with its top-left corner at (x, y).
+    pub fn draw_rect(&mut self, x: i64, y: i64, w: i64, h: i64, color: Color) {
+        if w <= 0 || h <= 0 {
+            return;
+        }
+        let (right, bottom) = (x + w - 1, y + h - 1);
+        self.draw_line(x, y, right, y, color);
+        self.draw_line(x, bottom, right, bottom, color);
+        self.draw_line(x, y, x, bottom, color);
+        self.draw_line(right, y, right, bottom, color);
+    }
+
     pub fn to_ppm(&self) -> String {
+        self.to_plain_ppm(255)
+    }
+
+    // A plain (P3) PPM whose channels run from 0 to max_value. Lines are
+    // kept to 70 characters, as the format asks.
+    pub fn to_plain_ppm(&self, max_value: u16) -> String {
         let mut ppm = String::new();
         ppm.push_str("P3\n");
         ppm.push_str(&format!("{} {}\n", self.width, self.height));
-        ppm.push_str("255\n");
+        ppm.push_str(&format!("{max_value}\n"));
 
         for y in 0..self.height {
             let mut line = String::new();
             let mut line_len = 0;
             for x in 0..self.width {
-                let color = self.pixel_at(x, y);
-                let (r, g, b) = (
-                    Canvas::scale_color(color.red),
-                    Canvas::scale_color(color.green),
-                    Canvas::scale_color(color.blue),
-                );
-                for val in [r, g, b] {
+                for val in self.samples_at(x, y, max_value) {
                     let s = val.to_string();
                     // +1 for the space if not first in line
                     let extra = if line_len == 0 { 0 } else { 1 };
@@ -96,12 +297,540 @@ impl Canvas {
         ppm
     }
 
-    fn scale_color(c: Float) -> u8 {
+    // A binary (P6) PPM whose channels run from 0 to max_value. Above 255
+    // each sample takes two bytes, most significant first.
+    pub fn to_binary_ppm(&self, max_value: u16) -> Vec<u8> {
+        let mut ppm = format!("P6\n{} {}\n{max_value}\n", self.width, self.height).into_bytes();
+        for y in 0..self.height {
+            for x in 0..self.width {
+                for val in self.samples_at(x, y, max_value) {
+                    if max_value > 255 {
+                        ppm.extend(val.to_be_bytes());
+                    } else {
+                        ppm.push(val as u8);
+                    }
+                }
+            }
+        }
+        ppm
+    }
+
+    fn samples_at(&self, x: usize, y: usize, max_value: u16) -> [u16; 3] {
+        let color = self.pixel_at(x, y);
+        [color.red, color.green, color.blue].map(|c| Canvas::scale_channel(c, max_value))
+    }
+
+    // The pixels row by row as red, green, blue triples, in f32 whatever the
+    // crate's Float is, for external tools such as denoisers.
+    #[allow(clippy::unnecessary_cast)]
+    pub fn as_f32_rgb(&self) -> Vec<f32> {
+        self.pixels
+            .iter()
+            .flat_map(|c| [c.red as f32, c.green as f32, c.blue as f32])
+            .collect()
+    }
+
+    #[allow(clippy::unnecessary_cast)]
+    pub fn from_f32_rgb(width: usize, height: usize, rgb: &[f32]) -> Canvas {
+        assert_eq!(
+            rgb.len(),
+            width * height * 3,
+            "a {width}x{height} canvas needs {} floats",
+            width * height * 3
+        );
+        let mut canvas = Canvas::new(width, height);
+        for (pixel, c) in canvas.pixels.iter_mut().zip(rgb.chunks_exact(3)) {
+            *pixel = Color::new(c[0] as Float, c[1] as Float, c[2] as Float);
+        }
+        canvas
+    }
+
+    pub fn write_ppm(&self, path: impl AsRef<Path>) -> Result<(), RpovError> {
+        self.write_ppm_with(path, PpmFormat::Plain, 255)
+    }
+
+    // Like write_ppm, in either format and with channels from 0 to
+    // max_value, such as 65535 for 16-bit output.
+    pub fn write_ppm_with(
+        &self,
+        path: impl AsRef<Path>,
+        format: PpmFormat,
+        max_value: u16,
+    ) -> Result<(), RpovError> {
+        let path = path.as_ref();
+        let bytes = match format {
+            PpmFormat::Plain => self.to_plain_ppm(max_value).into_bytes(),
+            PpmFormat::Binary => self.to_binary_ppm(max_value),
+        };
+        std::fs::write(path, bytes).map_err(|e| RpovError::io(path, e))
+    }
+
+    // An 8-bit PNG, RGBA when the canvas has an alpha channel and RGB
+    // otherwise. The image data is stored rather than compressed, so no
+    // codec is needed; files are about the size of a binary PPM.
+    pub fn to_png(&self) -> Vec<u8> {
+        let channels = if self.has_alpha() { 4 } else { 3 };
+        let mut raw = Vec::with_capacity((self.width * channels + 1) * self.height);
+        for y in 0..self.height {
+            raw.push(0); // no filter
+            for x in 0..self.width {
+                raw.extend(self.samples_at(x, y, 255).map(|v| v as u8));
+                if self.has_alpha() {
+                    raw.push(Canvas::scale_channel(self.alpha_at(x, y), 255) as u8);
+                }
+            }
+        }
+        let mut zlib = vec![0x78, 0x01];
+        let mut blocks = raw.chunks(0xffff).peekable();
+        if blocks.peek().is_none() {
+            zlib.extend([1, 0, 0, 0xff, 0xff]);
+        }
+        while let Some(block) = blocks.next() {
+            let len = block.len() as u16;
+            zlib.push(u8::from(blocks.peek().is_none()));
+            zlib.extend(len.to_le_bytes());
+            zlib.extend((!len).to_le_bytes());
+            zlib.extend(block);
+        }
+        zlib.extend(adler32(&raw).to_be_bytes());
+
+        let mut header = Vec::with_capacity(13);
+        header.extend((self.width as u32).to_be_bytes());
+        header.extend((self.height as u32).to_be_bytes());
+        let color_type = if self.has_alpha() { 6 } else { 2 };
+        header.extend([8, color_type, 0, 0, 0]); // 8-bit RGB(A), not interlaced
+
+        let mut png = b"\x89PNG\r\n\x1a\n".to_vec();
+        png_chunk(&mut png, b"IHDR", &header);
+        png_chunk(&mut png, b"IDAT", &zlib);
+        png_chunk(&mut png, b"IEND", &[]);
+        png
+    }
+
+    pub fn write_png(&self, path: impl AsRef<Path>) -> Result<(), RpovError> {
+        let path = path.as_ref();
+        std::fs::write(path, self.to_png()).map_err(|e| RpovError::io(path, e))
+    }
+
+    pub fn read_ppm(path: impl AsRef<Path>) -> Result<Canvas, RpovError> {
+        let path = path.as_ref();
+        let bytes = std::fs::read(path).map_err(|e| RpovError::io(path, e))?;
+        Canvas::from_ppm_bytes(&bytes).map_err(|e| RpovError::image_at(path, e))
+    }
+
+    // Reads a plain (P3) PPM, as written by to_ppm. Channels are scaled by
+    // the file's maximum value, so they come back between 0 and 1.
+    pub fn from_ppm(ppm: &str) -> Result<Canvas, ImageError> {
+        Canvas::from_ppm_bytes(ppm.as_bytes())
+    }
+
+    // Reads a plain (P3) or binary (P6) PPM with any maximum value up to
+    // 65535. Channels are scaled by the maximum, as in from_ppm.
+    pub fn from_ppm_bytes(ppm: &[u8]) -> Result<Canvas, ImageError> {
+        let mut header = PpmTokens::new(ppm);
+        let (line, magic) = header.next("P3 or P6")?;
+        let format = match magic {
+            "P3" => PpmFormat::Plain,
+            "P6" => PpmFormat::Binary,
+            _ => {
+                let message = format!("expected P3 or P6, found `{magic}`");
+                return Err(ImageError::new(line, message));
+            }
+        };
+        let (_, width) = header.number("a width")?;
+        let (size_line, height) = header.number("a height")?;
+        let (line, max) = header.number("a maximum value")?;
+        if max == 0 || max > 65535 {
+            return Err(ImageError::new(
+                line,
+                format!("the maximum value must be from 1 to 65535, got {max}"),
+            ));
+        }
+        // The size in the header is checked against the data that follows
+        // before anything is allocated for it. Plain samples take at least a
+        // digit each.
+        let stride = if max > 255 { 2 } else { 1 };
+        let sample_bytes = match format {
+            PpmFormat::Plain => 1,
+            PpmFormat::Binary => stride,
+        };
+        let Some(needed) = width
+            .checked_mul(height)
+            .and_then(|pixels| pixels.checked_mul(3 * sample_bytes))
+        else {
+            let message = format!("a {width}x{height} image is too large");
+            return Err(ImageError::new(size_line, message));
+        };
+        // A single whitespace byte separates the header from the data.
+        let data = ppm.get(header.pos + 1..).unwrap_or_default();
+        if data.len() < needed {
+            let message = match format {
+                PpmFormat::Plain => format!(
+                    "expected {} color values, found {} bytes",
+                    needed,
+                    data.len()
+                ),
+                PpmFormat::Binary => format!(
+                    "expected {needed} bytes of pixel data, found {}",
+                    data.len()
+                ),
+            };
+            return Err(ImageError::new(header.line, message));
+        }
+
+        let mut canvas = Canvas::new(width, height);
+        let scale = |line: usize, value: usize| {
+            if value > max {
+                let message = format!("color value {value} is above the maximum {max}");
+                return Err(ImageError::new(line, message));
+            }
+            Ok(value as Float / max as Float)
+        };
+        match format {
+            PpmFormat::Plain => {
+                let mut channel = || -> Result<Float, ImageError> {
+                    let (line, value) = header.number("a color value")?;
+                    scale(line, value)
+                };
+                for y in 0..height {
+                    for x in 0..width {
+                        let color = Color::new(channel()?, channel()?, channel()?);
+                        canvas.write_pixel(x, y, color);
+                    }
+                }
+            }
+            PpmFormat::Binary => {
+                let mut samples = data[..needed].chunks_exact(stride).map(|b| match b {
+                    [hi, lo] => u16::from_be_bytes([*hi, *lo]) as usize,
+                    _ => b[0] as usize,
+                });
+                let mut channel = || scale(header.line, samples.next().unwrap_or_default());
+                for y in 0..height {
+                    for x in 0..width {
+                        let color = Color::new(channel()?, channel()?, channel()?);
+                        canvas.write_pixel(x, y, color);
+                    }
+                }
+            }
+        }
+        Ok(canvas)
+    }
+
+    fn scale_channel(c: Float, max_value: u16) -> u16 {
         let c = c.clamp(0.0, 1.0);
-        (c * 255.0).round() as u8
+        (c * max_value as Float).round() as u16
+    }
+}
+
+fn png_chunk(png: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    png.extend((data.len() as u32).to_be_bytes());
+    let start = png.len();
+    png.extend(kind);
+    png.extend(data);
+    let crc = crc32(&png[start..]);
+    png.extend(crc.to_be_bytes());
+}
+
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &b in bytes {
+        crc ^= b as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 {
+                (crc >> 1) ^ 0xedb8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+fn adler32(bytes: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for &byte in bytes {
+        a = (a + byte as u32) % 65521;
+        b = (b + a) % 65521;
+    }
+    (b << 16) | a
+}
+
+impl crate::memory::MemoryUsage for Canvas {
+    fn approx_bytes(&self) -> usize {
+        let alpha = self.alpha.as_ref().map_or(0, |a| a.capacity());
+        size_of::<Canvas>()
+            + self.pixels.capacity() * size_of::<Color>()
+            + alpha * size_of::<Float>()
+    }
+}
+
+// How Canvas::resize picks each pixel's color.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Filter {
+    // The source pixel under the new pixel's center.
+    Nearest,
+    // Blends the four source pixels around the new pixel's center.
+    Bilinear,
+    // Averages the source pixels the new pixel covers, weighted by overlap.
+    Box,
+}
+
+// The source pixels along one axis that a bilinear sample for destination
+// pixel i blends, with their weights. Samples past the edge clamp to it.
+fn linear_weights(i: usize, src: usize, dst: usize) -> Vec<(usize, Float)> {
+    let center = (i as Float + 0.5) * src as Float / dst as Float - 0.5;
+    let center = center.clamp(0.0, (src - 1) as Float);
+    let lo = center.floor() as usize;
+    let hi = (lo + 1).min(src - 1);
+    let t = center - lo as Float;
+    vec![(lo, 1.0 - t), (hi, t)]
+}
+
+// The source pixels along one axis that destination pixel i covers, each
+// weighted by how much of it is covered.
+fn box_weights(i: usize, src: usize, dst: usize) -> Vec<(usize, Float)> {
+    let scale = src as Float / dst as Float;
+    let (lo, hi) = (i as Float * scale, (i + 1) as Float * scale);
+    (lo.floor() as usize..(hi.ceil() as usize).min(src))
+        .map(|s| {
+            let overlap = hi.min((s + 1) as Float) - lo.max(s as Float);
+            (s, overlap / scale)
+        })
+        .filter(|&(_, w)| w > 0.0)
+        .collect()
+}
+
+fn cross_weights(xs: &[(usize, Float)], ys: &[(usize, Float)]) -> Vec<(usize, usize, Float)> {
+    ys.iter()
+        .flat_map(|&(sy, wy)| xs.iter().map(move |&(sx, wx)| (sx, sy, wx * wy)))
+        .collect()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PpmFormat {
+    // P3: decimal numbers separated by whitespace.
+    Plain,
+    // P6: a byte per channel, or two for maximum values above 255.
+    Binary,
+}
+
+// Whitespace-separated words of a PPM header or plain PPM body, skipping
+// comments and keeping track of the line they are on.
+struct PpmTokens<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+    line: usize,
+}
+
+impl<'a> PpmTokens<'a> {
+    fn new(bytes: &'a [u8]) -> Self {
+        Self {
+            bytes,
+            pos: 0,
+            line: 1,
+        }
+    }
+
+    fn next(&mut self, what: &str) -> Result<(usize, &'a str), ImageError> {
+        let mut comment = false;
+        while let Some(&b) = self.bytes.get(self.pos) {
+            if b == b'\n' {
+                comment = false;
+            } else if b == b'#' {
+                comment = true;
+            } else if !comment && !b.is_ascii_whitespace() {
+                break;
+            }
+            if b == b'\n' && self.pos + 1 < self.bytes.len() {
+                self.line += 1;
+            }
+            self.pos += 1;
+        }
+        let start = self.pos;
+        while self
+            .bytes
+            .get(self.pos)
+            .is_some_and(|b| !b.is_ascii_whitespace() && *b != b'#')
+        {
+            self.pos += 1;
+        }
+        if start == self.pos {
+            let message = format!("expected {what}, found the end of the file");
+            return Err(ImageError::new(self.line, message));
+        }
+        let word = std::str::from_utf8(&self.bytes[start..self.pos]).unwrap_or("?");
+        Ok((self.line, word))
+    }
+
+    fn number(&mut self, what: &str) -> Result<(usize, usize), ImageError> {
+        let (line, word) = self.next(what)?;
+        word.parse()
+            .map(|value| (line, value))
+            .map_err(|_| ImageError::new(line, format!("expected {what}, found `{word}`")))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImageError {
+    // Set when the image was read from a file.
+    pub path: Option<PathBuf>,
+    pub line: usize,
+    pub message: String,
+}
+
+impl ImageError {
+    fn new(line: usize, message: impl Into<String>) -> Self {
+        ImageError {
+            path: None,
+            line,
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for ImageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(path) = &self.path {
+            write!(f, "{}: ", path.display())?;
+        }
+        write!(f, "malformed PPM at line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for ImageError {}
+
+// Per-channel extremes and means of a canvas, and a histogram of luminance.
+// Luminance in [0, 1] is split evenly across the buckets; anything brighter
+// lands in the last bucket and anything negative in the first.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CanvasStatistics {
+    pub min: Color,
+    pub max: Color,
+    pub mean: Color,
+    pub histogram: Vec<usize>,
+}
+
+impl Canvas {
+    pub fn statistics(&self, buckets: usize) -> CanvasStatistics {
+        assert!(buckets > 0, "a histogram needs at least one bucket");
+        let inf = Float::INFINITY;
+        let mut min = Color::new(inf, inf, inf);
+        let mut max = Color::new(-inf, -inf, -inf);
+        let mut sum = Color::new(0.0, 0.0, 0.0);
+        let mut histogram = vec![0; buckets];
+        for p in &self.pixels {
+            min = Color::new(
+                min.red.min(p.red),
+                min.green.min(p.green),
+                min.blue.min(p.blue),
+            );
+            max = Color::new(
+                max.red.max(p.red),
+                max.green.max(p.green),
+                max.blue.max(p.blue),
+            );
+            sum = sum + *p;
+            let bucket = (p.luminance().max(0.0) * buckets as Float) as usize;
+            histogram[bucket.min(buckets - 1)] += 1;
+        }
+        CanvasStatistics {
+            min,
+            max,
+            mean: sum * (1.0 / self.pixels.len().max(1) as Float),
+            histogram,
+        }
+    }
+
+    // The luminance that p percent of pixels are at or below, by nearest rank.
+    // Zero for an empty canvas.
+    pub fn percentile_luminance(&self, p: Float) -> Float {
+        let mut luminances: Vec<Float> = self.pixels.iter().map(|c| c.luminance()).collect();
+        if luminances.is_empty() {
+            return 0.0;
+        }
+        let rank = (p.clamp(0.0, 100.0) / 100.0 * luminances.len() as Float).ceil() as usize;
+        let index = rank.clamp(1, luminances.len()) - 1;
+        *luminances
+            .select_nth_unstable_by(index, |a, b| a.total_cmp(b))
+            .1
+    }
+
+    // A text preview `columns` characters wide, for a quick look in a
+    // terminal. Each character is the average luminance of a block of pixels
+    // twice as tall as it is wide, since terminal cells are about that shape,
+    // picked from a ramp running from dark to bright.
+    pub fn to_ascii(&self, columns: usize) -> String {
+        const RAMP: &[u8] = b" .:-=+*#%@";
+        if self.width == 0 || self.height == 0 || columns == 0 {
+            return String::new();
+        }
+        let columns = columns.min(self.width);
+        let cell_width = self.width as Float / columns as Float;
+        let cell_height = cell_width * 2.0;
+        let rows = ((self.height as Float / cell_height).round() as usize).max(1);
+        // The pixels from cell i up to cell i + 1, never empty.
+        let span = |i: usize, size: Float, limit: usize| {
+            let start = ((i as Float * size) as usize).min(limit - 1);
+            let end = (((i + 1) as Float * size) as usize).clamp(start + 1, limit);
+            start..end
+        };
+        let mut out = String::new();
+        for row in 0..rows {
+            let ys = span(row, cell_height, self.height);
+            for column in 0..columns {
+                let xs = span(column, cell_width, self.width);
+                let count = (ys.len() * xs.len()) as Float;
+                let sum: Float = ys
+                    .clone()
+                    .flat_map(|y| xs.clone().map(move |x| (x, y)))
+                    .map(|(x, y)| self.pixel_at(x, y).luminance())
+                    .sum();
+                let level = (sum / count).clamp(0.0, 1.0);
+                let index = (level * (RAMP.len() - 1) as Float).round() as usize;
+                out.push(RAMP[index] as char);
+            }
+            out.push('\n');
+        }
+        out
     }
 }
 
+// Lays tiles out left to right, top to bottom, in cells sized to the largest
+// tile. Cells past the last tile of a ragged final row are left black.
+pub fn compose_grid(tiles: Vec<Canvas>, columns: usize) -> Canvas {
+    assert!(columns > 0, "compose_grid needs at least one column");
+    let cell_width = tiles.iter().map(|t| t.width).max().unwrap_or(0);
+    let cell_height = tiles.iter().map(|t| t.height).max().unwrap_or(0);
+    let rows = tiles.len().div_ceil(columns);
+    let mut grid = Canvas::new(cell_width * columns.min(tiles.len()), cell_height * rows);
+    for (i, tile) in tiles.iter().enumerate() {
+        grid.blit(
+            tile,
+            (i % columns) * cell_width,
+            (i / columns) * cell_height,
+        );
+    }
+    grid
+}
+
+// A red/cyan anaglyph: the red channel from the left image and green and
+// blue from the right, for viewing with coloured glasses. Place the two side
+// by side with compose_grid instead for cross-eyed or VR viewing.
+pub fn anaglyph(left: &Canvas, right: &Canvas) -> Canvas {
+    assert!(
+        left.width == right.width && left.height == right.height,
+        "anaglyph needs two images of the same size"
+    );
+    let mut out = Canvas::new(left.width, left.height);
+    for y in 0..left.height {
+        for x in 0..left.width {
+            let (l, r) = (left.pixel_at(x, y), right.pixel_at(x, y));
+            out.write_pixel(x, y, Color::new(l.red, r.green, r.blue));
+        }
+    }
+    out
+}
+
 // ...existing
 
 #[cfg(test)]
@@ -269,4 +998,461 @@ mod tests {
         assert_eq!(c.pixel_at(3, 2), color);
         assert_eq!(c.pixel_at(4, 4), black);
     }
+
+    fn filled(width: usize, height: usize, color: Color) -> Canvas {
+        let mut c = Canvas::new(width, height);
+        c.write_block(0, 0, width as i32, height as i32, color);
+        c
+    }
+
+    #[test]
+    fn blitting_clips_to_the_destination() {
+        let mut dst = Canvas::new(4, 4);
+        let red = Color::new(1.0, 0.0, 0.0);
+        dst.blit(&filled(3, 3, red), 2, 2);
+        assert_eq!(dst.pixel_at(2, 2), red);
+        assert_eq!(dst.pixel_at(3, 3), red);
+        assert_eq!(dst.pixel_at(1, 1), COLOR_BLACK);
+    }
+
+    #[test]
+    fn nearest_resizing_repeats_pixels_in_blocks() {
+        let white = Color::new(1.0, 1.0, 1.0);
+        let mut checkers = Canvas::new(2, 2);
+        checkers.write_pixel(1, 0, white);
+        checkers.write_pixel(0, 1, white);
+        let big = checkers.resize(4, 4, Filter::Nearest);
+        for y in 0..4 {
+            for x in 0..4 {
+                let expected = checkers.pixel_at(x / 2, y / 2);
+                assert_eq!(big.pixel_at(x, y), expected, "{x}, {y}");
+            }
+        }
+    }
+
+    #[test]
+    fn bilinear_resizing_blends_neighbours() {
+        let mut pair = Canvas::with_alpha(2, 1);
+        pair.write_pixel(1, 0, Color::new(1.0, 1.0, 1.0));
+        pair.write_alpha(1, 0, 1.0);
+        let wide = pair.resize(3, 1, Filter::Bilinear);
+        crate::check_colors!(wide.pixel_at(1, 0), Color::new(0.5, 0.5, 0.5));
+        crate::check_floats!(wide.alpha_at(1, 0), 0.5);
+        // The edges clamp rather than fading to black.
+        assert_eq!(wide.pixel_at(2, 0), Color::new(1.0, 1.0, 1.0));
+    }
+
+    #[test]
+    fn shrinking_averages_the_covered_pixels() {
+        let white = Color::new(1.0, 1.0, 1.0);
+        let mut stripes = Canvas::new(8, 2);
+        for x in (0..8).step_by(2) {
+            stripes.write_pixel(x, 0, white);
+            stripes.write_pixel(x, 1, white);
+        }
+        // Bilinear would land every sample on one stripe or the other.
+        for filter in [Filter::Bilinear, Filter::Box] {
+            let small = stripes.resize(2, 1, filter);
+            crate::check_colors!(small.pixel_at(0, 0), Color::new(0.5, 0.5, 0.5));
+            crate::check_colors!(small.pixel_at(1, 0), Color::new(0.5, 0.5, 0.5));
+        }
+    }
+
+    #[test]
+    fn resizing_to_the_same_size_changes_nothing() {
+        let mut c = Canvas::with_alpha(3, 2);
+        c.write_pixel(2, 1, Color::new(0.3, 0.6, 0.9));
+        c.write_alpha(0, 0, 0.25);
+        for filter in [Filter::Nearest, Filter::Bilinear, Filter::Box] {
+            let same = c.resize(3, 2, filter);
+            assert_eq!(same.to_ppm(), c.to_ppm());
+            assert_eq!(same.alpha_at(0, 0), 0.25);
+        }
+    }
+
+    #[test]
+    fn composing_a_grid_with_a_ragged_last_row() {
+        let red = Color::new(1.0, 0.0, 0.0);
+        let green = Color::new(0.0, 1.0, 0.0);
+        let blue = Color::new(0.0, 0.0, 1.0);
+        let tiles = vec![filled(2, 2, red), filled(2, 2, green), filled(2, 1, blue)];
+        let grid = compose_grid(tiles, 2);
+        assert_eq!((grid.width, grid.height), (4, 4));
+        assert_eq!(grid.pixel_at(0, 0), red);
+        assert_eq!(grid.pixel_at(3, 1), green);
+        assert_eq!(grid.pixel_at(1, 2), blue);
+        assert_eq!(grid.pixel_at(1, 3), COLOR_BLACK);
+        assert_eq!(grid.pixel_at(3, 3), COLOR_BLACK);
+    }
+
+    #[test]
+    fn composing_fewer_tiles_than_columns() {
+        let grid = compose_grid(vec![Canvas::new(3, 2)], 4);
+        assert_eq!((grid.width, grid.height), (3, 2));
+        let empty = compose_grid(vec![], 4);
+        assert_eq!((empty.width, empty.height), (0, 0));
+    }
+
+    #[test]
+    fn an_anaglyph_takes_red_from_the_left_and_cyan_from_the_right() {
+        let mut left = Canvas::new(2, 1);
+        let mut right = Canvas::new(2, 1);
+        left.write_pixel(0, 0, Color::new(0.9, 0.8, 0.7));
+        right.write_pixel(0, 0, Color::new(0.1, 0.2, 0.3));
+        right.write_pixel(1, 0, Color::new(1.0, 1.0, 1.0));
+        let a = anaglyph(&left, &right);
+        assert_eq!(a.pixel_at(0, 0), Color::new(0.9, 0.2, 0.3));
+        assert_eq!(a.pixel_at(1, 0), Color::new(0.0, 1.0, 1.0));
+    }
+
+    #[test]
+    fn canvases_are_opaque_unless_alpha_is_written() {
+        let mut c = Canvas::new(2, 2);
+        assert!(!c.has_alpha());
+        assert_eq!(c.alpha_at(1, 1), 1.0);
+        c.write_alpha(0, 0, 0.25);
+        assert_eq!(c.alpha_at(0, 0), 0.25);
+        assert_eq!(c.alpha_at(1, 1), 1.0);
+    }
+
+    #[test]
+    fn a_canvas_with_alpha_starts_transparent_and_ppm_ignores_it() {
+        let c = Canvas::with_alpha(2, 1);
+        assert_eq!(c.alpha_at(0, 0), 0.0);
+        assert_eq!(c.to_ppm(), Canvas::new(2, 1).to_ppm());
+    }
+
+    #[test]
+    fn statistics_of_a_half_black_half_white_canvas() {
+        let mut c = Canvas::new(4, 2);
+        c.write_block(0, 0, 4, 1, Color::new(1.0, 1.0, 1.0));
+        let stats = c.statistics(4);
+        assert_eq!(stats.min, COLOR_BLACK);
+        assert_eq!(stats.max, Color::new(1.0, 1.0, 1.0));
+        assert_eq!(stats.mean, Color::new(0.5, 0.5, 0.5));
+        assert_eq!(stats.histogram, vec![4, 0, 0, 4]);
+    }
+
+    #[test]
+    fn luminance_percentiles_use_the_nearest_rank() {
+        let mut c = Canvas::new(10, 1);
+        for x in 0..10 {
+            let v = (x + 1) as Float / 10.0;
+            c.write_pixel(x, 0, Color::new(v, v, v));
+        }
+        crate::check_floats!(c.percentile_luminance(50.0), 0.5);
+        crate::check_floats!(c.percentile_luminance(95.0), 1.0);
+        crate::check_floats!(c.percentile_luminance(0.0), 0.1);
+    }
+
+    fn lit_pixels(c: &Canvas) -> usize {
+        c.pixels.iter().filter(|p| **p != COLOR_BLACK).count()
+    }
+
+    #[test]
+    fn a_line_sets_both_endpoints() {
+        let mut c = Canvas::new(10, 10);
+        let red = Color::new(1.0, 0.0, 0.0);
+        c.draw_line(2, 7, 8, 3, red);
+        assert_eq!(c.pixel_at(2, 7), red);
+        assert_eq!(c.pixel_at(8, 3), red);
+        assert_eq!(lit_pixels(&c), 7);
+    }
+
+    #[test]
+    fn a_diagonal_line_sets_one_pixel_per_step() {
+        let mut c = Canvas::new(10, 10);
+        c.draw_line(0, 0, 9, 9, Color::new(1.0, 1.0, 1.0));
+        assert_eq!(lit_pixels(&c), 10);
+        assert!((0..10).all(|i| c.pixel_at(i, i) != COLOR_BLACK));
+    }
+
+    #[test]
+    fn shapes_are_clipped_to_the_canvas() {
+        let mut c = Canvas::new(5, 5);
+        let white = Color::new(1.0, 1.0, 1.0);
+        c.draw_line(-5, 2, 20, 2, white);
+        assert_eq!(lit_pixels(&c), 5);
+        c.draw_circle(0, 0, 3, white);
+        c.draw_rect(-1, -1, 10, 10, white);
+        assert_eq!(c.pixel_at(3, 0), white);
+    }
+
+    #[test]
+    fn a_rect_outlines_its_border() {
+        let mut c = Canvas::new(10, 10);
+        c.draw_rect(1, 1, 4, 3, Color::new(1.0, 1.0, 1.0));
+        assert_eq!(lit_pixels(&c), 10);
+        assert_eq!(c.pixel_at(2, 2), COLOR_BLACK);
+    }
+
+    #[test]
+    fn a_circle_touches_its_extremes() {
+        let mut c = Canvas::new(11, 11);
+        let white = Color::new(1.0, 1.0, 1.0);
+        c.draw_circle(5, 5, 4, white);
+        for (x, y) in [(9, 5), (1, 5), (5, 9), (5, 1)] {
+            assert_eq!(c.pixel_at(x, y), white);
+        }
+        assert_eq!(c.pixel_at(5, 5), COLOR_BLACK);
+    }
+
+    #[test]
+    fn averaging_two_identical_canvases_gives_the_same_canvas() {
+        let c = filled(3, 2, Color::new(0.2, 0.4, 1.6));
+        let mut sum = Canvas::new(3, 2);
+        sum.accumulate(&c, 1.0);
+        sum.accumulate(&c, 1.0);
+        sum.scale(0.5);
+        assert_eq!(sum.pixel_at(2, 1), Color::new(0.2, 0.4, 1.6));
+    }
+
+    #[test]
+    fn accumulating_sums_alpha_when_present() {
+        let mut sum = Canvas::with_alpha(1, 1);
+        let mut half = Canvas::with_alpha(1, 1);
+        half.write_alpha(0, 0, 0.5);
+        sum.accumulate(&half, 1.0);
+        sum.accumulate(&Canvas::new(1, 1), 1.0);
+        sum.scale(0.5);
+        assert_eq!(sum.alpha_at(0, 0), 0.75);
+    }
+
+    #[test]
+    fn an_ascii_preview_is_dark_where_the_canvas_is_dark() {
+        let mut c = Canvas::new(40, 20);
+        for y in 0..20 {
+            for x in 20..40 {
+                c.write_pixel(x, y, Color::new(1.0, 1.0, 1.0));
+            }
+        }
+        let ascii = c.to_ascii(20);
+        // Cells two pixels wide are four tall, so 20 rows become 5 lines.
+        let lines: Vec<_> = ascii.lines().collect();
+        assert_eq!(lines.len(), 5);
+        for line in lines {
+            assert_eq!(line, format!("{}{}", " ".repeat(10), "@".repeat(10)));
+        }
+    }
+
+    #[test]
+    fn an_ascii_preview_is_never_wider_than_the_canvas() {
+        let c = Canvas::new(3, 1);
+        assert_eq!(c.to_ascii(80), "   \n");
+        assert_eq!(Canvas::new(0, 0).to_ascii(80), "");
+    }
+
+    #[test]
+    fn png_output_has_the_header_and_stored_pixels() {
+        let mut c = Canvas::new(2, 1);
+        c.write_pixel(1, 0, Color::new(1.0, 0.5, 0.0));
+        let png = c.to_png();
+        assert_eq!(&png[..8], b"\x89PNG\r\n\x1a\n");
+        assert_eq!(&png[12..16], b"IHDR");
+        assert_eq!(&png[16..24], &[0, 0, 0, 2, 0, 0, 0, 1]);
+        // The CRC of an empty IEND chunk is fixed.
+        assert_eq!(
+            &png[png.len() - 8..],
+            &[b'I', b'E', b'N', b'D', 0xae, 0x42, 0x60, 0x82]
+        );
+        let row = [0, 0, 0, 0, 255, 128, 0];
+        assert!(png.windows(row.len()).any(|w| w == row));
+    }
+
+    // The IHDR fields and the unfiltered pixel bytes of a PNG from to_png.
+    fn decode_png(png: &[u8]) -> ([u8; 13], Vec<Vec<u8>>) {
+        let (mut header, mut zlib) = ([0; 13], vec![]);
+        let mut at = 8;
+        while at < png.len() {
+            let len = u32::from_be_bytes(png[at..at + 4].try_into().unwrap()) as usize;
+            let data = &png[at + 8..at + 8 + len];
+            match &png[at + 4..at + 8] {
+                b"IHDR" => header.copy_from_slice(data),
+                b"IDAT" => zlib.extend_from_slice(data),
+                _ => {}
+            }
+            at += len + 12;
+        }
+        // Stored deflate blocks: a final flag, then LEN, NLEN and the bytes.
+        let (mut raw, mut at) = (vec![], 2);
+        loop {
+            let last = zlib[at] == 1;
+            let len = u16::from_le_bytes([zlib[at + 1], zlib[at + 2]]) as usize;
+            raw.extend_from_slice(&zlib[at + 5..at + 5 + len]);
+            at += 5 + len;
+            if last {
+                break;
+            }
+        }
+        let width = u32::from_be_bytes(header[..4].try_into().unwrap()) as usize;
+        let channels = if header[9] == 6 { 4 } else { 3 };
+        let rows = raw
+            .chunks(width * channels + 1)
+            .map(|row| {
+                assert_eq!(row[0], 0);
+                row[1..].to_vec()
+            })
+            .collect();
+        (header, rows)
+    }
+
+    #[test]
+    fn a_rendered_png_keeps_the_coverage_alpha() {
+        use crate::{
+            camera::Camera,
+            floats::PI,
+            options::RenderOptions,
+            transformations::view_transform,
+            tuples::{point, vector},
+            world::default_world,
+        };
+        let mut c = Camera::new(21, 21, PI / 3.0);
+        c.set_transform(view_transform(
+            point(0.0, 0.0, -5.0),
+            point(0.0, 0.0, 0.0),
+            vector(0.0, 1.0, 0.0),
+        ));
+        let mut w = default_world();
+        w.options = RenderOptions::new().with_aa_samples(4);
+        let image = w.build().render(&c);
+        let (header, rows) = decode_png(&image.to_png());
+        assert_eq!(header[8..10], [8, 6]);
+        assert_eq!(rows.len(), 21);
+        assert!(rows.iter().all(|r| r.len() == 21 * 4));
+        let alpha = |x: usize, y: usize| rows[y][x * 4 + 3];
+        assert_eq!(alpha(0, 0), 0);
+        assert_eq!(alpha(10, 10), 255);
+        assert!(
+            (0..21).any(|x| (1..255).contains(&alpha(x, 10))),
+            "{:?}",
+            (0..21).map(|x| alpha(x, 10)).collect::<Vec<_>>()
+        );
+
+        let (header, rows) = decode_png(&Canvas::new(2, 1).to_png());
+        assert_eq!(header[8..10], [8, 2]);
+        assert_eq!(rows[0].len(), 6);
+    }
+
+    #[test]
+    fn a_canvas_survives_a_round_trip_through_ppm() {
+        let mut c = Canvas::new(30, 4);
+        c.write_pixel(0, 0, Color::new(1.0, 0.0, 0.2));
+        c.write_pixel(29, 3, Color::new(0.0, 0.6, 1.0));
+        let back = Canvas::from_ppm(&c.to_ppm()).unwrap();
+        assert_eq!((back.width, back.height), (30, 4));
+        assert_eq!(back.to_ppm(), c.to_ppm());
+    }
+
+    fn gradient(width: usize) -> Canvas {
+        let mut c = Canvas::new(width, 1);
+        for x in 0..width {
+            let v = x as Float / (width - 1) as Float;
+            c.write_pixel(x, 0, Color::new(v, v, v));
+        }
+        c
+    }
+
+    #[test]
+    fn sixteen_bit_ppms_keep_more_levels() {
+        let c = gradient(1000);
+        let levels = |back: Canvas| {
+            let mut reds: Vec<_> = (0..back.width).map(|x| back.pixel_at(x, 0).red).collect();
+            reds.dedup();
+            reds.len()
+        };
+        for format in [PpmFormat::Plain, PpmFormat::Binary] {
+            let (deep, shallow) = match format {
+                PpmFormat::Plain => (
+                    Canvas::from_ppm(&c.to_plain_ppm(65535)).unwrap(),
+                    Canvas::from_ppm(&c.to_plain_ppm(255)).unwrap(),
+                ),
+                PpmFormat::Binary => (
+                    Canvas::from_ppm_bytes(&c.to_binary_ppm(65535)).unwrap(),
+                    Canvas::from_ppm_bytes(&c.to_binary_ppm(255)).unwrap(),
+                ),
+            };
+            assert_eq!(levels(shallow), 256);
+            assert_eq!(levels(deep), 1000);
+        }
+    }
+
+    #[test]
+    fn binary_ppms_follow_the_spec_layout() {
+        let mut c = Canvas::new(2, 1);
+        c.write_pixel(1, 0, Color::new(1.0, 0.5, 0.0));
+
+        let ppm = c.to_binary_ppm(255);
+        // "P6\n2 1\n255\n" is 11 bytes, then 3 bytes per pixel.
+        assert_eq!(&ppm[..11], b"P6\n2 1\n255\n");
+        assert_eq!(ppm.len(), 11 + 2 * 3);
+        assert_eq!(&ppm[14..], &[255, 128, 0]);
+
+        let ppm = c.to_binary_ppm(65535);
+        // "P6\n2 1\n65535\n" is 13 bytes, then 6 bytes per pixel, big-endian.
+        assert_eq!(&ppm[..13], b"P6\n2 1\n65535\n");
+        assert_eq!(ppm.len(), 13 + 2 * 6);
+        assert_eq!(&ppm[19..], &[0xff, 0xff, 0x80, 0x00, 0x00, 0x00]);
+    }
+
+    #[test]
+    fn ppms_round_trip_at_every_width() {
+        let c = gradient(300);
+        for max in [15, 255, 1023, 65535] {
+            let plain = c.to_plain_ppm(max);
+            assert_eq!(Canvas::from_ppm(&plain).unwrap().to_plain_ppm(max), plain);
+            let binary = c.to_binary_ppm(max);
+            let back = Canvas::from_ppm_bytes(&binary).unwrap();
+            assert_eq!(back.to_binary_ppm(max), binary);
+        }
+    }
+
+    #[test]
+    fn reading_a_ppm_skips_comments_and_scales_by_the_maximum() {
+        let c = Canvas::from_ppm("P3 # plain\n1 1\n# max\n15\n15 0 5\n").unwrap();
+        assert_eq!(c.pixel_at(0, 0), Color::new(1.0, 0.0, 1.0 / 3.0));
+    }
+
+    #[test]
+    fn malformed_ppms_report_the_line_at_fault() {
+        for (ppm, line, needle) in [
+            ("P5\n1 1\n255\n", 1, "expected P3 or P6"),
+            ("P6\n1 1\n255\n\x00\x00", 3, "expected 3 bytes"),
+            ("P3\n1 1\n65536\n0 0 0\n", 3, "from 1 to 65535"),
+            ("P3\n1 x\n255\n0 0 0\n", 2, "expected a height"),
+            ("P3\n1 1\n255\n0 256 0\n", 4, "above the maximum"),
+            ("P3\n2 1\n255\n0 0 0\n", 4, "end of the file"),
+            ("P3\n4000000000 4000000000 255\n", 2, "too large"),
+            (
+                "P3\n100000 100000\n255\n0 0 0\n",
+                3,
+                "expected 30000000000 color values",
+            ),
+            // Sizes that overflow once multiplied by the bytes per pixel.
+            ("P6\n4000000000 4000000000\n255\n", 2, "too large"),
+            ("P6\n3074457345618258603 1\n65535\n", 2, "too large"),
+            (
+                "P6\n100000 100000\n65535\n\x00",
+                3,
+                "expected 60000000000 bytes",
+            ),
+        ] {
+            let e = Canvas::from_ppm(ppm).err().unwrap();
+            assert_eq!(e.line, line, "{e}");
+            assert!(e.to_string().contains(needle), "{e}");
+        }
+    }
+
+    #[test]
+    fn f32_rgb_buffers_hold_three_floats_per_pixel_and_round_trip() {
+        let mut c = Canvas::new(5, 3);
+        c.write_pixel(0, 0, Color::new(0.25, 1.5, -0.5));
+        c.write_pixel(4, 2, Color::new(0.1, 0.2, 0.3));
+        let rgb = c.as_f32_rgb();
+        assert_eq!(rgb.len(), 5 * 3 * 3);
+        assert_eq!(&rgb[..3], &[0.25, 1.5, -0.5]);
+        let back = Canvas::from_f32_rgb(5, 3, &rgb);
+        for (x, y) in [(0, 0), (4, 2), (2, 1)] {
+            crate::assert_color_eq!(back.pixel_at(x, y), c.pixel_at(x, y));
+        }
+    }
 }
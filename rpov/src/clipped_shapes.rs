@@ -0,0 +1,194 @@
+use std::collections::HashSet;
+use std::sync::Arc;
+
+use crate::{
+    bounds::{BoundingBox, BoundingSphere},
+    floats::{EPSILON, Float},
+    intersections::{Intersection, Shape},
+    materials::Material,
+    matrices::Matrix4,
+    rays::Ray,
+    shapes::{Intersectable, ShapeFunctions},
+    tuples::{Tuple4, vector},
+};
+
+// A shape cut down to the part that lies inside a world-space box. Where the
+// box slices through a closed shape the cut is capped with the box face, so a
+// clipped sphere still looks solid.
+#[derive(Debug, Clone)]
+pub struct ClippedShape {
+    pub inner: Arc<dyn Shape>,
+    pub clip: BoundingBox,
+}
+
+impl ClippedShape {
+    pub fn new(inner: Arc<dyn Shape>, clip: BoundingBox) -> Self {
+        Self { inner, clip }
+    }
+
+    // Outward normal of the clip box face the point lies on, if any.
+    fn face_normal(&self, p: &Tuple4) -> Option<Tuple4> {
+        let (min, max) = (self.clip.min, self.clip.max);
+        let faces = [
+            (p.x - min.x, vector(-1.0, 0.0, 0.0)),
+            (max.x - p.x, vector(1.0, 0.0, 0.0)),
+            (p.y - min.y, vector(0.0, -1.0, 0.0)),
+            (max.y - p.y, vector(0.0, 1.0, 0.0)),
+            (p.z - min.z, vector(0.0, 0.0, -1.0)),
+            (max.z - p.z, vector(0.0, 0.0, 1.0)),
+        ];
+        faces
+            .into_iter()
+            .find(|(distance, _)| distance.abs() < EPSILON)
+            .map(|(_, normal)| normal)
+    }
+}
+
+impl ShapeFunctions for ClippedShape {
+    // Patterns keep following the inner shape's object space.
+    fn transform(&self) -> Matrix4 {
+        self.inner.transform()
+    }
+
+    fn transform_inverse(&self) -> Matrix4 {
+        self.inner.transform_inverse()
+    }
+
+    fn material(&self) -> &Material {
+        self.inner.material()
+    }
+
+    fn id(&self) -> u64 {
+        self.inner.id()
+    }
+
+    fn tags(&self) -> &HashSet<String> {
+        self.inner.tags()
+    }
+
+    fn resolved_material(&self) -> &Material {
+        self.inner.resolved_material()
+    }
+
+    fn normal_at(&self, world_point: &Tuple4) -> Tuple4 {
+        match self.face_normal(world_point) {
+            Some(normal) => normal,
+            None => self.inner.normal_at(world_point),
+        }
+    }
+
+    fn local_normal_at(&self, local_point: &Tuple4) -> Tuple4 {
+        self.inner.local_normal_at(local_point)
+    }
+
+    fn local_tangent_at(&self, local_point: &Tuple4) -> Option<Tuple4> {
+        self.inner.local_tangent_at(local_point)
+    }
+
+    // Clipping only removes parts of the inner shape.
+    fn bounding_sphere(&self) -> Option<BoundingSphere> {
+        self.inner.bounding_sphere()
+    }
+}
+
+impl Intersectable<ClippedShape> for ClippedShape {
+    // The clip box is in world space, so the ray is not transformed here.
+    fn intersect<'a>(&'a self, ray: Ray) -> Vec<Intersection<'a>> {
+        self.local_intersect(ray)
+    }
+
+    fn intersect_bounded<'a>(&'a self, ray: Ray, t_max: Float) -> Vec<Intersection<'a>> {
+        self.local_intersect_bounded(ray, t_max)
+    }
+
+    fn hits_before(&self, ray: Ray, t_max: Float) -> bool {
+        self.intersects_before(ray, t_max)
+    }
+
+    fn local_intersect<'a>(&'a self, ray: Ray) -> Vec<Intersection<'a>> {
+        let Some((b0, b1)) = self.clip.intersect(ray) else {
+            return vec![];
+        };
+        let inner_xs = self.inner.intersect_ray(ray);
+        if !inner_xs.len().is_multiple_of(2) {
+            // Not a closed shape, so there is nothing to cap.
+            return inner_xs
+                .into_iter()
+                .filter(|i| (b0..=b1).contains(&i.t))
+                .map(|i| Intersection { object: self, ..i })
+                .collect();
+        }
+        let ts: Vec<_> = inner_xs.iter().map(|i| i.t).collect();
+        let mut xs = vec![];
+        for span in ts.chunks(2) {
+            let (t0, t1) = (span[0].max(b0), span[1].min(b1));
+            if t0 <= t1 {
+                xs.push(Intersection::new(t0, self));
+                xs.push(Intersection::new(t1, self));
+            }
+        }
+        xs
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        colors::Color,
+        lighting::point_light,
+        rays::ray,
+        spheres::Sphere,
+        tuples::{check_tuple, point},
+        world::WorldBuilder,
+    };
+
+    fn half_sphere(min_y: Float, max_y: Float) -> ClippedShape {
+        let clip = BoundingBox::new(point(-2.0, min_y, -2.0), point(2.0, max_y, 2.0));
+        ClippedShape::new(Arc::new(Sphere::new()), clip)
+    }
+
+    #[test]
+    fn a_clipped_sphere_is_capped_where_the_box_cuts_it() {
+        let s = half_sphere(-2.0, 0.0);
+        let r = ray(point(0.0, 5.0, 0.0), vector(0.0, -1.0, 0.0));
+        let xs = s.intersect(r);
+        assert_eq!(xs.len(), 2);
+        assert_eq!(xs[0].t, 5.0);
+        assert_eq!(xs[1].t, 6.0);
+        check_tuple(s.normal_at(&r.position(xs[0].t)), vector(0.0, 1.0, 0.0));
+        check_tuple(s.normal_at(&r.position(xs[1].t)), vector(0.0, -1.0, 0.0));
+    }
+
+    #[test]
+    fn the_kept_surface_uses_the_inner_normal() {
+        let s = half_sphere(0.0, 2.0);
+        let r = ray(point(0.0, 5.0, 0.0), vector(0.0, -1.0, 0.0));
+        let xs = s.intersect(r);
+        assert_eq!(xs.len(), 2);
+        assert_eq!(xs[0].t, 4.0);
+        assert_eq!(xs[1].t, 5.0);
+        check_tuple(s.normal_at(&r.position(xs[0].t)), vector(0.0, 1.0, 0.0));
+    }
+
+    #[test]
+    fn rays_missing_the_kept_part_miss_the_shape() {
+        let s = half_sphere(0.0, 2.0);
+        let r = ray(point(0.0, -0.5, -5.0), vector(0.0, 0.0, 1.0));
+        assert!(s.intersect(r).is_empty());
+    }
+
+    #[test]
+    fn clipping_removes_the_shadow_of_the_cut_part() {
+        let mut w = WorldBuilder::new();
+        w.light = Some(point_light(
+            point(0.0, 10.0, 0.0),
+            Color::new(1.0, 1.0, 1.0),
+        ));
+        let clip = BoundingBox::new(point(0.5, -2.0, -2.0), point(2.0, 2.0, 2.0));
+        w.shapes
+            .push(Box::new(ClippedShape::new(Arc::new(Sphere::new()), clip)));
+        assert!(!w.is_shadowed(point(0.0, -2.0, 0.0)));
+        assert!(w.is_shadowed(point(0.8, -2.0, 0.0)));
+    }
+}
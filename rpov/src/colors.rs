@@ -1,4 +1,4 @@
-#[derive(Debug, Copy, Clone, Display)]
+#[derive(Debug, Copy, Clone, PartialEq, Display)]
 #[display("Color(r={red}, g={green}, b={blue})")]
 pub struct Color {
     pub red: Float,
@@ -10,8 +10,49 @@ impl Color {
     pub fn new(red: Float, green: Float, blue: Float) -> Color {
         Color { red, green, blue }
     }
+
+    // Perceived brightness, with the Rec. 709 weights.
+    pub fn luminance(&self) -> Float {
+        0.2126 * self.red + 0.7152 * self.green + 0.0722 * self.blue
+    }
+
+    // Black-body color for a temperature in kelvin, using Tanner Helland's fit
+    // of the Planckian locus. Scaled so 6500K is white; temperatures outside
+    // 1000K..40000K are clamped to that range.
+    pub fn from_temperature(kelvin: Float) -> Color {
+        let raw = |kelvin: Float| {
+            let t = kelvin.clamp(1000.0, 40000.0) / 100.0;
+            let red = if t <= 66.0 {
+                255.0
+            } else {
+                329.698_73 * (t - 60.0).powf(-0.133_204_76)
+            };
+            let green = if t <= 66.0 {
+                99.470_8 * t.ln() - 161.119_57
+            } else {
+                288.122_17 * (t - 60.0).powf(-0.075_514_85)
+            };
+            let blue = if t >= 66.0 {
+                255.0
+            } else if t <= 19.0 {
+                0.0
+            } else {
+                138.517_73 * (t - 10.0).ln() - 305.044_8
+            };
+            Color::new(red, green, blue)
+        };
+        let c = raw(kelvin);
+        let white = raw(6500.0);
+        Color::new(
+            (c.red / white.red).clamp(0.0, 1.0),
+            (c.green / white.green).clamp(0.0, 1.0),
+            (c.blue / white.blue).clamp(0.0, 1.0),
+        )
+    }
 }
 
+// The primaries, black and white, and a few common mixes. The mixes use the
+// CSS values, so COLOR_GRAY is 128/255 rather than exactly half.
 pub const COLOR_RED: Color = Color {
     red: 1.0,
     green: 0.0,
@@ -37,12 +78,117 @@ pub const COLOR_BLACK: Color = Color {
     green: 0.0,
     blue: 0.0,
 };
+pub const COLOR_GRAY: Color = Color {
+    red: 128.0 / 255.0,
+    green: 128.0 / 255.0,
+    blue: 128.0 / 255.0,
+};
+pub const COLOR_YELLOW: Color = Color {
+    red: 1.0,
+    green: 1.0,
+    blue: 0.0,
+};
+pub const COLOR_CYAN: Color = Color {
+    red: 0.0,
+    green: 1.0,
+    blue: 1.0,
+};
+pub const COLOR_MAGENTA: Color = Color {
+    red: 1.0,
+    green: 0.0,
+    blue: 1.0,
+};
+pub const COLOR_ORANGE: Color = Color {
+    red: 1.0,
+    green: 165.0 / 255.0,
+    blue: 0.0,
+};
+
+impl Color {
+    // Looks up one of the constants above by name, ignoring case. Accepts the
+    // CSS spellings grey, aqua, fuchsia and lime too. Unlike CSS, green is
+    // the full-strength primary.
+    pub fn named(name: &str) -> Option<Color> {
+        let color = match name.trim().to_ascii_lowercase().as_str() {
+            "black" => COLOR_BLACK,
+            "white" => COLOR_WHITE,
+            "red" => COLOR_RED,
+            "green" | "lime" => COLOR_GREEN,
+            "blue" => COLOR_BLUE,
+            "gray" | "grey" => COLOR_GRAY,
+            "yellow" => COLOR_YELLOW,
+            "cyan" | "aqua" => COLOR_CYAN,
+            "magenta" | "fuchsia" => COLOR_MAGENTA,
+            "orange" => COLOR_ORANGE,
+            _ => return None,
+        };
+        Some(color)
+    }
+
+    // Scales the whole color down when any channel is over `max`, so bright
+    // highlights keep their hue instead of washing out toward yellow or white
+    // as channels saturate one by one.
+    pub fn clamp_preserving_hue(&self, max: Float) -> Color {
+        let brightest = self.red.max(self.green).max(self.blue);
+        if brightest <= max {
+            return *self;
+        }
+        *self * (max / brightest)
+    }
+
+    // Like ==, but with the tolerance given rather than EPSILON.
+    pub fn approx_eq(&self, other: &Color, epsilon: Float) -> bool {
+        (self.red - other.red).abs() <= epsilon
+            && (self.green - other.green).abs() <= epsilon
+            && (self.blue - other.blue).abs() <= epsilon
+    }
+}
 
 use std::ops::{Add, Mul, Sub};
 
 use derive_more::Display;
 
-use crate::floats::{EPSILON, Float};
+use crate::{
+    floats::Float,
+    tuples::{ParseError, parse_call},
+};
+
+// Parses `Color(r=.., g=.., b=..)` as written by Display, `color(r, g, b)`,
+// and `#rrggbb` hex.
+impl std::str::FromStr for Color {
+    type Err = ParseError;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        let s = input.trim();
+        if let Some(hex) = s.strip_prefix('#') {
+            // Checked before slicing, which would panic inside a multibyte
+            // character.
+            if !hex.is_ascii() || hex.len() != 6 {
+                return Err(ParseError::new(input, "hex colors need 6 digits"));
+            }
+            let channel = |i: usize| {
+                u8::from_str_radix(&hex[i..i + 2], 16)
+                    .map(|v| v as Float / 255.0)
+                    .map_err(|_| ParseError::new(input, format!("`{}` is not hex", &hex[i..i + 2])))
+            };
+            return Ok(Color::new(channel(0)?, channel(2)?, channel(4)?));
+        }
+        let (name, v) = parse_call(s)?;
+        if name != "Color" && name != "color" {
+            return Err(ParseError::new(
+                input,
+                format!("expected `color(...)`, got `{name}`"),
+            ));
+        }
+        if v.len() != 3 {
+            return Err(ParseError::new(
+                input,
+                format!("color takes 3 values, got {}", v.len()),
+            ));
+        }
+        Ok(Color::new(v[0], v[1], v[2]))
+    }
+}
 
 impl Add for Color {
     type Output = Color;
@@ -94,15 +240,6 @@ impl Mul<Color> for Color {
     }
 }
 
-// For assert_eq! in tests
-impl PartialEq for Color {
-    fn eq(&self, other: &Self) -> bool {
-        (self.red - other.red).abs() < EPSILON
-            && (self.green - other.green).abs() < EPSILON
-            && (self.blue - other.blue).abs() < EPSILON
-    }
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -122,7 +259,17 @@ mod tests {
         let c1 = Color::new(0.9, 0.6, 0.75);
         let c2 = Color::new(0.7, 0.1, 0.25);
         let result = c1 + c2;
-        assert_eq!(result, Color::new(1.6, 0.7, 1.0));
+        crate::assert_color_eq!(result, Color::new(1.6, 0.7, 1.0));
+    }
+
+    #[test]
+    fn equality_is_exact_and_approx_eq_takes_a_tolerance() {
+        let a = Color::new(0.5, 0.5, 0.5);
+        let b = Color::new(0.5, 0.5, 0.5001);
+        assert_ne!(a, b);
+        assert!(a.approx_eq(&b, 0.001));
+        assert!(!a.approx_eq(&b, 0.00001));
+        crate::assert_color_eq!(a, b);
     }
 
     // Scenario: Subtracting colors
@@ -131,7 +278,7 @@ mod tests {
         let c1 = Color::new(0.9, 0.6, 0.75);
         let c2 = Color::new(0.7, 0.1, 0.25);
         let result = c1 - c2;
-        assert_eq!(result, Color::new(0.2, 0.5, 0.5));
+        crate::assert_color_eq!(result, Color::new(0.2, 0.5, 0.5));
     }
 
     // Scenario: Multiplying a color by a scalar
@@ -148,6 +295,112 @@ mod tests {
         let c1 = Color::new(1.0, 0.2, 0.4);
         let c2 = Color::new(0.9, 1.0, 0.1);
         let result = c1 * c2;
-        assert_eq!(result, Color::new(0.9, 0.2, 0.04));
+        crate::assert_color_eq!(result, Color::new(0.9, 0.2, 0.04));
+    }
+
+    #[test]
+    fn candle_light_is_orange() {
+        let c = Color::from_temperature(1900.0);
+        assert!(c.red > c.green && c.green > c.blue);
+    }
+
+    #[test]
+    fn daylight_is_white() {
+        assert_eq!(Color::from_temperature(6500.0), COLOR_WHITE);
+    }
+
+    #[test]
+    fn hot_stars_are_blue() {
+        let c = Color::from_temperature(20000.0);
+        assert!(c.blue > c.green && c.green > c.red);
+    }
+
+    #[test]
+    fn blue_increases_with_temperature() {
+        let blues: Vec<_> = (10..=400)
+            .map(|k| Color::from_temperature(k as Float * 100.0).blue)
+            .collect();
+        assert!(blues.windows(2).all(|w| w[1] >= w[0]));
+        assert!(Color::from_temperature(4000.0).blue > Color::from_temperature(3000.0).blue);
+    }
+
+    #[test]
+    fn temperatures_outside_the_fit_are_clamped() {
+        assert_eq!(
+            Color::from_temperature(100.0),
+            Color::from_temperature(1000.0)
+        );
+        assert_eq!(
+            Color::from_temperature(1.0e6),
+            Color::from_temperature(40000.0)
+        );
+    }
+
+    #[test]
+    fn colors_round_trip_through_display() {
+        let c = Color::new(-0.5, 0.4, 1.7);
+        assert_eq!(c.to_string().parse::<Color>().unwrap(), c);
+        assert_eq!(
+            "color(0.25, 0, 1)".parse::<Color>().unwrap(),
+            Color::new(0.25, 0.0, 1.0)
+        );
+    }
+
+    #[test]
+    fn colors_parse_from_hex() {
+        let c: Color = "#ff8000".parse().unwrap();
+        assert_eq!(c, Color::new(1.0, 128.0 / 255.0, 0.0));
+        let err = "#ff80".parse::<Color>().unwrap_err();
+        assert!(err.message.contains("6 digits"));
+        assert!("#gg0000".parse::<Color>().is_err());
+    }
+
+    #[test]
+    fn malformed_colors_give_descriptive_errors() {
+        let err = "point(1, 2, 3)".parse::<Color>().unwrap_err();
+        assert!(err.message.contains("got `point`"));
+        let err = "color(1, 2)".parse::<Color>().unwrap_err();
+        assert!(err.message.contains("3 values"));
+        let err = "#€000".parse::<Color>().unwrap_err();
+        assert!(err.message.contains("6 digits"));
+        let err = "#12345g".parse::<Color>().unwrap_err();
+        assert!(err.message.contains("`5g` is not hex"));
+    }
+
+    #[test]
+    fn luminance_weights_green_most() {
+        assert!((Color::new(1.0, 1.0, 1.0).luminance() - 1.0).abs() < 1e-6);
+        let green = Color::new(0.0, 1.0, 0.0).luminance();
+        assert!(green > Color::new(1.0, 0.0, 0.0).luminance());
+        assert!(green > Color::new(0.0, 0.0, 1.0).luminance());
+    }
+
+    #[test]
+    fn the_color_constants() {
+        let channels = |c: Color| (c.red, c.green, c.blue);
+        let gray = 128.0 / 255.0;
+        for (c, expected) in [
+            (COLOR_BLACK, (0.0, 0.0, 0.0)),
+            (COLOR_WHITE, (1.0, 1.0, 1.0)),
+            (COLOR_RED, (1.0, 0.0, 0.0)),
+            (COLOR_GREEN, (0.0, 1.0, 0.0)),
+            (COLOR_BLUE, (0.0, 0.0, 1.0)),
+            (COLOR_GRAY, (gray, gray, gray)),
+            (COLOR_YELLOW, (1.0, 1.0, 0.0)),
+            (COLOR_CYAN, (0.0, 1.0, 1.0)),
+            (COLOR_MAGENTA, (1.0, 0.0, 1.0)),
+            (COLOR_ORANGE, (1.0, 165.0 / 255.0, 0.0)),
+        ] {
+            assert_eq!(channels(c), expected);
+        }
+    }
+
+    #[test]
+    fn looking_up_colors_by_name() {
+        assert_eq!(Color::named("white"), Some(COLOR_WHITE));
+        assert_eq!(Color::named(" Grey "), Some(COLOR_GRAY));
+        assert_eq!(Color::named("FUCHSIA"), Some(COLOR_MAGENTA));
+        assert_eq!(Color::named("chartreuse"), None);
+        assert_eq!(Color::named(""), None);
     }
 }
@@ -0,0 +1,152 @@
+// One error type for everything the crate can fail at, so callers can use ?
+// across loading, validating and saving. Each module keeps its own error with
+// the details that matter there; this wraps them.
+use std::{fmt, path::PathBuf};
+
+use crate::{
+    camera::CameraError, canvas::ImageError, matrices::TransformError, options::OptionsError,
+    tuples::ParseError,
+};
+
+#[derive(Debug)]
+pub enum RpovError {
+    // The path is None when the error came from plain I/O through `?`.
+    Io {
+        path: Option<PathBuf>,
+        source: std::io::Error,
+    },
+    Image(ImageError),
+    Camera(CameraError),
+    Transform(TransformError),
+    Options(OptionsError),
+    Parse(ParseError),
+}
+
+impl RpovError {
+    pub fn io(path: impl Into<PathBuf>, source: std::io::Error) -> Self {
+        RpovError::Io {
+            path: Some(path.into()),
+            source,
+        }
+    }
+
+    // Names the file an image error came from.
+    pub fn image_at(path: impl Into<PathBuf>, error: ImageError) -> Self {
+        RpovError::Image(ImageError {
+            path: Some(path.into()),
+            ..error
+        })
+    }
+}
+
+impl fmt::Display for RpovError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RpovError::Io {
+                path: Some(path),
+                source,
+            } => write!(f, "{}: {source}", path.display()),
+            RpovError::Io { path: None, source } => write!(f, "{source}"),
+            RpovError::Image(e) => write!(f, "{e}"),
+            RpovError::Camera(e) => write!(f, "{e}"),
+            RpovError::Transform(e) => write!(f, "{e}"),
+            RpovError::Options(e) => write!(f, "{e}"),
+            RpovError::Parse(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl std::error::Error for RpovError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            RpovError::Io { source, .. } => Some(source),
+            RpovError::Image(e) => Some(e),
+            RpovError::Camera(e) => Some(e),
+            RpovError::Transform(e) => Some(e),
+            RpovError::Options(e) => Some(e),
+            RpovError::Parse(e) => Some(e),
+        }
+    }
+}
+
+impl From<std::io::Error> for RpovError {
+    fn from(source: std::io::Error) -> Self {
+        RpovError::Io { path: None, source }
+    }
+}
+
+impl From<ImageError> for RpovError {
+    fn from(e: ImageError) -> Self {
+        RpovError::Image(e)
+    }
+}
+
+impl From<CameraError> for RpovError {
+    fn from(e: CameraError) -> Self {
+        RpovError::Camera(e)
+    }
+}
+
+impl From<TransformError> for RpovError {
+    fn from(e: TransformError) -> Self {
+        RpovError::Transform(e)
+    }
+}
+
+impl From<OptionsError> for RpovError {
+    fn from(e: OptionsError) -> Self {
+        RpovError::Options(e)
+    }
+}
+
+impl From<ParseError> for RpovError {
+    fn from(e: ParseError) -> Self {
+        RpovError::Parse(e)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{canvas::Canvas, floats::PI, spheres::Sphere, transformations::scaling};
+
+    #[test]
+    fn reading_a_missing_file_names_the_file() {
+        let Err(e) = Canvas::read_ppm("no/such/dir/missing.ppm") else {
+            panic!("read a file that isn't there");
+        };
+        assert!(matches!(e, RpovError::Io { .. }));
+        assert!(e.to_string().contains("no/such/dir/missing.ppm"), "{e}");
+    }
+
+    #[test]
+    fn a_malformed_ppm_names_the_file_and_line() {
+        let path = std::env::temp_dir().join("rpov-malformed.ppm");
+        std::fs::write(&path, "P3\n2 1\n255\n0 0 0\n255 oops 0\n").unwrap();
+        let read = Canvas::read_ppm(&path);
+        std::fs::remove_file(&path).unwrap();
+        let Err(e) = read else {
+            panic!("read a malformed PPM");
+        };
+        let message = e.to_string();
+        assert!(message.contains("rpov-malformed.ppm"), "{message}");
+        assert!(message.contains("line 5"), "{message}");
+        assert!(message.contains("oops"), "{message}");
+    }
+
+    #[test]
+    fn errors_from_each_module_convert_for_question_mark() {
+        fn all() -> Result<(), RpovError> {
+            crate::camera::Camera::new(0, 10, PI / 2.0).validate()?;
+            Ok(())
+        }
+        assert!(matches!(all(), Err(RpovError::Camera(_))));
+
+        let mut s = Sphere::new();
+        let e: RpovError = s.set_transform(scaling(0.0, 1.0, 1.0)).unwrap_err().into();
+        assert!(
+            e.to_string().contains(&format!("Sphere(id={})", s.id)),
+            "{e}"
+        );
+    }
+}
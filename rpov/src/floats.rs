@@ -10,6 +10,19 @@ pub type Float = f64;
 #[cfg(feature = "f64")]
 pub use std::f64::consts;
 
+// The type intersection solvers do their arithmetic in. With the
+// precise-intersections feature it is f64 even when Float is f32, which keeps
+// hits on shapes far from the origin accurate; t values are stored as Float.
+#[cfg(feature = "precise-intersections")]
+pub type Wide = f64;
+#[cfg(not(feature = "precise-intersections"))]
+pub type Wide = Float;
+
+#[allow(clippy::unnecessary_cast)]
+pub fn narrow(x: Wide) -> Float {
+    x as Float
+}
+
 pub const ONE: Float = 1.0;
 pub const TWO: Float = 2.0;
 pub const PI: Float = consts::PI;
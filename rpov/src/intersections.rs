@@ -1,35 +1,188 @@
+#![cfg_attr(not(test), deny(clippy::unwrap_used))]
+
 use std::fmt::Debug;
 
 use crate::{
     floats::Float,
-    planes::Plane,
-    shapes::{ShapeFunctions, TestShape},
-    spheres::Sphere,
+    materials::Sidedness,
+    rays::{Ray, RayKind},
+    shapes::{Intersectable, ShapeFunctions},
+    tuples::Tuple4,
 };
 
-pub trait Shape: ShapeFunctions + Debug {}
+pub trait Shape: ShapeFunctions + Debug + Send + Sync {
+    // Object-safe entry point so heterogeneous shapes can live in one list.
+    fn intersect_ray(&self, ray: Ray) -> Vec<Intersection<'_>>;
+    fn intersect_ray_bounded(&self, ray: Ray, t_max: Float) -> Vec<Intersection<'_>>;
+    fn intersects_ray_before(&self, ray: Ray, t_max: Float) -> bool;
+    // Lets a world of boxed shapes be cloned.
+    fn clone_box(&self) -> Box<dyn Shape>;
+    // The concrete type's name, for telling shapes apart behind a Box.
+    fn kind(&self) -> &'static str;
+}
 
-impl Shape for Sphere {}
-impl Shape for Plane {}
-impl Shape for TestShape {}
+impl<T: Intersectable<T> + ShapeFunctions + Debug + Clone + Send + Sync + 'static> Shape for T {
+    fn intersect_ray(&self, ray: Ray) -> Vec<Intersection<'_>> {
+        self.intersect(ray)
+    }
+    fn intersect_ray_bounded(&self, ray: Ray, t_max: Float) -> Vec<Intersection<'_>> {
+        self.intersect_bounded(ray, t_max)
+    }
+    fn intersects_ray_before(&self, ray: Ray, t_max: Float) -> bool {
+        self.hits_before(ray, t_max)
+    }
+    fn clone_box(&self) -> Box<dyn Shape> {
+        Box::new(self.clone())
+    }
+    fn kind(&self) -> &'static str {
+        std::any::type_name::<T>()
+    }
+}
+
+// The shape's kind without its module path or parameters, for reports.
+pub(crate) fn short_kind(shape: &dyn Shape) -> &'static str {
+    let name = shape.kind();
+    let name = name.split('<').next().unwrap_or(name);
+    name.rsplit("::").next().unwrap_or(name)
+}
+
+impl Clone for Box<dyn Shape> {
+    fn clone(&self) -> Self {
+        self.clone_box()
+    }
+}
 
 #[derive(Copy, Clone, Debug)]
 pub struct Intersection<'a> {
     pub t: Float,
     pub object: &'a dyn Shape,
+    // Surface coordinates, for shapes that can work them out while intersecting.
+    pub uv: Option<(Float, Float)>,
+    // The world-space normal, for shapes whose surface depends on the ray
+    // and so can't be found from the point alone. Used instead of normal_at.
+    pub normal: Option<Tuple4>,
 }
 
 impl<'a> Intersection<'a> {
     pub fn new(t: Float, object: &'a dyn Shape) -> Self {
-        Self { t, object }
+        Self {
+            t,
+            object,
+            uv: None,
+            normal: None,
+        }
+    }
+
+    pub fn with_uv(t: Float, object: &'a dyn Shape, uv: (Float, Float)) -> Self {
+        Self {
+            t,
+            object,
+            uv: Some(uv),
+            normal: None,
+        }
+    }
+
+    pub fn with_normal(t: Float, object: &'a dyn Shape, normal: Tuple4) -> Self {
+        Self {
+            t,
+            object,
+            uv: None,
+            normal: Some(normal),
+        }
+    }
+
+    // The normal at `point`, which should be where the hit is.
+    pub fn normal_at(&self, point: &Tuple4) -> Tuple4 {
+        self.normal.unwrap_or_else(|| self.object.normal_at(point))
+    }
+
+    // Whether a ray of this kind sees the hit at all. The one place the
+    // material visibility flags are applied to intersections.
+    pub fn visible_to(&self, kind: RayKind) -> bool {
+        self.object.resolved_material().visible_to(kind)
+    }
+
+    // Whether `r` arrives on a side of the surface its material shows. A
+    // shadow ray runs from the surface to the light, so what counts is the
+    // side the light sees.
+    pub fn faces(&self, r: &Ray) -> bool {
+        let sidedness = self.object.resolved_material().sidedness;
+        if sidedness == Sidedness::TwoSided {
+            return true;
+        }
+        let normal = self.normal_at(&r.position(self.t));
+        let towards = match r.kind {
+            RayKind::Shadow => -r.direction,
+            _ => r.direction,
+        };
+        let front = normal.dot(towards) < 0.0;
+        match sidedness {
+            Sidedness::FrontOnly => front,
+            Sidedness::BackOnly => !front,
+            Sidedness::TwoSided => true,
+        }
     }
 }
 
+// Hits closer together than this are taken to be the same surface point.
+pub const T_MERGE_EPSILON: Float = 1.0e-4;
+
 pub fn hit<'a>(intersections: &[Intersection<'a>]) -> Option<Intersection<'a>> {
-    intersections
-        .iter()
-        .filter(|i| i.t >= 0.0)
-        .min_by(|a, b| a.t.partial_cmp(&b.t).unwrap())
+    hit_with_merge_epsilon(intersections, T_MERGE_EPSILON)
+}
+
+// Among hits within merge_epsilon of the nearest, the one whose material has
+// the highest priority wins, then the shape made last, which is the one with
+// the highest id, and then the nearer of its hits.
+pub fn hit_with_merge_epsilon<'a>(
+    intersections: &[Intersection<'a>],
+    merge_epsilon: Float,
+) -> Option<Intersection<'a>> {
+    nearest_hit(intersections, merge_epsilon, |_| true)
+}
+
+// The hit a ray of the given kind sees, skipping shapes whose material hides
+// them from it.
+pub fn hit_for<'a>(intersections: &[Intersection<'a>], kind: RayKind) -> Option<Intersection<'a>> {
+    hit_for_with_merge_epsilon(intersections, kind, T_MERGE_EPSILON)
+}
+
+pub fn hit_for_with_merge_epsilon<'a>(
+    intersections: &[Intersection<'a>],
+    kind: RayKind,
+    merge_epsilon: Float,
+) -> Option<Intersection<'a>> {
+    nearest_hit(intersections, merge_epsilon, |i| i.visible_to(kind))
+}
+
+// Like hit_for_with_merge_epsilon, but nothing nearer than `near` counts.
+pub fn hit_beyond<'a>(
+    intersections: &[Intersection<'a>],
+    kind: RayKind,
+    merge_epsilon: Float,
+    near: Float,
+) -> Option<Intersection<'a>> {
+    nearest_hit(intersections, merge_epsilon, |i| {
+        i.t >= near && i.visible_to(kind)
+    })
+}
+
+fn nearest_hit<'a>(
+    intersections: &[Intersection<'a>],
+    merge_epsilon: Float,
+    keep: impl Fn(&Intersection<'a>) -> bool,
+) -> Option<Intersection<'a>> {
+    let candidates = || intersections.iter().filter(|i| i.t >= 0.0 && keep(i));
+    let nearest = candidates().min_by(|a, b| a.t.total_cmp(&b.t))?.t;
+    candidates()
+        .filter(|i| i.t - nearest <= merge_epsilon)
+        .max_by(|a, b| {
+            let priority = |i: &Intersection| i.object.resolved_material().priority;
+            priority(a)
+                .cmp(&priority(b))
+                .then(a.object.id().cmp(&b.object.id()))
+                .then(b.t.total_cmp(&a.t))
+        })
         .copied()
 }
 
@@ -41,6 +194,7 @@ mod tests {
 
     use super::*;
     use crate::{
+        planes::Plane,
         rays::ray,
         shapes::Intersectable,
         spheres::Sphere,
@@ -60,6 +214,16 @@ mod tests {
         assert_same_object!(i.object, &s);
     }
 
+    #[test]
+    fn intersections_carry_no_uv_unless_given_one() {
+        let s = Sphere::new();
+        assert_eq!(Intersection::new(1.0, &s).uv, None);
+        assert_eq!(
+            Intersection::with_uv(1.0, &s, (0.25, 0.75)).uv,
+            Some((0.25, 0.75))
+        );
+    }
+
     // Scenario: Aggregating intersections
     //   Given s ← sphere()
     //     And i1 ← intersection(1, s)
@@ -237,7 +401,7 @@ mod tests {
         );
         let i = Intersection::new(SQRT_2, &shape);
         let comps = i.prepare_computations(r, None);
-        assert_eq!(comps.reflectv, vector(0.0, SQRT_2 / 2.0, SQRT_2 / 2.0));
+        assert_eq!(comps.reflectv(), vector(0.0, SQRT_2 / 2.0, SQRT_2 / 2.0));
     }
 
     // Scenario Outline: Finding n1 and n2 at various intersections
@@ -267,15 +431,18 @@ mod tests {
     #[test]
     fn finding_n1_and_n2_at_various_intersections() {
         let mut a = crate::spheres::glass_sphere();
-        a.transform = crate::transformations::scaling(2.0, 2.0, 2.0);
+        a.set_transform(crate::transformations::scaling(2.0, 2.0, 2.0))
+            .unwrap();
         a.material.refractive_index = 1.5;
 
         let mut b = crate::spheres::glass_sphere();
-        b.transform = crate::transformations::translation(0.0, 0.0, -0.25);
+        b.set_transform(crate::transformations::translation(0.0, 0.0, -0.25))
+            .unwrap();
         b.material.refractive_index = 2.0;
 
         let mut c = crate::spheres::glass_sphere();
-        c.transform = crate::transformations::translation(0.0, 0.0, 0.25);
+        c.set_transform(crate::transformations::translation(0.0, 0.0, 0.25))
+            .unwrap();
         c.material.refractive_index = 2.5;
 
         let r = ray(point(0.0, 0.0, -4.0), vector(0.0, 0.0, 1.0));
@@ -302,8 +469,8 @@ mod tests {
         for (index, expected_n1, expected_n2) in test_cases {
             let i = &xs[index];
             let comps = i.prepare_computations(r, sxs.clone());
-            assert_eq!(comps.n1, expected_n1, "at {index}");
-            assert_eq!(comps.n2, expected_n2, "at {index}");
+            assert_eq!(comps.n1(), expected_n1, "at {index}");
+            assert_eq!(comps.n2(), expected_n2, "at {index}");
         }
     }
 
@@ -320,11 +487,112 @@ mod tests {
     fn the_under_point_is_offset_below_the_surface() {
         let r = ray(point(0.0, 0.0, -5.0), vector(0.0, 0.0, 1.0));
         let mut shape = crate::spheres::glass_sphere();
-        shape.transform = crate::transformations::translation(0.0, 0.0, 1.0);
+        shape
+            .set_transform(crate::transformations::translation(0.0, 0.0, 1.0))
+            .unwrap();
         let i = Intersection::new(5.0, &shape);
         let xs = vec![i];
         let comps = i.prepare_computations(r, Some(xs));
         assert!(comps.under_point.z > crate::floats::EPSILON / 2.0);
         assert!(comps.point.z < comps.under_point.z);
     }
+
+    #[test]
+    fn coincident_hits_prefer_priority_then_the_later_shape() {
+        let a = Sphere::new();
+        let mut b = Sphere::new();
+        let c = Sphere::new();
+        let xs = vec![Intersection::new(3.0, &a), Intersection::new(3.00001, &c)];
+        assert_same_object!(hit(&xs).unwrap().object, &c);
+        b.material.priority = 1;
+        let xs = vec![
+            Intersection::new(3.0, &a),
+            Intersection::new(3.00001, &b),
+            Intersection::new(3.00002, &c),
+        ];
+        assert_same_object!(hit(&xs).unwrap().object, &b);
+        let xs = vec![Intersection::new(3.0, &a), Intersection::new(3.5, &b)];
+        assert_same_object!(hit(&xs).unwrap().object, &a);
+    }
+
+    #[test]
+    fn coincident_hits_of_equal_priority_go_to_the_shape_made_last() {
+        let floor = crate::planes::Plane::new();
+        let decal = crate::rectangles::Rectangle::new(1.0, 1.0);
+        assert!(decal.id > floor.id);
+        // Whichever order the hits come in, and whichever is nearer.
+        for xs in [
+            vec![
+                Intersection::new(2.0, &floor),
+                Intersection::new(2.0, &decal),
+            ],
+            vec![
+                Intersection::new(2.0, &decal),
+                Intersection::new(2.0, &floor),
+            ],
+            vec![
+                Intersection::new(2.0, &decal),
+                Intersection::new(2.00001, &floor),
+            ],
+            vec![
+                Intersection::new(2.0, &floor),
+                Intersection::new(2.00001, &decal),
+            ],
+        ] {
+            assert_same_object!(hit(&xs).unwrap().object, &decal);
+        }
+        // Both hits on one shape: the nearer.
+        let s = Sphere::new();
+        let xs = vec![Intersection::new(1.0, &s), Intersection::new(1.00001, &s)];
+        assert_eq!(hit(&xs).unwrap().t, 1.0);
+    }
+
+    #[test]
+    fn hit_for_skips_shapes_hidden_from_the_ray_kind() {
+        type Flag = fn(&mut crate::materials::Material);
+        let flags: [(Flag, RayKind); 3] = [
+            (|m| m.visible_to_camera = false, RayKind::Camera),
+            (|m| m.casts_shadow = false, RayKind::Shadow),
+            (|m| m.visible_in_reflections = false, RayKind::Reflection),
+        ];
+        let kinds = [
+            RayKind::Camera,
+            RayKind::Shadow,
+            RayKind::Reflection,
+            RayKind::Refraction,
+        ];
+        for (clear, hidden_from) in flags {
+            let mut blocker = Sphere::new();
+            clear(&mut blocker.material);
+            let behind = Sphere::new();
+            let xs = vec![
+                Intersection::new(1.0, &blocker),
+                Intersection::new(2.0, &behind),
+            ];
+            for kind in kinds {
+                let hidden = kind == hidden_from
+                    || (hidden_from == RayKind::Reflection && kind == RayKind::Refraction);
+                let h = hit_for(&xs, kind).unwrap();
+                assert_eq!(h.t, if hidden { 2.0 } else { 1.0 }, "{kind:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn hit_for_matches_hit_with_default_materials() {
+        let s = Sphere::new();
+        let xs = vec![
+            Intersection::new(-1.0, &s),
+            Intersection::new(3.0, &s),
+            Intersection::new(2.0, &s),
+        ];
+        for kind in [
+            RayKind::Camera,
+            RayKind::Shadow,
+            RayKind::Reflection,
+            RayKind::Refraction,
+        ] {
+            assert_eq!(hit_for(&xs, kind).map(|i| i.t), hit(&xs).map(|i| i.t));
+        }
+    }
 }
@@ -1,19 +1,38 @@
+pub mod any_shapes;
+pub mod billboards;
+pub mod bounds;
+pub mod brdf;
 pub mod camera;
 pub mod canvas;
+pub mod clipped_shapes;
 pub mod colors;
+pub mod error;
 pub mod floats;
 pub mod intersections;
 pub mod lighting;
 pub mod materials;
 pub mod matrices;
+pub mod memory;
+pub mod metrics;
 pub mod normals;
+pub mod onb;
+pub mod options;
 pub mod patterns;
 pub mod planes;
+#[cfg(feature = "profile")]
+pub mod profiling;
+pub mod quaternions;
 pub mod rays;
+pub mod rectangles;
+pub mod scene;
+pub mod sdf_shapes;
 pub mod shapes;
 pub mod spheres;
+#[cfg(any(test, feature = "testutils"))]
+pub mod testutils;
 pub mod transformations;
 pub mod tuples;
+pub mod warnings;
 pub mod world;
 
 #[macro_export]
@@ -40,11 +59,48 @@ macro_rules! check_floats {
     };
 }
 
-#[macro_export(local_inner_macros)]
+// Colors compare exactly with ==. Computed colors should be checked with
+// this instead, channel by channel to within `epsilon`, which defaults to
+// floats::EPSILON so the same literals hold for f32 and f64 builds.
+#[macro_export]
+macro_rules! assert_color_eq {
+    ($a:expr, $b:expr) => {
+        $crate::assert_color_eq!($a, $b, $crate::floats::EPSILON)
+    };
+    ($a:expr, $b:expr, $epsilon:expr) => {{
+        let (a, b): ($crate::colors::Color, $crate::colors::Color) = ($a, $b);
+        let epsilon = $epsilon;
+        assert!(
+            a.approx_eq(&b, epsilon),
+            "colors differ by more than {}\n  left: {}\n right: {}\n  diff: red {}, green {}, blue {}",
+            epsilon,
+            a,
+            b,
+            a.red - b.red,
+            a.green - b.green,
+            a.blue - b.blue
+        );
+    }};
+}
+
+#[macro_export]
 macro_rules! check_colors {
     ($a:expr, $b:expr) => {
-        check_floats!($a.red, $b.red);
-        check_floats!($a.green, $b.green);
-        check_floats!($a.blue, $b.blue);
+        $crate::assert_color_eq!($a, $b)
     };
 }
+
+// Times the rest of the enclosing block under the `profile` feature.
+#[cfg(feature = "profile")]
+#[macro_export]
+macro_rules! profile_scope {
+    ($name:expr) => {
+        let _profile_scope = $crate::profiling::Scope::enter($name);
+    };
+}
+
+#[cfg(not(feature = "profile"))]
+#[macro_export]
+macro_rules! profile_scope {
+    ($name:expr) => {};
+}
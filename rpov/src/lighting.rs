@@ -1,14 +1,23 @@
-use crate::colors::{COLOR_BLACK, Color};
+use std::collections::HashSet;
+
+use crate::brdf::Brdf;
+use crate::colors::{COLOR_BLACK, COLOR_WHITE, Color};
 use crate::floats::Float;
 use crate::intersections::Shape;
 use crate::materials::Material;
 use crate::tuples::{PointOrVector, Tuple4};
 use crate::world::Computations;
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct PointLight {
     pub position: Tuple4,
     pub intensity: Color,
+    // When set, the light only reaches shapes with one of these tags;
+    // everything else gets ambient light alone.
+    pub affects: Option<HashSet<String>>,
+    // When set, points farther away than this get no light at all, and no
+    // shadow rays are cast toward the light from them.
+    pub max_range: Option<Float>,
 }
 
 pub fn point_light(position: Tuple4, intensity: Color) -> PointLight {
@@ -16,12 +25,61 @@ pub fn point_light(position: Tuple4, intensity: Color) -> PointLight {
         position.is_point(),
         "Position must be a point, got {position:?}"
     );
+    debug_assert!(
+        intensity.red >= 0.0 && intensity.green >= 0.0 && intensity.blue >= 0.0,
+        "light intensity must be non-negative, got {intensity:?}"
+    );
     PointLight {
         position,
         intensity,
+        affects: None,
+        max_range: None,
+    }
+}
+
+impl PointLight {
+    // Limits the light to shapes tagged with any of `tags`.
+    pub fn with_affects<T: Into<String>>(mut self, tags: impl IntoIterator<Item = T>) -> Self {
+        self.affects = Some(tags.into_iter().map(Into::into).collect());
+        self
+    }
+
+    pub fn with_max_range(mut self, range: Float) -> Self {
+        self.max_range = Some(range);
+        self
+    }
+
+    // Whether `point` is within the light's max_range, if it has one.
+    pub fn reaches(&self, point: Tuple4) -> bool {
+        self.max_range
+            .is_none_or(|range| (self.position - point).magnitude() <= range)
+    }
+
+    pub fn illuminates(&self, shape: &dyn Shape) -> bool {
+        self.affects
+            .as_ref()
+            .is_none_or(|affects| !affects.is_disjoint(shape.tags()))
     }
 }
 
+// A light whose color comes from a temperature in kelvin.
+pub fn point_light_k(position: Tuple4, kelvin: Float, brightness: Float) -> PointLight {
+    point_light(position, Color::from_temperature(kelvin) * brightness)
+}
+
+// Everything about a point being shaded apart from its material.
+pub struct ShadingContext<'a> {
+    pub point: Tuple4,
+    pub eyev: Tuple4,
+    pub normalv: Tuple4,
+    pub light: &'a PointLight,
+    // The share of the light that gets past any occluders, per channel.
+    // Black is the same as being in shadow, white as not.
+    pub attenuation: Color,
+    pub object: &'a dyn Shape,
+}
+
+// The book's lighting function, kept for its tests and the chapter examples.
 pub fn lighting(
     material: &Material,
     object: &dyn Shape,
@@ -31,60 +89,45 @@ pub fn lighting(
     normalv: Tuple4,
     in_shadow: bool,
 ) -> Color {
-    let c = if material.pattern.is_some() {
-        let pattern = material.pattern.as_ref().unwrap();
-        pattern.pattern_at_shape(object, position)
-    } else {
-        material.color
+    let ctx = ShadingContext {
+        point: position,
+        eyev,
+        normalv,
+        light,
+        attenuation: if in_shadow { COLOR_BLACK } else { COLOR_WHITE },
+        object,
     };
-    // combine the surface color with the light's color/intensity
-    let effective_color = c * light.intensity;
-
-    // find the direction to the light source
-    let lightv = (light.position - position).normalize();
+    shade_surface(&ctx, material)
+}
 
-    // compute the ambient contribution
+// Light reflected toward the eye from the surface itself: ambient, plus the
+// material's BRDF for the light, filtered by the attenuation.
+pub fn shade_surface(ctx: &ShadingContext, material: &Material) -> Color {
+    crate::profile_scope!("lighting");
+    material.debug_check();
+    let c = material.surface_color(ctx.object, ctx.point);
+    // combine the surface color with the light's color/intensity
+    let effective_color = c * ctx.light.intensity;
     let ambient = effective_color * material.ambient;
 
-    // light_dot_normal represents the cosine of the angle between the
-    // light vector and the normal vector. A negative number means the
-    // light is on the other side of the surface.
-    let light_dot_normal = lightv.dot(normalv);
-
-    if in_shadow {
+    let transmitted = ctx.attenuation;
+    if transmitted.red <= 0.0 && transmitted.green <= 0.0 && transmitted.blue <= 0.0 {
         return ambient;
     }
 
-    let (diffuse, specular) = if light_dot_normal < 0.0 {
-        (COLOR_BLACK, COLOR_BLACK)
-    } else {
-        // compute the diffuse contribution
-        let diffuse = effective_color * material.diffuse * light_dot_normal;
-
-        // reflect_dot_eye represents the cosine of the angle between the
-        // reflection vector and the eye vector. A negative number means the
-        // light reflects away from the eye.
-        let reflectv = (-lightv).reflect(normalv);
-        let reflect_dot_eye = reflectv.dot(eyev);
-
-        if reflect_dot_eye <= 0.0 {
-            (diffuse, COLOR_BLACK)
-        } else {
-            // compute the specular contribution
-            let factor = reflect_dot_eye.powf(material.shininess);
-            let specular = light.intensity * material.specular * factor;
-            (diffuse, specular)
-        }
+    // find the direction to the light source, if it isn't right on the point
+    let Some(lightv) = (ctx.light.position - ctx.point).try_normalize() else {
+        return ambient;
     };
-
-    ambient + diffuse + specular
+    let (diffuse, specular) = material.brdf().eval(lightv, ctx.eyev, ctx.normalv);
+    ambient + effective_color * diffuse * transmitted + ctx.light.intensity * specular * transmitted
 }
 
 pub fn schlick(comps: &Computations) -> Float {
     let mut cos = comps.eyev.dot(comps.normalv);
 
-    if comps.n1 > comps.n2 {
-        let n_ratio = comps.n1 / comps.n2;
+    if comps.n1() > comps.n2() {
+        let n_ratio = comps.n1() / comps.n2();
         let sin2_t = n_ratio.powi(2) * (1.0 - cos.powi(2));
         if sin2_t > 1.0 {
             return 1.0;
@@ -93,7 +136,7 @@ pub fn schlick(comps: &Computations) -> Float {
         cos = cos_t;
     }
 
-    let r0 = ((comps.n1 - comps.n2) / (comps.n1 + comps.n2)).powi(2);
+    let r0 = ((comps.n1() - comps.n2()) / (comps.n1() + comps.n2())).powi(2);
 
     r0 + (1.0 - r0) * (1.0 - cos).powi(5)
 }
@@ -102,7 +145,7 @@ pub fn schlick(comps: &Computations) -> Float {
 mod tests {
     use super::*;
     use crate::colors::Color;
-    use crate::materials::Material;
+    use crate::materials::{Material, SpecularModel};
     use crate::spheres::Sphere;
     use crate::tuples::{point, vector};
 
@@ -121,6 +164,12 @@ mod tests {
         assert_eq!(light.intensity, intensity);
     }
 
+    #[test]
+    fn a_point_light_from_a_temperature_is_scaled_by_brightness() {
+        let light = point_light_k(point(0.0, 0.0, 0.0), 6500.0, 2.0);
+        assert_eq!(light.intensity, Color::new(2.0, 2.0, 2.0));
+    }
+
     fn setup() -> (Material, Tuple4) {
         (Material::new(), point(0.0, 0.0, 0.0))
     }
@@ -142,7 +191,7 @@ mod tests {
             normalv,
             in_shadow,
         );
-        assert_eq!(result, Color::new(1.9, 1.9, 1.9));
+        crate::assert_color_eq!(result, Color::new(1.9, 1.9, 1.9));
     }
 
     // Scenario: Lighting with the eye between light and surface, eye offset 45°
@@ -163,7 +212,7 @@ mod tests {
             normalv,
             in_shadow,
         );
-        assert_eq!(result, Color::new(1.0, 1.0, 1.0));
+        crate::assert_color_eq!(result, Color::new(1.0, 1.0, 1.0));
     }
 
     // Scenario: Lighting with eye opposite surface, light offset 45°
@@ -183,7 +232,7 @@ mod tests {
             normalv,
             in_shadow,
         );
-        assert_eq!(result, Color::new(0.736_396_1, 0.736_396_1, 0.736_396_1));
+        crate::assert_color_eq!(result, Color::new(0.736_396_1, 0.736_396_1, 0.736_396_1));
     }
 
     // Scenario: Lighting with eye in the path of the reflection vector
@@ -204,7 +253,7 @@ mod tests {
             normalv,
             in_shadow,
         );
-        assert_eq!(result, Color::new(1.636_396, 1.636_396, 1.636_396));
+        crate::assert_color_eq!(result, Color::new(1.636_396, 1.636_396, 1.636_396));
     }
 
     // Scenario: Lighting with the light behind the surface
@@ -224,6 +273,43 @@ mod tests {
             normalv,
             in_shadow,
         );
-        assert_eq!(result, Color::new(0.1, 0.1, 0.1));
+        crate::assert_color_eq!(result, Color::new(0.1, 0.1, 0.1));
+    }
+
+    #[test]
+    fn phong_and_blinn_phong_agree_at_normal_incidence() {
+        let (mut m, position) = setup();
+        let eyev = vector(0.0, 0.0, -1.0);
+        let normalv = vector(0.0, 0.0, -1.0);
+        let light = point_light(point(0.0, 0.0, -10.0), Color::new(1.0, 1.0, 1.0));
+        let s = Sphere::new();
+        let phong = lighting(&m, &s, &light, position, eyev, normalv, false);
+        m.specular_model = SpecularModel::BlinnPhong;
+        let blinn = lighting(&m, &s, &light, position, eyev, normalv, false);
+        assert_eq!(phong, blinn);
+        crate::assert_color_eq!(blinn, Color::new(1.9, 1.9, 1.9));
+    }
+
+    #[test]
+    fn blinn_phong_has_a_wider_highlight_at_grazing_angles() {
+        let (mut m, position) = setup();
+        m.shininess = 10.0;
+        let eyev = vector(0.0, 0.0, -1.0);
+        let normalv = vector(0.0, 0.0, -1.0);
+        let light = point_light(point(0.0, 10.0, -10.0), Color::new(1.0, 1.0, 1.0));
+        let s = Sphere::new();
+        let phong = lighting(&m, &s, &light, position, eyev, normalv, false);
+        m.specular_model = SpecularModel::BlinnPhong;
+        let blinn = lighting(&m, &s, &light, position, eyev, normalv, false);
+        crate::assert_color_eq!(phong, Color::new(0.764521, 0.764521, 0.764521));
+        crate::assert_color_eq!(blinn, Color::new(0.774327, 0.774327, 0.774327));
+        assert!(blinn.red > phong.red);
+    }
+
+    #[cfg(debug_assertions)]
+    #[test]
+    #[should_panic(expected = "light intensity must be non-negative")]
+    fn negative_light_intensity_panics_in_debug_builds() {
+        point_light(point(0.0, 0.0, 0.0), Color::new(1.0, -1.0, 1.0));
     }
 }
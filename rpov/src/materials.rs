@@ -1,11 +1,40 @@
+use std::fmt;
 use std::sync::Arc;
 
+use crate::brdf::{AnyBrdf, Brdf};
+use crate::colors::Color;
 use crate::floats::Float;
+use crate::intersections::Shape;
+use crate::rays::RayKind;
+use crate::tuples::Tuple4;
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum SpecularModel {
+    #[default]
+    Phong,
+    // Uses the half vector between the light and the eye. The shininess is
+    // multiplied by 4 so highlights roughly match Phong's size.
+    BlinnPhong,
+}
+
+// Which sides of a surface can be seen. The front is the side the shape's
+// own normal points out of.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Sidedness {
+    #[default]
+    TwoSided,
+    FrontOnly,
+    BackOnly,
+}
 
 #[derive(Debug, Clone)]
 pub struct Material {
     pub color: crate::colors::Color,
     pub pattern: Option<Arc<dyn crate::patterns::Pattern>>,
+    // Bends the shading normal. Colors are tangent-space directions, each
+    // channel mapped from [0, 1] to [-1, 1]: red along the tangent, green
+    // along the bitangent and blue along the normal.
+    pub normal_map: Option<Arc<dyn crate::patterns::Pattern>>,
     pub ambient: Float,
     pub diffuse: Float,
     pub specular: Float,
@@ -13,6 +42,31 @@ pub struct Material {
     pub reflective: Float,
     pub transparency: Float,
     pub refractive_index: Float,
+    pub specular_model: SpecularModel,
+    // Decides between coincident surfaces; the highest priority is drawn.
+    pub priority: i32,
+    // When false, the shape never shadows itself. Safe for convex shapes.
+    pub self_shadow: bool,
+    // Spreads reflections over a cone; 0 is a perfect mirror, 1 a hemisphere.
+    pub roughness: Float,
+    // The same for refraction, giving frosted glass.
+    pub translucency_blur: Float,
+    // Treats the surface as a zero-thickness pane, such as a window made of
+    // one rectangle. Refracted rays carry straight on instead of bending into
+    // a body they never leave; Schlick reflectance still applies.
+    pub thin_surface: bool,
+    // Light absorbed per unit of distance travelled inside the shape, per
+    // channel, following Beer-Lambert. Black absorbs nothing.
+    pub absorbance: crate::colors::Color,
+    // Which rays see the shape. One hidden from the camera that still casts
+    // shadows works as a shadow catcher. Reflections here cover every
+    // secondary ray, refracted ones included.
+    pub visible_to_camera: bool,
+    pub visible_in_reflections: bool,
+    pub casts_shadow: bool,
+    // Rays reaching a hidden side pass straight through, and it casts no
+    // shadow on anything the light would see it from.
+    pub sidedness: Sidedness,
 }
 
 impl Default for Material {
@@ -26,6 +80,7 @@ impl Material {
         Material {
             color: crate::colors::Color::new(1.0, 1.0, 1.0),
             pattern: None,
+            normal_map: None,
             ambient: 0.1,
             diffuse: 0.9,
             specular: 0.9,
@@ -33,9 +88,164 @@ impl Material {
             reflective: 0.0,
             transparency: 0.0,
             refractive_index: 1.0,
+            specular_model: SpecularModel::Phong,
+            priority: 0,
+            self_shadow: true,
+            roughness: 0.0,
+            translucency_blur: 0.0,
+            thin_surface: false,
+            absorbance: crate::colors::Color::new(0.0, 0.0, 0.0),
+            visible_to_camera: true,
+            visible_in_reflections: true,
+            casts_shadow: true,
+            sidedness: Sidedness::TwoSided,
+        }
+    }
+
+    // Freezes the material for sharing between shapes, so many instances can
+    // use one allocation. Make any changes before sharing it.
+    pub fn shared(self) -> Arc<Material> {
+        Arc::new(self)
+    }
+
+    // Material fields are set directly, so they are checked where they are
+    // first used rather than when the material is built.
+    pub fn debug_check(&self) {
+        for (name, value) in [
+            ("ambient", self.ambient),
+            ("diffuse", self.diffuse),
+            ("specular", self.specular),
+            ("shininess", self.shininess),
+            ("reflective", self.reflective),
+            ("transparency", self.transparency),
+        ] {
+            debug_assert!(
+                value >= 0.0,
+                "material {name} must be non-negative, got {value}"
+            );
+        }
+    }
+
+    // Numbers and colors within `epsilon` of each other. Patterns have no
+    // equality of their own, so they only match when both materials share
+    // the same one, or neither has any.
+    pub fn approx_eq(&self, other: &Material, epsilon: Float) -> bool {
+        let close = |a: Float, b: Float| (a - b).abs() <= epsilon;
+        let same_pattern =
+            |a: &Option<Arc<dyn crate::patterns::Pattern>>,
+             b: &Option<Arc<dyn crate::patterns::Pattern>>| {
+                match (a, b) {
+                    (None, None) => true,
+                    (Some(a), Some(b)) => Arc::ptr_eq(a, b),
+                    _ => false,
+                }
+            };
+        same_pattern(&self.pattern, &other.pattern)
+            && same_pattern(&self.normal_map, &other.normal_map)
+            && self.color.approx_eq(&other.color, epsilon)
+            && self.absorbance.approx_eq(&other.absorbance, epsilon)
+            && close(self.ambient, other.ambient)
+            && close(self.diffuse, other.diffuse)
+            && close(self.specular, other.specular)
+            && close(self.shininess, other.shininess)
+            && close(self.reflective, other.reflective)
+            && close(self.transparency, other.transparency)
+            && close(self.refractive_index, other.refractive_index)
+            && close(self.roughness, other.roughness)
+            && close(self.translucency_blur, other.translucency_blur)
+            && self.specular_model == other.specular_model
+            && self.priority == other.priority
+            && self.self_shadow == other.self_shadow
+            && self.thin_surface == other.thin_surface
+            && self.visible_to_camera == other.visible_to_camera
+            && self.visible_in_reflections == other.visible_in_reflections
+            && self.casts_shadow == other.casts_shadow
+            && self.sidedness == other.sidedness
+    }
+
+    // Poses an animated pattern for time `t`. Static materials are left as
+    // they are.
+    pub fn set_time(&mut self, t: Float) {
+        if let Some(posed) = self.pattern.as_ref().and_then(|p| p.at_time(t)) {
+            self.pattern = Some(posed);
+        }
+    }
+
+    // The BRDF the specular model picks, with this material's coefficients.
+    pub fn brdf(&self) -> impl Brdf + use<> {
+        AnyBrdf::new(
+            self.specular_model,
+            self.diffuse,
+            self.specular,
+            self.shininess,
+        )
+    }
+
+    // The color at `point` on `object` before lighting: the pattern's, if
+    // there is one.
+    pub fn surface_color(&self, object: &dyn Shape, point: Tuple4) -> Color {
+        match &self.pattern {
+            Some(pattern) => pattern.pattern_at_shape(object, point),
+            None => self.color,
+        }
+    }
+
+    // The normal shading uses at `point`: `normal` bent by the normal map,
+    // if there is one. A map color of (0.5, 0.5, 1) leaves it as it is.
+    pub fn shading_normal(&self, object: &dyn Shape, point: Tuple4, normal: Tuple4) -> Tuple4 {
+        let Some(map) = &self.normal_map else {
+            return normal;
+        };
+        let c = map.pattern_at_shape(object, point);
+        let (x, y, z) = (c.red * 2.0 - 1.0, c.green * 2.0 - 1.0, c.blue * 2.0 - 1.0);
+        if x == 0.0 && y == 0.0 {
+            return normal;
+        }
+        let tangent = object.tangent_at(&point, normal);
+        let bitangent = normal.cross(tangent);
+        (tangent * x + bitangent * y + normal * z)
+            .try_normalize()
+            .unwrap_or(normal)
+    }
+
+    pub fn visible_to(&self, kind: RayKind) -> bool {
+        match kind {
+            RayKind::Camera => self.visible_to_camera,
+            RayKind::Shadow => self.casts_shadow,
+            RayKind::Reflection | RayKind::Refraction => self.visible_in_reflections,
         }
     }
 }
+// The fields that decide how a material looks. Reflection and refraction
+// are only listed when the material uses them.
+impl fmt::Display for Material {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let c = self.color;
+        write!(
+            f,
+            "color ({}, {}, {}), ambient {}, diffuse {}, specular {}, shininess {}",
+            c.red, c.green, c.blue, self.ambient, self.diffuse, self.specular, self.shininess
+        )?;
+        if self.pattern.is_some() {
+            write!(f, ", patterned")?;
+        }
+        if self.normal_map.is_some() {
+            write!(f, ", normal mapped")?;
+        }
+        if self.reflective > 0.0 {
+            write!(f, ", reflective {}", self.reflective)?;
+        }
+        if self.transparency > 0.0 {
+            write!(
+                f,
+                ", transparency {}, refractive index {}",
+                self.transparency, self.refractive_index
+            )?;
+        }
+        Ok(())
+    }
+}
+
 // Scenario: The default material
 //   Given m ← material()
 //   Then m.color = color(1, 1, 1)
@@ -139,4 +349,49 @@ mod tests {
         assert_eq!(m.transparency, 0.0);
         assert_eq!(m.refractive_index, 1.0);
     }
+
+    #[cfg(debug_assertions)]
+    #[test]
+    #[should_panic(expected = "material ambient must be non-negative")]
+    fn negative_ambient_panics_in_debug_builds() {
+        let (mut m, position) = test_setup();
+        m.ambient = -0.1;
+        let light = crate::lighting::point_light(point(0.0, 0.0, -10.0), Color::new(1.0, 1.0, 1.0));
+        let v = crate::tuples::vector(0.0, 0.0, -1.0);
+        crate::lighting::lighting(&m, &Sphere::new(), &light, position, v, v, false);
+    }
+
+    #[test]
+    fn shapes_sharing_a_material_use_one_allocation() {
+        use crate::{planes::Plane, shapes::ShapeFunctions, world::WorldBuilder};
+        let mut m = Material::new();
+        m.color = Color::new(1.0, 0.0, 0.0);
+        m.reflective = 0.5;
+        let shared = m.shared();
+        let mut a = Sphere::new();
+        let mut b = Plane::new();
+        a.shared_material = Some(shared.clone());
+        b.shared_material = Some(shared.clone());
+        assert!(std::ptr::eq(a.material(), b.material()));
+        assert_eq!(b.material().color, Color::new(1.0, 0.0, 0.0));
+        assert_eq!(a.material().reflective, 0.5);
+
+        let mut w = WorldBuilder::new();
+        w.objects.push(a);
+        w.planes.push(b);
+        let scene = w.build();
+        assert!(std::ptr::eq(scene.shapes[0].material(), &*shared));
+        assert!(std::ptr::eq(scene.shapes[1].material(), &*shared));
+        assert_eq!(Arc::strong_count(&shared), 3);
+    }
+
+    #[test]
+    fn a_shared_material_takes_the_place_of_the_shapes_own() {
+        use crate::shapes::ShapeFunctions;
+        let mut s = Sphere::new();
+        s.material.ambient = 0.7;
+        assert_eq!(s.material().ambient, 0.7);
+        s.shared_material = Some(Material::new().shared());
+        assert_eq!(s.material().ambient, 0.1);
+    }
 }
@@ -1,8 +1,8 @@
 use std::iter::zip;
 
 use crate::{
-    floats::Float,
-    tuples::{Tuple4, TupleElement},
+    floats::{EPSILON, Float},
+    tuples::{Tuple4, TupleElement, vector},
 };
 
 pub trait MatrixElement: TupleElement {}
@@ -37,7 +37,7 @@ impl<T: MatrixElement, const N: usize> Matrix<T, N> {
         Self: Determinant,
     {
         let det = self.determinant();
-        assert!(self.is_invertible(), "Matrix is not invertible");
+        assert!(self.is_invertible(), "Matrix is not invertible: {self:?}");
 
         let mut result = [[<Matrix<T, N> as Determinant>::Output::default(); N]; N];
         #[allow(clippy::needless_range_loop)]
@@ -86,6 +86,43 @@ pub type Matrix2 = Matrix<Float, 2>;
 pub type Matrix3 = Matrix<Float, 3>;
 pub type Matrix4 = Matrix<Float, 4>;
 
+// Transforms with a smaller determinant are treated as singular, since their
+// inverse would be mostly rounding error.
+pub const SINGULAR_DETERMINANT: Float = 1.0e-12;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TransformError {
+    pub shape: String,
+    pub determinant: Float,
+    // Boxed to keep Result<(), TransformError> small.
+    pub transform: Box<Matrix4>,
+}
+
+impl std::fmt::Display for TransformError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "transform of {} is not invertible (determinant {}): {:?}",
+            self.shape, self.determinant, self.transform
+        )
+    }
+}
+
+impl std::error::Error for TransformError {}
+
+// Checks that `transform` can be inverted, naming `shape` in the error.
+pub fn validate_transform(shape: &str, transform: Matrix4) -> Result<(), TransformError> {
+    let determinant = transform.determinant();
+    if determinant.abs() < SINGULAR_DETERMINANT || !determinant.is_finite() {
+        return Err(TransformError {
+            shape: shape.to_string(),
+            determinant,
+            transform: Box::new(transform),
+        });
+    }
+    Ok(())
+}
+
 fn dot_product<T: MatrixElement, const N: usize>(a: &[T; N], b: &[T; 4]) -> T {
     zip(a.iter(), b.iter())
         .map(|(x, y)| *x * *y)
@@ -125,6 +162,131 @@ impl Matrix<Float, 4> {
             w: r[3],
         }
     }
+
+    // Splits an affine transform into translation * rotation * scaling. The
+    // scale comes from the column norms, with x negated for a reflection.
+    // None for projective, singular or sheared matrices.
+    pub fn decompose(&self) -> Option<(Tuple4, Matrix4, Tuple4)> {
+        let d = &self.data;
+        if d[3] != [0.0, 0.0, 0.0, 1.0] {
+            return None;
+        }
+        let mut columns = [0, 1, 2].map(|c| vector(d[0][c], d[1][c], d[2][c]));
+        let mut scale = columns.map(|c| c.magnitude());
+        if scale.iter().any(|s| *s < EPSILON) {
+            return None;
+        }
+        for (c, s) in columns.iter_mut().zip(scale) {
+            *c = *c / s;
+        }
+        for (a, b) in [(0, 1), (0, 2), (1, 2)] {
+            if columns[a].dot(columns[b]).abs() > EPSILON {
+                return None;
+            }
+        }
+        if columns[0].cross(columns[1]).dot(columns[2]) < 0.0 {
+            columns[0] = -columns[0];
+            scale[0] = -scale[0];
+        }
+        let [x, y, z] = columns;
+        let rotation = Matrix4::from([
+            [x.x, y.x, z.x, 0.0],
+            [x.y, y.y, z.y, 0.0],
+            [x.z, y.z, z.z, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ]);
+        Some((
+            vector(d[0][3], d[1][3], d[2][3]),
+            rotation,
+            vector(scale[0], scale[1], scale[2]),
+        ))
+    }
+
+    // A short human-readable form, such as "translate (0, 1, 0), scale (2, 2, 2)".
+    // Falls back to the raw rows when the matrix does not decompose.
+    pub fn describe(&self) -> String {
+        if *self == Matrix4::identity() {
+            return "identity".to_string();
+        }
+        let Some((translation, rotation, scale)) = self.decompose() else {
+            return format!("matrix {:?}", self.data);
+        };
+        let triple = |t: Tuple4| format!("({}, {}, {})", rounded(t.x), rounded(t.y), rounded(t.z));
+        let mut parts = vec![];
+        if translation.magnitude() > EPSILON {
+            parts.push(format!("translate {}", triple(translation)));
+        }
+        let q = crate::quaternions::Quaternion::from_rotation_matrix(&rotation);
+        let half_angle = q.w.abs().min(1.0).acos();
+        if half_angle > EPSILON {
+            let axis = vector(q.x, q.y, q.z) * q.w.signum() / half_angle.sin();
+            parts.push(format!(
+                "rotate {}° about {}",
+                rounded((half_angle * 2.0).to_degrees()),
+                triple(axis)
+            ));
+        }
+        if [scale.x, scale.y, scale.z]
+            .iter()
+            .any(|s| (s - 1.0).abs() > EPSILON)
+        {
+            parts.push(format!("scale {}", triple(scale)));
+        }
+        parts.join(", ")
+    }
+
+    // Only rotates and translates, so lengths and angles are kept.
+    pub fn is_rigid(&self) -> bool {
+        self.decompose()
+            .is_some_and(|(_, _, s)| [s.x, s.y, s.z].iter().all(|s| (s - 1.0).abs() < EPSILON))
+    }
+
+    // Scales every axis by the same amount, possibly with rotation and
+    // translation too.
+    pub fn has_uniform_scale(&self) -> bool {
+        self.decompose().is_some_and(|(_, _, s)| {
+            (s.x.abs() - s.y.abs()).abs() < EPSILON && (s.y.abs() - s.z.abs()).abs() < EPSILON
+        })
+    }
+
+    // Every element within `epsilon` of the matching one in `other`.
+    pub fn approx_eq(&self, other: &Matrix4, epsilon: Float) -> bool {
+        zip(self.data.as_flattened(), other.data.as_flattened())
+            .all(|(a, b)| (a - b).abs() <= epsilon)
+    }
+
+    // Rows one after another, the order Matrix4::from takes them in.
+    pub fn from_row_major(values: &[Float; 16]) -> Matrix4 {
+        let mut data = [[0.0; 4]; 4];
+        for (row, chunk) in data.iter_mut().zip(values.chunks_exact(4)) {
+            row.copy_from_slice(chunk);
+        }
+        Matrix { data }
+    }
+
+    // Columns one after another, as glTF and most graphics APIs store them.
+    pub fn from_column_major(values: &[Float; 16]) -> Matrix4 {
+        Matrix4::from_row_major(values).transpose()
+    }
+
+    pub fn to_row_major(&self) -> [Float; 16] {
+        let mut values = [0.0; 16];
+        values.copy_from_slice(self.data.as_flattened());
+        values
+    }
+
+    pub fn to_column_major(&self) -> [Float; 16] {
+        self.transpose().to_row_major()
+    }
+}
+
+// Row-major, like Matrix4::from. Fails unless there are exactly 16 values.
+impl TryFrom<&[Float]> for Matrix4 {
+    type Error = std::array::TryFromSliceError;
+
+    fn try_from(values: &[Float]) -> Result<Self, Self::Error> {
+        Ok(Matrix4::from_row_major(values.try_into()?))
+    }
 }
 impl<T: MatrixElement, const N: usize> Matrix<T, N> {
     #[allow(clippy::needless_range_loop)]
@@ -188,7 +350,11 @@ pub trait Determinant {
 
     fn cofactor(&self, row: usize, col: usize) -> Self::Output {
         let minor = self.minor(row, col);
-        if (row + col) % 2 == 0 { minor } else { -minor }
+        if (row + col).is_multiple_of(2) {
+            minor
+        } else {
+            -minor
+        }
     }
     fn is_invertible(&self) -> bool {
         let def = <Self as Determinant>::Output::default();
@@ -240,6 +406,12 @@ impl<T: MatrixElement> Determinant for Matrix<T, 4> {
     }
 }
 
+// Four decimal places, enough to read a transform without float noise.
+// Adding zero turns -0 into 0.
+fn rounded(x: Float) -> Float {
+    (x * 10000.0).round() / 10000.0 + 0.0
+}
+
 pub fn check(inv: Matrix4, expected: Matrix4) {
     for row in 0..4 {
         for col in 0..4 {
@@ -899,4 +1071,111 @@ mod tests {
         let result = c * b_inv;
         check(result, a);
     }
+
+    #[test]
+    fn near_singular_transforms_are_rejected() {
+        let tiny = crate::transformations::scaling(1.0e-5, 1.0e-5, 1.0e-5);
+        let err = validate_transform("Sphere(id=7)", tiny).unwrap_err();
+        assert!(
+            err.to_string()
+                .starts_with("transform of Sphere(id=7) is not invertible")
+        );
+        assert!(validate_transform("Plane", Matrix4::identity()).is_ok());
+    }
+
+    #[test]
+    fn decomposing_a_composed_transform_recovers_each_part() {
+        use crate::transformations::{rotation_y, scaling, translation};
+        let m = translation(1.0, -2.0, 3.5) * rotation_y(0.8) * scaling(2.0, 0.5, 3.0);
+        let (t, r, s) = m.decompose().unwrap();
+        crate::tuples::check_tuple(t, vector(1.0, -2.0, 3.5));
+        check(r, rotation_y(0.8));
+        crate::tuples::check_tuple(s, vector(2.0, 0.5, 3.0));
+        check(translation(t.x, t.y, t.z) * r * scaling(s.x, s.y, s.z), m);
+    }
+
+    #[test]
+    fn a_reflection_decomposes_into_a_negative_scale() {
+        use crate::transformations::{rotation_z, scaling};
+        let m = rotation_z(0.3) * scaling(-1.0, 2.0, 2.0);
+        let (_, r, s) = m.decompose().unwrap();
+        crate::tuples::check_tuple(s, vector(-1.0, 2.0, 2.0));
+        check(r, rotation_z(0.3));
+    }
+
+    #[test]
+    fn sheared_and_singular_matrices_do_not_decompose() {
+        use crate::transformations::{scaling, shearing};
+        assert!(shearing(1.0, 0.0, 0.0, 0.0, 0.0, 0.0).decompose().is_none());
+        assert!(scaling(1.0, 0.0, 1.0).decompose().is_none());
+    }
+
+    #[test]
+    fn rigid_and_uniformly_scaled_transforms_are_recognised() {
+        use crate::transformations::{rotation_x, scaling, translation};
+        let rigid = translation(3.0, 0.0, 1.0) * rotation_x(1.2);
+        assert!(rigid.is_rigid());
+        assert!(rigid.has_uniform_scale());
+        let uniform = rigid * scaling(2.0, 2.0, 2.0);
+        assert!(!uniform.is_rigid());
+        assert!(uniform.has_uniform_scale());
+        assert!(!(rigid * scaling(2.0, 1.0, 2.0)).has_uniform_scale());
+    }
+
+    #[test]
+    fn describing_a_transform() {
+        use crate::floats::PI;
+        use crate::transformations::{rotation_y, scaling, translation};
+        assert_eq!(Matrix4::identity().describe(), "identity");
+        assert_eq!(
+            (translation(1.0, 2.0, 3.0) * rotation_y(PI / 2.0) * scaling(2.0, 2.0, 2.0)).describe(),
+            "translate (1, 2, 3), rotate 90° about (0, 1, 0), scale (2, 2, 2)"
+        );
+        assert!(
+            crate::transformations::shearing(1.0, 0.0, 0.0, 0.0, 0.0, 0.0)
+                .describe()
+                .starts_with("matrix ")
+        );
+    }
+
+    #[test]
+    fn a_matrix_round_trips_through_both_layouts() {
+        let values: [Float; 16] = std::array::from_fn(|i| i as Float + 0.5);
+        let m = Matrix4::from_row_major(&values);
+        assert_eq!(m[(1, 2)], 6.5);
+        assert_eq!(m.to_row_major(), values);
+        let c = Matrix4::from_column_major(&values);
+        assert_eq!(c[(2, 1)], 6.5);
+        assert_eq!(c.to_column_major(), values);
+        assert_eq!(c, m.transpose());
+    }
+
+    #[test]
+    fn a_gltf_node_matrix_matches_the_transform_built_from_its_parts() {
+        use crate::transformations::{rotation_y, scaling, translation};
+        use crate::tuples::point;
+        // translation(1, 2, 3) * rotation_y(π/2) * scaling(2, 2, 2), one
+        // column at a time.
+        let gltf: [Float; 16] = [
+            0.0, 0.0, -2.0, 0.0, //
+            0.0, 2.0, 0.0, 0.0, //
+            2.0, 0.0, 0.0, 0.0, //
+            1.0, 2.0, 3.0, 1.0,
+        ];
+        let m = Matrix4::from_column_major(&gltf);
+        let built = translation(1.0, 2.0, 3.0)
+            * rotation_y(crate::floats::PI / 2.0)
+            * scaling(2.0, 2.0, 2.0);
+        assert!(m.approx_eq(&built, EPSILON));
+        crate::tuples::check_tuple(m * point(1.0, 0.0, 0.0), built * point(1.0, 0.0, 0.0));
+        crate::tuples::check_tuple(m * point(1.0, 0.0, 0.0), point(1.0, 2.0, 1.0));
+    }
+
+    #[test]
+    fn a_matrix_can_only_be_made_from_a_slice_of_16() {
+        let values = [1.0; 17];
+        assert!(Matrix4::try_from(&values[..16]).is_ok());
+        assert!(Matrix4::try_from(&values[..15]).is_err());
+        assert!(Matrix4::try_from(&values[..]).is_err());
+    }
 }
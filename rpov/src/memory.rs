@@ -0,0 +1,187 @@
+// Rough memory use, for finding out what a large scene spends it on. Sizes
+// are estimates: heap allocations are counted by capacity, and allocator
+// overhead is left out.
+use std::{
+    collections::{BTreeMap, HashSet},
+    fmt,
+    sync::Arc,
+};
+
+use crate::{
+    canvas::Canvas,
+    intersections::{Shape, short_kind},
+    materials::Material,
+    patterns::Pattern,
+};
+
+pub trait MemoryUsage {
+    fn approx_bytes(&self) -> usize;
+}
+
+// Shapes keep everything they use inline, apart from their tags, shared
+// materials and patterns, which are counted on their own.
+impl MemoryUsage for dyn Shape + '_ {
+    fn approx_bytes(&self) -> usize {
+        let tags: usize = self
+            .tags()
+            .iter()
+            .map(|t| size_of::<String>() + t.capacity())
+            .sum();
+        size_of_val(self) + tags
+    }
+}
+
+impl MemoryUsage for dyn Pattern + '_ {
+    fn approx_bytes(&self) -> usize {
+        size_of_val(self)
+    }
+}
+
+// Not counting the patterns, which may be shared.
+impl MemoryUsage for Material {
+    fn approx_bytes(&self) -> usize {
+        size_of::<Material>()
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MemoryReport {
+    // Shapes by type name.
+    pub counts: BTreeMap<&'static str, usize>,
+    pub geometry_bytes: usize,
+    // Each shared material once, and what materials stored in a shape keep
+    // on the heap.
+    pub material_bytes: usize,
+    pub pattern_bytes: usize,
+    // Images such as the environment.
+    pub texture_bytes: usize,
+}
+
+impl MemoryReport {
+    pub fn total_bytes(&self) -> usize {
+        self.geometry_bytes + self.material_bytes + self.pattern_bytes + self.texture_bytes
+    }
+}
+
+impl fmt::Display for MemoryReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (name, count) in &self.counts {
+            writeln!(f, "{name}: {count}")?;
+        }
+        writeln!(f, "geometry: {} bytes", self.geometry_bytes)?;
+        writeln!(f, "materials: {} bytes", self.material_bytes)?;
+        writeln!(f, "patterns: {} bytes", self.pattern_bytes)?;
+        writeln!(f, "textures: {} bytes", self.texture_bytes)?;
+        write!(f, "total: {} bytes", self.total_bytes())
+    }
+}
+
+// Builds a MemoryReport, remembering which shared materials and patterns it
+// has already counted.
+#[derive(Default)]
+pub(crate) struct MemoryTally {
+    report: MemoryReport,
+    materials: HashSet<usize>,
+    patterns: HashSet<usize>,
+}
+
+impl MemoryTally {
+    pub(crate) fn add_shape(&mut self, shape: &dyn Shape) {
+        let report = &mut self.report;
+        *report.counts.entry(short_kind(shape)).or_default() += 1;
+        let size = shape.approx_bytes();
+        report.geometry_bytes += size;
+
+        let m = shape.resolved_material();
+        let address = m as *const Material as usize;
+        let start = shape as *const dyn Shape as *const u8 as usize;
+        if (start..start + size).contains(&address) {
+            // Already counted with the shape, apart from its heap.
+            report.material_bytes += m.approx_bytes() - size_of::<Material>();
+        } else if self.materials.insert(address) {
+            report.material_bytes += m.approx_bytes();
+        }
+        for pattern in m.pattern.iter().chain(m.normal_map.iter()) {
+            if self
+                .patterns
+                .insert(Arc::as_ptr(pattern) as *const () as usize)
+            {
+                report.pattern_bytes += pattern.approx_bytes();
+            }
+        }
+    }
+
+    pub(crate) fn add_texture(&mut self, canvas: &Canvas) {
+        self.report.texture_bytes += canvas.approx_bytes();
+    }
+
+    pub(crate) fn finish(self) -> MemoryReport {
+        self.report
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::colors::Color;
+    use crate::options::Environment;
+    use crate::patterns::stripe_pattern;
+    use crate::planes::Plane;
+    use crate::spheres::Sphere;
+    use crate::world::WorldBuilder;
+
+    #[test]
+    fn shared_materials_and_patterns_are_counted_once() {
+        let mut shared = Material::new();
+        let stripes: Arc<dyn Pattern> = Arc::new(stripe_pattern(
+            Color::new(1.0, 1.0, 1.0),
+            Color::new(0.0, 0.0, 0.0),
+        ));
+        shared.pattern = Some(stripes.clone());
+        let shared = shared.shared();
+        let world = |n: usize| {
+            let mut w = WorldBuilder::new();
+            for _ in 0..n {
+                let mut s = Sphere::new();
+                s.shared_material = Some(shared.clone());
+                w.objects.push(s);
+            }
+            w
+        };
+        let report = world(1000).memory_report();
+        assert_eq!(report.counts.get("Sphere"), Some(&1000));
+        assert_eq!(report.geometry_bytes, 1000 * size_of::<Sphere>());
+        assert_eq!(report.material_bytes, size_of::<Material>());
+        assert_eq!(report.pattern_bytes, size_of_val(&*stripes));
+
+        // More spheres add their own size and nothing else.
+        let more = world(1500).memory_report();
+        assert_eq!(
+            more.total_bytes() - report.total_bytes(),
+            500 * size_of::<Sphere>()
+        );
+        assert_eq!(more.material_bytes, report.material_bytes);
+        assert_eq!(more.pattern_bytes, report.pattern_bytes);
+    }
+
+    #[test]
+    fn shapes_add_their_tags_and_materials_inside_them_add_nothing() {
+        let mut w = WorldBuilder::new();
+        let mut floor = Plane::new();
+        floor.tags.insert("floor".to_string());
+        w.planes.push(floor);
+        w.options.background = Environment::Image {
+            canvas: Arc::new(Canvas::new(4, 2)),
+            rotation: 0.0,
+        };
+        let report = w.memory_report();
+        assert_eq!(report.counts.get("Plane"), Some(&1));
+        let tag = w.planes[0].tags.iter().next().unwrap();
+        assert_eq!(
+            report.geometry_bytes,
+            size_of::<Plane>() + size_of::<String>() + tag.capacity()
+        );
+        assert_eq!(report.material_bytes, 0);
+        assert!(report.texture_bytes >= 8 * size_of::<Color>());
+    }
+}
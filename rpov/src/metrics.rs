@@ -0,0 +1,172 @@
+// Render counters for applications that want them in their own telemetry.
+// RenderOptions carries a Metrics, which the renderer feeds as it goes; the
+// default throws everything away. Renders are spread over threads, so
+// implementations have to be Send + Sync.
+use std::{
+    fmt,
+    sync::{
+        Arc, LazyLock,
+        atomic::{AtomicU64, Ordering},
+    },
+};
+
+use crate::rays::RayKind;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum MetricKey {
+    PrimaryRays,
+    ShadowRays,
+    ReflectRays,
+    RefractRays,
+    // Shapes a ray was tested against, after bounding spheres have culled
+    // the ones it can't reach.
+    IntersectionTests,
+    // Time spent rendering each pixel, reported per pixel.
+    PixelTimeNs,
+    // Hits whose reflection vector was worked out, which only reflective
+    // surfaces need.
+    ReflectVectors,
+}
+
+impl MetricKey {
+    pub const ALL: [MetricKey; 7] = [
+        MetricKey::PrimaryRays,
+        MetricKey::ShadowRays,
+        MetricKey::ReflectRays,
+        MetricKey::RefractRays,
+        MetricKey::IntersectionTests,
+        MetricKey::PixelTimeNs,
+        MetricKey::ReflectVectors,
+    ];
+
+    // The counter for rays of this kind.
+    pub fn rays(kind: RayKind) -> MetricKey {
+        match kind {
+            RayKind::Camera => MetricKey::PrimaryRays,
+            RayKind::Shadow => MetricKey::ShadowRays,
+            RayKind::Reflection => MetricKey::ReflectRays,
+            RayKind::Refraction => MetricKey::RefractRays,
+        }
+    }
+}
+
+pub trait Metrics: Send + Sync {
+    fn incr(&self, key: MetricKey, by: u64);
+    fn timing(&self, key: MetricKey, nanos: u64);
+
+    // Whether the renderer should count and time anything at all. When
+    // false it skips reading the clock and making the calls.
+    fn enabled(&self) -> bool {
+        true
+    }
+}
+
+// Ignores everything.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NullMetrics;
+
+impl Metrics for NullMetrics {
+    fn incr(&self, _key: MetricKey, _by: u64) {}
+
+    fn timing(&self, _key: MetricKey, _nanos: u64) {}
+
+    fn enabled(&self) -> bool {
+        false
+    }
+}
+
+// Running totals for each key. Timings are summed like counts.
+#[derive(Debug, Default)]
+pub struct CountingMetrics {
+    totals: [AtomicU64; MetricKey::ALL.len()],
+}
+
+impl CountingMetrics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn get(&self, key: MetricKey) -> u64 {
+        self.totals[key as usize].load(Ordering::Relaxed)
+    }
+
+    pub fn reset(&self) {
+        for total in &self.totals {
+            total.store(0, Ordering::Relaxed);
+        }
+    }
+}
+
+impl Metrics for CountingMetrics {
+    fn incr(&self, key: MetricKey, by: u64) {
+        self.totals[key as usize].fetch_add(by, Ordering::Relaxed);
+    }
+
+    fn timing(&self, key: MetricKey, nanos: u64) {
+        self.incr(key, nanos);
+    }
+}
+
+static NULL_METRICS: LazyLock<Arc<dyn Metrics>> = LazyLock::new(|| Arc::new(NullMetrics));
+
+// The Metrics a RenderOptions reports to. Options compare equal when they
+// share the same one.
+#[derive(Clone)]
+pub struct SharedMetrics(pub Arc<dyn Metrics>);
+
+impl SharedMetrics {
+    pub fn new(metrics: Arc<dyn Metrics>) -> Self {
+        Self(metrics)
+    }
+}
+
+impl Default for SharedMetrics {
+    fn default() -> Self {
+        Self(NULL_METRICS.clone())
+    }
+}
+
+impl std::ops::Deref for SharedMetrics {
+    type Target = dyn Metrics;
+
+    fn deref(&self) -> &(dyn Metrics + 'static) {
+        self.0.as_ref()
+    }
+}
+
+impl PartialEq for SharedMetrics {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl fmt::Debug for SharedMetrics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("SharedMetrics")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counting_metrics_sum_counts_and_timings() {
+        let m = CountingMetrics::new();
+        m.incr(MetricKey::ShadowRays, 2);
+        m.incr(MetricKey::ShadowRays, 3);
+        m.timing(MetricKey::PixelTimeNs, 40);
+        assert_eq!(m.get(MetricKey::ShadowRays), 5);
+        assert_eq!(m.get(MetricKey::PixelTimeNs), 40);
+        assert_eq!(m.get(MetricKey::PrimaryRays), 0);
+        m.reset();
+        assert_eq!(m.get(MetricKey::ShadowRays), 0);
+    }
+
+    #[test]
+    fn default_metrics_are_shared() {
+        assert_eq!(SharedMetrics::default(), SharedMetrics::default());
+        let counting: Arc<dyn Metrics> = Arc::new(CountingMetrics::new());
+        assert_ne!(SharedMetrics::default(), SharedMetrics::new(counting));
+    }
+}
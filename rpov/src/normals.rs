@@ -73,7 +73,7 @@ mod tests {
     //   Then n = vector(0, floats::FRAC_1_SQRT_2, -floats::FRAC_1_SQRT_2)
     #[test]
     fn test_computing_normal_on_translated_sphere() {
-        let s = Sphere::with_transform(translation(0.0, 1.0, 0.0));
+        let s = Sphere::with_transform(translation(0.0, 1.0, 0.0)).unwrap();
         let n = s.normal_at(&point(0.0, 1.0 + FRAC_1_SQRT_2, -FRAC_1_SQRT_2));
         check_tuple(n, vector(0.0, FRAC_1_SQRT_2, -FRAC_1_SQRT_2));
     }
@@ -87,7 +87,7 @@ mod tests {
     #[test]
     fn test_computing_normal_on_transformed_sphere() {
         let m = scaling(1.0, 0.5, 1.0) * rotation_z(PI / 5.0);
-        let s = Sphere::with_transform(m);
+        let s = Sphere::with_transform(m).unwrap();
         let val = SQRT_2 / 2.0;
         let n = s.normal_at(&point(0.0, val, -val));
         check_tuple(n, vector(0.0, 0.97014, -0.24254));
@@ -0,0 +1,149 @@
+// An orthonormal basis around a normal, for turning directions sampled
+// around the z axis into world space. Ambient occlusion, glossy reflection
+// and normal maps all build one.
+use crate::floats::{Float, PI};
+use crate::tuples::{Tuple4, vector};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Onb {
+    pub tangent: Tuple4,
+    pub bitangent: Tuple4,
+    pub normal: Tuple4,
+}
+
+impl Onb {
+    // The tangent is square to the x axis, or to the y axis when the normal
+    // lies close to x, so no normal leaves it without a direction.
+    pub fn from_normal(n: Tuple4) -> Self {
+        let normal = n.normalize();
+        let helper = if normal.x.abs() < 0.9 {
+            vector(1.0, 0.0, 0.0)
+        } else {
+            vector(0.0, 1.0, 0.0)
+        };
+        let tangent = helper.cross(normal).normalize();
+        let bitangent = normal.cross(tangent);
+        Onb {
+            tangent,
+            bitangent,
+            normal,
+        }
+    }
+
+    // `local` is in tangent, bitangent and normal components.
+    pub fn to_world(&self, local: Tuple4) -> Tuple4 {
+        self.tangent * local.x + self.bitangent * local.y + self.normal * local.z
+    }
+
+    // The unit vector at polar angle acos(cos_theta) from the normal and
+    // azimuth phi from the tangent.
+    fn direction(&self, cos_theta: Float, phi: Float) -> Tuple4 {
+        let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+        self.to_world(vector(
+            sin_theta * phi.cos(),
+            sin_theta * phi.sin(),
+            cos_theta,
+        ))
+        .normalize()
+    }
+}
+
+// Maps u1 and u2 in [0, 1) to a direction in the hemisphere around the
+// normal, denser towards it in proportion to the cosine.
+pub fn sample_cosine_hemisphere(onb: &Onb, u1: Float, u2: Float) -> Tuple4 {
+    onb.direction((1.0 - u1).sqrt(), 2.0 * PI * u2)
+}
+
+// Like sample_cosine_hemisphere, but spread evenly over the hemisphere.
+pub fn sample_uniform_hemisphere(onb: &Onb, u1: Float, u2: Float) -> Tuple4 {
+    sample_cone(onb, PI / 2.0, u1, u2)
+}
+
+// Spread evenly over the directions within `angle` of the normal.
+pub fn sample_cone(onb: &Onb, angle: Float, u1: Float, u2: Float) -> Tuple4 {
+    onb.direction(1.0 - u1 * (1.0 - angle.cos()), 2.0 * PI * u2)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::check_floats;
+
+    // Evenly spread over the sphere, plus normals on and near each axis.
+    fn normals() -> Vec<Tuple4> {
+        let golden = PI * (3.0 - (5.0 as Float).sqrt());
+        let mut normals: Vec<Tuple4> = (0..1000)
+            .map(|i| {
+                let y = 1.0 - 2.0 * (i as Float + 0.5) / 1000.0;
+                let r = (1.0 - y * y).sqrt();
+                let phi = golden * i as Float;
+                vector(r * phi.cos(), y, r * phi.sin())
+            })
+            .collect();
+        for s in [1.0, -1.0] {
+            normals.push(vector(s, 0.0, 0.0));
+            normals.push(vector(0.0, s, 0.0));
+            normals.push(vector(0.0, 0.0, s));
+            normals.push(vector(1e-4, s, -1e-4));
+            normals.push(vector(s, 1e-4, 1e-4));
+        }
+        normals
+    }
+
+    // u1 and u2 on an n x n grid of cell centers.
+    fn grid(n: usize) -> impl Iterator<Item = (Float, Float)> {
+        (0..n * n).map(move |k| {
+            let u1 = ((k / n) as Float + 0.5) / n as Float;
+            let u2 = ((k % n) as Float + 0.5) / n as Float;
+            (u1, u2)
+        })
+    }
+
+    #[test]
+    fn bases_are_orthonormal() {
+        for n in normals() {
+            let onb = Onb::from_normal(n * 3.0);
+            let Onb {
+                tangent: t,
+                bitangent: b,
+                normal,
+            } = onb;
+            for v in [t, b, normal] {
+                check_floats!(v.magnitude(), 1.0);
+            }
+            check_floats!(t.dot(b), 0.0);
+            check_floats!(t.dot(normal), 0.0);
+            check_floats!(b.dot(normal), 0.0);
+            // Right-handed, so the normal is tangent x bitangent.
+            check_floats!(t.cross(b).dot(normal), 1.0);
+            check_floats!(onb.to_world(vector(0.0, 0.0, 1.0)).dot(normal), 1.0);
+        }
+    }
+
+    #[test]
+    fn hemisphere_samples_lie_above_the_surface() {
+        for n in normals().into_iter().step_by(37) {
+            let onb = Onb::from_normal(n);
+            for (u1, u2) in grid(8) {
+                assert!(sample_cosine_hemisphere(&onb, u1, u2).dot(onb.normal) > 0.0);
+                assert!(sample_uniform_hemisphere(&onb, u1, u2).dot(onb.normal) > 0.0);
+                let d = sample_cone(&onb, 0.1, u1, u2);
+                assert!(d.dot(onb.normal) >= (0.1 as Float).cos() - 1e-4);
+            }
+        }
+    }
+
+    #[test]
+    fn mean_cosines_match_the_distributions() {
+        let onb = Onb::from_normal(vector(0.3, -0.8, 0.5));
+        let mean = |sample: fn(&Onb, Float, Float) -> Tuple4| {
+            let n = 64;
+            let sum: Float = grid(n)
+                .map(|(u1, u2)| sample(&onb, u1, u2).dot(onb.normal))
+                .sum();
+            sum / (n * n) as Float
+        };
+        assert!((mean(sample_cosine_hemisphere) - 2.0 / 3.0).abs() < 1e-3);
+        assert!((mean(sample_uniform_hemisphere) - 0.5).abs() < 1e-3);
+    }
+}
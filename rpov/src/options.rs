@@ -0,0 +1,757 @@
+use std::{fmt, sync::Arc};
+
+use crate::{
+    canvas::Canvas,
+    colors::{COLOR_BLACK, Color},
+    floats::{EPSILON, Float, PI},
+    intersections::T_MERGE_EPSILON,
+    metrics::{Metrics, SharedMetrics},
+    tuples::Tuple4,
+    warnings::SharedWarnings,
+};
+
+pub const DEFAULT_MAX_RECURSION_DEPTH: u32 = 5;
+
+// Well under one step in an 8-bit image, even for bright highlights.
+pub const DEFAULT_MIN_THROUGHPUT: Float = 1.0e-4;
+
+// What shade_hit does with colors outside [0, 1]. Bright lights and materials
+// whose ambient and diffuse add up to more than 1 overshoot, and negative
+// colors in a scene come out as negative light.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ColorPolicy {
+    // Raise negative channels to 0, so they don't drag averages down. Bright
+    // colors are left for a later tone mapping pass.
+    Clamp,
+    // Leave colors as they are.
+    #[default]
+    AllowHdr,
+}
+
+impl ColorPolicy {
+    pub fn apply(self, c: Color) -> Color {
+        match self {
+            ColorPolicy::Clamp => Color::new(c.red.max(0.0), c.green.max(0.0), c.blue.max(0.0)),
+            ColorPolicy::AllowHdr => c,
+        }
+    }
+}
+
+// How OutputOptions brings exposed colors into [0, 1].
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum ToneMapper {
+    // Clip every channel on its own, which shifts the hue of anything bright.
+    #[default]
+    Clamp,
+    // Scale over-bright colors down as a whole with clamp_preserving_hue,
+    // then blend them toward white by `desaturation` times the share that
+    // was scaled away, so the brightest highlights still read as bright.
+    HuePreservingClamp {
+        desaturation: Float,
+    },
+}
+
+impl ToneMapper {
+    pub fn apply(self, c: Color) -> Color {
+        let c = Color::new(c.red.max(0.0), c.green.max(0.0), c.blue.max(0.0));
+        match self {
+            ToneMapper::Clamp => Color::new(c.red.min(1.0), c.green.min(1.0), c.blue.min(1.0)),
+            ToneMapper::HuePreservingClamp { desaturation } => {
+                let brightest = c.red.max(c.green).max(c.blue);
+                let scaled = c.clamp_preserving_hue(1.0);
+                if brightest <= 1.0 {
+                    return scaled;
+                }
+                let blend = (desaturation * (1.0 - 1.0 / brightest)).clamp(0.0, 1.0);
+                scaled + (Color::new(1.0, 1.0, 1.0) - scaled) * blend
+            }
+        }
+    }
+}
+
+// What rays that hit nothing see.
+#[derive(Clone)]
+pub enum Environment {
+    Color(Color),
+    // An equirectangular image wrapped around the world: columns run once
+    // around the y axis from -z through -x, +z and +x, so the seam is behind
+    // a camera looking down +z, and rows from straight up to straight down.
+    // `rotation` turns the image about the y axis, in radians.
+    Image {
+        canvas: Arc<Canvas>,
+        rotation: Float,
+    },
+}
+
+impl Environment {
+    // The color seen looking along `direction`.
+    pub fn color_toward(&self, direction: Tuple4) -> Color {
+        match self {
+            Environment::Color(c) => *c,
+            Environment::Image { canvas, rotation } => {
+                sample_equirectangular(canvas, direction, *rotation)
+            }
+        }
+    }
+}
+
+impl From<Color> for Environment {
+    fn from(c: Color) -> Self {
+        Environment::Color(c)
+    }
+}
+
+// Images are compared by identity, as comparing every pixel would be slow.
+impl PartialEq for Environment {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Environment::Color(a), Environment::Color(b)) => a == b,
+            (
+                Environment::Image { canvas, rotation },
+                Environment::Image {
+                    canvas: other_canvas,
+                    rotation: other_rotation,
+                },
+            ) => Arc::ptr_eq(canvas, other_canvas) && rotation == other_rotation,
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Debug for Environment {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Environment::Color(c) => f.debug_tuple("Color").field(c).finish(),
+            Environment::Image { canvas, rotation } => f
+                .debug_struct("Image")
+                .field("width", &canvas.width)
+                .field("height", &canvas.height)
+                .field("rotation", rotation)
+                .finish(),
+        }
+    }
+}
+
+// Bilinear lookup that wraps around the seam where longitude goes from
+// 2 PI back to 0. Above the centers of the top row, and below those of the
+// bottom one, it fades into the row's average, so every direction close to
+// a pole gets the same color instead of a pinched star of them.
+fn sample_equirectangular(canvas: &Canvas, direction: Tuple4, rotation: Float) -> Color {
+    let (width, height) = (canvas.width, canvas.height);
+    let Some(d) = direction.try_normalize() else {
+        return COLOR_BLACK;
+    };
+    if width == 0 || height == 0 {
+        return COLOR_BLACK;
+    }
+    let u = ((d.x.atan2(d.z) + PI + rotation) / (2.0 * PI)).rem_euclid(1.0);
+    let v = d.y.clamp(-1.0, 1.0).acos() / PI;
+    let px = u * width as Float - 0.5;
+    let py = v * height as Float - 0.5;
+
+    let x0 = px.floor();
+    let fx = px - x0;
+    let column = |x: Float| (x as isize).rem_euclid(width as isize) as usize;
+    let (left, right) = (column(x0), column(x0 + 1.0));
+    let row = |y: usize| canvas.pixel_at(left, y) * (1.0 - fx) + canvas.pixel_at(right, y) * fx;
+    let average = |y: usize| {
+        let sum = (0..width).fold(COLOR_BLACK, |sum, x| sum + canvas.pixel_at(x, y));
+        sum * (1.0 / width as Float)
+    };
+
+    let last = (height - 1) as Float;
+    if py < 0.0 {
+        let t = (py + 0.5) * 2.0;
+        return average(0) * (1.0 - t) + row(0) * t;
+    }
+    if py > last {
+        let t = (last + 0.5 - py) * 2.0;
+        return average(height - 1) * (1.0 - t) + row(height - 1) * t;
+    }
+    let y0 = py.floor();
+    let fy = py - y0;
+    let y0 = y0 as usize;
+    row(y0) * (1.0 - fy) + row((y0 + 1).min(height - 1)) * fy
+}
+
+// Dims ambient light by how much of the sky above a point is blocked by
+// geometry within max_distance, judged from `samples` rays.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AmbientOcclusion {
+    pub samples: usize,
+    pub max_distance: Float,
+}
+
+// Everything that controls how a world is turned into an image. The defaults
+// reproduce the book renderer: one ray per pixel, a black background.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RenderOptions {
+    // Rays per pixel along each axis, so a value of 2 traces 4 rays per pixel.
+    pub aa_samples: usize,
+    pub max_recursion_depth: u32,
+    // Secondary rays whose share of the pixel, the product of the
+    // reflectivities and transparencies along the way, falls below this are
+    // not traced, however shallow they are. 0 traces to max_recursion_depth.
+    pub min_throughput: Float,
+    pub shadow_bias: Float,
+    // The shadow bias grows as 1 / cos of the angle between the normal and
+    // the light, so light grazing a surface does not catch it again, up to
+    // this limit. Equal to shadow_bias for a fixed offset.
+    pub max_shadow_bias: Float,
+    pub background: Environment,
+    // See intersections::hit_with_merge_epsilon.
+    pub t_merge_epsilon: Float,
+    // Rays averaged for each blurred reflection or refraction.
+    pub blur_samples: usize,
+    // Whether transparent objects tint the light passing through them rather
+    // than casting a full shadow.
+    pub colored_shadows: bool,
+    pub color_policy: ColorPolicy,
+    pub ambient_occlusion: Option<AmbientOcclusion>,
+    // How far past each dirty rectangle render_dirty re-traces, in pixels, to
+    // catch shadows and reflections close to what changed.
+    pub dirty_margin: usize,
+    // Whether render_dirty re-renders the whole image for worlds with
+    // reflective or transparent materials, which can show a change anywhere.
+    pub dirty_conservative: bool,
+    // Where ray counts and pixel timings go. Nowhere, by default.
+    pub metrics: SharedMetrics,
+    // What the render recovered from. Each new set of options starts its
+    // own counts; clones share them.
+    pub warnings: SharedWarnings,
+}
+
+// How a rendered canvas is scaled into displayable values. Pixel values are
+// multiplied by the exposure, or, when auto-exposing, by whatever maps the
+// given luminance percentile to 1.0, and then tone mapped into [0, 1].
+#[derive(Debug, Clone, PartialEq)]
+pub struct OutputOptions {
+    pub exposure: Float,
+    pub auto_expose_percentile: Option<Float>,
+    pub tone_mapper: ToneMapper,
+}
+
+impl Default for OutputOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl OutputOptions {
+    pub fn new() -> Self {
+        Self {
+            exposure: 1.0,
+            auto_expose_percentile: None,
+            tone_mapper: ToneMapper::Clamp,
+        }
+    }
+
+    pub fn with_exposure(mut self, exposure: Float) -> Self {
+        self.exposure = exposure;
+        self
+    }
+
+    pub fn auto_expose_to(mut self, percentile: Float) -> Self {
+        self.auto_expose_percentile = Some(percentile);
+        self
+    }
+
+    pub fn with_tone_mapper(mut self, tone_mapper: ToneMapper) -> Self {
+        self.tone_mapper = tone_mapper;
+        self
+    }
+
+    // The factor pixels are multiplied by. A black canvas is left alone
+    // rather than scaled by infinity.
+    pub fn exposure_for(&self, canvas: &Canvas) -> Float {
+        match self.auto_expose_percentile {
+            Some(p) => {
+                let luminance = canvas.percentile_luminance(p);
+                if luminance > 0.0 {
+                    1.0 / luminance
+                } else {
+                    1.0
+                }
+            }
+            None => self.exposure,
+        }
+    }
+
+    pub fn apply(&self, canvas: &Canvas) -> Canvas {
+        let exposure = self.exposure_for(canvas);
+        let mut out = canvas.clone();
+        for y in 0..out.height {
+            for x in 0..out.width {
+                let c = out.pixel_at(x, y) * exposure;
+                out.write_pixel(x, y, self.tone_mapper.apply(c));
+            }
+        }
+        out
+    }
+
+    pub fn validate(&self) -> Result<(), OptionsError> {
+        if !(self.exposure >= 0.0 && self.exposure.is_finite()) {
+            return Err(OptionsError {
+                field: "exposure",
+                message: format!("must be finite and non-negative, got {}", self.exposure),
+            });
+        }
+        if let Some(p) = self.auto_expose_percentile
+            && !(0.0..=100.0).contains(&p)
+        {
+            return Err(OptionsError {
+                field: "auto_expose_percentile",
+                message: format!("must be between 0 and 100, got {p}"),
+            });
+        }
+        if let ToneMapper::HuePreservingClamp { desaturation } = self.tone_mapper
+            && !(0.0..=1.0).contains(&desaturation)
+        {
+            return Err(OptionsError {
+                field: "tone_mapper",
+                message: format!("desaturation must be between 0 and 1, got {desaturation}"),
+            });
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct OptionsError {
+    pub field: &'static str,
+    pub message: String,
+}
+
+impl fmt::Display for OptionsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "invalid render option `{}`: {}",
+            self.field, self.message
+        )
+    }
+}
+
+impl std::error::Error for OptionsError {}
+
+impl Default for RenderOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RenderOptions {
+    pub fn new() -> Self {
+        Self {
+            aa_samples: 1,
+            max_recursion_depth: DEFAULT_MAX_RECURSION_DEPTH,
+            min_throughput: DEFAULT_MIN_THROUGHPUT,
+            shadow_bias: EPSILON,
+            max_shadow_bias: EPSILON * 10.0,
+            background: Environment::Color(COLOR_BLACK),
+            t_merge_epsilon: T_MERGE_EPSILON,
+            blur_samples: 16,
+            colored_shadows: false,
+            color_policy: ColorPolicy::AllowHdr,
+            ambient_occlusion: None,
+            dirty_margin: 4,
+            dirty_conservative: true,
+            metrics: SharedMetrics::default(),
+            warnings: SharedWarnings::default(),
+        }
+    }
+
+    pub fn with_aa_samples(mut self, aa_samples: usize) -> Self {
+        self.aa_samples = aa_samples;
+        self
+    }
+
+    pub fn with_max_recursion_depth(mut self, depth: u32) -> Self {
+        self.max_recursion_depth = depth;
+        self
+    }
+
+    pub fn with_min_throughput(mut self, min_throughput: Float) -> Self {
+        self.min_throughput = min_throughput;
+        self
+    }
+
+    pub fn with_shadow_bias(mut self, bias: Float) -> Self {
+        self.shadow_bias = bias;
+        self
+    }
+
+    pub fn with_max_shadow_bias(mut self, bias: Float) -> Self {
+        self.max_shadow_bias = bias;
+        self
+    }
+
+    // The offset along the normal for a shadow ray leaving at `cos_light`,
+    // the cosine between the surface normal and the direction to the light.
+    pub fn shadow_bias_for(&self, cos_light: Float) -> Float {
+        (self.shadow_bias / cos_light.abs())
+            .min(self.max_shadow_bias)
+            .max(self.shadow_bias)
+    }
+
+    pub fn with_background(mut self, background: impl Into<Environment>) -> Self {
+        self.background = background.into();
+        self
+    }
+
+    pub fn with_t_merge_epsilon(mut self, t_merge_epsilon: Float) -> Self {
+        self.t_merge_epsilon = t_merge_epsilon;
+        self
+    }
+
+    pub fn with_blur_samples(mut self, blur_samples: usize) -> Self {
+        self.blur_samples = blur_samples;
+        self
+    }
+
+    pub fn with_colored_shadows(mut self, colored_shadows: bool) -> Self {
+        self.colored_shadows = colored_shadows;
+        self
+    }
+
+    pub fn with_color_policy(mut self, color_policy: ColorPolicy) -> Self {
+        self.color_policy = color_policy;
+        self
+    }
+
+    pub fn with_ambient_occlusion(mut self, samples: usize, max_distance: Float) -> Self {
+        self.ambient_occlusion = Some(AmbientOcclusion {
+            samples,
+            max_distance,
+        });
+        self
+    }
+
+    pub fn with_dirty_margin(mut self, margin: usize) -> Self {
+        self.dirty_margin = margin;
+        self
+    }
+
+    pub fn with_dirty_conservative(mut self, conservative: bool) -> Self {
+        self.dirty_conservative = conservative;
+        self
+    }
+
+    pub fn with_metrics(mut self, metrics: Arc<dyn Metrics>) -> Self {
+        self.metrics = SharedMetrics::new(metrics);
+        self
+    }
+
+    pub fn validate(&self) -> Result<(), OptionsError> {
+        if let Some(ao) = self.ambient_occlusion {
+            if ao.samples == 0 {
+                return Err(OptionsError {
+                    field: "ambient_occlusion.samples",
+                    message: "must be at least 1".to_string(),
+                });
+            }
+            if ao.max_distance.is_nan() || ao.max_distance <= 0.0 {
+                return Err(OptionsError {
+                    field: "ambient_occlusion.max_distance",
+                    message: format!("must be positive, got {}", ao.max_distance),
+                });
+            }
+        }
+        if self.aa_samples == 0 {
+            return Err(OptionsError {
+                field: "aa_samples",
+                message: "must be at least 1".to_string(),
+            });
+        }
+        if self.blur_samples == 0 {
+            return Err(OptionsError {
+                field: "blur_samples",
+                message: "must be at least 1".to_string(),
+            });
+        }
+        if !(self.shadow_bias >= 0.0 && self.shadow_bias.is_finite()) {
+            return Err(OptionsError {
+                field: "shadow_bias",
+                message: format!("must be finite and non-negative, got {}", self.shadow_bias),
+            });
+        }
+        if !(self.min_throughput >= 0.0 && self.min_throughput <= 1.0) {
+            return Err(OptionsError {
+                field: "min_throughput",
+                message: format!("must be between 0 and 1, got {}", self.min_throughput),
+            });
+        }
+        if !(self.max_shadow_bias >= 0.0 && self.max_shadow_bias.is_finite()) {
+            return Err(OptionsError {
+                field: "max_shadow_bias",
+                message: format!(
+                    "must be finite and non-negative, got {}",
+                    self.max_shadow_bias
+                ),
+            });
+        }
+        if self.max_shadow_bias < self.shadow_bias {
+            return Err(OptionsError {
+                field: "max_shadow_bias",
+                message: format!(
+                    "must be at least shadow_bias ({}), got {}",
+                    self.shadow_bias, self.max_shadow_bias
+                ),
+            });
+        }
+        if !(self.t_merge_epsilon >= 0.0 && self.t_merge_epsilon.is_finite()) {
+            return Err(OptionsError {
+                field: "t_merge_epsilon",
+                message: format!(
+                    "must be finite and non-negative, got {}",
+                    self.t_merge_epsilon
+                ),
+            });
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_options_are_valid() {
+        assert!(RenderOptions::default().validate().is_ok());
+    }
+
+    // The book renderer: one ray per pixel, a black background and the
+    // book's recursion limit and epsilons.
+    #[test]
+    fn default_options_match_the_book_renderer() {
+        let o = RenderOptions::default();
+        assert_eq!(o.aa_samples, 1);
+        assert_eq!(o.max_recursion_depth, DEFAULT_MAX_RECURSION_DEPTH);
+        assert_eq!(o.min_throughput, DEFAULT_MIN_THROUGHPUT);
+        assert_eq!(o.shadow_bias, EPSILON);
+        assert!(o.max_shadow_bias >= o.shadow_bias);
+        assert_eq!(o.background, Environment::Color(COLOR_BLACK));
+        assert_eq!(o.t_merge_epsilon, T_MERGE_EPSILON);
+        assert!(!o.colored_shadows);
+        assert_eq!(o.color_policy, ColorPolicy::AllowHdr);
+        assert_eq!(o.ambient_occlusion, None);
+    }
+
+    #[test]
+    fn builder_sets_fields() {
+        let o = RenderOptions::new()
+            .with_aa_samples(3)
+            .with_max_recursion_depth(2)
+            .with_shadow_bias(0.01)
+            .with_background(Color::new(0.1, 0.2, 0.3));
+        assert_eq!(o.aa_samples, 3);
+        assert_eq!(o.max_recursion_depth, 2);
+        assert_eq!(o.shadow_bias, 0.01);
+        assert_eq!(o.background, Environment::Color(Color::new(0.1, 0.2, 0.3)));
+    }
+
+    // Red on the left half, blue on the right; each row a little brighter
+    // than the one above.
+    fn environment_image() -> Environment {
+        let mut canvas = Canvas::new(8, 4);
+        for y in 0..4 {
+            for x in 0..8 {
+                let level = 0.25 * (y + 1) as Float;
+                let c = if x < 4 {
+                    Color::new(level, 0.0, 0.0)
+                } else {
+                    Color::new(0.0, 0.0, level)
+                };
+                canvas.write_pixel(x, y, c);
+            }
+        }
+        Environment::Image {
+            canvas: Arc::new(canvas),
+            rotation: 0.0,
+        }
+    }
+
+    #[test]
+    fn looking_at_a_pole_sees_the_average_of_its_row() {
+        let env = environment_image();
+        crate::check_colors!(
+            env.color_toward(crate::tuples::vector(0.0, 1.0, 0.0)),
+            Color::new(0.125, 0.0, 0.125)
+        );
+        crate::check_colors!(
+            env.color_toward(crate::tuples::vector(0.0, -1.0, 0.0)),
+            Color::new(0.5, 0.0, 0.5)
+        );
+    }
+
+    #[test]
+    fn the_environment_is_continuous_across_the_seam() {
+        let env = environment_image();
+        let left = env.color_toward(crate::tuples::vector(-0.001, 0.0, -1.0));
+        let right = env.color_toward(crate::tuples::vector(0.001, 0.0, -1.0));
+        let seam = env.color_toward(crate::tuples::vector(0.0, 0.0, -1.0));
+        for c in [left, right] {
+            assert!((c.red - seam.red).abs() < 0.01, "{c:?} {seam:?}");
+            assert!((c.blue - seam.blue).abs() < 0.01, "{c:?} {seam:?}");
+        }
+        // Halfway between the first column, red, and the last, blue.
+        crate::check_floats!(seam.red, seam.blue);
+    }
+
+    #[test]
+    fn rotating_the_environment_turns_it_about_y() {
+        let Environment::Image { canvas, .. } = environment_image() else {
+            unreachable!();
+        };
+        let turned = Environment::Image {
+            canvas,
+            rotation: PI,
+        };
+        let ahead = crate::tuples::vector(-1.0, 0.0, 0.0);
+        assert!(environment_image().color_toward(ahead).red > 0.0);
+        assert_eq!(turned.color_toward(ahead).red, 0.0);
+    }
+
+    #[test]
+    fn the_shadow_bias_grows_at_grazing_angles() {
+        let o = RenderOptions::new()
+            .with_shadow_bias(0.001)
+            .with_max_shadow_bias(0.01);
+        crate::check_floats!(o.shadow_bias_for(1.0), 0.001);
+        crate::check_floats!(o.shadow_bias_for(-0.5), 0.002);
+        crate::check_floats!(o.shadow_bias_for(0.05), 0.01);
+        crate::check_floats!(o.shadow_bias_for(0.0), 0.01);
+        let fixed = o.with_max_shadow_bias(0.001);
+        crate::check_floats!(fixed.shadow_bias_for(0.1), 0.001);
+    }
+
+    #[test]
+    fn a_max_shadow_bias_below_the_shadow_bias_fails_validation() {
+        let o = RenderOptions::new().with_shadow_bias(0.01);
+        let err = o
+            .clone()
+            .with_max_shadow_bias(0.001)
+            .validate()
+            .unwrap_err();
+        assert_eq!(err.field, "max_shadow_bias");
+        assert!(err.to_string().contains("0.001"));
+        assert!(o.with_max_shadow_bias(0.01).validate().is_ok());
+    }
+
+    #[test]
+    fn zero_samples_fail_validation() {
+        let err = RenderOptions::new()
+            .with_aa_samples(0)
+            .validate()
+            .unwrap_err();
+        assert_eq!(err.field, "aa_samples");
+        assert!(err.to_string().contains("aa_samples"));
+    }
+
+    #[test]
+    fn negative_bias_fails_validation() {
+        let err = RenderOptions::new()
+            .with_shadow_bias(-0.5)
+            .validate()
+            .unwrap_err();
+        assert_eq!(err.field, "shadow_bias");
+        assert!(err.to_string().contains("-0.5"));
+    }
+
+    #[test]
+    fn negative_merge_epsilon_fails_validation() {
+        let err = RenderOptions::new()
+            .with_t_merge_epsilon(-1.0)
+            .validate()
+            .unwrap_err();
+        assert_eq!(err.field, "t_merge_epsilon");
+    }
+
+    #[test]
+    fn options_can_be_shared_between_threads() {
+        fn assert_send_sync<T: Send + Sync + Clone>() {}
+        assert_send_sync::<RenderOptions>();
+    }
+
+    #[test]
+    fn auto_exposing_a_uniform_canvas_maps_it_to_white() {
+        let mut c = Canvas::new(4, 4);
+        c.write_block(0, 0, 4, 4, Color::new(2.0, 2.0, 2.0));
+        let out = OutputOptions::new().auto_expose_to(95.0).apply(&c);
+        for y in 0..4 {
+            for x in 0..4 {
+                crate::check_colors!(out.pixel_at(x, y), Color::new(1.0, 1.0, 1.0));
+            }
+        }
+    }
+
+    #[test]
+    fn fixed_exposure_scales_and_clamps() {
+        let mut c = Canvas::new(2, 1);
+        c.write_pixel(0, 0, Color::new(0.2, 0.4, 0.8));
+        let out = OutputOptions::new().with_exposure(2.0).apply(&c);
+        assert_eq!(out.pixel_at(0, 0), Color::new(0.4, 0.8, 1.0));
+        assert_eq!(out.pixel_at(1, 0), COLOR_BLACK);
+    }
+
+    #[test]
+    fn out_of_range_percentile_fails_validation() {
+        let err = OutputOptions::new()
+            .auto_expose_to(120.0)
+            .validate()
+            .unwrap_err();
+        assert_eq!(err.field, "auto_expose_percentile");
+    }
+
+    #[test]
+    fn hue_preserving_clamp_scales_bright_colors_as_a_whole() {
+        let orange = Color::new(2.0, 1.0, 0.5);
+        assert_eq!(orange.clamp_preserving_hue(1.0), Color::new(1.0, 0.5, 0.25));
+        assert_eq!(ToneMapper::Clamp.apply(orange), Color::new(1.0, 1.0, 0.5));
+        let mapper = ToneMapper::HuePreservingClamp { desaturation: 0.0 };
+        assert_eq!(mapper.apply(orange), Color::new(1.0, 0.5, 0.25));
+    }
+
+    #[test]
+    fn hue_preserving_clamp_leaves_in_gamut_colors_alone() {
+        let c = Color::new(0.9, 0.3, 0.1);
+        assert_eq!(c.clamp_preserving_hue(1.0), c);
+        let mapper = ToneMapper::HuePreservingClamp { desaturation: 1.0 };
+        assert_eq!(mapper.apply(c), c);
+    }
+
+    #[test]
+    fn desaturation_blends_overshooting_colors_toward_white() {
+        // Half of (2, 1, 0.5) is scaled away, so full desaturation goes half
+        // of the way from (1, 0.5, 0.25) to white.
+        let mapper = ToneMapper::HuePreservingClamp { desaturation: 1.0 };
+        assert_eq!(
+            mapper.apply(Color::new(2.0, 1.0, 0.5)),
+            Color::new(1.0, 0.75, 0.625)
+        );
+    }
+
+    #[test]
+    fn output_options_apply_the_chosen_tone_mapper() {
+        let mut c = Canvas::new(1, 1);
+        c.write_pixel(0, 0, Color::new(1.0, 0.5, 0.25));
+        let out = OutputOptions::new()
+            .with_exposure(2.0)
+            .with_tone_mapper(ToneMapper::HuePreservingClamp { desaturation: 0.0 })
+            .apply(&c);
+        assert_eq!(out.pixel_at(0, 0), Color::new(1.0, 0.5, 0.25));
+        let err = OutputOptions::new()
+            .with_tone_mapper(ToneMapper::HuePreservingClamp { desaturation: 2.0 })
+            .validate()
+            .unwrap_err();
+        assert_eq!(err.field, "tone_mapper");
+    }
+
+    #[test]
+    fn clamping_raises_negative_channels_and_keeps_bright_ones() {
+        let c = Color::new(-0.5, 0.5, 3.0);
+        assert_eq!(ColorPolicy::Clamp.apply(c), Color::new(0.0, 0.5, 3.0));
+        assert_eq!(ColorPolicy::AllowHdr.apply(c), c);
+    }
+}
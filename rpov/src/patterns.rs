@@ -1,24 +1,71 @@
-use std::fmt::Debug;
+use std::{fmt::Debug, sync::Arc};
 
 // Feature: Patterns
-use crate::{colors::Color, intersections::Shape, matrices::Matrix4, tuples::Tuple4};
+use crate::{
+    colors::Color,
+    floats::{Float, PI},
+    intersections::Shape,
+    matrices::{Matrix4, validate_transform},
+    tuples::Tuple4,
+    warnings::{self, Warning},
+};
 
 #[derive(Debug, PartialEq, Copy, Clone)]
 pub struct StripePattern {
     pub a: Color,
     pub b: Color,
     pub transform: Matrix4,
+    pub space: PatternSpace,
 }
 
-pub trait Pattern: Debug {
+// The space a pattern's own transform is applied in. Object-space patterns
+// move and scale with the shape; world-space ones stay put, which suits
+// floors and other planes that are moved around without meaning to change.
+#[derive(Debug, PartialEq, Eq, Copy, Clone, Default)]
+pub enum PatternSpace {
+    #[default]
+    Object,
+    World,
+}
+
+impl PatternSpace {
+    // The point in the space the pattern's transform starts from.
+    pub fn point_for(self, object: &dyn Shape, world_point: Tuple4) -> Tuple4 {
+        match self {
+            PatternSpace::Object => object.transform_inverse() * world_point,
+            PatternSpace::World => world_point,
+        }
+    }
+}
+
+// A singular pattern transform would otherwise stop the render at the first
+// pixel that uses it.
+fn inverse_or_identity(transform: Matrix4) -> Matrix4 {
+    if validate_transform("pattern", transform).is_ok() {
+        transform.inverse()
+    } else {
+        warnings::note(Warning::SingularPatternTransform);
+        Matrix4::identity()
+    }
+}
+
+pub trait Pattern: Debug + Send + Sync {
     fn pattern_at(&self, point: crate::tuples::Tuple4) -> Color;
     fn transform_inverse(&self) -> Matrix4;
+    fn space(&self) -> PatternSpace {
+        PatternSpace::Object
+    }
     fn pattern_at_shape(&self, object: &dyn Shape, world_point: crate::tuples::Tuple4) -> Color {
-        let object_point = object.transform_inverse() * world_point;
+        crate::profile_scope!("pattern");
+        let object_point = self.space().point_for(object, world_point);
         let pattern_point = self.transform_inverse() * object_point;
 
         self.pattern_at(pattern_point)
     }
+    // The pattern as it is at time `t`, or None if it doesn't change.
+    fn at_time(&self, _t: Float) -> Option<Arc<dyn Pattern>> {
+        None
+    }
 }
 
 pub fn stripe_pattern(a: Color, b: Color) -> StripePattern {
@@ -26,6 +73,7 @@ pub fn stripe_pattern(a: Color, b: Color) -> StripePattern {
         a,
         b,
         transform: Matrix4::identity(),
+        space: PatternSpace::Object,
     }
 }
 impl StripePattern {
@@ -42,8 +90,8 @@ impl StripePattern {
         object: &dyn Shape,
         world_point: crate::tuples::Tuple4,
     ) -> Color {
-        let object_point = object.transform_inverse() * world_point;
-        let pattern_point = self.transform.inverse() * object_point;
+        let object_point = self.space.point_for(object, world_point);
+        let pattern_point = inverse_or_identity(self.transform) * object_point;
 
         self.stripe_at(pattern_point)
     }
@@ -53,7 +101,11 @@ impl Pattern for StripePattern {
         self.stripe_at(point)
     }
     fn transform_inverse(&self) -> Matrix4 {
-        self.transform.inverse()
+        inverse_or_identity(self.transform)
+    }
+
+    fn space(&self) -> PatternSpace {
+        self.space
     }
 }
 
@@ -62,6 +114,60 @@ pub struct GradientPattern {
     pub a: Color,
     pub b: Color,
     pub transform: Matrix4,
+    pub space: PatternSpace,
+    pub easing: Easing,
+    pub repeat: GradientRepeat,
+}
+
+// Reshapes the 0..1 blend between a gradient's colors.
+#[derive(Debug, PartialEq, Eq, Copy, Clone, Default)]
+pub enum Easing {
+    #[default]
+    Linear,
+    SmoothStep,
+    // Starts slowly and speeds up.
+    EaseIn,
+    // Starts quickly and slows down.
+    EaseOut,
+    // Half a cosine wave, softer at both ends than SmoothStep.
+    Sine,
+}
+
+impl Easing {
+    pub fn apply(self, t: Float) -> Float {
+        match self {
+            Easing::Linear => t,
+            Easing::SmoothStep => t * t * (3.0 - 2.0 * t),
+            Easing::EaseIn => t * t,
+            Easing::EaseOut => 1.0 - (1.0 - t) * (1.0 - t),
+            Easing::Sine => (1.0 - (t * PI).cos()) / 2.0,
+        }
+    }
+}
+
+// What a gradient does outside x in 0..1.
+#[derive(Debug, PartialEq, Eq, Copy, Clone, Default)]
+pub enum GradientRepeat {
+    // Holds the end colors.
+    Clamp,
+    // Jumps back to the first color at every whole x, as in the book.
+    #[default]
+    Repeat,
+    // Runs back and forth, so there is no seam.
+    Mirror,
+}
+
+impl GradientRepeat {
+    pub fn fraction(self, x: Float) -> Float {
+        match self {
+            GradientRepeat::Clamp => x.clamp(0.0, 1.0),
+            GradientRepeat::Repeat => x - x.floor(),
+            GradientRepeat::Mirror => {
+                let f = x - (x / 2.0).floor() * 2.0;
+                if f > 1.0 { 2.0 - f } else { f }
+            }
+        }
+    }
 }
 
 pub fn gradient_pattern(a: Color, b: Color) -> GradientPattern {
@@ -69,22 +175,30 @@ pub fn gradient_pattern(a: Color, b: Color) -> GradientPattern {
         a,
         b,
         transform: Matrix4::identity(),
+        space: PatternSpace::Object,
+        easing: Easing::default(),
+        repeat: GradientRepeat::default(),
     }
 }
 
 impl Pattern for GradientPattern {
     fn pattern_at(&self, point: Tuple4) -> Color {
         let distance = self.b - self.a;
-        let fraction = point.x - point.x.floor();
+        let fraction = self.easing.apply(self.repeat.fraction(point.x));
         self.a + distance * fraction
     }
     fn transform_inverse(&self) -> Matrix4 {
-        self.transform.inverse()
+        inverse_or_identity(self.transform)
+    }
+
+    fn space(&self) -> PatternSpace {
+        self.space
     }
 }
 #[derive(Debug, PartialEq, Copy, Clone)]
 pub struct TestPattern {
     pub transform: Matrix4,
+    pub space: PatternSpace,
 }
 
 impl Default for TestPattern {
@@ -97,6 +211,7 @@ impl TestPattern {
     pub fn new() -> Self {
         TestPattern {
             transform: Matrix4::identity(),
+            space: PatternSpace::Object,
         }
     }
 }
@@ -107,7 +222,11 @@ impl Pattern for TestPattern {
     }
 
     fn transform_inverse(&self) -> Matrix4 {
-        self.transform.inverse()
+        inverse_or_identity(self.transform)
+    }
+
+    fn space(&self) -> PatternSpace {
+        self.space
     }
 }
 
@@ -116,6 +235,7 @@ pub struct RingPattern {
     pub a: Color,
     pub b: Color,
     pub transform: Matrix4,
+    pub space: PatternSpace,
 }
 
 pub fn ring_pattern(a: Color, b: Color) -> RingPattern {
@@ -123,6 +243,7 @@ pub fn ring_pattern(a: Color, b: Color) -> RingPattern {
         a,
         b,
         transform: Matrix4::identity(),
+        space: PatternSpace::Object,
     }
 }
 
@@ -136,7 +257,11 @@ impl Pattern for RingPattern {
     }
 
     fn transform_inverse(&self) -> Matrix4 {
-        self.transform.inverse()
+        inverse_or_identity(self.transform)
+    }
+
+    fn space(&self) -> PatternSpace {
+        self.space
     }
 }
 
@@ -145,6 +270,7 @@ pub struct CheckersPattern {
     pub a: Color,
     pub b: Color,
     pub transform: Matrix4,
+    pub space: PatternSpace,
 }
 
 pub fn checkers_pattern(a: Color, b: Color) -> CheckersPattern {
@@ -152,6 +278,7 @@ pub fn checkers_pattern(a: Color, b: Color) -> CheckersPattern {
         a,
         b,
         transform: Matrix4::identity(),
+        space: PatternSpace::Object,
     }
 }
 
@@ -165,7 +292,77 @@ impl Pattern for CheckersPattern {
     }
 
     fn transform_inverse(&self) -> Matrix4 {
-        self.transform.inverse()
+        inverse_or_identity(self.transform)
+    }
+
+    fn space(&self) -> PatternSpace {
+        self.space
+    }
+}
+
+// Moves another pattern over time. The transform `transform_at` gives for
+// the current time is applied on top of the pattern's own, so a translation
+// that grows with t scrolls the pattern across the surface.
+#[derive(Clone)]
+pub struct AnimatedPattern {
+    pub pattern: Arc<dyn Pattern>,
+    pub transform_at: Arc<dyn Fn(Float) -> Matrix4 + Send + Sync>,
+    time: Float,
+    inverse: Matrix4,
+}
+
+impl AnimatedPattern {
+    pub fn new(
+        pattern: Arc<dyn Pattern>,
+        transform_at: impl Fn(Float) -> Matrix4 + Send + Sync + 'static,
+    ) -> Self {
+        Self::posed(pattern, Arc::new(transform_at), 0.0)
+    }
+
+    fn posed(
+        pattern: Arc<dyn Pattern>,
+        transform_at: Arc<dyn Fn(Float) -> Matrix4 + Send + Sync>,
+        time: Float,
+    ) -> Self {
+        let inverse = pattern.transform_inverse() * inverse_or_identity(transform_at(time));
+        Self {
+            pattern,
+            transform_at,
+            time,
+            inverse,
+        }
+    }
+
+    pub fn time(&self) -> Float {
+        self.time
+    }
+}
+
+impl Debug for AnimatedPattern {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AnimatedPattern")
+            .field("pattern", &self.pattern)
+            .field("time", &self.time)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Pattern for AnimatedPattern {
+    fn pattern_at(&self, point: Tuple4) -> Color {
+        self.pattern.pattern_at(point)
+    }
+
+    fn transform_inverse(&self) -> Matrix4 {
+        self.inverse
+    }
+
+    fn space(&self) -> PatternSpace {
+        self.pattern.space()
+    }
+
+    fn at_time(&self, t: Float) -> Option<Arc<dyn Pattern>> {
+        let posed = Self::posed(self.pattern.clone(), self.transform_at.clone(), t);
+        Some(Arc::new(posed))
     }
 }
 
@@ -176,8 +373,10 @@ mod tests {
 
     use crate::{
         colors::Color,
+        matrices::Matrix4,
         patterns::{
-            Pattern, StripePattern, TestPattern, checkers_pattern, gradient_pattern, stripe_pattern,
+            AnimatedPattern, Easing, GradientRepeat, Pattern, PatternSpace, StripePattern,
+            TestPattern, checkers_pattern, gradient_pattern, stripe_pattern,
         },
         spheres::Sphere,
         tuples::point,
@@ -296,7 +495,9 @@ mod tests {
     #[test]
     fn stripes_with_an_object_transformation() {
         let mut object = crate::spheres::Sphere::new();
-        object.transform = crate::transformations::scaling(2.0, 2.0, 2.0);
+        object
+            .set_transform(crate::transformations::scaling(2.0, 2.0, 2.0))
+            .unwrap();
         let (white, _black, pattern) = default_white_black_stripe();
         let c = pattern.stripe_at_object(&object, point(1.5, 0.0, 0.0));
         assert_eq!(c, white);
@@ -328,7 +529,9 @@ mod tests {
     #[test]
     fn stripes_with_both_an_object_and_a_pattern_transformation() {
         let mut object = crate::spheres::Sphere::new();
-        object.transform = crate::transformations::scaling(2.0, 2.0, 2.0);
+        object
+            .set_transform(crate::transformations::scaling(2.0, 2.0, 2.0))
+            .unwrap();
         let (white, _black, mut pattern) = default_white_black_stripe();
         pattern.transform = crate::transformations::translation(0.5, 0.0, 0.0);
         let c = pattern.stripe_at_object(&object, point(2.5, 0.0, 0.0));
@@ -367,7 +570,9 @@ mod tests {
     #[test]
     fn a_test_pattern_with_an_object_transformation() {
         let mut shape = Sphere::new();
-        shape.transform = crate::transformations::scaling(2.0, 2.0, 2.0);
+        shape
+            .set_transform(crate::transformations::scaling(2.0, 2.0, 2.0))
+            .unwrap();
         let pattern = test_pattern();
         let c = pattern.pattern_at_shape(&shape, point(2.0, 3.0, 4.0));
         assert_eq!(c, Color::new(1.0, 1.5, 2.0));
@@ -398,7 +603,9 @@ mod tests {
     #[test]
     fn a_test_pattern_with_both_an_object_and_a_pattern_transformation() {
         let mut shape = Sphere::new();
-        shape.transform = crate::transformations::scaling(2.0, 2.0, 2.0);
+        shape
+            .set_transform(crate::transformations::scaling(2.0, 2.0, 2.0))
+            .unwrap();
 
         let mut pattern = test_pattern();
         pattern.transform = crate::transformations::translation(0.5, 1.0, 1.5);
@@ -433,6 +640,46 @@ mod tests {
         assert_eq!(pattern.pattern_at(point(1.0, 0.0, 0.0)), white);
     }
 
+    #[test]
+    fn easing_reshapes_the_blend() {
+        use crate::floats::Float;
+        let cases: [(Easing, [Float; 3]); 5] = [
+            (Easing::Linear, [0.25, 0.5, 0.75]),
+            (Easing::SmoothStep, [0.15625, 0.5, 0.84375]),
+            (Easing::EaseIn, [0.0625, 0.25, 0.5625]),
+            (Easing::EaseOut, [0.4375, 0.75, 0.9375]),
+            (Easing::Sine, [0.146447, 0.5, 0.853553]),
+        ];
+        for (easing, expected) in cases {
+            let mut pattern =
+                gradient_pattern(Color::new(0.0, 0.0, 0.0), Color::new(1.0, 1.0, 1.0));
+            pattern.easing = easing;
+            for (x, e) in [0.25, 0.5, 0.75].into_iter().zip(expected) {
+                crate::check_floats!(pattern.pattern_at(point(x, 0.0, 0.0)).red, e);
+            }
+        }
+    }
+
+    #[test]
+    fn gradient_repeat_modes() {
+        let (white, black, _) = default_white_black_stripe();
+        let mut pattern = gradient_pattern(white, black);
+        pattern.repeat = GradientRepeat::Mirror;
+        assert_eq!(
+            pattern.pattern_at(point(1.25, 0.0, 0.0)),
+            pattern.pattern_at(point(0.75, 0.0, 0.0))
+        );
+        assert_eq!(
+            pattern.pattern_at(point(-0.25, 0.0, 0.0)),
+            pattern.pattern_at(point(0.25, 0.0, 0.0))
+        );
+        assert_eq!(pattern.pattern_at(point(2.0, 0.0, 0.0)), white);
+
+        pattern.repeat = GradientRepeat::Clamp;
+        assert_eq!(pattern.pattern_at(point(-3.0, 0.0, 0.0)), white);
+        assert_eq!(pattern.pattern_at(point(1.5, 0.0, 0.0)), black);
+    }
+
     // Scenario: A ring should extend in both x and z
     //   Given pattern ← ring_pattern(white, black)
     //   Then pattern_at(pattern, point(0, 0, 0)) = white
@@ -491,4 +738,73 @@ mod tests {
         assert_eq!(pattern.pattern_at(point(0.0, 0.0, 0.99)), white);
         assert_eq!(pattern.pattern_at(point(0.0, 0.0, 1.01)), black);
     }
+
+    fn render_checkered_floor(space: PatternSpace, floor_transform: Matrix4) -> String {
+        use crate::{
+            camera::Camera, floats::PI, lighting::point_light, planes::Plane,
+            transformations::view_transform, tuples::vector, world::WorldBuilder,
+        };
+        let mut pattern = checkers_pattern(Color::new(1.0, 1.0, 1.0), Color::new(0.0, 0.0, 0.0));
+        pattern.transform = crate::transformations::scaling(2.0, 2.0, 2.0);
+        pattern.space = space;
+        let mut floor = Plane::new();
+        floor.set_transform(floor_transform).unwrap();
+        floor.material.pattern = Some(Arc::new(pattern));
+        let mut w = WorldBuilder::with_light(point_light(
+            point(-10.0, 10.0, -10.0),
+            Color::new(1.0, 1.0, 1.0),
+        ));
+        w.planes.push(floor);
+        let mut c = Camera::new(20, 10, PI / 3.0);
+        c.set_transform(view_transform(
+            point(0.0, 3.0, -5.0),
+            point(0.0, 0.0, 0.0),
+            vector(0.0, 1.0, 0.0),
+        ));
+        crate::world::render(c, w).to_ppm()
+    }
+
+    #[test]
+    fn a_world_space_pattern_stays_put_when_its_plane_moves() {
+        let moved = crate::transformations::translation(3.0, 0.0, 7.0);
+        assert_eq!(
+            render_checkered_floor(PatternSpace::World, Matrix4::identity()),
+            render_checkered_floor(PatternSpace::World, moved)
+        );
+        assert_ne!(
+            render_checkered_floor(PatternSpace::Object, Matrix4::identity()),
+            render_checkered_floor(PatternSpace::Object, moved)
+        );
+    }
+
+    #[test]
+    fn patterns_default_to_object_space() {
+        assert_eq!(
+            stripe_pattern(Color::new(1.0, 1.0, 1.0), Color::new(0.0, 0.0, 0.0)).space(),
+            PatternSpace::Object
+        );
+        assert_eq!(TestPattern::new().space(), PatternSpace::Object);
+    }
+
+    #[test]
+    fn a_scrolling_stripe_moves_with_time() {
+        let (white, black, stripes) = default_white_black_stripe();
+        let scrolling = AnimatedPattern::new(Arc::new(stripes), |t| {
+            crate::transformations::translation(t, 0.0, 0.0)
+        });
+        let shape = Sphere::new();
+        let p = point(0.25, 0.0, 0.0);
+        assert_eq!(scrolling.pattern_at_shape(&shape, p), white);
+        let later = scrolling.at_time(0.5).unwrap();
+        // The stripes have moved half a stripe along +x, so the point is now
+        // in the black stripe behind the edge that used to be at 0.
+        assert_eq!(later.pattern_at_shape(&shape, p), black);
+        assert_eq!(later.pattern_at_shape(&shape, point(0.75, 0.0, 0.0)), white);
+    }
+
+    #[test]
+    fn static_patterns_do_not_change_with_time() {
+        let (_, _, stripes) = default_white_black_stripe();
+        assert!(stripes.at_time(0.5).is_none());
+    }
 }
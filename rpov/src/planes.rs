@@ -1,25 +1,59 @@
+use std::collections::HashSet;
+use std::fmt;
+use std::sync::Arc;
+
 use crate::{
+    floats::Float,
     intersections::Intersection,
     materials::Material,
-    matrices::Matrix4,
+    matrices::{Matrix4, TransformError, validate_transform},
     rays::Ray,
-    shapes::{Intersectable, ShapeFunctions},
+    shapes::{Intersectable, ShapeFunctions, next_shape_id},
     tuples::{Tuple4, vector},
 };
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Plane {
-    pub transform: Matrix4,
+    pub id: u64,
+    transform: Matrix4,
     pub material: Material,
+    pub tags: HashSet<String>,
+    // Used instead of `material` when set.
+    pub shared_material: Option<Arc<Material>>,
+}
+
+impl fmt::Display for Plane {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Plane({}; {})",
+            self.transform.describe(),
+            self.material()
+        )
+    }
 }
 
 impl Plane {
     pub fn new() -> Self {
         Self {
+            id: next_shape_id(),
             transform: Matrix4::identity(),
             material: Material::new(),
+            tags: HashSet::new(),
+            shared_material: None,
         }
     }
+
+    pub fn transform(&self) -> Matrix4 {
+        self.transform
+    }
+
+    // Leaves the transform as it was when the new one can't be inverted.
+    pub fn set_transform(&mut self, transform: Matrix4) -> Result<(), TransformError> {
+        validate_transform("Plane", transform)?;
+        self.transform = transform;
+        Ok(())
+    }
 }
 
 impl Default for Plane {
@@ -29,12 +63,24 @@ impl Default for Plane {
 }
 
 impl ShapeFunctions for Plane {
+    fn id(&self) -> u64 {
+        self.id
+    }
+
+    fn tags(&self) -> &HashSet<String> {
+        &self.tags
+    }
+
+    fn transform(&self) -> Matrix4 {
+        self.transform
+    }
+
     fn transform_inverse(&self) -> Matrix4 {
         self.transform.inverse()
     }
 
     fn material(&self) -> &Material {
-        &self.material
+        self.shared_material.as_deref().unwrap_or(&self.material)
     }
 
     fn local_normal_at(&self, _local_point: &Tuple4) -> Tuple4 {
@@ -44,13 +90,131 @@ impl ShapeFunctions for Plane {
 
 impl Intersectable<Plane> for Plane {
     fn local_intersect<'a>(&'a self, _local_ray: Ray) -> Vec<Intersection<'a>> {
-        // implement this for plane
-        if _local_ray.direction.y.abs() < crate::floats::EPSILON {
-            return vec![];
+        match intersect_xz_plane(_local_ray) {
+            Some(t) => vec![Intersection::new(t, self)],
+            None => vec![],
         }
+    }
 
-        let t = -_local_ray.origin.y / _local_ray.direction.y;
-        vec![Intersection::new(t, self)]
+    fn intersects_before(&self, local_ray: Ray, t_max: Float) -> bool {
+        intersect_xz_plane(local_ray).is_some_and(|t| t >= 0.0 && t < t_max)
+    }
+}
+
+pub(crate) fn intersect_xz_plane(local_ray: Ray) -> Option<Float> {
+    if local_ray.direction.y.abs() < crate::floats::EPSILON {
+        return None;
+    }
+    Some(-local_ray.origin.y / local_ray.direction.y)
+}
+
+// A sine wave travelling across the xz plane. `direction` is (x, z) and should
+// be normalized.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Wave {
+    pub amplitude: Float,
+    pub frequency: Float,
+    pub direction: (Float, Float),
+}
+
+impl Wave {
+    pub fn new(amplitude: Float, frequency: Float, direction: (Float, Float)) -> Self {
+        Self {
+            amplitude,
+            frequency,
+            direction,
+        }
+    }
+}
+
+// A plane whose normals are perturbed by a sum of sine waves, e.g. for water.
+// Intersections stay on the flat plane; only the shading normal changes.
+#[derive(Debug, Clone)]
+pub struct WavyPlane {
+    pub id: u64,
+    transform: Matrix4,
+    pub material: Material,
+    pub tags: HashSet<String>,
+    pub waves: Vec<Wave>,
+    pub phase: Float,
+}
+
+impl WavyPlane {
+    pub fn new(waves: Vec<Wave>) -> Self {
+        Self {
+            id: next_shape_id(),
+            transform: Matrix4::identity(),
+            material: Material::new(),
+            tags: HashSet::new(),
+            waves,
+            phase: 0.0,
+        }
+    }
+
+    pub fn transform(&self) -> Matrix4 {
+        self.transform
+    }
+
+    // Leaves the transform as it was when the new one can't be inverted.
+    pub fn set_transform(&mut self, transform: Matrix4) -> Result<(), TransformError> {
+        validate_transform("WavyPlane", transform)?;
+        self.transform = transform;
+        Ok(())
+    }
+
+    pub fn height_at(&self, x: Float, z: Float) -> Float {
+        self.waves
+            .iter()
+            .map(|w| {
+                let (dx, dz) = w.direction;
+                w.amplitude * (w.frequency * (dx * x + dz * z) + self.phase).sin()
+            })
+            .sum()
+    }
+}
+
+impl ShapeFunctions for WavyPlane {
+    fn id(&self) -> u64 {
+        self.id
+    }
+
+    fn tags(&self) -> &HashSet<String> {
+        &self.tags
+    }
+
+    fn transform(&self) -> Matrix4 {
+        self.transform
+    }
+
+    fn transform_inverse(&self) -> Matrix4 {
+        self.transform.inverse()
+    }
+
+    fn material(&self) -> &Material {
+        &self.material
+    }
+
+    // The normal of the height field y = h(x, z) is (-dh/dx, 1, -dh/dz).
+    fn local_normal_at(&self, local_point: &Tuple4) -> Tuple4 {
+        let (mut dh_dx, mut dh_dz) = (0.0, 0.0);
+        for w in &self.waves {
+            let (dx, dz) = w.direction;
+            let slope = w.amplitude
+                * w.frequency
+                * (w.frequency * (dx * local_point.x + dz * local_point.z) + self.phase).cos();
+            dh_dx += slope * dx;
+            dh_dz += slope * dz;
+        }
+        vector(-dh_dx, 1.0, -dh_dz).normalize()
+    }
+}
+
+impl Intersectable<WavyPlane> for WavyPlane {
+    fn local_intersect<'a>(&'a self, local_ray: Ray) -> Vec<Intersection<'a>> {
+        match intersect_xz_plane(local_ray) {
+            Some(t) => vec![Intersection::new(t, self)],
+            None => vec![],
+        }
     }
 }
 
@@ -59,7 +223,8 @@ mod tests {
     use super::*;
     use crate::{
         assert_same_object,
-        tuples::{point, vector},
+        floats::FRAC_1_SQRT_2,
+        tuples::{check_tuple, point, vector},
     };
 
     // Scenario: The normal of a plane is constant everywhere
@@ -140,4 +305,57 @@ mod tests {
         assert_eq!(xs[0].t, 1.0);
         assert_same_object!(xs[0].object, &p);
     }
+
+    #[test]
+    fn a_wavy_plane_normal_is_flat_at_a_crest() {
+        let mut p = WavyPlane::new(vec![Wave::new(0.1, 2.0, (1.0, 0.0))]);
+        p.phase = 0.0;
+        // sin(2x) peaks at x = π/4, where the slope is zero.
+        let n = p.local_normal_at(&point(crate::floats::PI / 4.0, 0.0, 3.0));
+        check_tuple(n, vector(0.0, 1.0, 0.0));
+    }
+
+    #[test]
+    fn a_wavy_plane_normal_matches_the_analytic_slope() {
+        let p = WavyPlane::new(vec![Wave::new(0.1, 2.0, (1.0, 0.0))]);
+        // dh/dx = 0.1 * 2 * cos(0) = 0.2
+        let n = p.local_normal_at(&point(0.0, 0.0, 0.0));
+        check_tuple(n, vector(-0.2, 1.0, 0.0).normalize());
+
+        // the phase shifts the wave so the origin becomes a trough
+        let mut p = p;
+        p.phase = -crate::floats::PI / 2.0;
+        let n = p.local_normal_at(&point(0.0, 0.0, 0.0));
+        check_tuple(n, vector(0.0, 1.0, 0.0));
+        assert!((p.height_at(0.0, 0.0) + 0.1).abs() < 1e-5);
+    }
+
+    #[test]
+    fn wavy_plane_normals_are_normalized() {
+        let p = WavyPlane::new(vec![
+            Wave::new(0.3, 1.5, (1.0, 0.0)),
+            Wave::new(0.2, 3.0, (0.0, 1.0)),
+            Wave::new(0.05, 7.0, (FRAC_1_SQRT_2, FRAC_1_SQRT_2)),
+        ]);
+        for i in 0..20 {
+            let x = i as Float * 0.37 - 3.0;
+            let n = p.local_normal_at(&point(x, 0.0, -x * 0.5));
+            assert!((n.magnitude() - 1.0).abs() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn flat_water_is_identical_to_a_plane() {
+        let water = WavyPlane::new(vec![Wave::new(0.0, 4.0, (1.0, 0.0))]);
+        let p = Plane::new();
+        let r = Ray::new(point(0.3, 1.0, -2.0), vector(0.1, -1.0, 0.2));
+        let xs = water.local_intersect(r);
+        let expected = p.local_intersect(r);
+        assert_eq!(xs.len(), 1);
+        assert_eq!(xs[0].t, expected[0].t);
+        for x in [-2.0, 0.0, 0.7, 5.0] {
+            let at = point(x, 0.0, x * 2.0);
+            assert_eq!(water.local_normal_at(&at), p.local_normal_at(&at));
+        }
+    }
 }
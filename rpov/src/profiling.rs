@@ -0,0 +1,133 @@
+// Scoped timers for the `profile` feature. Each section accumulates its own
+// (exclusive) time, so time spent in a nested section is not counted twice
+// and the sections add up to the time of the outermost one.
+use std::{cell::RefCell, collections::BTreeMap, time::Duration, time::Instant};
+
+struct Frame {
+    name: &'static str,
+    start: Instant,
+    children: Duration,
+}
+
+thread_local! {
+    static STACK: RefCell<Vec<Frame>> = const { RefCell::new(vec![]) };
+    static TOTALS: RefCell<BTreeMap<&'static str, (Duration, u64)>> = const { RefCell::new(BTreeMap::new()) };
+}
+
+pub struct Scope;
+
+impl Scope {
+    pub fn enter(name: &'static str) -> Scope {
+        STACK.with(|stack| {
+            stack.borrow_mut().push(Frame {
+                name,
+                start: Instant::now(),
+                children: Duration::ZERO,
+            })
+        });
+        Scope
+    }
+}
+
+impl Drop for Scope {
+    fn drop(&mut self) {
+        STACK.with(|stack| {
+            let mut stack = stack.borrow_mut();
+            let frame = stack.pop().expect("profiling scope stack underflow");
+            let elapsed = frame.start.elapsed();
+            if let Some(parent) = stack.last_mut() {
+                parent.children += elapsed;
+            }
+            TOTALS.with(|totals| {
+                let mut totals = totals.borrow_mut();
+                let entry = totals.entry(frame.name).or_default();
+                entry.0 += elapsed.saturating_sub(frame.children);
+                entry.1 += 1;
+            });
+        });
+    }
+}
+
+// Exclusive time and call count for each section seen on this thread.
+pub fn totals() -> BTreeMap<&'static str, (Duration, u64)> {
+    TOTALS.with(|totals| totals.borrow().clone())
+}
+
+pub fn reset() {
+    TOTALS.with(|totals| totals.borrow_mut().clear());
+}
+
+pub fn report() -> String {
+    let totals = totals();
+    let sum: Duration = totals.values().map(|(time, _)| *time).sum();
+    let mut out = String::from("section            time (ms)   calls   share\n");
+    for (name, (time, calls)) in &totals {
+        let share = if sum.is_zero() {
+            0.0
+        } else {
+            time.as_secs_f64() / sum.as_secs_f64() * 100.0
+        };
+        out.push_str(&format!(
+            "{name:<18} {:>10.3} {calls:>7} {share:>6.1}%\n",
+            time.as_secs_f64() * 1000.0
+        ));
+    }
+    out.push_str(&format!(
+        "{:<18} {:>10.3}\n",
+        "total",
+        sum.as_secs_f64() * 1000.0
+    ));
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        camera::Camera,
+        floats::PI,
+        transformations::view_transform,
+        tuples::{point, vector},
+        world::{default_world, render},
+    };
+
+    #[test]
+    fn nested_scopes_record_exclusive_time() {
+        reset();
+        let start = Instant::now();
+        {
+            let _outer = Scope::enter("outer");
+            std::thread::sleep(Duration::from_millis(5));
+            let _inner = Scope::enter("inner");
+            std::thread::sleep(Duration::from_millis(5));
+        }
+        let wall = start.elapsed();
+        let totals = totals();
+        let (outer, inner) = (totals["outer"].0, totals["inner"].0);
+        assert!(inner >= Duration::from_millis(5));
+        assert!(outer >= Duration::from_millis(5));
+        // Counting the inner sleep in both would push the sum past the wall
+        // time, however slow the machine.
+        assert!(outer + inner <= wall, "{outer:?} + {inner:?} > {wall:?}");
+    }
+
+    #[test]
+    fn rendering_reports_intersect_and_shading_time() {
+        reset();
+        let mut c = Camera::new(41, 41, PI / 2.0);
+        c.set_transform(view_transform(
+            point(0.0, 0.0, -5.0),
+            point(0.0, 0.0, 0.0),
+            vector(0.0, 1.0, 0.0),
+        ));
+        let start = Instant::now();
+        render(c, default_world());
+        let wall = start.elapsed();
+        let totals = totals();
+        assert!(totals["intersect"].0 > Duration::ZERO);
+        assert!(totals["shade_hit"].0 > Duration::ZERO);
+        let sum: Duration = totals.values().map(|(time, _)| *time).sum();
+        assert!(sum <= wall, "{sum:?} {wall:?}");
+        assert!(report().contains("lighting"));
+    }
+}
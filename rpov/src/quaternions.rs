@@ -0,0 +1,246 @@
+use std::ops::Mul;
+
+use crate::{floats::Float, matrices::Matrix4, tuples::Tuple4};
+
+// A rotation stored as a unit quaternion. Unlike chained Euler rotations it
+// has no gimbal lock, and two orientations can be blended with `slerp`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Quaternion {
+    pub w: Float,
+    pub x: Float,
+    pub y: Float,
+    pub z: Float,
+}
+
+impl Default for Quaternion {
+    fn default() -> Self {
+        Self::identity()
+    }
+}
+
+impl Quaternion {
+    pub fn new(w: Float, x: Float, y: Float, z: Float) -> Self {
+        Self { w, x, y, z }
+    }
+
+    pub fn identity() -> Self {
+        Self::new(1.0, 0.0, 0.0, 0.0)
+    }
+
+    // Rotates by `angle` radians about `axis`, turning the same way as
+    // rotation_x, rotation_y and rotation_z do about their axes.
+    pub fn from_axis_angle(axis: Tuple4, angle: Float) -> Self {
+        let axis = axis.normalize();
+        let (sin, cos) = (angle / 2.0).sin_cos();
+        Self::new(cos, axis.x * sin, axis.y * sin, axis.z * sin)
+    }
+
+    // The rotation part of `m`, which must be a pure rotation in its upper
+    // 3x3. Picks the largest of w, x, y and z to divide by, so it stays
+    // accurate near 180 degree turns.
+    pub fn from_rotation_matrix(m: &Matrix4) -> Self {
+        let trace = m[(0, 0)] + m[(1, 1)] + m[(2, 2)];
+        let q = if trace > 0.0 {
+            let s = (trace + 1.0).sqrt() * 2.0;
+            Self::new(
+                s / 4.0,
+                (m[(2, 1)] - m[(1, 2)]) / s,
+                (m[(0, 2)] - m[(2, 0)]) / s,
+                (m[(1, 0)] - m[(0, 1)]) / s,
+            )
+        } else if m[(0, 0)] > m[(1, 1)] && m[(0, 0)] > m[(2, 2)] {
+            let s = (1.0 + m[(0, 0)] - m[(1, 1)] - m[(2, 2)]).sqrt() * 2.0;
+            Self::new(
+                (m[(2, 1)] - m[(1, 2)]) / s,
+                s / 4.0,
+                (m[(0, 1)] + m[(1, 0)]) / s,
+                (m[(0, 2)] + m[(2, 0)]) / s,
+            )
+        } else if m[(1, 1)] > m[(2, 2)] {
+            let s = (1.0 + m[(1, 1)] - m[(0, 0)] - m[(2, 2)]).sqrt() * 2.0;
+            Self::new(
+                (m[(0, 2)] - m[(2, 0)]) / s,
+                (m[(0, 1)] + m[(1, 0)]) / s,
+                s / 4.0,
+                (m[(1, 2)] + m[(2, 1)]) / s,
+            )
+        } else {
+            let s = (1.0 + m[(2, 2)] - m[(0, 0)] - m[(1, 1)]).sqrt() * 2.0;
+            Self::new(
+                (m[(1, 0)] - m[(0, 1)]) / s,
+                (m[(0, 2)] + m[(2, 0)]) / s,
+                (m[(1, 2)] + m[(2, 1)]) / s,
+                s / 4.0,
+            )
+        };
+        q.normalize()
+    }
+
+    pub fn to_matrix4(&self) -> Matrix4 {
+        let Self { w, x, y, z } = *self;
+        Matrix4::from([
+            [
+                1.0 - 2.0 * (y * y + z * z),
+                2.0 * (x * y - w * z),
+                2.0 * (x * z + w * y),
+                0.0,
+            ],
+            [
+                2.0 * (x * y + w * z),
+                1.0 - 2.0 * (x * x + z * z),
+                2.0 * (y * z - w * x),
+                0.0,
+            ],
+            [
+                2.0 * (x * z - w * y),
+                2.0 * (y * z + w * x),
+                1.0 - 2.0 * (x * x + y * y),
+                0.0,
+            ],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    pub fn dot(&self, other: Quaternion) -> Float {
+        self.w * other.w + self.x * other.x + self.y * other.y + self.z * other.z
+    }
+
+    pub fn normalize(&self) -> Self {
+        let length = self.dot(*self).sqrt();
+        Self::new(
+            self.w / length,
+            self.x / length,
+            self.y / length,
+            self.z / length,
+        )
+    }
+
+    // The inverse rotation, for unit quaternions.
+    pub fn conjugate(&self) -> Self {
+        Self::new(self.w, -self.x, -self.y, -self.z)
+    }
+
+    // Turns from `a` at t = 0 to `b` at t = 1 at a constant angular speed,
+    // taking the shorter way round.
+    pub fn slerp(a: Quaternion, b: Quaternion, t: Float) -> Quaternion {
+        let mut cos = a.dot(b);
+        let b = if cos < 0.0 {
+            cos = -cos;
+            Self::new(-b.w, -b.x, -b.y, -b.z)
+        } else {
+            b
+        };
+        // Nearly the same rotation: the sine below would be close to zero,
+        // and a straight line between them is just as good.
+        let (wa, wb) = if cos > 0.9995 {
+            (1.0 - t, t)
+        } else {
+            let theta = cos.acos();
+            let sin = theta.sin();
+            (((1.0 - t) * theta).sin() / sin, (t * theta).sin() / sin)
+        };
+        Self::new(
+            wa * a.w + wb * b.w,
+            wa * a.x + wb * b.x,
+            wa * a.y + wb * b.y,
+            wa * a.z + wb * b.z,
+        )
+        .normalize()
+    }
+}
+
+// `a * b` rotates by `b` first and then by `a`, the same order as the
+// matching matrices.
+impl Mul<Quaternion> for Quaternion {
+    type Output = Quaternion;
+
+    fn mul(self, o: Quaternion) -> Quaternion {
+        Quaternion::new(
+            self.w * o.w - self.x * o.x - self.y * o.y - self.z * o.z,
+            self.w * o.x + self.x * o.w + self.y * o.z - self.z * o.y,
+            self.w * o.y - self.x * o.z + self.y * o.w + self.z * o.x,
+            self.w * o.z + self.x * o.y - self.y * o.x + self.z * o.w,
+        )
+    }
+}
+
+impl From<Quaternion> for Matrix4 {
+    fn from(q: Quaternion) -> Self {
+        q.to_matrix4()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        floats::consts::PI,
+        matrices::check,
+        transformations::{rotation_x, rotation_y, rotation_z},
+        tuples::vector,
+    };
+
+    #[test]
+    fn the_identity_quaternion_is_the_identity_matrix() {
+        assert_eq!(Quaternion::identity().to_matrix4(), Matrix4::identity());
+    }
+
+    #[test]
+    fn axis_angle_rotations_match_the_rotation_matrices() {
+        check(
+            Quaternion::from_axis_angle(vector(1.0, 0.0, 0.0), PI / 3.0).to_matrix4(),
+            rotation_x(PI / 3.0),
+        );
+        check(
+            Quaternion::from_axis_angle(vector(0.0, 1.0, 0.0), PI / 5.0).to_matrix4(),
+            rotation_y(PI / 5.0),
+        );
+        check(
+            Quaternion::from_axis_angle(vector(0.0, 0.0, 1.0), -PI / 2.0).to_matrix4(),
+            rotation_z(-PI / 2.0),
+        );
+    }
+
+    #[test]
+    fn an_axis_angle_rotation_survives_a_round_trip_through_a_matrix() {
+        for (axis, angle) in [
+            (vector(1.0, 2.0, 3.0), 0.7),
+            (vector(0.0, 1.0, 0.0), PI * 0.99),
+            (vector(-1.0, 0.5, 0.2), 2.5),
+            (vector(0.0, 0.0, 1.0), -1.2),
+        ] {
+            let q = Quaternion::from_axis_angle(axis, angle);
+            let back = Quaternion::from_rotation_matrix(&q.to_matrix4());
+            // q and -q are the same rotation.
+            crate::check_floats!(back.dot(q).abs(), 1.0);
+        }
+    }
+
+    #[test]
+    fn multiplying_composes_like_matrices() {
+        let a = Quaternion::from_axis_angle(vector(1.0, 0.0, 0.0), PI / 2.0);
+        let b = Quaternion::from_axis_angle(vector(0.0, 1.0, 0.0), PI / 4.0);
+        check(
+            (a * b).to_matrix4(),
+            rotation_x(PI / 2.0) * rotation_y(PI / 4.0),
+        );
+    }
+
+    #[test]
+    fn slerp_starts_and_ends_at_its_endpoints() {
+        let a = Quaternion::from_axis_angle(vector(0.0, 1.0, 0.0), 0.3);
+        let b = Quaternion::from_axis_angle(vector(1.0, 1.0, 0.0), 1.9);
+        check(Quaternion::slerp(a, b, 0.0).to_matrix4(), a.to_matrix4());
+        check(Quaternion::slerp(a, b, 1.0).to_matrix4(), b.to_matrix4());
+    }
+
+    #[test]
+    fn slerp_halfway_between_two_rotations_is_the_rotation_between() {
+        let a = Quaternion::identity();
+        let b = Quaternion::from_axis_angle(vector(0.0, 0.0, 1.0), PI / 2.0);
+        check(
+            Quaternion::slerp(a, b, 0.5).to_matrix4(),
+            rotation_z(PI / 4.0),
+        );
+    }
+}
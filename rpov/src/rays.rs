@@ -1,20 +1,46 @@
 use crate::floats::Float;
 use crate::matrices::Matrix4;
-use crate::tuples::Tuple4;
+use crate::tuples::{Tuple4, point};
+
+// Where a ray came from, so shapes can choose which rays see them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RayKind {
+    #[default]
+    Camera,
+    Shadow,
+    Reflection,
+    Refraction,
+}
 
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub struct Ray {
     pub origin: Tuple4,
     pub direction: Tuple4,
+    pub kind: RayKind,
 }
 
 pub fn ray(origin: Tuple4, direction: Tuple4) -> Ray {
-    Ray { origin, direction }
+    Ray::new(origin, direction)
 }
 
 impl Ray {
     pub fn new(origin: Tuple4, direction: Tuple4) -> Self {
-        Self { origin, direction }
+        Self {
+            origin,
+            direction,
+            kind: RayKind::Camera,
+        }
+    }
+
+    // A ray along `direction` normalized, or None when there is no
+    // direction to follow, so degenerate rays are dropped where they are made.
+    pub fn try_new(origin: Tuple4, direction: Tuple4) -> Option<Self> {
+        Some(Self::new(origin, direction.try_normalize()?))
+    }
+
+    pub fn with_kind(mut self, kind: RayKind) -> Self {
+        self.kind = kind;
+        self
     }
 
     pub fn position(&self, t: Float) -> Tuple4 {
@@ -25,10 +51,31 @@ impl Ray {
         Ray {
             origin: m * self.origin,
             direction: m * self.direction,
+            kind: self.kind,
         }
     }
 }
 
+// Rays from origin through the centers of a pixels x pixels grid on a square
+// wall of wall_size, facing the origin across z = wall_z. Yields (x, y, ray)
+// row by row, with y = 0 at the top, for plotting without a Camera.
+pub fn rays_toward_plane(
+    origin: Tuple4,
+    wall_z: Float,
+    wall_size: Float,
+    pixels: usize,
+) -> impl ExactSizeIterator<Item = (usize, usize, Ray)> {
+    let pixel_size = wall_size / pixels as Float;
+    let half = wall_size / 2.0;
+    (0..pixels * pixels).map(move |i| {
+        let (x, y) = (i % pixels, i / pixels);
+        let world_x = -half + pixel_size * (x as Float + 0.5);
+        let world_y = half - pixel_size * (y as Float + 0.5);
+        let position = point(world_x, world_y, wall_z);
+        (x, y, ray(origin, (position - origin).normalize()))
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -96,4 +143,37 @@ mod tests {
         assert_eq!(r2.origin, point(2.0, 6.0, 12.0));
         assert_eq!(r2.direction, vector(0.0, 3.0, 0.0));
     }
+
+    #[test]
+    fn the_center_of_a_wall_grid_is_straight_ahead() {
+        let rays: Vec<_> = rays_toward_plane(point(0.0, 0.0, -5.0), 10.0, 7.0, 3).collect();
+        assert_eq!(rays.len(), 9);
+        let (x, y, center) = rays[4];
+        assert_eq!((x, y), (1, 1));
+        assert_eq!(center.direction, vector(0.0, 0.0, 1.0));
+
+        let behind = rays_toward_plane(point(0.0, 0.0, -5.0), -10.0, 7.0, 3);
+        assert_eq!(behind.len(), 9);
+        assert_eq!(
+            behind.into_iter().nth(4).unwrap().2.direction,
+            vector(0.0, 0.0, -1.0)
+        );
+    }
+
+    #[test]
+    fn wall_grid_corners_are_symmetric() {
+        let rays: Vec<_> = rays_toward_plane(point(0.0, 0.0, -5.0), 10.0, 7.0, 3).collect();
+        let d = |i: usize| rays[i].2.direction;
+        let (top_left, top_right, bottom_left, bottom_right) = (d(0), d(2), d(6), d(8));
+        assert!(top_left.x < 0.0 && top_left.y > 0.0);
+        for (corner, (sx, sy)) in [
+            (top_right, (-1.0, 1.0)),
+            (bottom_left, (1.0, -1.0)),
+            (bottom_right, (-1.0, -1.0)),
+        ] {
+            crate::check_floats!(corner.x, sx * top_left.x);
+            crate::check_floats!(corner.y, sy * top_left.y);
+            crate::check_floats!(corner.z, top_left.z);
+        }
+    }
 }
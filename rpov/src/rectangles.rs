@@ -0,0 +1,176 @@
+use std::collections::HashSet;
+use std::sync::Arc;
+
+use crate::{
+    bounds::BoundingSphere,
+    floats::Float,
+    intersections::Intersection,
+    materials::Material,
+    matrices::{Matrix4, TransformError, validate_transform},
+    planes::intersect_xz_plane,
+    rays::Ray,
+    shapes::{Intersectable, ShapeFunctions, next_shape_id},
+    tuples::{Tuple4, point, vector},
+};
+
+// A finite plane in xz, centered at the origin, facing +y.
+#[derive(Debug, Clone)]
+pub struct Rectangle {
+    pub id: u64,
+    transform: Matrix4,
+    pub material: Material,
+    pub tags: HashSet<String>,
+    // Used instead of `material` when set.
+    pub shared_material: Option<Arc<Material>>,
+    pub width: Float,
+    pub height: Float,
+}
+
+impl Rectangle {
+    pub fn new(width: Float, height: Float) -> Self {
+        Self {
+            id: next_shape_id(),
+            transform: Matrix4::identity(),
+            material: Material::new(),
+            tags: HashSet::new(),
+            shared_material: None,
+            width,
+            height,
+        }
+    }
+
+    pub fn transform(&self) -> Matrix4 {
+        self.transform
+    }
+
+    // Leaves the transform as it was when the new one can't be inverted.
+    pub fn set_transform(&mut self, transform: Matrix4) -> Result<(), TransformError> {
+        validate_transform("Rectangle", transform)?;
+        self.transform = transform;
+        Ok(())
+    }
+
+    // World-space corners, in winding order.
+    pub fn corners(&self) -> [Tuple4; 4] {
+        let (hw, hh) = (self.width / 2.0, self.height / 2.0);
+        [
+            self.transform * point(-hw, 0.0, -hh),
+            self.transform * point(hw, 0.0, -hh),
+            self.transform * point(hw, 0.0, hh),
+            self.transform * point(-hw, 0.0, hh),
+        ]
+    }
+}
+
+impl ShapeFunctions for Rectangle {
+    fn id(&self) -> u64 {
+        self.id
+    }
+
+    fn tags(&self) -> &HashSet<String> {
+        &self.tags
+    }
+
+    fn transform(&self) -> Matrix4 {
+        self.transform
+    }
+
+    fn transform_inverse(&self) -> Matrix4 {
+        self.transform.inverse()
+    }
+
+    fn material(&self) -> &Material {
+        self.shared_material.as_deref().unwrap_or(&self.material)
+    }
+
+    fn local_normal_at(&self, _local_point: &Tuple4) -> Tuple4 {
+        vector(0.0, 1.0, 0.0)
+    }
+
+    fn bounding_sphere(&self) -> Option<BoundingSphere> {
+        let half_diagonal = self.width.hypot(self.height) / 2.0;
+        Some(BoundingSphere::around_origin(self.transform, half_diagonal))
+    }
+}
+
+impl Intersectable<Rectangle> for Rectangle {
+    fn local_intersect<'a>(&'a self, local_ray: Ray) -> Vec<Intersection<'a>> {
+        let Some(t) = intersect_xz_plane(local_ray) else {
+            return vec![];
+        };
+        let p = local_ray.position(t);
+        if p.x.abs() > self.width / 2.0 || p.z.abs() > self.height / 2.0 {
+            return vec![];
+        }
+        let uv = (p.x / self.width + 0.5, p.z / self.height + 0.5);
+        vec![Intersection::with_uv(t, self, uv)]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        colors::Color, lighting::point_light, spheres::Sphere, transformations::translation,
+        tuples::check_tuple, world::WorldBuilder,
+    };
+
+    #[test]
+    fn rectangle_hits_carry_uv_through_to_computations() {
+        let r = Rectangle::new(2.0, 4.0);
+        let ray = Ray::new(point(0.5, 1.0, -1.0), vector(0.0, -1.0, 0.0));
+        let xs = r.intersect(ray);
+        assert_eq!(xs[0].uv, Some((0.75, 0.25)));
+        let comps = xs[0].prepare_computations(ray, Some(xs.clone()));
+        assert_eq!(comps.uv, Some((0.75, 0.25)));
+    }
+
+    #[test]
+    fn a_ray_hits_a_rectangle_inside_its_extent() {
+        let r = Rectangle::new(2.0, 1.0);
+        let xs = r.local_intersect(Ray::new(point(0.9, 1.0, 0.4), vector(0.0, -1.0, 0.0)));
+        assert_eq!(xs.len(), 1);
+        assert_eq!(xs[0].t, 1.0);
+    }
+
+    #[test]
+    fn a_ray_misses_just_outside_each_edge() {
+        let r = Rectangle::new(2.0, 1.0);
+        for (x, z) in [(1.01, 0.0), (-1.01, 0.0), (0.0, 0.51), (0.0, -0.51)] {
+            let xs = r.local_intersect(Ray::new(point(x, 1.0, z), vector(0.0, -1.0, 0.0)));
+            assert!(xs.is_empty(), "({x}, {z})");
+        }
+    }
+
+    #[test]
+    fn a_coplanar_ray_misses_a_rectangle() {
+        let r = Rectangle::new(2.0, 2.0);
+        let xs = r.local_intersect(Ray::new(point(-5.0, 0.0, 0.0), vector(1.0, 0.0, 0.0)));
+        assert!(xs.is_empty());
+    }
+
+    #[test]
+    fn rectangle_corners_follow_the_transform() {
+        let mut r = Rectangle::new(2.0, 4.0);
+        r.set_transform(translation(0.0, 3.0, 0.0)).unwrap();
+        let c = r.corners();
+        check_tuple(c[0], point(-1.0, 3.0, -2.0));
+        check_tuple(c[2], point(1.0, 3.0, 2.0));
+    }
+
+    #[test]
+    fn a_small_rectangle_shadows_a_sphere() {
+        let mut w = WorldBuilder::with_light(point_light(
+            point(0.0, 10.0, 0.0),
+            Color::new(1.0, 1.0, 1.0),
+        ));
+        w.objects.push(Sphere::new());
+        let mut blocker = Rectangle::new(0.5, 0.5);
+        blocker.set_transform(translation(0.0, 5.0, 0.0)).unwrap();
+        w.shapes.push(Box::new(blocker));
+
+        assert!(w.is_shadowed(point(0.0, 1.01, 0.0)));
+        // the light reaches the side of the sphere past the rectangle's edge
+        assert!(!w.is_shadowed(point(1.01, 0.0, 0.0)));
+    }
+}
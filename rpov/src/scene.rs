@@ -0,0 +1,264 @@
+#![cfg_attr(not(test), deny(clippy::unwrap_used))]
+
+use std::{collections::HashSet, ops::Deref};
+
+use crate::{
+    any_shapes::AnyShape,
+    bounds::BoundingSphere,
+    camera::Camera,
+    canvas::Canvas,
+    floats::Float,
+    intersections::{Intersection, Shape},
+    materials::Material,
+    matrices::Matrix4,
+    rays::Ray,
+    shapes::{Intersectable, ShapeFunctions},
+    tuples::Tuple4,
+    world::{ShapeStore, WorldBuilder, render_pixels_jittered},
+};
+
+// A world frozen for rendering. Nothing can change once it is built, so
+// per-shape work such as inverting transforms is done once up front. Being
+// Send + Sync, a scene can be shared between render threads.
+pub struct Scene<S = Vec<Box<dyn Shape>>> {
+    world: WorldBuilder<S>,
+}
+
+impl<S> Deref for Scene<S> {
+    type Target = WorldBuilder<S>;
+
+    fn deref(&self) -> &WorldBuilder<S> {
+        &self.world
+    }
+}
+
+impl<S: ShapeStore> Scene<S> {
+    pub fn render(&self, c: &Camera) -> Canvas {
+        render_pixels_jittered(c, &self.world, (0.5, 0.5))
+    }
+}
+
+impl WorldBuilder {
+    pub fn build(self) -> Scene {
+        let WorldBuilder {
+            objects,
+            light,
+            planes,
+            shapes,
+            options,
+        } = self;
+        // Keep the builder's order, so hits at equal t sort as they do unfrozen.
+        let mut frozen: Vec<Box<dyn crate::intersections::Shape>> = vec![];
+        frozen.extend(objects.into_iter().map(|s| Box::new(Frozen::new(s)) as _));
+        frozen.extend(planes.into_iter().map(|p| Box::new(Frozen::new(p)) as _));
+        frozen.extend(shapes);
+        Scene {
+            world: WorldBuilder {
+                objects: vec![],
+                light,
+                planes: vec![],
+                shapes: frozen,
+                options,
+            },
+        }
+    }
+}
+
+impl WorldBuilder<Vec<AnyShape>> {
+    // Freezes the world as it is. Caching inverses would mean boxing each
+    // shape, which an inline store is there to avoid, so they are still
+    // worked out per ray.
+    pub fn build(self) -> Scene<Vec<AnyShape>> {
+        Scene { world: self }
+    }
+}
+
+// A shape with its inverse transform computed once. Only for shapes that
+// use the default intersect and normal_at.
+#[derive(Debug, Clone)]
+struct Frozen<S> {
+    shape: S,
+    inverse: Matrix4,
+    bound: Option<BoundingSphere>,
+}
+
+impl<S: ShapeFunctions> Frozen<S> {
+    fn new(shape: S) -> Self {
+        let inverse = shape.transform_inverse();
+        let bound = shape.bounding_sphere();
+        Self {
+            shape,
+            inverse,
+            bound,
+        }
+    }
+}
+
+impl<S: ShapeFunctions> ShapeFunctions for Frozen<S> {
+    fn transform(&self) -> Matrix4 {
+        self.shape.transform()
+    }
+
+    fn transform_inverse(&self) -> Matrix4 {
+        self.inverse
+    }
+
+    fn bounding_sphere(&self) -> Option<BoundingSphere> {
+        self.bound
+    }
+
+    fn material(&self) -> &Material {
+        self.shape.material()
+    }
+
+    fn id(&self) -> u64 {
+        self.shape.id()
+    }
+
+    fn tags(&self) -> &HashSet<String> {
+        self.shape.tags()
+    }
+
+    fn resolved_material(&self) -> &Material {
+        self.shape.resolved_material()
+    }
+
+    fn local_normal_at(&self, local_point: &Tuple4) -> Tuple4 {
+        self.shape.local_normal_at(local_point)
+    }
+
+    fn local_tangent_at(&self, local_point: &Tuple4) -> Option<Tuple4> {
+        self.shape.local_tangent_at(local_point)
+    }
+}
+
+impl<S> Intersectable<Frozen<S>> for Frozen<S>
+where
+    S: Intersectable<S> + ShapeFunctions + std::fmt::Debug + Clone + Send + Sync + 'static,
+{
+    fn local_intersect<'a>(&'a self, local_ray: Ray) -> Vec<Intersection<'a>> {
+        self.local_intersect_bounded(local_ray, Float::INFINITY)
+    }
+
+    fn local_intersect_bounded<'a>(
+        &'a self,
+        local_ray: Ray,
+        t_max: Float,
+    ) -> Vec<Intersection<'a>> {
+        self.shape
+            .local_intersect_bounded(local_ray, t_max)
+            .into_iter()
+            .map(|i| Intersection { object: self, ..i })
+            .collect()
+    }
+
+    fn intersects_before(&self, local_ray: Ray, t_max: Float) -> bool {
+        self.shape.intersects_before(local_ray, t_max)
+    }
+
+    // Keeps the wrapped shape's batched path, with the hits pointing back at
+    // the frozen shape like single rays' do. intersect_batch brings the rays
+    // into object space with the cached inverse before they get here.
+    fn local_intersect_batch<'a>(
+        &'a self,
+        local_rays: &[Ray],
+        out: &mut Vec<(usize, Intersection<'a>)>,
+    ) {
+        let start = out.len();
+        self.shape.local_intersect_batch(local_rays, out);
+        for (_, x) in &mut out[start..] {
+            x.object = self;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        floats::PI,
+        planes::Plane,
+        rays::ray,
+        transformations::view_transform,
+        tuples::{point, vector},
+        world::default_world,
+    };
+
+    #[test]
+    fn a_scene_can_be_shared_between_threads() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<Scene>();
+    }
+
+    #[test]
+    fn a_scene_shades_like_the_world_it_was_built_from() {
+        let mut w = default_world();
+        w.planes.push(Plane::new());
+        let scene = {
+            let mut copy = default_world();
+            copy.planes.push(Plane::new());
+            copy.build()
+        };
+        for (x, y) in [(0.0, 0.0), (0.3, 0.2), (2.0, -1.5), (0.0, 3.0)] {
+            let r = ray(
+                point(0.0, 0.0, -5.0),
+                (point(x, y, 0.0) - point(0.0, 0.0, -5.0)).normalize(),
+            );
+            assert_eq!(w.color_at(r), scene.color_at(r));
+        }
+    }
+
+    #[test]
+    fn rendering_a_scene_matches_rendering_the_unfrozen_world() {
+        let mut c = Camera::new(11, 11, PI / 2.0);
+        c.set_transform(view_transform(
+            point(0.0, 0.0, -5.0),
+            point(0.0, 0.0, 0.0),
+            vector(0.0, 1.0, 0.0),
+        ));
+        let expected = render_pixels_jittered(&c, &default_world(), (0.5, 0.5));
+        assert_eq!(
+            default_world().build().render(&c).to_ppm(),
+            expected.to_ppm()
+        );
+    }
+
+    #[test]
+    fn a_frozen_shape_batches_like_the_shape_it_wraps() {
+        use crate::{spheres::Sphere, transformations::scaling};
+        let sphere = Sphere::with_transform(scaling(2.0, 1.0, 1.0)).unwrap();
+        let frozen = Frozen::new(sphere.clone());
+        let rays: Vec<_> = (0..20)
+            .map(|i| {
+                ray(
+                    point(i as Float * 0.2 - 2.0, 0.5, -5.0),
+                    vector(0.0, 0.0, 1.0),
+                )
+            })
+            .collect();
+        let (mut expected, mut batch) = (vec![], vec![]);
+        sphere.intersect_batch(&rays, &mut expected);
+        // Only the inverse cached at freezing is used; the wrapped shape's
+        // own transform is never looked at again.
+        let mut frozen = frozen;
+        frozen.shape.set_transform(scaling(0.5, 0.5, 0.5)).unwrap();
+        frozen.intersect_batch(&rays, &mut batch);
+        assert!(!batch.is_empty());
+        assert_eq!(
+            batch.iter().map(|(i, x)| (*i, x.t)).collect::<Vec<_>>(),
+            expected.iter().map(|(i, x)| (*i, x.t)).collect::<Vec<_>>()
+        );
+        for (_, x) in &batch {
+            crate::assert_same_object!(x.object, &frozen);
+        }
+    }
+
+    #[test]
+    fn building_moves_every_shape_into_the_scene() {
+        let mut w = default_world();
+        w.planes.push(Plane::new());
+        let scene = w.build();
+        assert!(scene.objects.is_empty() && scene.planes.is_empty());
+        assert_eq!(scene.shapes.len(), 3);
+    }
+}
@@ -0,0 +1,240 @@
+use std::collections::HashSet;
+use std::fmt;
+use std::sync::Arc;
+
+use crate::{
+    floats::{EPSILON, Float},
+    intersections::Intersection,
+    materials::Material,
+    matrices::{Matrix4, TransformError, validate_transform},
+    rays::Ray,
+    shapes::{Intersectable, ShapeFunctions, next_shape_id},
+    tuples::{Tuple4, vector},
+};
+
+pub type Sdf = Arc<dyn Fn(Tuple4) -> Float + Send + Sync>;
+
+// A shape defined by a signed distance function (negative inside), found by
+// sphere tracing. Useful for shapes without an analytic intersection.
+#[derive(Clone)]
+pub struct SdfShape {
+    pub id: u64,
+    transform: Matrix4,
+    pub material: Material,
+    pub tags: HashSet<String>,
+    pub sdf: Sdf,
+    pub max_steps: usize,
+    // Must stay well below the shadow bias, or shadow rays hit their own surface.
+    pub hit_epsilon: Float,
+    pub max_distance: Float,
+}
+
+impl fmt::Debug for SdfShape {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SdfShape")
+            .field("transform", &self.transform)
+            .field("material", &self.material)
+            .field("max_steps", &self.max_steps)
+            .field("hit_epsilon", &self.hit_epsilon)
+            .field("max_distance", &self.max_distance)
+            .finish_non_exhaustive()
+    }
+}
+
+impl SdfShape {
+    pub fn new(sdf: impl Fn(Tuple4) -> Float + Send + Sync + 'static) -> Self {
+        Self {
+            id: next_shape_id(),
+            transform: Matrix4::identity(),
+            material: Material::new(),
+            tags: HashSet::new(),
+            sdf: Arc::new(sdf),
+            max_steps: 256,
+            hit_epsilon: EPSILON / 20.0,
+            max_distance: 100.0,
+        }
+    }
+
+    pub fn transform(&self) -> Matrix4 {
+        self.transform
+    }
+
+    // Leaves the transform as it was when the new one can't be inverted.
+    pub fn set_transform(&mut self, transform: Matrix4) -> Result<(), TransformError> {
+        validate_transform("SdfShape", transform)?;
+        self.transform = transform;
+        Ok(())
+    }
+
+    pub fn with_max_steps(mut self, max_steps: usize) -> Self {
+        self.max_steps = max_steps;
+        self
+    }
+
+    pub fn with_hit_epsilon(mut self, hit_epsilon: Float) -> Self {
+        self.hit_epsilon = hit_epsilon;
+        self
+    }
+
+    pub fn with_max_distance(mut self, max_distance: Float) -> Self {
+        self.max_distance = max_distance;
+        self
+    }
+}
+
+impl ShapeFunctions for SdfShape {
+    fn id(&self) -> u64 {
+        self.id
+    }
+
+    fn tags(&self) -> &HashSet<String> {
+        &self.tags
+    }
+
+    fn transform(&self) -> Matrix4 {
+        self.transform
+    }
+
+    fn transform_inverse(&self) -> Matrix4 {
+        self.transform.inverse()
+    }
+
+    fn material(&self) -> &Material {
+        &self.material
+    }
+
+    fn local_normal_at(&self, local_point: &Tuple4) -> Tuple4 {
+        // Much smaller steps drown the difference in f32 rounding.
+        let h = EPSILON;
+        let d = |dx, dy, dz| {
+            (self.sdf)(*local_point + vector(dx, dy, dz))
+                - (self.sdf)(*local_point - vector(dx, dy, dz))
+        };
+        vector(d(h, 0.0, 0.0), d(0.0, h, 0.0), d(0.0, 0.0, h)).normalize()
+    }
+}
+
+impl Intersectable<SdfShape> for SdfShape {
+    fn local_intersect<'a>(&'a self, local_ray: Ray) -> Vec<Intersection<'a>> {
+        // March in units of distance along a unit direction, then convert back
+        // to the ray's own parameterization.
+        let length = local_ray.direction.magnitude();
+        let direction = local_ray.direction / length;
+        // Rays starting inside march towards the exit instead.
+        let side = (self.sdf)(local_ray.origin).signum();
+        let mut s = 0.0;
+        for _ in 0..self.max_steps {
+            let distance = side * (self.sdf)(local_ray.origin + direction * s);
+            if distance < self.hit_epsilon {
+                return vec![Intersection::new(s / length, self)];
+            }
+            s += distance;
+            if s > self.max_distance {
+                break;
+            }
+        }
+        vec![]
+    }
+}
+
+pub fn sdf_sphere(radius: Float) -> impl Fn(Tuple4) -> Float + Send + Sync {
+    move |p| vector(p.x, p.y, p.z).magnitude() - radius
+}
+
+// A box with the given half extents whose edges are rounded by `radius`.
+pub fn sdf_rounded_box(
+    half_extents: Tuple4,
+    radius: Float,
+) -> impl Fn(Tuple4) -> Float + Send + Sync {
+    move |p| {
+        let q = vector(
+            p.x.abs() - half_extents.x + radius,
+            p.y.abs() - half_extents.y + radius,
+            p.z.abs() - half_extents.z + radius,
+        );
+        let outside = vector(q.x.max(0.0), q.y.max(0.0), q.z.max(0.0)).magnitude();
+        let inside = q.x.max(q.y).max(q.z).min(0.0);
+        outside + inside - radius
+    }
+}
+
+// Two spheres blended together with a polynomial smooth minimum.
+pub fn sdf_metaballs(
+    a: Tuple4,
+    b: Tuple4,
+    radius: Float,
+    smoothness: Float,
+) -> impl Fn(Tuple4) -> Float + Send + Sync {
+    move |p| {
+        let da = (p - a).magnitude() - radius;
+        let db = (p - b).magnitude() - radius;
+        let h = (0.5 + 0.5 * (db - da) / smoothness).clamp(0.0, 1.0);
+        db + (da - db) * h - smoothness * h * (1.0 - h)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        rays::ray,
+        spheres::Sphere,
+        transformations::translation,
+        tuples::{check_tuple, point},
+    };
+
+    #[test]
+    fn sphere_tracing_matches_the_analytic_sphere() {
+        let sdf = SdfShape::new(sdf_sphere(1.0));
+        let analytic = Sphere::new();
+        for y in [0.0, 0.3, 0.6, 0.9] {
+            let r = ray(point(0.0, y, -5.0), vector(0.0, 0.0, 1.0));
+            let traced = sdf.intersect(r);
+            let exact = analytic.intersect(r);
+            assert_eq!(traced.len(), 1);
+            assert!((traced[0].t - exact[0].t).abs() < EPSILON);
+            let p = r.position(traced[0].t);
+            assert!(sdf.normal_at(&p).dot(analytic.normal_at(&p)) > 0.99999);
+        }
+    }
+
+    #[test]
+    fn sdf_shapes_follow_their_transform() {
+        let mut s = SdfShape::new(sdf_sphere(1.0));
+        s.set_transform(translation(0.0, 0.0, 2.0)).unwrap();
+        let r = ray(point(0.0, 0.0, -5.0), vector(0.0, 0.0, 1.0));
+        let xs = s.intersect(r);
+        assert!((xs[0].t - 6.0).abs() < EPSILON);
+    }
+
+    #[test]
+    fn a_ray_starting_inside_finds_the_exit() {
+        let s = SdfShape::new(sdf_sphere(1.0));
+        let r = ray(point(0.0, 0.0, 0.0), vector(0.0, 0.0, 1.0));
+        let xs = s.intersect(r);
+        assert!((xs[0].t - 1.0).abs() < EPSILON);
+    }
+
+    #[test]
+    fn a_rounded_box_is_hit_on_its_face() {
+        let s = SdfShape::new(sdf_rounded_box(vector(1.0, 1.0, 1.0), 0.2));
+        let r = ray(point(0.0, 0.0, -5.0), vector(0.0, 0.0, 1.0));
+        let xs = s.intersect(r);
+        assert!((xs[0].t - 4.0).abs() < EPSILON);
+        check_tuple(s.normal_at(&r.position(xs[0].t)), vector(0.0, 0.0, -1.0));
+    }
+
+    #[test]
+    fn running_out_of_steps_is_a_miss() {
+        let s = SdfShape::new(sdf_sphere(1.0)).with_max_steps(3);
+        let r = ray(point(0.0, 1.001, -5.0), vector(0.0, 0.0, 1.0));
+        assert!(s.intersect(r).is_empty());
+    }
+
+    #[test]
+    fn rays_past_the_max_distance_miss() {
+        let s = SdfShape::new(sdf_sphere(1.0)).with_max_distance(3.0);
+        let r = ray(point(0.0, 0.0, -5.0), vector(0.0, 0.0, 1.0));
+        assert!(s.intersect(r).is_empty());
+    }
+}
@@ -1,16 +1,37 @@
-use std::cell::RefCell;
+pub mod prefab;
 
+use std::{
+    collections::HashSet,
+    sync::{
+        Mutex,
+        atomic::{AtomicU64, Ordering},
+    },
+};
+
+use crate::bounds::BoundingSphere;
+use crate::floats::Float;
 use crate::intersections::Intersection;
 use crate::materials::Material;
 use crate::matrices::Matrix4;
 use crate::rays::Ray;
-use crate::tuples::{Tuple4, point};
+use crate::tuples::{Tuple4, point, vector};
+use crate::warnings::{self, Warning};
+
+static NEXT_SHAPE_ID: AtomicU64 = AtomicU64::new(0);
+
+// A new id for each shape made. They count up, so a shape made later has a
+// larger id, whatever its type.
+pub fn next_shape_id() -> u64 {
+    NEXT_SHAPE_ID.fetch_add(1, Ordering::Relaxed)
+}
 
 #[derive(Debug)]
 pub struct TestShape {
+    pub id: u64,
     pub transform: Matrix4,
     pub material: Material,
-    pub saved_ray: RefCell<Option<Ray>>,
+    pub tags: HashSet<String>,
+    pub saved_ray: Mutex<Option<Ray>>,
 }
 
 impl Default for TestShape {
@@ -22,9 +43,24 @@ impl Default for TestShape {
 impl TestShape {
     pub fn new() -> Self {
         TestShape {
+            id: next_shape_id(),
             transform: Matrix4::identity(),
             material: Material::new(),
-            saved_ray: RefCell::new(None),
+            tags: HashSet::new(),
+            saved_ray: Mutex::new(None),
+        }
+    }
+}
+
+// A Mutex isn't Clone, so copy out the ray it holds.
+impl Clone for TestShape {
+    fn clone(&self) -> Self {
+        TestShape {
+            id: self.id,
+            transform: self.transform,
+            material: self.material.clone(),
+            tags: self.tags.clone(),
+            saved_ray: Mutex::new(*self.saved_ray.lock().unwrap()),
         }
     }
 }
@@ -38,6 +74,65 @@ pub trait Intersectable<T: ShapeFunctions> {
         self.local_intersect(local_ray)
     }
     fn local_intersect<'a>(&'a self, local_ray: Ray) -> Vec<Intersection<'a>>;
+
+    // Intersects every ray in `rays`, pushing each hit with the index of the
+    // ray that made it. The transform is inverted once for the whole batch.
+    fn intersect_batch<'a>(&'a self, rays: &[Ray], out: &mut Vec<(usize, Intersection<'a>)>)
+    where
+        Self: ShapeFunctions,
+    {
+        let inverse = self.transform_inverse();
+        let local: Vec<Ray> = rays.iter().map(|r| r.transform(inverse)).collect();
+        self.local_intersect_batch(&local, out);
+    }
+    // Shapes can override this to share work across the batch.
+    fn local_intersect_batch<'a>(
+        &'a self,
+        local_rays: &[Ray],
+        out: &mut Vec<(usize, Intersection<'a>)>,
+    ) {
+        for (i, r) in local_rays.iter().enumerate() {
+            out.extend(self.local_intersect(*r).into_iter().map(|x| (i, x)));
+        }
+    }
+
+    // Only intersections with t < t_max are returned, which lets shapes skip
+    // work for hits that could never be chosen.
+    fn intersect_bounded<'a>(&'a self, ray: Ray, t_max: Float) -> Vec<Intersection<'a>>
+    where
+        Self: ShapeFunctions,
+    {
+        let local_ray = ray.transform(self.transform_inverse());
+        self.local_intersect_bounded(local_ray, t_max)
+    }
+    fn local_intersect_bounded<'a>(
+        &'a self,
+        local_ray: Ray,
+        t_max: Float,
+    ) -> Vec<Intersection<'a>> {
+        let mut xs = self.local_intersect(local_ray);
+        xs.retain(|i| {
+            if i.t.is_nan() {
+                warnings::note(Warning::NanIntersection);
+            }
+            i.t < t_max
+        });
+        xs
+    }
+
+    // Whether the ray meets the shape anywhere in 0 <= t < t_max. Shadow rays
+    // need nothing more, so shapes can answer without building intersections.
+    fn hits_before(&self, ray: Ray, t_max: Float) -> bool
+    where
+        Self: ShapeFunctions,
+    {
+        self.intersects_before(ray.transform(self.transform_inverse()), t_max)
+    }
+    fn intersects_before(&self, local_ray: Ray, t_max: Float) -> bool {
+        self.local_intersect_bounded(local_ray, t_max)
+            .iter()
+            .any(|i| i.t >= 0.0)
+    }
 }
 
 pub trait ShapeFunctions {
@@ -51,11 +146,76 @@ pub trait ShapeFunctions {
     }
 
     fn local_normal_at(&self, local_point: &Tuple4) -> Tuple4;
+
+    // A direction along the surface at `local_point` that follows the
+    // shape's texture coordinates, for shapes that have them.
+    fn local_tangent_at(&self, _local_point: &Tuple4) -> Option<Tuple4> {
+        None
+    }
+
+    // A world-space unit tangent at `world_point`, square to `world_normal`,
+    // for normal maps. Shapes without texture coordinates get one at a fixed
+    // angle to the world axes, so a flat face gets the same one all over.
+    fn tangent_at(&self, world_point: &Tuple4, world_normal: Tuple4) -> Tuple4 {
+        let along_uv = self
+            .local_tangent_at(&(self.transform_inverse() * *world_point))
+            .map(|t| self.transform() * t);
+        // Square to the y axis rather than Onb's x, so walls keep the
+        // tangent their normal maps were made for.
+        let fixed = || {
+            let helper = if world_normal.y.abs() < 0.9 {
+                vector(0.0, 1.0, 0.0)
+            } else {
+                vector(1.0, 0.0, 0.0)
+            };
+            helper.cross(world_normal)
+        };
+        [along_uv, Some(fixed())]
+            .into_iter()
+            .flatten()
+            .find_map(|t| (t - world_normal * t.dot(world_normal)).try_normalize())
+            .unwrap_or_else(|| vector(1.0, 0.0, 0.0))
+    }
+
     fn material(&self) -> &Material;
+
+    // From next_shape_id when the shape was made, and kept by its clones.
+    // Decides between coincident hits of the same priority.
+    fn id(&self) -> u64;
+
+    // Names a light's `affects` set can pick the shape out by. They belong
+    // to the shape, so shapes sharing a material can be told apart.
+    fn tags(&self) -> &HashSet<String>;
+
+    // The material used for shading. Shapes nested in a group override this to
+    // fall back to the nearest ancestor's material when they have none of their own.
+    // A material set on the shape itself always wins.
+    fn resolved_material(&self) -> &Material {
+        self.material()
+    }
+    fn transform(&self) -> Matrix4;
     fn transform_inverse(&self) -> Matrix4;
+
+    // A world-space sphere the shape lies inside, or None for shapes that
+    // must always be tested, such as infinite planes.
+    fn bounding_sphere(&self) -> Option<BoundingSphere> {
+        None
+    }
 }
 
 impl ShapeFunctions for TestShape {
+    fn id(&self) -> u64 {
+        self.id
+    }
+
+    fn transform(&self) -> Matrix4 {
+        self.transform
+    }
+
+    fn tags(&self) -> &HashSet<String> {
+        &self.tags
+    }
+
     fn transform_inverse(&self) -> Matrix4 {
         self.transform.inverse()
     }
@@ -71,7 +231,7 @@ impl ShapeFunctions for TestShape {
 
 impl Intersectable<TestShape> for TestShape {
     fn local_intersect<'a>(&'a self, local_ray: Ray) -> Vec<Intersection<'a>> {
-        *self.saved_ray.borrow_mut() = Some(local_ray);
+        *self.saved_ray.lock().unwrap() = Some(local_ray);
         vec![]
     }
 }
@@ -152,7 +312,7 @@ mod tests {
         let mut s = test_shape();
         set_transform(&mut s, scaling(2.0, 2.0, 2.0));
         s.intersect(r);
-        let saved_ray = s.saved_ray.borrow().unwrap();
+        let saved_ray = s.saved_ray.lock().unwrap().unwrap();
         check_tuple(saved_ray.origin, point(0.0, 0.0, -2.5));
         check_tuple(saved_ray.direction, vector(0.0, 0.0, 0.5));
     }
@@ -170,7 +330,7 @@ mod tests {
         let mut s = test_shape();
         set_transform(&mut s, translation(5.0, 0.0, 0.0));
         s.intersect(r);
-        let saved_ray = s.saved_ray.borrow().unwrap();
+        let saved_ray = s.saved_ray.lock().unwrap().unwrap();
         assert_eq!(saved_ray.origin, point(-5.0, 0.0, -5.0));
         assert_eq!(saved_ray.direction, vector(0.0, 0.0, 1.0));
     }
@@ -202,4 +362,19 @@ mod tests {
         let n = s.normal_at(&point(0.0, SQRT_2 / 2.0, -SQRT_2 / 2.0));
         check_tuple(n, vector(0.0, 0.97014, -0.24254));
     }
+
+    // A wall facing -z gets a horizontal tangent, and a floor one along z.
+    #[test]
+    fn shapes_without_texture_coordinates_get_tangents_square_to_the_y_axis() {
+        let s = test_shape();
+        let p = point(0.0, 0.0, 0.0);
+        check_tuple(
+            s.tangent_at(&p, vector(0.0, 0.0, -1.0)),
+            vector(-1.0, 0.0, 0.0),
+        );
+        check_tuple(
+            s.tangent_at(&p, vector(0.0, 1.0, 0.0)),
+            vector(0.0, 0.0, 1.0),
+        );
+    }
 }
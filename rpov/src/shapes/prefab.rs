@@ -0,0 +1,87 @@
+//! Ready-made shapes with the materials scenes keep reaching for.
+
+use std::sync::Arc;
+
+use crate::{colors::Color, patterns::checkers_pattern, planes::Plane, spheres::Sphere};
+
+pub use crate::spheres::glass_sphere;
+
+pub fn mirror_sphere() -> Sphere {
+    let mut s = Sphere::new();
+    s.material.color = Color::new(0.0, 0.0, 0.0);
+    s.material.diffuse = 0.0;
+    s.material.ambient = 0.0;
+    s.material.specular = 1.0;
+    s.material.shininess = 300.0;
+    s.material.reflective = 1.0;
+    s
+}
+
+pub fn matte_sphere(color: Color) -> Sphere {
+    let mut s = Sphere::new();
+    s.material.color = color;
+    s.material.specular = 0.0;
+    s
+}
+
+pub fn matte_plane(color: Color) -> Plane {
+    let mut p = Plane::new();
+    p.material.color = color;
+    p.material.specular = 0.0;
+    p
+}
+
+pub fn checkered_floor(a: Color, b: Color) -> Plane {
+    let mut p = Plane::new();
+    p.material.pattern = Some(Arc::new(checkers_pattern(a, b)));
+    p.material.specular = 0.0;
+    p.material.reflective = 0.1;
+    p
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tuples::point;
+
+    #[test]
+    fn mirror_sphere_reflects_everything() {
+        let s = mirror_sphere();
+        assert_eq!(s.material.reflective, 1.0);
+        assert_eq!(s.material.diffuse, 0.0);
+        assert_eq!(s.material.transparency, 0.0);
+    }
+
+    #[test]
+    fn matte_sphere_has_no_highlight() {
+        let s = matte_sphere(Color::new(0.2, 0.4, 0.6));
+        assert_eq!(s.material.color, Color::new(0.2, 0.4, 0.6));
+        assert_eq!(s.material.specular, 0.0);
+        assert_eq!(s.material.reflective, 0.0);
+    }
+
+    #[test]
+    fn matte_plane_has_no_highlight() {
+        let p = matte_plane(Color::new(0.2, 0.4, 0.6));
+        assert_eq!(p.material.color, Color::new(0.2, 0.4, 0.6));
+        assert_eq!(p.material.specular, 0.0);
+        assert_eq!(p.material.reflective, 0.0);
+    }
+
+    #[test]
+    fn checkered_floor_alternates_and_reflects_slightly() {
+        let white = Color::new(1.0, 1.0, 1.0);
+        let black = Color::new(0.0, 0.0, 0.0);
+        let p = checkered_floor(white, black);
+        let pattern = p.material.pattern.as_ref().unwrap();
+        assert!(format!("{pattern:?}").starts_with("CheckersPattern"));
+        assert_eq!(pattern.pattern_at(point(0.5, 0.0, 0.5)), white);
+        assert_eq!(pattern.pattern_at(point(1.5, 0.0, 0.5)), black);
+        assert_eq!(p.material.reflective, 0.1);
+    }
+
+    #[test]
+    fn glass_sphere_is_available_here_too() {
+        assert_eq!(glass_sphere().material.refractive_index, 1.5);
+    }
+}
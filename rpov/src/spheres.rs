@@ -1,21 +1,26 @@
+use crate::bounds::BoundingSphere;
+use crate::floats::{EPSILON, Float, Wide, narrow};
 use crate::intersections::Intersection;
 use crate::materials::Material;
-use crate::matrices::Matrix4;
+use crate::matrices::{Matrix4, TransformError, validate_transform};
 use crate::rays::Ray;
 use crate::shapes::Intersectable;
-use crate::shapes::ShapeFunctions;
+use crate::shapes::{ShapeFunctions, next_shape_id};
+use crate::transformations::{scaling, translation};
 use crate::tuples::Tuple4;
-use crate::tuples::point;
+use crate::tuples::{point, vector};
+use std::collections::HashSet;
 use std::fmt;
-use std::sync::atomic::{AtomicU64, Ordering};
-
-static SPHERE_ID_COUNTER: AtomicU64 = AtomicU64::new(0);
+use std::sync::Arc;
 
 #[derive(Debug, Clone)]
 pub struct Sphere {
     pub id: u64,
-    pub transform: Matrix4,
+    transform: Matrix4,
     pub material: Material,
+    pub tags: HashSet<String>,
+    // Used instead of `material` when set.
+    pub shared_material: Option<Arc<Material>>,
 }
 
 impl PartialEq for Sphere {
@@ -26,7 +31,13 @@ impl PartialEq for Sphere {
 
 impl fmt::Display for Sphere {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "Sphere(id={}, transform={:?})", self.id, self.transform)
+        write!(
+            f,
+            "Sphere(id={}, {}; {})",
+            self.id,
+            self.transform.describe(),
+            self.material()
+        )
     }
 }
 
@@ -39,58 +50,209 @@ impl Default for Sphere {
 impl Sphere {
     pub fn new() -> Self {
         Self {
-            id: SPHERE_ID_COUNTER.fetch_add(1, Ordering::SeqCst),
+            id: next_shape_id(),
             transform: Matrix4::identity(),
             material: Material::new(),
+            tags: HashSet::new(),
+            shared_material: None,
         }
     }
 
-    pub fn with_transform(transform: Matrix4) -> Self {
-        Self {
-            id: SPHERE_ID_COUNTER.fetch_add(1, Ordering::SeqCst),
-            transform,
-            material: Material::new(),
+    // Fails when the transform can't be inverted.
+    pub fn with_transform(transform: Matrix4) -> Result<Self, TransformError> {
+        let mut sphere = Self::new();
+        sphere.set_transform(transform)?;
+        Ok(sphere)
+    }
+
+    // A sphere of the given radius centered on `center`. Fails for a zero
+    // radius.
+    pub fn at(center: Tuple4, radius: Float) -> Result<Self, TransformError> {
+        Self::ellipsoid(center, radius, radius, radius)
+    }
+
+    // A sphere stretched to the given radius along each axis.
+    pub fn ellipsoid(
+        center: Tuple4,
+        rx: Float,
+        ry: Float,
+        rz: Float,
+    ) -> Result<Self, TransformError> {
+        Self::with_transform(translation(center.x, center.y, center.z) * scaling(rx, ry, rz))
+    }
+
+    pub fn center(&self) -> Tuple4 {
+        self.transform * point(0.0, 0.0, 0.0)
+    }
+
+    // The radius, when the transform scales every axis alike. None for
+    // ellipsoids and sheared spheres, which have no single radius.
+    pub fn radius(&self) -> Option<Float> {
+        if !self.transform.has_uniform_scale() {
+            return None;
         }
+        let (_, _, scale) = self.transform.decompose()?;
+        Some(scale.y.abs())
+    }
+
+    pub fn transform(&self) -> Matrix4 {
+        self.transform
+    }
+
+    // Leaves the transform as it was when the new one can't be inverted.
+    pub fn set_transform(&mut self, transform: Matrix4) -> Result<(), TransformError> {
+        validate_transform(&format!("Sphere(id={})", self.id), transform)?;
+        self.transform = transform;
+        Ok(())
     }
 }
 impl ShapeFunctions for Sphere {
+    fn id(&self) -> u64 {
+        self.id
+    }
+
+    fn tags(&self) -> &HashSet<String> {
+        &self.tags
+    }
+
+    fn transform(&self) -> Matrix4 {
+        self.transform
+    }
+
     fn transform_inverse(&self) -> Matrix4 {
         self.transform.inverse()
     }
 
     fn material(&self) -> &Material {
-        &self.material
+        self.shared_material.as_deref().unwrap_or(&self.material)
     }
 
     fn local_normal_at(&self, local_point: &Tuple4) -> Tuple4 {
         *local_point - point(0.0, 0.0, 0.0)
     }
+
+    // Around the y axis, like lines of latitude. None at the poles.
+    fn local_tangent_at(&self, local_point: &Tuple4) -> Option<Tuple4> {
+        vector(local_point.z, 0.0, -local_point.x).try_normalize()
+    }
+
+    fn bounding_sphere(&self) -> Option<BoundingSphere> {
+        Some(BoundingSphere::around_origin(self.transform, 1.0))
+    }
 }
 
 impl Intersectable<Sphere> for Sphere {
     fn local_intersect<'a>(&'a self, local_ray: Ray) -> Vec<Intersection<'a>> {
-        // let local_ray = r.transform(self.transform.inverse());
-        let sphere_to_ray = local_ray.origin - point(0.0, 0.0, 0.0);
-
-        let a = local_ray.direction.dot(local_ray.direction);
-        let b = 2.0 * local_ray.direction.dot(sphere_to_ray);
-        let c = sphere_to_ray.dot(sphere_to_ray) - 1.0;
-
-        let discriminant = b.powi(2) - 4.0 * a * c;
+        self.local_intersect_bounded(local_ray, Float::INFINITY)
+    }
 
-        if discriminant < 0.0 {
-            return vec![];
+    fn local_intersect_bounded<'a>(
+        &'a self,
+        local_ray: Ray,
+        t_max: Float,
+    ) -> Vec<Intersection<'a>> {
+        let (a, b, c) = coefficients(local_ray);
+        match roots(a, b, c) {
+            None => vec![],
+            Some((near, _)) if near >= t_max => vec![],
+            Some((near, far)) if far >= t_max => vec![Intersection::new(near, self)],
+            Some((near, far)) => vec![Intersection::new(near, self), Intersection::new(far, self)],
         }
+    }
 
-        let t1 = (-b - discriminant.sqrt()) / (2.0 * a);
-        let t2 = (-b + discriminant.sqrt()) / (2.0 * a);
+    fn intersects_before(&self, local_ray: Ray, t_max: Float) -> bool {
+        let (a, b, c) = coefficients(local_ray);
+        // Starting outside and heading away, both roots lie behind the ray.
+        if c > 0.0 && b > 0.0 {
+            return false;
+        }
+        match roots(a, b, c) {
+            Some((near, far)) => (near >= 0.0 && near < t_max) || (far >= 0.0 && far < t_max),
+            None => false,
+        }
+    }
 
-        if t1 > t2 {
-            return vec![Intersection::new(t2, self), Intersection::new(t1, self)];
+    // Works out the quadratic's coefficients a component array at a time
+    // rather than ray by ray. The roots are the same ones intersect finds.
+    fn local_intersect_batch<'a>(
+        &'a self,
+        local: &[Ray],
+        out: &mut Vec<(usize, Intersection<'a>)>,
+    ) {
+        let component = |f: fn(&Ray) -> Float| -> Vec<Wide> {
+            local.iter().map(|r| Wide::from(f(r))).collect()
+        };
+        let (ox, oy, oz) = (
+            component(|r| r.origin.x),
+            component(|r| r.origin.y),
+            component(|r| r.origin.z),
+        );
+        let (dx, dy, dz) = (
+            component(|r| r.direction.x),
+            component(|r| r.direction.y),
+            component(|r| r.direction.z),
+        );
+        let n = local.len();
+        let a: Vec<Wide> = (0..n)
+            .map(|i| dx[i] * dx[i] + dy[i] * dy[i] + dz[i] * dz[i])
+            .collect();
+        let b: Vec<Wide> = (0..n)
+            .map(|i| 2.0 * (dx[i] * ox[i] + dy[i] * oy[i] + dz[i] * oz[i]))
+            .collect();
+        let c: Vec<Wide> = (0..n)
+            .map(|i| (ox[i] * ox[i] + oy[i] * oy[i] + oz[i] * oz[i]) - 1.0)
+            .collect();
+        for i in 0..n {
+            if let Some((near, far)) = roots(a[i], b[i], c[i]) {
+                out.push((i, Intersection::new(near, self)));
+                out.push((i, Intersection::new(far, self)));
+            }
         }
+    }
+}
+
+// a, b and c of the quadratic in t for where the ray meets the unit sphere.
+fn coefficients(local_ray: Ray) -> (Wide, Wide, Wide) {
+    let wide = |t: Tuple4| [t.x, t.y, t.z].map(Wide::from);
+    let dot = |u: [Wide; 3], v: [Wide; 3]| u[0] * v[0] + u[1] * v[1] + u[2] * v[2];
+    let sphere_to_ray = wide(local_ray.origin);
+    let direction = wide(local_ray.direction);
+
+    let a = dot(direction, direction);
+    let b = 2.0 * dot(direction, sphere_to_ray);
+    let c = dot(sphere_to_ray, sphere_to_ray) - 1.0;
+    (a, b, c)
+}
 
-        vec![Intersection::new(t1, self), Intersection::new(t2, self)]
+// The t values, nearest first, where a ray with these quadratic coefficients
+// meets the unit sphere.
+fn roots(a: Wide, b: Wide, c: Wide) -> Option<(Float, Float)> {
+    let mut discriminant = b.powi(2) - 4.0 * a * c;
+
+    // Near zero the sign of the discriminant is float noise; treat it as a
+    // tangent hit so silhouettes don't flicker between hit and miss. The band
+    // is a fixed width around the silhouette: scaling it by b² instead would
+    // swallow every hit on a small sphere far away, turning both roots into
+    // one.
+    let epsilon = Wide::from(EPSILON);
+    if discriminant.abs() < epsilon * epsilon * 4.0 * a {
+        discriminant = 0.0;
     }
+    if discriminant < 0.0 {
+        return None;
+    }
+
+    // Avoid subtracting two nearly equal numbers when b² dominates 4ac, which
+    // loses most of the precision of the smaller root on huge spheres.
+    let (t1, t2) = if discriminant == 0.0 {
+        let t = -b / (2.0 * a);
+        (t, t)
+    } else {
+        let q = -0.5 * (b + b.signum() * discriminant.sqrt());
+        (q / a, c / q)
+    };
+    let (near, far) = if t1 > t2 { (t2, t1) } else { (t1, t2) };
+    Some((narrow(near), narrow(far)))
 }
 
 pub fn glass_sphere() -> Sphere {
@@ -204,7 +366,7 @@ mod tests {
     #[test]
     fn a_sphere_default_transformation() {
         let s = Sphere::new();
-        assert_eq!(s.transform, Matrix4::identity());
+        assert_eq!(s.transform(), Matrix4::identity());
     }
 
     // Scenario: Changing a sphere's transformation
@@ -215,8 +377,30 @@ mod tests {
     #[test]
     fn changing_a_sphere_transformation() {
         let t = translation(2.0, 3.0, 4.0);
-        let s = Sphere::with_transform(t);
-        assert_eq!(s.transform, t);
+        let s = Sphere::with_transform(t).unwrap();
+        assert_eq!(s.transform(), t);
+    }
+
+    #[test]
+    fn a_flattened_transform_is_rejected_with_the_sphere_id() {
+        let mut s = Sphere::new();
+        let err = s.set_transform(scaling(1.0, 0.0, 1.0)).unwrap_err();
+        assert!(err.to_string().contains(&format!("Sphere(id={})", s.id)));
+        assert_eq!(s.transform(), Matrix4::identity());
+    }
+
+    #[test]
+    fn every_constructor_rejects_a_flattened_transform() {
+        assert!(Sphere::with_transform(scaling(1.0, 0.0, 1.0)).is_err());
+        assert!(Sphere::at(point(1.0, 2.0, 3.0), 0.0).is_err());
+        assert!(Sphere::ellipsoid(point(0.0, 0.0, 0.0), 1.0, 2.0, 0.0).is_err());
+    }
+
+    #[test]
+    fn a_small_but_invertible_scale_is_accepted() {
+        let mut s = Sphere::new();
+        s.set_transform(scaling(0.01, 0.01, 0.01)).unwrap();
+        assert_eq!(s.transform(), scaling(0.01, 0.01, 0.01));
     }
 
     // Scenario: Intersecting a scaled sphere with a ray
@@ -230,7 +414,7 @@ mod tests {
     #[test]
     fn intersecting_a_scaled_sphere_with_a_ray() {
         let r = ray(point(0.0, 0.0, -5.0), vector(0.0, 0.0, 1.0));
-        let s = Sphere::with_transform(scaling(2.0, 2.0, 2.0));
+        let s = Sphere::with_transform(scaling(2.0, 2.0, 2.0)).unwrap();
         let xs = s.intersect(r);
         assert_eq!(xs.len(), 2);
         assert_eq!(xs[0].t, 3.0);
@@ -246,7 +430,7 @@ mod tests {
     #[test]
     fn intersecting_a_translated_sphere_with_a_ray() {
         let r = ray(point(0.0, 0.0, -5.0), vector(0.0, 0.0, 1.0));
-        let s = Sphere::with_transform(translation(5.0, 0.0, 0.0));
+        let s = Sphere::with_transform(translation(5.0, 0.0, 0.0)).unwrap();
         let xs = s.intersect(r);
         assert_eq!(xs.len(), 0);
     }
@@ -259,7 +443,7 @@ mod tests {
     #[test]
     fn a_helper_for_producing_a_sphere_with_a_glassy_material() {
         let s = glass_sphere();
-        assert_eq!(s.transform, Matrix4::identity());
+        assert_eq!(s.transform(), Matrix4::identity());
         assert_eq!(s.material.transparency, 1.0);
         assert_eq!(s.material.refractive_index, 1.5);
     }
@@ -320,4 +504,209 @@ mod tests {
         let reflectance = crate::lighting::schlick(&comps);
         assert!((reflectance - 0.48873).abs() < crate::floats::EPSILON);
     }
+
+    #[test]
+    fn a_bounded_intersection_skips_roots_beyond_t_max() {
+        let r = ray(point(0.0, 0.0, -5.0), vector(0.0, 0.0, 1.0));
+        let s = Sphere::with_transform(scaling(2.0, 2.0, 2.0)).unwrap();
+        assert_eq!(s.intersect_bounded(r, 10.0).len(), 2);
+        let xs = s.intersect_bounded(r, 5.0);
+        assert_eq!(xs.len(), 1);
+        assert_eq!(xs[0].t, 3.0);
+        assert!(s.intersect_bounded(r, 3.0).is_empty());
+    }
+
+    #[test]
+    fn sweeping_across_the_limb_changes_from_hit_to_miss_once() {
+        let s = Sphere::with_transform(scaling(3.0, 3.0, 3.0)).unwrap();
+        let hits: Vec<bool> = (0..2000)
+            .map(|i| {
+                let y = 2.99 + i as Float * 1.0e-5;
+                let r = ray(point(0.0, y, -10.0), vector(0.0, 0.0, 1.0));
+                !s.intersect(r).is_empty()
+            })
+            .collect();
+        let transitions = hits.windows(2).filter(|w| w[0] != w[1]).count();
+        assert!(hits[0]);
+        assert_eq!(transitions, 1);
+    }
+
+    #[test]
+    fn a_nearly_tangent_ray_hits_twice_at_the_same_t() {
+        let r = ray(point(0.0, 1.0 + 1.0e-6, -5.0), vector(0.0, 0.0, 1.0));
+        let s = Sphere::new();
+        let xs = s.intersect(r);
+        assert_eq!(xs.len(), 2);
+        assert_eq!(xs[0].t, xs[1].t);
+    }
+
+    #[test]
+    fn a_small_distant_sphere_is_hit_on_both_sides() {
+        let s =
+            Sphere::with_transform(translation(0.0, 0.0, 100.0) * scaling(0.1, 0.1, 0.1)).unwrap();
+        let r = ray(point(0.0, 0.0, 0.0), vector(0.0, 0.0, 1.0));
+        let xs = s.intersect(r);
+        assert_eq!(xs.len(), 2);
+        assert!((xs[0].t - 99.9).abs() < 1.0e-2, "{}", xs[0].t);
+        assert!((xs[1].t - 100.1).abs() < 1.0e-2, "{}", xs[1].t);
+    }
+
+    #[test]
+    fn intersecting_a_giant_distant_sphere_keeps_both_roots_precise() {
+        let s = Sphere::with_transform(translation(0.0, 0.0, 2.0e4) * scaling(1.0e4, 1.0e4, 1.0e4))
+            .unwrap();
+        let r = ray(point(0.0, 0.0, 0.0), vector(0.0, 0.0, 1.0));
+        let xs = s.intersect(r);
+        assert_eq!(xs.len(), 2);
+        // f32 cannot resolve 1e-3 at 3e4, so only f64 gets the tight bound.
+        let tolerance = if cfg!(feature = "f64") {
+            1.0e-3
+        } else {
+            1.0e-2
+        };
+        assert!((xs[0].t - 1.0e4).abs() < tolerance, "{}", xs[0].t);
+        assert!((xs[1].t - 3.0e4).abs() < tolerance, "{}", xs[1].t);
+    }
+
+    #[test]
+    fn the_near_root_is_precise_for_a_ray_starting_just_above_the_surface() {
+        // o·o - 1 is exact here, so any error comes from the solver itself.
+        let h = 1.0 / 256.0;
+        let r = ray(point(0.0, 1.0 + h, 0.0), vector(0.6, -0.8, 0.0));
+        let s = Sphere::new();
+        let xs = s.local_intersect(r);
+        let h64 = f64::from(h);
+        let (a, b, c) = (1.0, -1.6 * (1.0 + h64), 2.0 * h64 + h64 * h64);
+        let expected = c / (-0.5 * (b - (b * b - 4.0 * a * c).sqrt()));
+        let relative_error = (f64::from(xs[0].t) - expected).abs() / expected;
+        assert!(relative_error < 5.0e-7, "{relative_error}");
+    }
+
+    #[test]
+    fn a_giant_sphere_floor_is_lit_evenly() {
+        use crate::{
+            camera::Camera,
+            colors::Color,
+            lighting::point_light,
+            transformations::view_transform,
+            world::{WorldBuilder, render},
+        };
+        let mut floor =
+            Sphere::with_transform(translation(0.0, -1.0e4, 0.0) * scaling(1.0e4, 1.0e4, 1.0e4))
+                .unwrap();
+        floor.material.specular = 0.0;
+        let mut w = WorldBuilder::with_light(point_light(
+            point(0.0, 100.0, 0.0),
+            Color::new(1.0, 1.0, 1.0),
+        ));
+        w.objects.push(floor);
+        let mut c = Camera::new(20, 20, crate::floats::PI / 3.0);
+        c.set_transform(view_transform(
+            point(0.0, 1.0, -3.0),
+            point(0.0, 0.0, 3.0),
+            vector(0.0, 1.0, 0.0),
+        ));
+        let image = render(c, w);
+        // Self-shadowing rings would leave pixels at ambient-only brightness.
+        for y in 10..20 {
+            for x in 0..20 {
+                assert!(image.pixel_at(x, y).red > 0.5, "acne at ({x}, {y})");
+            }
+        }
+    }
+
+    #[cfg(any(feature = "precise-intersections", feature = "f64"))]
+    #[test]
+    fn hits_on_a_distant_sphere_stay_accurate() {
+        let s = Sphere::with_transform(translation(5000.0, 0.0, 0.0)).unwrap();
+        let r = ray(point(0.0, 0.0, 0.0), vector(1.0, 0.0, 0.0));
+        let xs = s.intersect(r);
+        assert_eq!(xs.len(), 2);
+        assert!((xs[0].t - 4999.0).abs() < 1e-3, "{}", xs[0].t);
+        assert!((xs[1].t - 5001.0).abs() < 1e-3, "{}", xs[1].t);
+    }
+
+    #[test]
+    fn a_batch_finds_the_same_hits_as_intersecting_ray_by_ray() {
+        let s =
+            Sphere::with_transform(translation(0.5, 0.0, 3.0) * scaling(2.0, 1.0, 1.5)).unwrap();
+        let rays: Vec<_> = (0..50)
+            .map(|i| {
+                let y = i as Float / 10.0 - 2.5;
+                ray(point(0.3, y, -5.0), vector(0.05, y / 10.0, 1.0).normalize())
+            })
+            .collect();
+        let mut batch = vec![];
+        s.intersect_batch(&rays, &mut batch);
+        let one_by_one: Vec<(usize, Float)> = rays
+            .iter()
+            .enumerate()
+            .flat_map(|(i, r)| s.intersect(*r).into_iter().map(move |x| (i, x.t)))
+            .collect();
+        assert!(!one_by_one.is_empty() && one_by_one.len() < 100);
+        assert_eq!(
+            batch.iter().map(|(i, x)| (*i, x.t)).collect::<Vec<_>>(),
+            one_by_one
+        );
+    }
+
+    #[test]
+    fn a_sphere_at_a_point_intersects_like_the_composed_transform() {
+        let a = Sphere::at(point(1.0, 2.0, 3.0), 2.0).unwrap();
+        let b =
+            Sphere::with_transform(translation(1.0, 2.0, 3.0) * scaling(2.0, 2.0, 2.0)).unwrap();
+        for r in [
+            ray(point(1.0, 2.0, -5.0), vector(0.0, 0.0, 1.0)),
+            ray(point(0.0, 0.0, 0.0), vector(1.0, 2.0, 3.0).normalize()),
+            ray(point(-4.0, 3.0, 3.0), vector(1.0, 0.0, 0.0)),
+        ] {
+            let ts = |s: &Sphere| s.intersect(r).iter().map(|i| i.t).collect::<Vec<_>>();
+            assert_eq!(ts(&a), ts(&b));
+            assert_eq!(ts(&a).len(), 2);
+        }
+    }
+
+    #[test]
+    fn center_and_radius_give_back_what_the_sphere_was_made_with() {
+        let s = Sphere::at(point(1.0, 2.0, 3.0), 2.0).unwrap();
+        crate::tuples::check_tuple(s.center(), point(1.0, 2.0, 3.0));
+        check_floats!(s.radius().unwrap(), 2.0);
+
+        let e = Sphere::ellipsoid(point(-1.0, 0.5, 0.0), 1.0, 2.0, 3.0).unwrap();
+        crate::tuples::check_tuple(e.center(), point(-1.0, 0.5, 0.0));
+        assert_eq!(e.radius(), None);
+
+        let sheared = Sphere::with_transform(crate::transformations::shearing(
+            1.0, 0.0, 0.0, 0.0, 0.0, 0.0,
+        ))
+        .unwrap();
+        assert_eq!(sheared.radius(), None);
+    }
+
+    #[test]
+    fn a_sphere_is_hit_before_t_max_only_within_the_range() {
+        let s = Sphere::new();
+        let towards = ray(point(0.0, 0.0, -5.0), vector(0.0, 0.0, 1.0));
+        assert!(s.intersects_before(towards, 4.5));
+        assert!(!s.intersects_before(towards, 4.0));
+        let inside = ray(point(0.0, 0.0, 0.0), vector(0.0, 0.0, 1.0));
+        assert!(s.intersects_before(inside, 1.5));
+        assert!(!s.intersects_before(inside, 0.5));
+        let away = ray(point(0.0, 0.0, 5.0), vector(0.0, 0.0, 1.0));
+        assert!(!s.intersects_before(away, Float::INFINITY));
+        let miss = ray(point(0.0, 2.0, -5.0), vector(0.0, 0.0, 1.0));
+        assert!(!s.intersects_before(miss, Float::INFINITY));
+    }
+
+    #[test]
+    fn sphere_tangents_run_around_the_y_axis() {
+        let s = Sphere::with_transform(scaling(2.0, 2.0, 2.0)).unwrap();
+        let p = point(0.0, 0.0, -2.0);
+        assert_eq!(s.tangent_at(&p, s.normal_at(&p)), vector(-1.0, 0.0, 0.0));
+        // The poles have no eastward direction, so any square one will do.
+        let pole = point(0.0, 2.0, 0.0);
+        let t = s.tangent_at(&pole, s.normal_at(&pole));
+        check_floats!(t.dot(vector(0.0, 1.0, 0.0)), 0.0);
+        check_floats!(t.magnitude(), 1.0);
+    }
 }
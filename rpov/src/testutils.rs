@@ -0,0 +1,200 @@
+// Helpers for property-testing shapes: proptest strategies for shapes and rays
+// with well-behaved transforms, and assertions for the invariants every
+// shape's intersections should keep. Built for this crate's tests and, with
+// the `testutils` feature, for anyone writing shapes of their own.
+use proptest::prelude::*;
+
+use crate::{
+    bounds::BoundingBox,
+    floats::Float,
+    intersections::Intersection,
+    matrices::Matrix4,
+    planes::Plane,
+    rays::Ray,
+    shapes::ShapeFunctions,
+    spheres::Sphere,
+    transformations::{rotation_x, rotation_y, rotation_z, scaling, translation},
+    tuples::{Tuple4, point, vector},
+};
+
+// Shapes whose surface is the zero set of a function in object space.
+pub trait ImplicitSurface: ShapeFunctions {
+    // Zero on the surface. Away from it, roughly the distance in object
+    // space, though only the sign and closeness to zero matter.
+    fn surface_equation(&self, local_point: &Tuple4) -> Float;
+}
+
+impl ImplicitSurface for Sphere {
+    fn surface_equation(&self, local_point: &Tuple4) -> Float {
+        (*local_point - point(0.0, 0.0, 0.0)).magnitude() - 1.0
+    }
+}
+
+impl ImplicitSurface for Plane {
+    fn surface_equation(&self, local_point: &Tuple4) -> Float {
+        local_point.y
+    }
+}
+
+pub fn assert_intersections_sorted(xs: &[Intersection]) {
+    for pair in xs.windows(2) {
+        assert!(
+            pair[0].t <= pair[1].t,
+            "intersections out of order: t={} before t={}",
+            pair[0].t,
+            pair[1].t
+        );
+    }
+}
+
+// Checks the normal at each of `r`'s intersections.
+pub fn assert_normals_unit_length(xs: &[Intersection], r: Ray) {
+    for i in xs {
+        let n = i.normal_at(&r.position(i.t));
+        assert!(
+            (n.magnitude() - 1.0).abs() < 1e-3,
+            "normal {n:?} at t={} has length {}",
+            i.t,
+            n.magnitude()
+        );
+    }
+}
+
+// `tolerance` is in object space, where the surface equation is evaluated.
+pub fn assert_hit_on_surface<S: ImplicitSurface>(shape: &S, r: Ray, t: Float, tolerance: Float) {
+    let local_point = shape.transform_inverse() * r.position(t);
+    let value = shape.surface_equation(&local_point);
+    assert!(
+        value.abs() <= tolerance,
+        "hit at t={t} is {value} off the surface (object space {local_point:?})"
+    );
+}
+
+// Translation, rotation and scaling kept within a range where f32 stays
+// accurate: no axis is squashed below a quarter or stretched past four.
+pub fn arbitrary_transform() -> impl Strategy<Value = Matrix4> {
+    let offset = -5.0 as Float..5.0;
+    let angle = -3.2 as Float..3.2;
+    let scale = 0.25 as Float..4.0;
+    (
+        (offset.clone(), offset.clone(), offset),
+        (angle.clone(), angle.clone(), angle),
+        (scale.clone(), scale.clone(), scale),
+    )
+        .prop_map(|((x, y, z), (rx, ry, rz), (sx, sy, sz))| {
+            translation(x, y, z)
+                * rotation_x(rx)
+                * rotation_y(ry)
+                * rotation_z(rz)
+                * scaling(sx, sy, sz)
+        })
+}
+
+impl Sphere {
+    pub fn arbitrary() -> impl Strategy<Value = Sphere> {
+        arbitrary_transform().prop_map(|transform| {
+            Sphere::with_transform(transform).expect("arbitrary transforms are invertible")
+        })
+    }
+}
+
+impl Plane {
+    pub fn arbitrary() -> impl Strategy<Value = Plane> {
+        arbitrary_transform().prop_map(|transform| {
+            let mut p = Plane::new();
+            p.set_transform(transform)
+                .expect("arbitrary transforms are invertible");
+            p
+        })
+    }
+}
+
+impl Ray {
+    // Unit rays aimed at a point inside `bbox`, starting between 1 and 20
+    // units back from it.
+    pub fn arbitrary_toward(bbox: BoundingBox) -> impl Strategy<Value = Ray> {
+        let (min, max) = (bbox.min, bbox.max);
+        let unit = -1.0 as Float..1.0;
+        (
+            (min.x..=max.x, min.y..=max.y, min.z..=max.z),
+            (unit.clone(), unit.clone(), unit),
+            1.0 as Float..20.0,
+        )
+            .prop_filter_map("no direction", |((x, y, z), (dx, dy, dz), back)| {
+                let target = point(x, y, z);
+                let r = Ray::try_new(target, vector(dx, dy, dz))?;
+                Some(Ray::new(target - r.direction * back, r.direction))
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{floats::EPSILON, shapes::Intersectable};
+
+    fn config() -> ProptestConfig {
+        ProptestConfig::with_cases(300)
+    }
+
+    // The box a unit sphere transformed by arbitrary_transform can reach.
+    fn scene_box() -> BoundingBox {
+        BoundingBox::new(point(-9.0, -9.0, -9.0), point(9.0, 9.0, 9.0))
+    }
+
+    fn ray_toward_sphere() -> impl Strategy<Value = (Sphere, Ray)> {
+        Sphere::arbitrary().prop_flat_map(|s| {
+            let center = s.center();
+            let near = BoundingBox::new(
+                center - vector(0.5, 0.5, 0.5),
+                center + vector(0.5, 0.5, 0.5),
+            );
+            (Just(s), Ray::arbitrary_toward(near))
+        })
+    }
+
+    proptest! {
+        #![proptest_config(config())]
+
+        #[test]
+        fn sphere_hits_lie_on_the_sphere((s, r) in ray_toward_sphere()) {
+            let xs = s.intersect(r);
+            assert_intersections_sorted(&xs);
+            assert_normals_unit_length(&xs, r);
+            for i in &xs {
+                assert_hit_on_surface(&s, r, i.t, 1e-3);
+            }
+        }
+
+        #[test]
+        fn sphere_normals_face_rays_from_outside((s, r) in ray_toward_sphere()) {
+            let local_origin = s.transform_inverse() * r.origin;
+            prop_assume!(s.surface_equation(&local_origin) > EPSILON);
+            let xs = s.intersect(r);
+            if let Some(first) = xs.iter().find(|i| i.t > 0.0) {
+                let n = first.normal_at(&r.position(first.t));
+                prop_assert!(n.dot(r.direction) < 0.0, "{n:?} faces along {:?}", r.direction);
+            }
+        }
+
+        #[test]
+        fn plane_hits_lie_on_the_plane(p in Plane::arbitrary(), r in Ray::arbitrary_toward(scene_box())) {
+            let xs = p.intersect(r);
+            assert_intersections_sorted(&xs);
+            assert_normals_unit_length(&xs, r);
+            for i in &xs {
+                // A ray nearly parallel to the plane magnifies any error in
+                // t, so allow for it along the surface as well as across.
+                assert_hit_on_surface(&p, r, i.t, 1e-3 * (1.0 + i.t.abs()));
+            }
+        }
+
+        #[test]
+        fn transforms_undo_with_their_inverse(m in arbitrary_transform(), r in Ray::arbitrary_toward(scene_box())) {
+            prop_assert!((m * m.inverse()).approx_eq(&Matrix4::identity(), 1e-3));
+            let back = r.transform(m).transform(m.inverse());
+            prop_assert!(back.origin.approx_eq(&r.origin, 1e-2), "{:?} vs {:?}", back.origin, r.origin);
+            prop_assert!(back.direction.approx_eq(&r.direction, 1e-3), "{:?} vs {:?}", back.direction, r.direction);
+        }
+    }
+}
@@ -82,6 +82,80 @@ impl Display for Tuple4 {
     }
 }
 
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseError {
+    pub input: String,
+    pub message: String,
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "cannot parse `{}`: {}", self.input, self.message)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+impl ParseError {
+    pub(crate) fn new(input: &str, message: impl Into<String>) -> Self {
+        Self {
+            input: input.to_string(),
+            message: message.into(),
+        }
+    }
+}
+
+// Splits `name(a, b, c)` into the name and its arguments. Arguments may be
+// written `label=value`, in which case the label is dropped.
+pub(crate) fn parse_call(input: &str) -> Result<(&str, Vec<Float>), ParseError> {
+    let s = input.trim();
+    let (name, rest) = s
+        .split_once('(')
+        .ok_or_else(|| ParseError::new(input, "expected `name(...)`"))?;
+    let args = rest
+        .strip_suffix(')')
+        .ok_or_else(|| ParseError::new(input, "missing closing `)`"))?;
+    let values = args
+        .split(',')
+        .map(|arg| {
+            let arg = arg.trim();
+            let value = arg.split_once('=').map_or(arg, |(_, v)| v.trim());
+            value
+                .parse::<Float>()
+                .map_err(|_| ParseError::new(input, format!("`{arg}` is not a number")))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok((name.trim(), values))
+}
+
+// Parses the forms Display writes: point(x, y, z), vector(x, y, z) and
+// tuple(x, y, z, w).
+impl std::str::FromStr for Tuple4 {
+    type Err = ParseError;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        let (name, v) = parse_call(input)?;
+        let expected = if name == "tuple" { 4 } else { 3 };
+        if !matches!(name, "point" | "vector" | "tuple") {
+            return Err(ParseError::new(
+                input,
+                format!("unknown tuple kind `{name}`, expected point, vector or tuple"),
+            ));
+        }
+        if v.len() != expected {
+            return Err(ParseError::new(
+                input,
+                format!("{name} takes {expected} values, got {}", v.len()),
+            ));
+        }
+        Ok(match name {
+            "point" => point(v[0], v[1], v[2]),
+            "vector" => vector(v[0], v[1], v[2]),
+            _ => make_tuple(v[0], v[1], v[2], v[3]),
+        })
+    }
+}
+
 impl Tuple4 {
     pub fn new(x: Float, y: Float, z: Float, w: Float) -> Self {
         Tuple4 { x, y, z, w }
@@ -121,6 +195,20 @@ impl Tuple4 {
         }
     }
 
+    // Like normalize, but None for a zero vector, or one with NaN or
+    // infinite components, instead of panicking.
+    pub fn try_normalize(&self) -> Option<Tuple4> {
+        let mag = self.magnitude();
+        (mag > 0.0 && mag.is_finite()).then(|| *self / mag)
+    }
+
+    pub fn approx_eq(&self, other: &Tuple4, epsilon: Float) -> bool {
+        (self.x - other.x).abs() <= epsilon
+            && (self.y - other.y).abs() <= epsilon
+            && (self.z - other.z).abs() <= epsilon
+            && (self.w - other.w).abs() <= epsilon
+    }
+
     pub fn dot(&self, other: Tuple4) -> Float {
         self.x * other.x + self.y * other.y + self.z * other.z + self.w * other.w
     }
@@ -500,6 +588,17 @@ mod test {
         assert!((norm.magnitude() - 1.0).abs() <= 1e-6);
     }
 
+    #[test]
+    fn try_normalize_gives_none_for_vectors_without_a_direction() {
+        assert_eq!(
+            vector(4.0, 0.0, 0.0).try_normalize(),
+            Some(vector(1.0, 0.0, 0.0))
+        );
+        assert_eq!(vector(0.0, 0.0, 0.0).try_normalize(), None);
+        assert_eq!(vector(Float::INFINITY, 0.0, 0.0).try_normalize(), None);
+        assert_eq!(vector(Float::NAN, 1.0, 0.0).try_normalize(), None);
+    }
+
     // Scenario: The dot product of two tuples
     //   Given a ← vector(1, 2, 3)
     //     And b ← vector(2, 3, 4)
@@ -548,4 +647,35 @@ mod test {
         let r = v.reflect(n);
         check_tuple(r, vector(1.0, 0.0, 0.0));
     }
+
+    #[test]
+    fn tuples_round_trip_through_display() {
+        for t in [
+            point(1.0, -2.5, 0.125),
+            vector(-0.5, 0.0, 3.0),
+            make_tuple(1.0, 2.0, 3.0, 0.5),
+        ] {
+            assert_eq!(t.to_string().parse::<Tuple4>().unwrap(), t);
+        }
+    }
+
+    #[test]
+    fn parsing_tolerates_extra_whitespace() {
+        let t: Tuple4 = "  point( 1 ,2,  -3 ) ".parse().unwrap();
+        assert_eq!(t, point(1.0, 2.0, -3.0));
+    }
+
+    #[test]
+    fn malformed_tuples_give_descriptive_errors() {
+        let err = "point(1, 2)".parse::<Tuple4>().unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "cannot parse `point(1, 2)`: point takes 3 values, got 2"
+        );
+        let err = "normal(1, 2, 3)".parse::<Tuple4>().unwrap_err();
+        assert!(err.message.contains("unknown tuple kind `normal`"));
+        let err = "vector(1, x, 3)".parse::<Tuple4>().unwrap_err();
+        assert!(err.message.contains("`x` is not a number"));
+        assert!("point(1, 2, 3".parse::<Tuple4>().is_err());
+    }
 }
@@ -0,0 +1,159 @@
+// Problems a render recovers from instead of panicking. They tend to happen
+// once per pixel, so they are counted rather than printed as they happen,
+// in the Warnings of the render's options, and left for the caller to
+// report.
+use std::{
+    cell::RefCell,
+    fmt,
+    sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    },
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Warning {
+    // Intersections with a NaN t, as from a transform with NaN entries. They
+    // are dropped.
+    NanIntersection,
+    // Pattern transforms that can't be inverted. The identity is used.
+    SingularPatternTransform,
+    // Hits shaded in a world without a light. They get ambient light only.
+    NoLight,
+}
+
+const ALL: [Warning; 3] = [
+    Warning::NanIntersection,
+    Warning::SingularPatternTransform,
+    Warning::NoLight,
+];
+
+impl Warning {
+    fn describe(self) -> &'static str {
+        match self {
+            Warning::NanIntersection => "intersections with a NaN t skipped",
+            Warning::SingularPatternTransform => {
+                "singular pattern transforms replaced by the identity"
+            }
+            Warning::NoLight => "hits shaded without a light",
+        }
+    }
+}
+
+// How often each kind of warning came up.
+#[derive(Debug, Default)]
+pub struct Warnings {
+    counts: [AtomicUsize; ALL.len()],
+}
+
+impl Warnings {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn note(&self, w: Warning) {
+        self.counts[w as usize].fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn count(&self, w: Warning) -> usize {
+        self.counts[w as usize].load(Ordering::Relaxed)
+    }
+
+    // One line per kind of warning seen. None when there is nothing to
+    // report.
+    pub fn report(&self) -> Option<String> {
+        let lines: Vec<String> = ALL
+            .iter()
+            .filter_map(|&w| match self.count(w) {
+                0 => None,
+                n => Some(format!("warning: {n} {}", w.describe())),
+            })
+            .collect();
+        (!lines.is_empty()).then(|| lines.join("\n"))
+    }
+}
+
+// The Warnings a RenderOptions counts into. Clones share the counts, so the
+// options passed to a render see what it ran into. The counts don't take
+// part in comparing options.
+#[derive(Clone, Default)]
+pub struct SharedWarnings(pub Arc<Warnings>);
+
+impl std::ops::Deref for SharedWarnings {
+    type Target = Warnings;
+
+    fn deref(&self) -> &Warnings {
+        &self.0
+    }
+}
+
+impl PartialEq for SharedWarnings {
+    fn eq(&self, _other: &Self) -> bool {
+        true
+    }
+}
+
+impl fmt::Debug for SharedWarnings {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("SharedWarnings")
+    }
+}
+
+// Where warnings noted on this thread go, for code such as patterns that
+// has no world to hand.
+thread_local!(static CURRENT: RefCell<Option<SharedWarnings>> = const { RefCell::new(None) });
+
+// Sends warnings noted on this thread to `warnings` until it is dropped.
+pub(crate) struct Collecting {
+    previous: Option<SharedWarnings>,
+}
+
+pub(crate) fn collect_into(warnings: &SharedWarnings) -> Collecting {
+    let previous = CURRENT.with(|current| current.replace(Some(warnings.clone())));
+    Collecting { previous }
+}
+
+impl Drop for Collecting {
+    fn drop(&mut self) {
+        CURRENT.with(|current| *current.borrow_mut() = self.previous.take());
+    }
+}
+
+// Counts w for the render running on this thread. Outside one, it is
+// dropped.
+pub(crate) fn note(w: Warning) {
+    CURRENT.with(|current| {
+        if let Some(warnings) = &*current.borrow() {
+            warnings.note(w);
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn notes_go_to_the_innermost_collector() {
+        let (outer, inner) = (SharedWarnings::default(), SharedWarnings::default());
+        note(Warning::NoLight);
+        {
+            let _outer = collect_into(&outer);
+            note(Warning::NoLight);
+            {
+                let _inner = collect_into(&inner);
+                note(Warning::NanIntersection);
+            }
+            note(Warning::NoLight);
+        }
+        note(Warning::NoLight);
+        assert_eq!(outer.count(Warning::NoLight), 2);
+        assert_eq!(outer.count(Warning::NanIntersection), 0);
+        assert_eq!(inner.count(Warning::NanIntersection), 1);
+        assert_eq!(
+            outer.report().as_deref(),
+            Some("warning: 2 hits shaded without a light")
+        );
+        assert_eq!(SharedWarnings::default().report(), None);
+    }
+}
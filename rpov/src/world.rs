@@ -1,282 +1,1491 @@
+#![cfg_attr(not(test), deny(clippy::unwrap_used))]
+
+pub mod demo;
+pub mod gallery;
+
 use indicatif::{ProgressBar, ProgressStyle};
 
-use std::{cell::Cell, vec};
+use std::{
+    cell::{Cell, OnceCell},
+    sync::Arc,
+    vec,
+};
 
 use crate::{
-    canvas::Canvas,
-    colors::{COLOR_BLACK, Color},
-    floats::{EPSILON, Float},
-    intersections::{Intersection, Shape, hit},
-    lighting::{PointLight, point_light, schlick},
-    materials::Material,
+    bounds::{BoundingBox, may_hit},
+    canvas::{Canvas, Filter},
+    colors::{COLOR_BLACK, COLOR_WHITE, Color},
+    floats::{EPSILON, Float, PI},
+    intersections::{Intersection, Shape, short_kind},
+    lighting::{PointLight, ShadingContext, point_light, schlick, shade_surface},
+    materials::{Material, Sidedness},
+    memory::{MemoryReport, MemoryTally},
+    metrics::{MetricKey, Metrics, NullMetrics},
+    onb::{self, Onb},
+    options::{AmbientOcclusion, Environment, OptionsError, RenderOptions},
     planes::Plane,
-    rays::Ray,
-    shapes::Intersectable,
+    rays::{Ray, RayKind},
+    scene::Scene,
+    shapes::{Intersectable, ShapeFunctions},
     spheres::Sphere,
     transformations::scaling,
-    tuples::{Tuple4, point},
+    tuples::{Tuple4, point, vector},
+    warnings::{self, Warning},
+};
+
+// Stands in for a missing light. Shapes are always treated as shadowed from
+// it, so all it contributes is a white ambient term.
+const NO_LIGHT: PointLight = PointLight {
+    position: Tuple4 {
+        x: 0.0,
+        y: 0.0,
+        z: 0.0,
+        w: 1.0,
+    },
+    intensity: COLOR_WHITE,
+    affects: None,
+    max_range: None,
 };
 
-pub struct World {
+// The mutable description of a scene. Call build() to freeze it into a Scene
+// for rendering.
+#[derive(Clone)]
+pub struct WorldBuilder<S = Vec<Box<dyn Shape>>> {
     pub objects: Vec<Sphere>,
     pub light: Option<PointLight>,
     pub planes: Vec<Plane>,
+    // Any other kind of shape.
+    pub shapes: S,
+    pub options: RenderOptions,
+}
+
+// Storage for a world's general shapes. Boxed trait objects take any shape,
+// while Vec<AnyShape> keeps the built-in shapes inline and skips the vtable.
+pub trait ShapeStore: Default + Send + Sync {
+    // Adds the ray's intersections to `xs` and returns how many shapes it was
+    // tested against.
+    fn intersect_into<'a>(&'a self, r: Ray, t_max: Float, xs: &mut Intersections<'a>) -> usize;
+    // Whether `hits` holds for any shape the ray may reach.
+    fn any_hit(&self, r: Ray, hits: &dyn Fn(&dyn Shape) -> bool) -> bool;
+    // Every shape, in the order they were added.
+    fn iter_shapes(&self) -> Box<dyn Iterator<Item = &dyn Shape> + '_>;
+    // The shape with ShapeFunctions::id `id`.
+    fn shape_mut(&mut self, id: u64) -> Option<&mut dyn Shape>;
+    // Whether there was a shape with that id to remove.
+    fn remove_shape(&mut self, id: u64) -> bool;
+}
+
+impl ShapeStore for Vec<Box<dyn Shape>> {
+    fn intersect_into<'a>(&'a self, r: Ray, t_max: Float, xs: &mut Intersections<'a>) -> usize {
+        let mut tested = 0;
+        for shape in self.iter().filter(|s| may_hit(s.as_ref(), r)) {
+            xs.append(&mut shape.intersect_ray_bounded(r, t_max));
+            tested += 1;
+        }
+        tested
+    }
+
+    fn any_hit(&self, r: Ray, hits: &dyn Fn(&dyn Shape) -> bool) -> bool {
+        self.iter()
+            .filter(|s| may_hit(s.as_ref(), r))
+            .any(|s| hits(s.as_ref()))
+    }
+
+    fn iter_shapes(&self) -> Box<dyn Iterator<Item = &dyn Shape> + '_> {
+        Box::new(self.iter().map(|s| s.as_ref()))
+    }
+
+    fn shape_mut(&mut self, id: u64) -> Option<&mut dyn Shape> {
+        let shape = self.iter_mut().find(|s| s.id() == id)?;
+        Some(shape.as_mut())
+    }
+
+    fn remove_shape(&mut self, id: u64) -> bool {
+        let before = self.len();
+        self.retain(|s| s.id() != id);
+        self.len() < before
+    }
 }
 
+// What shading needs to know about a hit. The reflection vector and the
+// refractive indices on either side are only worked out when first asked
+// for, as matte, opaque surfaces never need them.
 pub struct Computations<'a> {
     pub t: Float,
     pub object: &'a dyn Shape,
     pub point: Tuple4,
     pub eyev: Tuple4,
     pub normalv: Tuple4,
+    // The surface's own normal, facing the eye like normalv but never bent
+    // by a normal map.
+    pub geometric_normalv: Tuple4,
     pub inside: bool,
     pub over_point: Tuple4,
-    pub reflectv: Tuple4,
-    pub n1: Float,
-    pub n2: Float,
     pub under_point: Tuple4,
+    pub uv: Option<(Float, Float)>,
+    // Every intersection along the ray, for finding the containing objects.
+    xs: Intersections<'a>,
+    reflectv: OnceCell<Tuple4>,
+    refraction: OnceCell<Refraction>,
 }
 
-pub type Intersections<'a> = Vec<Intersection<'a>>;
+#[derive(Debug, Clone, Copy)]
+struct Refraction {
+    n1: Float,
+    n2: Float,
+    entering: bool,
+}
 
-impl Default for World {
-    fn default() -> Self {
-        Self::new()
+impl Computations<'_> {
+    pub fn reflectv(&self) -> Tuple4 {
+        self.reflectv_counted(&NullMetrics)
+    }
+
+    // Like reflectv, counting the vector in `metrics` when it is worked out.
+    fn reflectv_counted(&self, metrics: &dyn Metrics) -> Tuple4 {
+        *self.reflectv.get_or_init(|| {
+            if metrics.enabled() {
+                metrics.incr(MetricKey::ReflectVectors, 1);
+            }
+            (-self.eyev).reflect(self.normalv)
+        })
+    }
+
+    // The refractive index of the material the ray comes from.
+    pub fn n1(&self) -> Float {
+        self.refraction().n1
+    }
+
+    // The refractive index of the material the ray goes into.
+    pub fn n2(&self) -> Float {
+        self.refraction().n2
+    }
+
+    // Whether the ray goes into the object here rather than out of it. Unlike
+    // `inside`, this comes from counting the surfaces crossed, so it also
+    // holds for shapes without a closed outward-facing skin.
+    pub fn entering(&self) -> bool {
+        self.refraction().entering
+    }
+
+    fn refraction(&self) -> Refraction {
+        *self.refraction.get_or_init(|| {
+            let mut refraction = Refraction {
+                n1: 1.0,
+                n2: 1.0,
+                entering: !self.inside,
+            };
+            let mut containers: Vec<&dyn Shape> = Vec::new();
+            for intersect in &self.xs {
+                // Coincident surfaces share a t, so the object has to match too.
+                let is_hit = intersect.t == self.t && is_same_shape(intersect.object, self.object);
+                if is_hit {
+                    refraction.n1 = containers
+                        .last()
+                        .map_or(1.0, |o| o.resolved_material().refractive_index);
+                }
+                let mut found = false;
+                for (j, obj) in containers.iter().enumerate() {
+                    if is_same_shape(*obj, intersect.object) {
+                        containers.remove(j);
+                        found = true;
+                        break;
+                    }
+                }
+                if !found {
+                    containers.push(intersect.object);
+                }
+
+                if is_hit {
+                    refraction.entering = !found;
+                    refraction.n2 = containers
+                        .last()
+                        .map_or(1.0, |o| o.resolved_material().refractive_index);
+                    break;
+                }
+            }
+            refraction
+        })
     }
 }
 
-// Declare a thread-local static variable to count recursion depth.
-// It's initialized to 0 for each thread.
-thread_local!(static RECURSION_DEPTH: Cell<u32> = const {Cell::new(0)});
+pub type Intersections<'a> = Vec<Intersection<'a>>;
+
+// What a ray first hits, in world space, for picking. The normal faces the
+// ray's origin, flipped when the hit is on the inside of the object.
+#[derive(Debug, Clone)]
+pub struct HitInfo<'a> {
+    pub t: Float,
+    pub object: &'a dyn Shape,
+    // The object's ShapeFunctions::id, which stays the same when the world is
+    // built into a Scene.
+    pub object_id: u64,
+    pub point: Tuple4,
+    pub normal: Tuple4,
+    pub material: Material,
+    pub inside: bool,
+}
 
-// Define your maximum recursion depth.
-const MAX_RECURSION_DEPTH: u32 = 5;
+// What lies between a point and a light. The occluder is the nearest one,
+// and attenuation is the share of the light that still gets through.
+#[derive(Debug, Clone, Copy)]
+pub struct ShadowResult<'a> {
+    pub occluded: bool,
+    pub occluder: Option<&'a dyn Shape>,
+    // The occluder's ShapeFunctions::id.
+    pub occluder_id: Option<u64>,
+    pub occluder_t: Option<Float>,
+    pub attenuation: Color,
+}
 
-impl World {
-    pub fn new() -> Self {
+// The stretch of a ray that lies inside one object. A ray that never leaves
+// the object has an infinite t_exit.
+#[derive(Debug, Clone, Copy)]
+pub struct Segment<'a> {
+    pub object: &'a dyn Shape,
+    pub t_enter: Float,
+    pub t_exit: Float,
+}
+
+impl Segment<'_> {
+    pub fn length(&self) -> Float {
+        self.t_exit - self.t_enter
+    }
+}
+
+// The original name, kept so existing code keeps compiling.
+#[deprecated(note = "use WorldBuilder / Scene")]
+pub type World = WorldBuilder;
+
+impl<S: ShapeStore> Default for WorldBuilder<S> {
+    fn default() -> Self {
         RECURSION_DEPTH.with(|depth| assert_eq!(depth.get(), 0));
         Self {
             objects: vec![],
             light: None,
             planes: vec![],
+            shapes: S::default(),
+            options: RenderOptions::new(),
+        }
+    }
+}
+
+// Declare a thread-local static variable to count recursion depth.
+// It's initialized to 0 for each thread.
+thread_local!(static RECURSION_DEPTH: Cell<u32> = const {Cell::new(0)});
+// The share of the pixel the ray being traced contributes, for culling rays
+// that can no longer make a visible difference.
+thread_local!(static THROUGHPUT: Cell<Float> = const {Cell::new(1.0)});
+
+// Reflectivities and transparencies at or below this add nothing worth a ray.
+// Far below EPSILON, so subtle 0.1% reflections still show.
+pub const MIN_CONTRIBUTION: Float = 1.0e-6;
+
+impl<S: ShapeStore> WorldBuilder<S> {
+    // Poses every animated pattern on the spheres and planes for time `t`.
+    // Shared materials stay shared. Shapes in `shapes` are boxed out of
+    // reach, so their patterns keep still.
+    pub fn set_time(&mut self, t: Float) {
+        let mut posed: Vec<(*const Material, Arc<Material>)> = vec![];
+        let mut pose_shared = |shared: &mut Option<Arc<Material>>| {
+            let Some(m) = shared else {
+                return;
+            };
+            let key = Arc::as_ptr(m);
+            if let Some((_, p)) = posed.iter().find(|(k, _)| *k == key) {
+                *m = p.clone();
+            } else if let Some(pattern) = m.pattern.as_ref().and_then(|p| p.at_time(t)) {
+                *m = Arc::new(Material {
+                    pattern: Some(pattern),
+                    ..(**m).clone()
+                });
+                posed.push((key, m.clone()));
+            }
+        };
+        for s in &mut self.objects {
+            s.material.set_time(t);
+            pose_shared(&mut s.shared_material);
+        }
+        for p in &mut self.planes {
+            p.material.set_time(t);
+            pose_shared(&mut p.shared_material);
         }
     }
 
-    pub fn with_light(light: PointLight) -> Self {
-        Self {
-            objects: vec![],
-            light: Some(light),
-            planes: vec![],
+    // Spheres first, then planes and other shapes, each in the order they
+    // were added.
+    fn all_shapes(&self) -> impl Iterator<Item = &dyn Shape> {
+        let spheres = self.objects.iter().map(|s| s as &dyn Shape);
+        let planes = self.planes.iter().map(|p| p as &dyn Shape);
+        spheres.chain(planes).chain(self.shapes.iter_shapes())
+    }
+
+    // The shape with ShapeFunctions::id `id`, in whichever list it is kept.
+    pub fn object(&self, id: u64) -> Option<&dyn Shape> {
+        self.all_shapes().find(|s| s.id() == id)
+    }
+
+    pub fn object_mut(&mut self, id: u64) -> Option<&mut dyn Shape> {
+        if let Some(s) = self.objects.iter_mut().find(|s| s.id == id) {
+            return Some(s);
+        }
+        if let Some(p) = self.planes.iter_mut().find(|p| p.id == id) {
+            return Some(p);
         }
+        self.shapes.shape_mut(id)
+    }
+
+    // Whether there was a shape with that id to remove. Scenes are built
+    // from the world afresh, so nothing else needs updating.
+    pub fn remove_object(&mut self, id: u64) -> bool {
+        let before = self.objects.len() + self.planes.len();
+        self.objects.retain(|s| s.id != id);
+        self.planes.retain(|p| p.id != id);
+        self.objects.len() + self.planes.len() < before || self.shapes.remove_shape(id)
+    }
+
+    // Every shape with `tag`, in the order of all_shapes.
+    pub fn objects_with_tag(&self, tag: &str) -> Vec<&dyn Shape> {
+        self.all_shapes()
+            .filter(|s| s.tags().contains(tag))
+            .collect()
     }
 
     pub fn intersect(&self, r: Ray) -> Intersections<'_> {
+        self.intersect_bounded(r, Float::INFINITY)
+    }
+
+    // Like intersect, but drops everything at or beyond t_max.
+    pub fn intersect_bounded(&self, r: Ray, t_max: Float) -> Intersections<'_> {
+        crate::profile_scope!("intersect");
         let mut all_intersections = Vec::new();
-        for object in &self.objects {
-            all_intersections.append(&mut object.intersect(r));
+        let mut tested = self.planes.len();
+        for object in self.objects.iter().filter(|o| may_hit(*o, r)) {
+            all_intersections.append(&mut object.intersect_bounded(r, t_max));
+            tested += 1;
         }
         for plane in &self.planes {
-            all_intersections.append(&mut plane.intersect(r));
+            all_intersections.append(&mut plane.intersect_bounded(r, t_max));
         }
+        tested += self.shapes.intersect_into(r, t_max, &mut all_intersections);
+        let metrics = &self.options.metrics;
+        if metrics.enabled() {
+            metrics.incr(MetricKey::rays(r.kind), 1);
+            metrics.incr(MetricKey::IntersectionTests, tested as u64);
+        }
+        all_intersections.retain(|i| {
+            if i.t.is_nan() {
+                self.options.warnings.note(Warning::NanIntersection);
+                return false;
+            }
+            i.visible_to(r.kind) && i.faces(&r)
+        });
 
-        all_intersections.sort_by(|a, b| a.t.partial_cmp(&b.t).unwrap());
+        // A stable sort, so equal t values keep the order shapes were added in.
+        all_intersections.sort_by(|a, b| a.t.total_cmp(&b.t));
         all_intersections
     }
 
+    // Whether anything the ray can see lies in 0 <= t < t_max. Agrees with
+    // asking hit_for about intersect_bounded, but stops at the first shape
+    // that is hit and never builds or sorts the intersections.
+    pub fn intersect_any(&self, r: Ray, t_max: Float) -> bool {
+        self.intersect_any_excluding(r, t_max, None)
+    }
+
+    fn intersect_any_excluding(&self, r: Ray, t_max: Float, exclude: Option<&dyn Shape>) -> bool {
+        crate::profile_scope!("intersect_any");
+        let tested = Cell::new(0);
+        let hits = |shape: &dyn Shape| {
+            tested.set(tested.get() + 1);
+            if exclude.is_some_and(|e| is_same_shape(e, shape)) {
+                return false;
+            }
+            let m = shape.resolved_material();
+            if !m.visible_to(r.kind) {
+                return false;
+            }
+            // Which side was hit needs the normal, so one-sided shapes take
+            // the long way.
+            if m.sidedness != Sidedness::TwoSided {
+                return shape
+                    .intersect_ray_bounded(r, t_max)
+                    .iter()
+                    .any(|i| i.t >= 0.0 && i.faces(&r));
+            }
+            shape.intersects_ray_before(r, t_max)
+        };
+        let hit = self
+            .objects
+            .iter()
+            .filter(|o| may_hit(*o, r))
+            .any(|o| hits(o))
+            || self.planes.iter().any(|p| hits(p))
+            || self.shapes.any_hit(r, &hits);
+        let metrics = &self.options.metrics;
+        if metrics.enabled() {
+            metrics.incr(MetricKey::rays(r.kind), 1);
+            metrics.incr(MetricKey::IntersectionTests, tested.get());
+        }
+        hit
+    }
+
     pub fn shade_hit(&self, comps: Computations) -> Color {
-        let light = self.light.as_ref().expect("Light source not set in world");
-        let in_shadow = self.is_shadowed(comps.over_point);
-        let surface = crate::lighting::lighting(
-            comps.object.material(),
-            comps.object,
+        crate::profile_scope!("shade_hit");
+        // Without a light, shapes are lit as if in shadow, by the ambient term.
+        let light = self.light.as_ref().unwrap_or_else(|| {
+            self.options.warnings.note(Warning::NoLight);
+            &NO_LIGHT
+        });
+        let (over_point, transmitted) = self.light_reaching(&comps, light);
+        let m = comps.object.resolved_material();
+        let dimmed;
+        let m = if let Some(ao) = self.options.ambient_occlusion {
+            let open = self.ambient_occlusion(over_point, comps.normalv, ao);
+            dimmed = Material {
+                ambient: m.ambient * open,
+                ..m.clone()
+            };
+            &dimmed
+        } else {
+            m
+        };
+        let ctx = ShadingContext {
+            point: over_point,
+            eyev: comps.eyev,
+            normalv: comps.normalv,
             light,
-            comps.over_point,
-            comps.eyev,
-            comps.normalv,
-            in_shadow,
-        );
+            attenuation: transmitted,
+            object: comps.object,
+        };
+        let surface = shade_surface(&ctx, m);
 
         let reflected = self.reflected_color(&comps);
         let refracted = self.refracted_color(&comps);
 
-        let m = comps.object.material();
+        let m = comps.object.resolved_material();
 
-        if m.reflective > 0.0 && m.transparency > 0.0 {
+        let color = if m.reflective > 0.0 && m.transparency > 0.0 {
             let reflectance = schlick(&comps);
             surface + reflected * reflectance + refracted * (1.0 - reflectance)
         } else {
             surface + reflected + refracted
+        };
+        self.options.color_policy.apply(color)
+    }
+
+    // The point a hit is lit at, nudged off the surface, and the share of
+    // `light` that gets there. A light that doesn't affect the shape, or is
+    // out of range, leaves it with ambient only, just as if it were in shadow.
+    fn light_reaching(&self, comps: &Computations, light: &PointLight) -> (Tuple4, Color) {
+        let cos_light = (light.position - comps.point)
+            .try_normalize()
+            .map_or(1.0, |lightv| comps.geometric_normalv.dot(lightv));
+        let over_point =
+            comps.point + comps.geometric_normalv * self.options.shadow_bias_for(cos_light);
+        let m = comps.object.resolved_material();
+        let exclude = (!m.self_shadow).then_some(comps.object);
+        let transmitted =
+            if self.light.is_some() && light.illuminates(comps.object) && light.reaches(over_point)
+            {
+                self.shadow_attenuation_excluding(over_point, light, exclude)
+            } else {
+                COLOR_BLACK
+            };
+        (over_point, transmitted)
+    }
+
+    // The first thing the ray hits. Cheaper than prepare_computations, as
+    // nothing is worked out for refraction.
+    pub fn cast_ray(&self, r: Ray) -> Option<HitInfo<'_>> {
+        let xs = self.intersect(r);
+        let i = crate::intersections::hit_for_with_merge_epsilon(
+            &xs,
+            r.kind,
+            self.options.t_merge_epsilon,
+        )?;
+        let point = r.position(i.t);
+        let mut normal = i.normal_at(&point);
+        let inside = normal.dot(-r.direction) < 0.0;
+        if inside {
+            normal = -normal;
         }
+        Some(HitInfo {
+            t: i.t,
+            object: i.object,
+            object_id: i.object.id(),
+            point,
+            normal,
+            material: i.object.resolved_material().clone(),
+            inside,
+        })
     }
 
     pub fn color_at(&self, r: Ray) -> Color {
+        self.trace(r)
+            .unwrap_or_else(|| self.options.background.color_toward(r.direction))
+    }
+
+    // Like color_at, but None when the ray hits nothing.
+    fn trace(&self, r: Ray) -> Option<Color> {
+        self.trace_with(r, COLOR_BLACK, |comps| self.shade_hit(comps))
+    }
+
+    // Like trace, but the hit goes to `shade` instead of shade_hit. Rays past
+    // the recursion limit give `bail_out` without being traced.
+    fn trace_with<T>(
+        &self,
+        r: Ray,
+        bail_out: T,
+        shade: impl FnOnce(Computations) -> T,
+    ) -> Option<T> {
+        self.trace_clipped(r, (0.0, Float::INFINITY), bail_out, shade)
+    }
+
+    // Like trace_with, but the hit has to lie within `clip`, a (near, far)
+    // range of t. Intersections outside it still count toward which objects
+    // the hit is inside of.
+    fn trace_clipped<T>(
+        &self,
+        r: Ray,
+        clip: (Float, Float),
+        bail_out: T,
+        shade: impl FnOnce(Computations) -> T,
+    ) -> Option<T> {
+        crate::profile_scope!("color_at");
         RECURSION_DEPTH.with(|depth| {
             let current_depth = depth.get();
-            println!("depth: {current_depth:?} / {MAX_RECURSION_DEPTH:?}");
+            // Patterns and shapes deep in the trace note their warnings for
+            // the primary ray's world.
+            let _warnings =
+                (current_depth == 0).then(|| warnings::collect_into(&self.options.warnings));
+            let max_depth = self.options.max_recursion_depth;
             // 1. Check if the depth limit has been exceeded.
-            if current_depth >= MAX_RECURSION_DEPTH {
-                return COLOR_BLACK; // Bail out
+            if current_depth >= max_depth {
+                return Some(bail_out); // Bail out
             }
             depth.set(current_depth + 1);
-            let xs = self.intersect(r);
-            let hit = crate::intersections::hit(&xs);
-            let color = match hit {
-                Some(i) => {
-                    let comps = i.prepare_computations(r, Some(xs));
-                    self.shade_hit(comps)
-                }
-                None => COLOR_BLACK,
-            };
+            let xs = self.intersect_bounded(r, clip.1);
+            let hit =
+                crate::intersections::hit_beyond(&xs, r.kind, self.options.t_merge_epsilon, clip.0);
+            let result = hit.map(|i| {
+                let comps = i.prepare_computations(r, Some(xs));
+                shade(comps)
+            });
 
             depth.set(current_depth);
-            color
+            result
         })
     }
 
     pub fn is_shadowed(&self, point: Tuple4) -> bool {
-        let light = self.light.as_ref().expect("Light source not set in world");
+        self.is_shadowed_excluding(point, None)
+    }
+
+    // Like is_shadowed, but hits on `exclude` don't count.
+    pub fn is_shadowed_excluding(&self, point: Tuple4, exclude: Option<&dyn Shape>) -> bool {
+        // No light reaches anything in a world without one.
+        let Some(light) = self.light.as_ref() else {
+            return true;
+        };
+        if !light.reaches(point) {
+            return true;
+        }
+        let v = light.position - point;
+        let distance = v.magnitude();
+        // Nothing can come between a point and a light sitting on it.
+        let Some(r) = Ray::try_new(point, v) else {
+            return false;
+        };
+        self.intersect_any_excluding(r.with_kind(RayKind::Shadow), distance, exclude)
+    }
+
+    // The share of the light's intensity that reaches point, per channel. Each
+    // transparent occluder filters the light by its color times its
+    // transparency, once however often the shadow ray crosses it, and any
+    // opaque occluder blocks the light entirely. Unless colored_shadows is on,
+    // transparent occluders block the light too, as in the book.
+    pub fn shadow_attenuation(&self, point: Tuple4, light: &PointLight) -> Color {
+        self.shadow_attenuation_excluding(point, light, None)
+    }
+
+    // Like shadow_attenuation, but `exclude` never occludes.
+    pub fn shadow_attenuation_excluding(
+        &self,
+        point: Tuple4,
+        light: &PointLight,
+        exclude: Option<&dyn Shape>,
+    ) -> Color {
+        // Without colored shadows any occluder blocks the light outright, so
+        // the first one found settles it.
+        if !self.options.colored_shadows && light.reaches(point) {
+            let v = light.position - point;
+            let Some(r) = Ray::try_new(point, v) else {
+                return COLOR_WHITE;
+            };
+            let r = r.with_kind(RayKind::Shadow);
+            return if self.intersect_any_excluding(r, v.magnitude(), exclude) {
+                COLOR_BLACK
+            } else {
+                COLOR_WHITE
+            };
+        }
+        self.shadow_query_excluding(point, light, exclude)
+            .attenuation
+    }
+
+    // What shadows point from light, for finding out why a pixel is dark.
+    // Unlike is_shadowed, this finds every occluder rather than stopping at
+    // the first, so it is slower.
+    pub fn shadow_query(&self, point: Tuple4, light: &PointLight) -> ShadowResult<'_> {
+        self.shadow_query_excluding(point, light, None)
+    }
+
+    // Like shadow_query, but `exclude` never occludes.
+    pub fn shadow_query_excluding<'a>(
+        &'a self,
+        point: Tuple4,
+        light: &PointLight,
+        exclude: Option<&dyn Shape>,
+    ) -> ShadowResult<'a> {
+        let mut result = ShadowResult {
+            occluded: false,
+            occluder: None,
+            occluder_id: None,
+            occluder_t: None,
+            attenuation: COLOR_WHITE,
+        };
+        // Out of range counts as occluded, with nothing to blame.
+        if !light.reaches(point) {
+            result.occluded = true;
+            result.attenuation = COLOR_BLACK;
+            return result;
+        }
         let v = light.position - point;
         let distance = v.magnitude();
-        let direction = v.normalize();
+        let Some(r) = Ray::try_new(point, v) else {
+            return result;
+        };
+        let intersections = self.intersect_bounded(r.with_kind(RayKind::Shadow), distance);
+
+        let mut crossed: Vec<&dyn Shape> = vec![];
+        for i in intersections
+            .iter()
+            .filter(|i| i.t >= 0.0 && i.t < distance)
+        {
+            let seen = exclude.into_iter().chain(crossed.iter().copied());
+            if seen.into_iter().any(|s| is_same_shape(s, i.object)) {
+                continue;
+            }
+            if !result.occluded {
+                result.occluded = true;
+                result.occluder = Some(i.object);
+                result.occluder_id = Some(i.object.id());
+                result.occluder_t = Some(i.t);
+            }
+            let m = i.object.resolved_material();
+            if m.transparency <= 0.0 || !self.options.colored_shadows {
+                result.attenuation = COLOR_BLACK;
+                break;
+            }
+            result.attenuation = result.attenuation * (m.color * m.transparency);
+            crossed.push(i.object);
+        }
+        result
+    }
+
+    // The fraction of cosine-weighted rays from point, over the hemisphere
+    // around normal, that travel max_distance without hitting anything.
+    pub fn ambient_occlusion(&self, point: Tuple4, normal: Tuple4, ao: AmbientOcclusion) -> Float {
+        let open = (0..ao.samples)
+            .filter(|&i| {
+                let r = Ray::new(point, cosine_sample(normal, i)).with_kind(RayKind::Shadow);
+                !self.intersect_any(r, ao.max_distance)
+            })
+            .count();
+        open as Float / ao.samples as Float
+    }
 
-        let r = Ray::new(point, direction);
-        let intersections = self.intersect(r);
+    // Where the ray is inside each object, from the same entering and leaving
+    // count that prepare_computations uses, ordered by where they start.
+    pub fn ray_segments(&self, r: Ray) -> Vec<Segment<'_>> {
+        let mut open: Vec<(&dyn Shape, Float)> = vec![];
+        let mut segments = vec![];
+        for i in self.intersect(r) {
+            match open.iter().position(|(o, _)| is_same_shape(*o, i.object)) {
+                Some(j) => {
+                    let (object, t_enter) = open.remove(j);
+                    segments.push(Segment {
+                        object,
+                        t_enter,
+                        t_exit: i.t,
+                    });
+                }
+                None => open.push((i.object, i.t)),
+            }
+        }
+        segments.extend(open.into_iter().map(|(object, t_enter)| Segment {
+            object,
+            t_enter,
+            t_exit: Float::INFINITY,
+        }));
+        segments.sort_by(|a, b| a.t_enter.total_cmp(&b.t_enter));
+        segments
+    }
 
-        let h = hit(&intersections);
-        h.is_some() && h.unwrap().t < distance
+    // The color seen along a refracted ray. When the ray has just gone into
+    // an absorbing object, it is dimmed by the distance to the next surface it
+    // reaches, whether that is the object's far side or something inside it.
+    fn transmitted_color(&self, r: Ray, comps: &Computations) -> Color {
+        let absorbance = comps.object.resolved_material().absorbance;
+        if !comps.entering() || absorbance == COLOR_BLACK {
+            return self.color_at(r);
+        }
+        // A ray that never leaves is absorbed completely.
+        self.trace_with(r, COLOR_BLACK, |next| {
+            let d = next.t;
+            self.shade_hit(next) * transmittance(absorbance, d)
+        })
+        .unwrap_or(COLOR_BLACK)
     }
 
     pub fn reflected_color(&self, comps: &Computations) -> Color {
-        let r = comps.object.material().reflective;
-        if r < EPSILON {
+        crate::profile_scope!("reflected_color");
+        let r = comps.object.resolved_material().reflective;
+        if r <= MIN_CONTRIBUTION {
             return COLOR_BLACK;
         }
 
-        let reflect_ray = Ray::new(comps.over_point, comps.reflectv);
-        let color = self.color_at(reflect_ray);
-        color * r
+        self.with_throughput(r, || {
+            let reflectv = comps.reflectv_counted(&*self.options.metrics);
+            let roughness = comps.object.resolved_material().roughness;
+            if roughness <= 0.0 {
+                let reflect_ray =
+                    Ray::new(comps.over_point, reflectv).with_kind(RayKind::Reflection);
+                return self.color_at(reflect_ray) * r;
+            }
+
+            let n = self.options.blur_samples;
+            let total = (0..n).fold(COLOR_BLACK, |acc, i| {
+                let mut direction = cone_sample(reflectv, roughness * PI / 2.0, i);
+                // Grazing reflections can be jittered below the surface.
+                let below = direction.dot(comps.normalv);
+                if below < 0.0 {
+                    direction = direction - comps.normalv * (2.0 * below);
+                }
+                acc + self
+                    .color_at(Ray::new(comps.over_point, direction).with_kind(RayKind::Reflection))
+            });
+            total * (r / n as Float)
+        })
+    }
+
+    // Runs trace_secondary with the throughput scaled by weight, or returns
+    // black without running it when the result would be too faint to matter.
+    fn with_throughput(&self, weight: Float, trace_secondary: impl FnOnce() -> Color) -> Color {
+        let outer = THROUGHPUT.with(|t| t.get());
+        let inner = outer * weight;
+        if inner < self.options.min_throughput {
+            return COLOR_BLACK;
+        }
+        THROUGHPUT.with(|t| t.set(inner));
+        let color = trace_secondary();
+        THROUGHPUT.with(|t| t.set(outer));
+        color
     }
 
     pub fn refracted_color(&self, comps: &Computations) -> Color {
-        let mt = comps.object.material().transparency;
-        if mt == 0.0 {
+        crate::profile_scope!("refracted_color");
+        let mt = comps.object.resolved_material().transparency;
+        if mt <= MIN_CONTRIBUTION {
             return COLOR_BLACK;
         }
+        self.with_throughput(mt, || self.trace_refraction(comps, mt))
+    }
+
+    fn trace_refraction(&self, comps: &Computations, mt: Float) -> Color {
+        let m = comps.object.resolved_material();
+        // Entering and leaving a pane bends the ray back the way it came, so
+        // it carries on undeflected. Blur is not applied to thin surfaces.
+        if m.thin_surface {
+            let through = Ray::new(comps.under_point, -comps.eyev).with_kind(RayKind::Refraction);
+            return self.color_at(through) * mt;
+        }
 
-        let n_ratio = comps.n1 / comps.n2;
-        let cos_i = comps.eyev.dot(comps.normalv);
-        let sin2_t = n_ratio.powi(2) * (1.0 - cos_i.powi(2));
+        let n_ratio = comps.n1() / comps.n2();
+        if m.translucency_blur <= 0.0 {
+            let Some(direction) = refraction_direction(comps.eyev, comps.normalv, n_ratio) else {
+                return COLOR_BLACK;
+            };
+            let refract_ray = Ray::new(comps.under_point, direction).with_kind(RayKind::Refraction);
+            return self.transmitted_color(refract_ray, comps) * mt;
+        }
 
-        if sin2_t > 1.0 {
-            return COLOR_BLACK;
+        // Each sample bends around its own jittered normal, so some may be
+        // totally internally reflected while their neighbours get through.
+        let n = self.options.blur_samples;
+        let total = (0..n).fold(COLOR_BLACK, |acc, i| {
+            let mut normal = cone_sample(comps.normalv, m.translucency_blur * PI / 2.0, i);
+            if normal.dot(comps.eyev) <= 0.0 {
+                normal = comps.normalv;
+            }
+            let ray = match refraction_direction(comps.eyev, normal, n_ratio) {
+                Some(direction) => Ray::new(comps.under_point, direction),
+                None => Ray::new(comps.over_point, (-comps.eyev).reflect(normal)),
+            };
+            acc + self.transmitted_color(ray.with_kind(RayKind::Refraction), comps)
+        });
+        total * (mt / n as Float)
+    }
+}
+
+// Renders with the world's own options. Panics if they are invalid; use
+// render_with_options to get the error instead.
+pub fn render(c: crate::camera::Camera, w: WorldBuilder) -> Canvas {
+    let options = w.options.clone();
+    render_with_options(c, w, &options).unwrap_or_else(|e| panic!("{e}"))
+}
+
+// Renders with `options` in place of the world's own, which are replaced
+// whole rather than merged. Whatever the render recovers from is counted in
+// options.warnings rather than printed; with the profile feature, timings
+// are left in profiling::totals for the caller to report.
+pub fn render_with_options(
+    c: crate::camera::Camera,
+    mut w: WorldBuilder,
+    options: &RenderOptions,
+) -> Result<Canvas, OptionsError> {
+    options.validate()?;
+    w.options = options.clone();
+    Ok(w.build().render(&c))
+}
+
+// Updates `previous`, a render of an earlier state of the world, by
+// re-tracing only the pixels in `rects` and within options.dirty_margin of
+// them. Pass the rectangles Camera::project_bounds gives for
+// WorldBuilder::diff_bounds.
+//
+// This is exact only when nothing outside the rectangles can see what
+// changed. A long shadow, or a mirror across the room, can show it
+// elsewhere. With options.dirty_conservative, worlds with reflective or
+// transparent materials are rendered in full; the margin is all that
+// guards against far-reaching shadows.
+pub fn render_dirty(
+    c: &crate::camera::Camera,
+    w: &WorldBuilder,
+    previous: &Canvas,
+    rects: &[crate::camera::PixelRect],
+) -> Canvas {
+    assert!(
+        previous.width == c.hsize && previous.height == c.vsize,
+        "previous canvas is {}x{}, but the camera renders {}x{}",
+        previous.width,
+        previous.height,
+        c.hsize,
+        c.vsize
+    );
+    let mirrors = || {
+        let materials = w.objects.iter().map(|s| s.resolved_material());
+        let materials = materials.chain(w.planes.iter().map(|p| p.resolved_material()));
+        let mut materials = materials.chain(w.shapes.iter().map(|s| s.resolved_material()));
+        materials.any(|m| m.reflective > 0.0 || m.transparency > 0.0)
+    };
+    if w.options.dirty_conservative && mirrors() {
+        return render_pixels(c, w);
+    }
+    let scene = w.clone().build();
+    let mut image = previous.clone();
+    let margin = w.options.dirty_margin;
+    for rect in rects {
+        let rect = rect.expand(margin, c.hsize, c.vsize);
+        for y in rect.y0..rect.y1 {
+            for x in rect.x0..rect.x1 {
+                let (color, alpha) = render_pixel(c, &scene, x, y, (0.5, 0.5));
+                image.write_pixel(x, y, color);
+                if image.has_alpha() {
+                    image.write_alpha(x, y, alpha);
+                }
+            }
         }
-        let cos_t = (1.0 - sin2_t).sqrt();
-        let direction = comps.normalv * (n_ratio * cos_i - cos_t) - comps.eyev * n_ratio;
-        let refract_ray = Ray::new(comps.under_point, direction);
-        let color = self.color_at(refract_ray);
-        color * mt
     }
+    image
+}
+
+// Renders a frame for each of `times`, posing the world's animated patterns
+// for it first, and hands each frame to on_frame.
+pub fn render_animation(
+    c: &crate::camera::Camera,
+    w: &mut WorldBuilder,
+    times: impl IntoIterator<Item = Float>,
+    mut on_frame: impl FnMut(Float, Canvas),
+) {
+    for t in times {
+        w.set_time(t);
+        on_frame(t, render_pixels(c, w));
+    }
+}
+
+// Renders the left and right eye images of Camera::stereo.
+pub fn render_stereo(
+    c: &crate::camera::Camera,
+    w: &WorldBuilder,
+    eye_separation: Float,
+    convergence_distance: Float,
+) -> (Canvas, Canvas) {
+    let (left, right) = c.stereo(eye_separation, convergence_distance);
+    let scene = w.clone().build();
+    (scene.render(&left), scene.render(&right))
+}
+
+// Renders a Scene built from a copy of the world, leaving the world as it is.
+pub fn render_pixels(c: &crate::camera::Camera, w: &WorldBuilder) -> Canvas {
+    w.clone().build().render(c)
+}
+
+// Renders `passes` times, moving the sample positions within each pixel from
+// pass to pass, and calls on_pass with the running average after each one.
+pub fn render_accumulating(
+    c: crate::camera::Camera,
+    w: WorldBuilder,
+    passes: usize,
+    mut on_pass: impl FnMut(usize, &Canvas),
+) -> Canvas {
+    let scene = w.build();
+    let mut sum = Canvas::with_alpha(c.hsize, c.vsize);
+    let mut average = sum.clone();
+    for pass in 0..passes {
+        let jitter = (halton(pass + 1, 2), halton(pass + 1, 3));
+        sum.accumulate(&render_pixels_jittered(&c, &scene, jitter), 1.0);
+        average = sum.clone();
+        average.scale(1.0 / (pass + 1) as Float);
+        on_pass(pass, &average);
+    }
+    average
 }
 
-pub fn render(c: crate::camera::Camera, w: World) -> Canvas {
+// Renders at 1/2^(levels - 1) of the camera's resolution, then at each
+// doubling up to full size, and hands every level to on_level stretched to
+// full size. Nothing carries over from one level to the next, so the last is
+// the same as a direct render.
+pub fn render_progressive(
+    c: crate::camera::Camera,
+    w: WorldBuilder,
+    levels: u32,
+    mut on_level: impl FnMut(u32, &Canvas),
+) -> Canvas {
+    assert!(levels > 0, "render_progressive needs at least one level");
+    let scene = w.build();
     let mut image = Canvas::new(c.hsize, c.vsize);
+    for level in 0..levels {
+        let factor = 1usize << (levels - 1 - level);
+        let coarse = c.with_size(c.hsize.div_ceil(factor), c.vsize.div_ceil(factor));
+        image = scene
+            .render(&coarse)
+            .resize(c.hsize, c.vsize, Filter::Nearest);
+        on_level(level, &image);
+    }
+    image
+}
+
+// The index-th element of the Halton sequence in the given base, in 0..1.
+fn halton(mut index: usize, base: usize) -> Float {
+    let mut result = 0.0;
+    let mut f = 1.0;
+    while index > 0 {
+        f /= base as Float;
+        result += f * (index % base) as Float;
+        index /= base;
+    }
+    result
+}
+
+// The share of light that survives travelling a distance d through a
+// medium with the given absorbance.
+fn transmittance(absorbance: Color, d: Float) -> Color {
+    Color::new(
+        (-absorbance.red * d).exp(),
+        (-absorbance.green * d).exp(),
+        (-absorbance.blue * d).exp(),
+    )
+}
+
+// Snell's law, or None under total internal reflection.
+fn refraction_direction(eyev: Tuple4, normalv: Tuple4, n_ratio: Float) -> Option<Tuple4> {
+    let cos_i = eyev.dot(normalv);
+    let sin2_t = n_ratio.powi(2) * (1.0 - cos_i.powi(2));
+    if sin2_t > 1.0 {
+        return None;
+    }
+    let cos_t = (1.0 - sin2_t).sqrt();
+    Some(normalv * (n_ratio * cos_i - cos_t) - eyev * n_ratio)
+}
+
+// The i-th of a low-discrepancy set of unit vectors within `angle` of `axis`.
+fn cone_sample(axis: Tuple4, angle: Float, i: usize) -> Tuple4 {
+    let onb = Onb::from_normal(axis);
+    onb::sample_cone(&onb, angle, halton(i + 1, 2), halton(i + 1, 3))
+}
+
+// The i-th of a low-discrepancy set of unit vectors in the hemisphere around
+// `axis`, denser towards the axis in proportion to the cosine.
+fn cosine_sample(axis: Tuple4, i: usize) -> Tuple4 {
+    let onb = Onb::from_normal(axis);
+    onb::sample_cosine_hemisphere(&onb, halton(i + 1, 2), halton(i + 1, 3))
+}
+
+// `jitter` is where in each sub-pixel cell the sample is taken, from 0 to 1.
+pub(crate) fn render_pixels_jittered<S: ShapeStore>(
+    c: &crate::camera::Camera,
+    w: &WorldBuilder<S>,
+    jitter: (Float, Float),
+) -> Canvas {
+    crate::profile_scope!("render");
+    let mut image = Canvas::with_alpha(c.hsize, c.vsize);
 
     let bar = ProgressBar::new(c.vsize as u64);
     bar.set_style(ProgressStyle::with_template("{spinner:.green} [{elapsed_precise}] [{wide_bar:.cyan/blue}] {pos}/{len} ({eta}) {msg}")
-            .unwrap()
+            .expect("the progress template is valid")
             .progress_chars("#>- "));
     bar.set_message("Rendering...".to_string());
 
     for y in 0..c.vsize {
         bar.inc(1);
         for x in 0..c.hsize {
-            let r = c.ray_for_pixel(x, y);
-            let color = w.color_at(r);
+            let (color, alpha) = render_pixel(c, w, x, y, jitter);
             image.write_pixel(x, y, color);
+            image.write_alpha(x, y, alpha);
         }
     }
     bar.finish_and_clear();
     image
 }
 
-fn is_same_shape(a: &dyn Shape, b: &dyn Shape) -> bool {
-    let a_ptr = (a) as *const _ as *const ();
-    let b_ptr = (b) as *const _ as *const ();
-    a_ptr == b_ptr
+// The passes an OIDN-style denoiser takes alongside the noisy image.
+pub struct Aovs {
+    pub color: Canvas,
+    // World-space shading normals, each component from -1 to 1. Black where
+    // nothing was hit.
+    pub normal: Canvas,
+    // The surface color before any lighting. Black where nothing was hit.
+    pub albedo: Canvas,
 }
 
-pub fn default_world() -> World {
-    let light = point_light(point(-10.0, 10.0, -10.0), Color::new(1.0, 1.0, 1.0));
-
-    let mut s1 = Sphere::new();
-    s1.material = Material {
-        color: Color::new(0.8, 1.0, 0.6),
-        diffuse: 0.7,
-        specular: 0.2,
-        ..Material::new()
+// Renders the color, normal and albedo passes together. Each primary ray is
+// traced once and all three come from its hit; samples are averaged over the
+// pixel like the color.
+pub fn render_aovs<S: ShapeStore>(c: &crate::camera::Camera, w: &Scene<S>) -> Aovs {
+    crate::profile_scope!("render");
+    let mut aovs = Aovs {
+        color: Canvas::with_alpha(c.hsize, c.vsize),
+        normal: Canvas::new(c.hsize, c.vsize),
+        albedo: Canvas::new(c.hsize, c.vsize),
     };
+    let n = w.options.aa_samples;
+    let step = 1.0 / n as Float;
+    let weight = step * step;
+    let nothing = (COLOR_BLACK, COLOR_BLACK, COLOR_BLACK);
+    for y in 0..c.vsize {
+        for x in 0..c.hsize {
+            let (mut color, mut normal, mut albedo) = nothing;
+            let mut coverage = 0.0;
+            for (dx, dy) in sample_offsets(n, (0.5, 0.5)) {
+                let r = c.ray_for_pixel_offset(x, y, dx, dy);
+                let hit = w.trace_clipped(r, c.clip_range(), nothing, |comps| {
+                    let n = comps.normalv;
+                    let m = comps.object.resolved_material();
+                    let surface = m.surface_color(comps.object, comps.point);
+                    (Color::new(n.x, n.y, n.z), surface, w.shade_hit(comps))
+                });
+                let (n, a, shaded) = match hit {
+                    Some((n, a, shaded)) => {
+                        coverage += weight;
+                        (n, a, shaded)
+                    }
+                    None => {
+                        let background = w.options.background.color_toward(r.direction);
+                        (COLOR_BLACK, COLOR_BLACK, background)
+                    }
+                };
+                color = color + shaded * weight;
+                normal = normal + n * weight;
+                albedo = albedo + a * weight;
+            }
+            aovs.color.write_pixel(x, y, color);
+            aovs.color.write_alpha(x, y, coverage);
+            aovs.normal.write_pixel(x, y, normal);
+            aovs.albedo.write_pixel(x, y, albedo);
+        }
+    }
+    aovs
+}
 
-    let s2 = Sphere::with_transform(scaling(0.5, 0.5, 0.5));
+// How much of the world's light reaches what the primary ray through each
+// pixel's center hits, before the material does anything with it: white
+// where it is fully lit, black where it is in shadow, out of range or not
+// affected, and tinted behind colored shadows. Misses are black.
+pub fn render_light_coverage<S: ShapeStore>(c: &crate::camera::Camera, w: &Scene<S>) -> Canvas {
+    crate::profile_scope!("render");
+    let _warnings = warnings::collect_into(&w.options.warnings);
+    let mut coverage = Canvas::new(c.hsize, c.vsize);
+    let light = w.light.as_ref().unwrap_or(&NO_LIGHT);
+    for y in 0..c.vsize {
+        for x in 0..c.hsize {
+            let r = c.ray_for_pixel(x, y);
+            let reaching = w.trace_clipped(r, c.clip_range(), COLOR_BLACK, |comps| {
+                w.light_reaching(&comps, light).1
+            });
+            coverage.write_pixel(x, y, reaching.unwrap_or(COLOR_BLACK));
+        }
+    }
+    coverage
+}
 
-    World {
-        objects: vec![s1, s2],
-        light: Some(light),
-        planes: vec![],
+// White where the primary ray through a pixel's center first hits `shape`,
+// black elsewhere, including where something else is in front of it. Pass
+// one of the scene's own shapes, as scene.object(id) finds them; it is matched
+// by identity, not by value.
+pub fn render_object_mask<S: ShapeStore>(
+    c: &crate::camera::Camera,
+    w: &Scene<S>,
+    shape: &dyn Shape,
+) -> Canvas {
+    crate::profile_scope!("render");
+    let _warnings = warnings::collect_into(&w.options.warnings);
+    let mut mask = Canvas::new(c.hsize, c.vsize);
+    for y in 0..c.vsize {
+        for x in 0..c.hsize {
+            let r = c.ray_for_pixel(x, y);
+            let (near, far) = c.clip_range();
+            let xs = w.intersect_bounded(r, far);
+            let hit =
+                crate::intersections::hit_beyond(&xs, r.kind, w.options.t_merge_epsilon, near);
+            if hit.is_some_and(|i| is_same_shape(i.object, shape)) {
+                mask.write_pixel(x, y, COLOR_WHITE);
+            }
+        }
     }
+    mask
 }
 
-impl<'a> Intersection<'a> {
-    pub fn prepare_computations(
-        &self,
-        ray: Ray,
-        xs_or_none: Option<Intersections>,
-    ) -> Computations<'a> {
-        let point = ray.position(self.t);
-        let eyev = -ray.direction;
-        let mut normalv = self.object.normal_at(&point);
-        let inside = normalv.dot(eyev) < 0.0;
-        if inside {
-            normalv = -normalv;
+// The smallest rectangle holding every pixel of `mask` that isn't black, or
+// None when all of it is.
+pub fn mask_bounds(mask: &Canvas) -> Option<crate::camera::PixelRect> {
+    let mut rect: Option<crate::camera::PixelRect> = None;
+    for y in 0..mask.height {
+        for x in 0..mask.width {
+            if mask.pixel_at(x, y) == COLOR_BLACK {
+                continue;
+            }
+            rect = Some(match rect {
+                None => crate::camera::PixelRect::new(x, y, x + 1, y + 1),
+                Some(r) => crate::camera::PixelRect::new(
+                    r.x0.min(x),
+                    r.y0.min(y),
+                    r.x1.max(x + 1),
+                    r.y1.max(y + 1),
+                ),
+            });
         }
-        let reflectv = ray.direction.reflect(normalv);
-        let over_point = point + normalv * EPSILON;
-        let under_point = point - normalv * EPSILON;
+    }
+    rect
+}
+
+// The color of one pixel, and its alpha: the fraction of samples that hit
+// something.
+fn render_pixel<S: ShapeStore>(
+    c: &crate::camera::Camera,
+    w: &WorldBuilder<S>,
+    x: usize,
+    y: usize,
+    jitter: (Float, Float),
+) -> (Color, Float) {
+    let metrics = &w.options.metrics;
+    if !metrics.enabled() {
+        return sample_pixel(c, w, x, y, jitter);
+    }
+    let start = std::time::Instant::now();
+    let pixel = sample_pixel(c, w, x, y, jitter);
+    metrics.timing(MetricKey::PixelTimeNs, start.elapsed().as_nanos() as u64);
+    pixel
+}
+
+fn sample_pixel<S: ShapeStore>(
+    c: &crate::camera::Camera,
+    w: &WorldBuilder<S>,
+    x: usize,
+    y: usize,
+    jitter: (Float, Float),
+) -> (Color, Float) {
+    let clip = c.clip_range();
+    let sample = |r: Ray| match w.trace_clipped(r, clip, COLOR_BLACK, |comps| w.shade_hit(comps)) {
+        Some(color) => (color, 1.0),
+        None => (w.options.background.color_toward(r.direction), 0.0),
+    };
+    let n = w.options.aa_samples;
+    if n == 1 {
+        return sample(c.ray_for_pixel_offset(x, y, jitter.0, jitter.1));
+    }
+    let step = 1.0 / n as Float;
+    let mut sum = COLOR_BLACK;
+    let mut coverage = 0.0;
+    for (dx, dy) in sample_offsets(n, jitter) {
+        let (color, alpha) = sample(c.ray_for_pixel_offset(x, y, dx, dy));
+        sum = sum + color;
+        coverage += alpha;
+    }
+    (sum * (step * step), coverage * (step * step))
+}
 
-        let mut n1 = 1.0;
-        let mut n2 = 1.0;
-        let xs = xs_or_none.unwrap_or_default();
+// Where in a pixel each sample of a regular n x n grid is taken, row by row,
+// as offsets from the pixel's corner. `jitter` places the sample within its
+// cell, from 0 to 1 along each axis.
+fn sample_offsets(n: usize, jitter: (Float, Float)) -> impl Iterator<Item = (Float, Float)> {
+    let step = 1.0 / n as Float;
+    (0..n).flat_map(move |sy| {
+        (0..n).map(move |sx| {
+            let dx = (sx as Float + jitter.0) * step;
+            let dy = (sy as Float + jitter.1) * step;
+            (dx, dy)
+        })
+    })
+}
 
-        let mut containers: Vec<&dyn Shape> = Vec::new();
+impl WorldBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
 
-        for intersect in xs {
-            if intersect.t == self.t {
-                if containers.is_empty() {
-                    n1 = 1.0;
-                } else {
-                    n1 = containers.last().unwrap().material().refractive_index;
-                }
-            }
-            let mut found = false;
-            for (j, obj) in containers.iter().enumerate() {
-                if is_same_shape(*obj, intersect.object) {
-                    containers.remove(j);
-                    found = true;
-                    break;
+    pub fn with_light(light: PointLight) -> Self {
+        Self {
+            objects: vec![],
+            light: Some(light),
+            planes: vec![],
+            shapes: vec![],
+            options: RenderOptions::new(),
+        }
+    }
+
+    // Roughly how much memory the world's shapes, materials, patterns and
+    // images take up.
+    pub fn memory_report(&self) -> MemoryReport {
+        let mut tally = MemoryTally::default();
+        for shape in self.all_shapes() {
+            tally.add_shape(shape);
+        }
+        if let Environment::Image { canvas, .. } = &self.options.background {
+            tally.add_texture(canvas);
+        }
+        tally.finish()
+    }
+
+    // The world-space box around every bounded shape, and how many shapes
+    // were left out of it. Planes and shapes without a bounding sphere have
+    // no finite extent.
+    pub fn bounds(&self) -> (BoundingBox, usize) {
+        let unit = BoundingBox::new(point(-1.0, -1.0, -1.0), point(1.0, 1.0, 1.0));
+        let mut bounds = BoundingBox::empty();
+        for sphere in &self.objects {
+            bounds.merge(&unit.transform(sphere.transform()));
+        }
+        let mut unbounded = self.planes.len();
+        for shape in &self.shapes {
+            match shape.bounding_sphere() {
+                Some(b) => {
+                    let r = vector(b.radius, b.radius, b.radius);
+                    bounds.merge(&BoundingBox::new(b.center - r, b.center + r));
                 }
+                None => unbounded += 1,
             }
-            if !found {
-                containers.push(intersect.object);
+        }
+        (bounds, unbounded)
+    }
+
+    // A readable dump of the light and every shape with its transform and
+    // material, for debugging a scene. Shapes are listed in the order they
+    // are stored, so the output only changes when the world does.
+    pub fn describe(&self) -> String {
+        let mut lines = vec![format!(
+            "World: {} spheres, {} planes, {} other shapes",
+            self.objects.len(),
+            self.planes.len(),
+            self.shapes.len()
+        )];
+        match &self.light {
+            Some(light) => {
+                let i = light.intensity;
+                lines.push(format!(
+                    "  light at {}, intensity ({}, {}, {})",
+                    light.position, i.red, i.green, i.blue
+                ));
             }
+            None => lines.push("  no light".to_string()),
+        }
+        // Shapes go by their place in the world's lists, which unlike ids
+        // stays the same from one run to the next.
+        let line = |list: &str, i: usize, s: &dyn Shape| {
+            format!(
+                "  {list}[{i}]: {}({}; {})",
+                short_kind(s),
+                s.transform().describe(),
+                s.material()
+            )
+        };
+        for (i, s) in self.objects.iter().enumerate() {
+            lines.push(line("objects", i, s));
+        }
+        for (i, p) in self.planes.iter().enumerate() {
+            lines.push(line("planes", i, p));
+        }
+        for (i, s) in self.shapes.iter().enumerate() {
+            lines.push(line("shapes", i, &**s));
+        }
+        lines.join("\n")
+    }
 
-            if intersect.t == self.t {
-                if containers.is_empty() {
-                    n2 = 1.0;
-                } else {
-                    n2 = containers.last().unwrap().material().refractive_index;
+    // Where the two worlds differ: the bounds, in both states, of every shape
+    // that moved, changed material, or exists in only one of them. Spheres
+    // are matched by id and other shapes by position in their list. A changed
+    // light or unbounded shape affects everything and gives an infinite box.
+    pub fn diff_bounds(before: &WorldBuilder, after: &WorldBuilder) -> Vec<BoundingBox> {
+        let mut changed = vec![];
+        if before.light != after.light {
+            changed.push(BoundingBox::infinite());
+        }
+        for s in &before.objects {
+            match after.objects.iter().find(|t| t.id == s.id) {
+                Some(t) if same_shape_state(s, t, 0.0) => {}
+                Some(t) => changed.extend([shape_bounds(s), shape_bounds(t)]),
+                None => changed.push(shape_bounds(s)),
+            }
+        }
+        for t in &after.objects {
+            if !before.objects.iter().any(|s| s.id == t.id) {
+                changed.push(shape_bounds(t));
+            }
+        }
+        fn by_position<'a>(
+            a: impl ExactSizeIterator<Item = &'a dyn Shape>,
+            b: impl ExactSizeIterator<Item = &'a dyn Shape>,
+            changed: &mut Vec<BoundingBox>,
+        ) {
+            let (a, b): (Vec<_>, Vec<_>) = (a.collect(), b.collect());
+            for i in 0..a.len().max(b.len()) {
+                match (a.get(i), b.get(i)) {
+                    (Some(s), Some(t)) if same_shape_state(*s, *t, 0.0) => {}
+                    (s, t) => changed.extend(s.into_iter().chain(t).map(|x| shape_bounds(*x))),
                 }
-                break;
             }
         }
+        by_position(
+            before.planes.iter().map(|p| p as &dyn Shape),
+            after.planes.iter().map(|p| p as &dyn Shape),
+            &mut changed,
+        );
+        by_position(
+            before.shapes.iter().map(|s| s.as_ref()),
+            after.shapes.iter().map(|s| s.as_ref()),
+            &mut changed,
+        );
+        changed
+    }
+
+    // Whether the two worlds hold the same light and the same shapes in the
+    // same order, with transforms (compared through their inverses) and
+    // materials within `epsilon`. Sphere ids are ignored, so a world rebuilt
+    // from a description compares equal to the original. Render options are
+    // not compared.
+    pub fn structurally_eq(&self, other: &WorldBuilder, epsilon: Float) -> bool {
+        let lights = match (&self.light, &other.light) {
+            (None, None) => true,
+            (Some(a), Some(b)) => {
+                a.position.approx_eq(&b.position, epsilon)
+                    && a.intensity.approx_eq(&b.intensity, epsilon)
+            }
+            _ => false,
+        };
+        let same = |a: &dyn Shape, b: &dyn Shape| same_shape_state(a, b, epsilon);
+        fn pairwise<T>(a: &[T], b: &[T], same: impl Fn(&T, &T) -> bool) -> bool {
+            a.len() == b.len() && a.iter().zip(b).all(|(a, b)| same(a, b))
+        }
+        lights
+            && pairwise(&self.objects, &other.objects, |a, b| same(a, b))
+            && pairwise(&self.planes, &other.planes, |a, b| same(a, b))
+            && pairwise(&self.shapes, &other.shapes, |a, b| {
+                same(a.as_ref(), b.as_ref())
+            })
+    }
+}
+
+// Same kind of shape, placed and dressed alike to within epsilon.
+fn same_shape_state(a: &dyn Shape, b: &dyn Shape, epsilon: Float) -> bool {
+    a.kind() == b.kind()
+        && a.transform_inverse()
+            .approx_eq(&b.transform_inverse(), epsilon)
+        && a.material().approx_eq(b.material(), epsilon)
+        && a.tags() == b.tags()
+}
+
+// The world-space box around a shape, or everywhere for an unbounded one.
+fn shape_bounds(shape: &dyn Shape) -> BoundingBox {
+    match shape.bounding_sphere() {
+        Some(b) => {
+            let r = vector(b.radius, b.radius, b.radius);
+            BoundingBox::new(b.center - r, b.center + r)
+        }
+        None => BoundingBox::infinite(),
+    }
+}
+
+fn is_same_shape(a: &dyn Shape, b: &dyn Shape) -> bool {
+    let a_ptr = (a) as *const _ as *const ();
+    let b_ptr = (b) as *const _ as *const ();
+    a_ptr == b_ptr
+}
+
+pub fn default_world() -> WorldBuilder {
+    let light = point_light(point(-10.0, 10.0, -10.0), Color::new(1.0, 1.0, 1.0));
+
+    let mut s1 = Sphere::new();
+    s1.material = Material {
+        color: Color::new(0.8, 1.0, 0.6),
+        diffuse: 0.7,
+        specular: 0.2,
+        ..Material::new()
+    };
+
+    let mut s2 = Sphere::new();
+    s2.set_transform(scaling(0.5, 0.5, 0.5))
+        .expect("scene transforms are invertible");
+
+    WorldBuilder {
+        objects: vec![s1, s2],
+        light: Some(light),
+        planes: vec![],
+        shapes: vec![],
+        options: RenderOptions::new(),
+    }
+}
+
+impl<'a> Intersection<'a> {
+    pub fn prepare_computations(
+        &self,
+        ray: Ray,
+        xs_or_none: Option<Intersections<'a>>,
+    ) -> Computations<'a> {
+        let point = ray.position(self.t);
+        let eyev = -ray.direction;
+        let geometric = self.normal_at(&point);
+        let inside = geometric.dot(eyev) < 0.0;
+        let side = if inside { -1.0 } else { 1.0 };
+        // Offset points follow the true surface, so a normal map can't push
+        // them through it.
+        let over_point = point + geometric * (side * EPSILON);
+        let under_point = point - geometric * (side * EPSILON);
+        let normalv = self
+            .object
+            .resolved_material()
+            .shading_normal(self.object, point, geometric)
+            * side;
 
         Computations {
             t: self.t,
@@ -284,12 +1493,14 @@ impl<'a> Intersection<'a> {
             point,
             eyev,
             normalv,
+            geometric_normalv: geometric * side,
             inside,
             over_point,
-            reflectv,
-            n1,
-            n2,
             under_point,
+            uv: self.uv,
+            xs: xs_or_none.unwrap_or_default(),
+            reflectv: OnceCell::new(),
+            refraction: OnceCell::new(),
         }
     }
 }
@@ -301,10 +1512,12 @@ mod tests {
     use super::*;
     use crate::{
         floats::{PI, SQRT_2},
+        intersections::{hit, hit_for},
+        materials::Sidedness,
         patterns::TestPattern,
         planes::Plane,
         rays::ray,
-        transformations::scaling,
+        transformations::{scaling, translation},
         tuples::vector,
     };
 
@@ -314,7 +1527,7 @@ mod tests {
     //     And w has no light source
     #[test]
     fn creating_a_world() {
-        let w = World::new();
+        let w = WorldBuilder::new();
         assert!(w.objects.is_empty());
         assert!(w.light.is_none());
     }
@@ -342,7 +1555,7 @@ mod tests {
             ..Material::new()
         };
 
-        let mut s2 = Sphere::with_transform(scaling(0.5, 0.5, 0.5));
+        let mut s2 = Sphere::with_transform(scaling(0.5, 0.5, 0.5)).unwrap();
 
         let w = default_world();
         assert_eq!(w.light.unwrap(), light);
@@ -352,6 +1565,175 @@ mod tests {
         assert!(w.objects.contains(&s2));
     }
 
+    #[test]
+    fn worlds_built_the_same_way_are_structurally_equal_despite_new_ids() {
+        let (a, b) = (default_world(), default_world());
+        assert_ne!(a.objects[0].id, b.objects[0].id);
+        assert!(a.structurally_eq(&b, EPSILON));
+    }
+
+    #[test]
+    fn structural_equality_allows_differences_up_to_epsilon() {
+        let a = default_world();
+        let mut b = default_world();
+        b.objects[0].material.diffuse += 1e-4;
+        assert!(a.structurally_eq(&b, 1e-3));
+        assert!(!a.structurally_eq(&b, 1e-5));
+
+        let mut moved = default_world();
+        moved.light.as_mut().unwrap().position.x += 0.01;
+        assert!(!a.structurally_eq(&moved, EPSILON));
+    }
+
+    #[test]
+    fn structural_equality_tells_shape_kinds_apart() {
+        let mut a = WorldBuilder::new();
+        a.shapes.push(Box::new(Sphere::new()));
+        let mut b = WorldBuilder::new();
+        b.shapes.push(Box::new(Plane::new()));
+        assert!(!a.structurally_eq(&b, EPSILON));
+        assert!(a.structurally_eq(&a.clone(), EPSILON));
+    }
+
+    #[test]
+    fn diff_bounds_covers_what_moved_in_both_places() {
+        let before = default_world();
+        assert!(WorldBuilder::diff_bounds(&before, &before.clone()).is_empty());
+
+        let mut after = before.clone();
+        after.objects[1]
+            .set_transform(translation(2.0, 0.0, 0.0) * scaling(0.5, 0.5, 0.5))
+            .unwrap();
+        let boxes = WorldBuilder::diff_bounds(&before, &after);
+        assert_eq!(boxes.len(), 2);
+        assert!(boxes[0].contains_point(point(0.0, 0.0, 0.0)));
+        assert!(boxes[1].contains_point(point(2.0, 0.0, 0.0)));
+        assert!(!boxes[1].contains_point(point(0.0, 0.0, 0.0)));
+
+        let mut added = before.clone();
+        added
+            .objects
+            .push(Sphere::with_transform(translation(5.0, 0.0, 0.0)).unwrap());
+        let boxes = WorldBuilder::diff_bounds(&before, &added);
+        assert_eq!(boxes.len(), 1);
+        assert!(boxes[0].contains_point(point(5.0, 0.0, 0.0)));
+
+        let mut relit = before.clone();
+        relit.light.as_mut().unwrap().position.y = 0.0;
+        assert_eq!(
+            WorldBuilder::diff_bounds(&before, &relit),
+            vec![BoundingBox::infinite()]
+        );
+    }
+
+    #[test]
+    fn moving_a_small_sphere_re_traces_only_the_pixels_around_it() {
+        let mut before = WorldBuilder::with_light(point_light(
+            point(-10.0, 10.0, -10.0),
+            Color::new(1.0, 1.0, 1.0),
+        ));
+        before.planes.push(Plane::new());
+        before
+            .objects
+            .push(Sphere::with_transform(translation(-1.0, 1.0, 1.0)).unwrap());
+        before.objects.push(
+            Sphere::with_transform(translation(1.0, 0.2, -1.0) * scaling(0.2, 0.2, 0.2)).unwrap(),
+        );
+        let mut after = before.clone();
+        after.objects[1]
+            .set_transform(translation(1.2, 0.2, -1.0) * scaling(0.2, 0.2, 0.2))
+            .unwrap();
+
+        let mut c = crate::camera::Camera::new(120, 80, PI / 3.0);
+        c.set_transform(crate::transformations::view_transform(
+            point(0.0, 1.5, -5.0),
+            point(0.0, 0.5, 0.0),
+            vector(0.0, 1.0, 0.0),
+        ));
+        let rects: Vec<_> = WorldBuilder::diff_bounds(&before, &after)
+            .iter()
+            .filter_map(|b| c.project_bounds(b))
+            .map(|r| r.expand(after.options.dirty_margin, c.hsize, c.vsize))
+            .collect();
+        let dirty_pixels = (0..c.vsize)
+            .flat_map(|y| (0..c.hsize).map(move |x| (x, y)))
+            .filter(|&(x, y)| rects.iter().any(|r| r.contains(x, y)))
+            .count();
+        assert!(
+            dirty_pixels > 0 && dirty_pixels < c.hsize * c.vsize / 10,
+            "{dirty_pixels}"
+        );
+
+        let previous = render_pixels(&c, &before);
+        let dirty = render_dirty(&c, &after, &previous, &rects);
+        let full = render_pixels(&c, &after);
+        for y in 0..c.vsize {
+            for x in 0..c.hsize {
+                let expected = if rects.iter().any(|r| r.contains(x, y)) {
+                    full.pixel_at(x, y)
+                } else {
+                    previous.pixel_at(x, y)
+                };
+                assert_eq!(dirty.pixel_at(x, y), expected, "({x}, {y})");
+            }
+        }
+        // The sphere's shadow lies behind it, so nothing outside changed.
+        assert_eq!(dirty.to_ppm(), full.to_ppm());
+    }
+
+    #[test]
+    fn dirty_rendering_falls_back_to_a_full_render_with_mirrors_about() {
+        let mut w = default_world();
+        w.objects[0].material.reflective = 0.5;
+        let c = crate::camera::Camera::new(10, 10, PI / 2.0);
+        let previous = Canvas::with_alpha(10, 10);
+        assert_eq!(
+            render_dirty(&c, &w, &previous, &[]).to_ppm(),
+            render_pixels(&c, &w).to_ppm()
+        );
+        w.options = RenderOptions::new().with_dirty_conservative(false);
+        assert_eq!(
+            render_dirty(&c, &w, &previous, &[]).to_ppm(),
+            previous.to_ppm()
+        );
+    }
+
+    #[test]
+    fn dirty_rendering_sees_mirrors_through_shared_and_inherited_materials() {
+        let mirror = Material {
+            reflective: 0.5,
+            ..Material::new()
+        };
+        let c = crate::camera::Camera::new(10, 10, PI / 2.0);
+        let previous = Canvas::with_alpha(10, 10);
+        let mut shared = default_world();
+        shared.objects[0].shared_material = Some(mirror.clone().shared());
+        let mut inherited = default_world();
+        let sphere = inherited.objects.remove(0);
+        inherited.shapes.push(Box::new(InheritingSphere {
+            sphere,
+            own: None,
+            inherited: Some(mirror),
+        }));
+        for w in [shared, inherited] {
+            assert_eq!(
+                render_dirty(&c, &w, &previous, &[]).to_ppm(),
+                render_pixels(&c, &w).to_ppm()
+            );
+        }
+    }
+
+    #[test]
+    fn a_cloned_world_is_a_snapshot() {
+        let mut w = default_world();
+        w.shapes.push(Box::new(Plane::new()));
+        let before = w.clone();
+        assert!(w.structurally_eq(&before, EPSILON));
+        w.objects[1].set_transform(scaling(0.6, 0.6, 0.6)).unwrap();
+        assert!(!w.structurally_eq(&before, EPSILON));
+        crate::check_floats!(before.objects[1].transform()[(0, 0)], 0.5);
+    }
+
     // Scenario: Intersect a world with a ray
     //   Given w ← default_world()
     //     And r ← ray(point(0, 0, -5), vector(0, 0, 1))
@@ -389,7 +1771,7 @@ mod tests {
         let i = Intersection::new(4.0, shape);
         let comps = i.prepare_computations(r, None);
         let c = w.shade_hit(comps);
-        assert_eq!(c, Color::new(0.38066, 0.47583, 0.2855));
+        crate::assert_color_eq!(c, Color::new(0.38066, 0.47583, 0.2855));
     }
 
     // Scenario: Shading an intersection from the inside
@@ -413,7 +1795,7 @@ mod tests {
         let i = Intersection::new(0.5, shape);
         let comps = i.prepare_computations(r, None);
         let c = w.shade_hit(comps);
-        assert_eq!(c, Color::new(0.90498, 0.90498, 0.90498));
+        crate::assert_color_eq!(c, Color::new(0.90498, 0.90498, 0.90498));
     }
 
     // Scenario: The color when a ray misses
@@ -439,7 +1821,7 @@ mod tests {
         let w = default_world();
         let r = ray(point(0.0, 0.0, -5.0), vector(0.0, 0.0, 1.0));
         let c = w.color_at(r);
-        assert_eq!(c, Color::new(0.38066, 0.47583, 0.2855));
+        crate::assert_color_eq!(c, Color::new(0.38066, 0.47583, 0.2855));
     }
 
     // Scenario: The color with an intersection behind the ray
@@ -477,9 +1859,9 @@ mod tests {
         let from = point(0.0, 0.0, -5.0);
         let to = point(0.0, 0.0, 0.0);
         let up = vector(0.0, 1.0, 0.0);
-        c.transform = crate::transformations::view_transform(from, to, up);
+        c.set_transform(crate::transformations::view_transform(from, to, up));
         let image = render(c, w);
-        assert_eq!(image.pixel_at(5, 5), Color::new(0.38066, 0.47583, 0.2855));
+        crate::assert_color_eq!(image.pixel_at(5, 5), Color::new(0.38066, 0.47583, 0.2855));
     }
 
     // Scenario: There is no shadow when nothing is collinear with point and light
@@ -506,6 +1888,83 @@ mod tests {
         assert!(is_shadowed);
     }
 
+    #[test]
+    fn a_shape_can_be_excluded_from_the_shadow_test() {
+        let mut w = WorldBuilder::with_light(point_light(
+            point(0.0, 0.0, -10.0),
+            Color::new(1.0, 1.0, 1.0),
+        ));
+        w.shapes.push(Box::new(Sphere::new()));
+        let s = w.shapes[0].as_ref();
+        let p = point(0.0, 0.0, 0.0);
+        assert!(w.is_shadowed(p));
+        assert!(!w.is_shadowed_excluding(p, Some(s)));
+    }
+
+    #[test]
+    fn other_shapes_still_shadow_when_one_is_excluded() {
+        let mut w = default_world();
+        let p = point(10.0, -10.0, 10.0);
+        let inner = Sphere::with_transform(scaling(0.5, 0.5, 0.5)).unwrap();
+        w.objects.truncate(1);
+        w.objects.push(inner);
+        let outer = &w.objects[0];
+        assert!(w.is_shadowed_excluding(p, Some(outer)));
+    }
+
+    #[test]
+    fn shade_hit_skips_self_shadowing_when_disabled() {
+        let r = Ray::new(point(0.0, 0.0, 0.0), vector(0.0, 0.0, 1.0));
+        let shade = |self_shadow: bool| {
+            let mut w = WorldBuilder::with_light(point_light(
+                point(0.0, 0.0, -10.0),
+                Color::new(1.0, 1.0, 1.0),
+            ));
+            let mut s = Sphere::new();
+            s.material.self_shadow = self_shadow;
+            w.objects.push(s);
+            let i = Intersection::new(1.0, &w.objects[0]);
+            w.shade_hit(i.prepare_computations(r, None))
+        };
+        crate::assert_color_eq!(shade(true), Color::new(0.1, 0.1, 0.1));
+        assert!(shade(false).red > 0.5);
+    }
+
+    #[test]
+    fn a_light_with_an_affects_set_only_lights_tagged_shapes() {
+        let light = point_light(point(0.0, 0.0, -10.0), Color::new(1.0, 1.0, 1.0));
+        let mut w = WorldBuilder::with_light(light.with_affects(["hero"]));
+        // Tags belong to the shape, so one of two spheres sharing a material
+        // can be picked out.
+        let shared = Material::new().shared();
+        for x in [-1.5, 1.5] {
+            let mut s = Sphere::with_transform(translation(x, 0.0, 0.0)).unwrap();
+            s.shared_material = Some(shared.clone());
+            w.objects.push(s);
+        }
+        w.objects[0].tags.insert("hero".to_string());
+
+        let front_of = |x| w.color_at(ray(point(x, 0.0, -5.0), vector(0.0, 0.0, 1.0)));
+        assert!(front_of(-1.5).red > 0.5);
+        crate::assert_color_eq!(front_of(1.5), Color::new(0.1, 0.1, 0.1));
+    }
+
+    #[test]
+    fn tags_change_nothing_under_a_light_that_affects_everything() {
+        let mut c = crate::camera::Camera::new(11, 11, PI / 2.0);
+        c.set_transform(crate::transformations::view_transform(
+            point(0.0, 0.0, -5.0),
+            point(0.0, 0.0, 0.0),
+            vector(0.0, 1.0, 0.0),
+        ));
+        let expected = render_pixels(&c, &default_world());
+        let mut w = default_world();
+        for s in &mut w.objects {
+            s.tags.insert("hero".to_string());
+        }
+        assert_eq!(render_pixels(&c, &w).to_ppm(), expected.to_ppm());
+    }
+
     // Scenario: There is no shadow when an object is behind the light
     //   Given w ← default_world()
     //     And p ← point(-20, 20, -20)
@@ -550,18 +2009,19 @@ mod tests {
             Color::new(1.0, 1.0, 1.0),
         ));
         let s1 = Sphere::new();
-        let s2 = Sphere::with_transform(crate::transformations::translation(0.0, 0.0, 10.0));
-        let w = World {
+        let s2 =
+            Sphere::with_transform(crate::transformations::translation(0.0, 0.0, 10.0)).unwrap();
+        let w = WorldBuilder {
             objects: vec![s1, s2],
             light,
-            ..World::new()
+            ..WorldBuilder::new()
         };
 
         let r = ray(point(0.0, 0.0, 5.0), vector(0.0, 0.0, 1.0));
         let i = Intersection::new(4.0, &w.objects[1]);
         let comps = i.prepare_computations(r, None);
         let c = w.shade_hit(comps);
-        assert_eq!(c, Color::new(0.1, 0.1, 0.1));
+        crate::assert_color_eq!(c, Color::new(0.1, 0.1, 0.1));
     }
 
     // Scenario: The hit should offset the point
@@ -576,7 +2036,9 @@ mod tests {
     fn the_hit_should_offset_the_point() {
         let r = ray(point(0.0, 0.0, -5.0), vector(0.0, 0.0, 1.0));
         let mut shape = Sphere::new();
-        shape.transform = crate::transformations::translation(0.0, 0.0, 1.0);
+        shape
+            .set_transform(crate::transformations::translation(0.0, 0.0, 1.0))
+            .unwrap();
         let i = Intersection::new(5.0, &shape);
         let comps = i.prepare_computations(r, None);
         assert!(
@@ -624,7 +2086,9 @@ mod tests {
         let mut w = default_world();
         let mut shape = Plane::new();
         shape.material.reflective = 0.5;
-        shape.transform = crate::transformations::translation(0.0, -1.0, 0.0);
+        shape
+            .set_transform(crate::transformations::translation(0.0, -1.0, 0.0))
+            .unwrap();
         w.planes.push(shape);
         //     And r ← ray(point(0, 0, -3), vector(0, -√2/2, √2/2))
         let r = ray(
@@ -639,7 +2103,77 @@ mod tests {
         //     And color ← reflected_color(w, comps)
         let color = w.reflected_color(&comps);
         //   Then color = color(0.19032, 0.2379, 0.14274)
-        assert_eq!(color, Color::new(0.19032, 0.2379, 0.14274));
+        crate::assert_color_eq!(color, Color::new(0.19032, 0.2379, 0.14274));
+    }
+
+    // A glossy floor under a sphere that shades pure white wherever it is hit,
+    // so a reflected sample is either white or the black background.
+    fn glossy_floor_world(roughness: Float, blur_samples: usize) -> WorldBuilder {
+        let mut w = WorldBuilder::with_light(point_light(
+            point(-10.0, 10.0, -10.0),
+            Color::new(1.0, 1.0, 1.0),
+        ));
+        w.options = RenderOptions::new().with_blur_samples(blur_samples);
+        let mut floor = Plane::new();
+        floor.material.reflective = 1.0;
+        floor.material.roughness = roughness;
+        w.planes.push(floor);
+        let mut ball =
+            Sphere::with_transform(crate::transformations::translation(0.0, 1.5, 0.0)).unwrap();
+        ball.material.ambient = 1.0;
+        ball.material.diffuse = 0.0;
+        ball.material.specular = 0.0;
+        w.objects.push(ball);
+        w
+    }
+
+    // Reflected brightness along a line on the floor running up to the ball.
+    fn glossy_profile(w: &WorldBuilder) -> Vec<Float> {
+        let eye = point(0.0, 1.0, -6.0);
+        (0..40)
+            .map(|k| {
+                let p = point(0.0, 0.0, k as Float * 0.1 - 4.0);
+                let r = Ray::new(eye, (p - eye).normalize());
+                let i = Intersection::new((p - eye).magnitude(), &w.planes[0]);
+                w.reflected_color(&i.prepare_computations(r, None)).red
+            })
+            .collect()
+    }
+
+    #[test]
+    fn zero_roughness_reflects_a_single_ray() {
+        let sharp = glossy_profile(&glossy_floor_world(0.0, 1));
+        let many = glossy_profile(&glossy_floor_world(0.0, 64));
+        assert_eq!(sharp, many);
+        assert!(sharp.iter().all(|&v| v == 0.0 || v == 1.0));
+    }
+
+    #[test]
+    fn rough_reflections_blur_and_settle_as_samples_grow() {
+        let sharp = glossy_profile(&glossy_floor_world(0.0, 1));
+        let reference = glossy_profile(&glossy_floor_world(0.3, 1024));
+        let partial = |p: &[Float]| p.iter().filter(|&&v| v > 0.05 && v < 0.95).count();
+        assert_eq!(partial(&sharp), 0);
+        assert!(partial(&reference) > 3);
+
+        let error = |samples| {
+            let p = glossy_profile(&glossy_floor_world(0.3, samples));
+            p.iter()
+                .zip(&reference)
+                .map(|(a, b)| (a - b).powi(2))
+                .sum::<Float>()
+        };
+        assert!(error(64) < error(4));
+    }
+
+    #[test]
+    fn rough_reflections_of_an_even_background_keep_their_energy() {
+        let mut w = glossy_floor_world(0.3, 64);
+        w.objects.clear();
+        w.options.background = Color::new(0.5, 0.5, 0.5).into();
+        for p in glossy_profile(&w) {
+            assert!((p - 0.5).abs() < 1e-5, "{p}");
+        }
     }
 
     // Scenario: shade_hit() with a reflective material
@@ -658,7 +2192,9 @@ mod tests {
         let mut w = default_world();
         let mut shape = Plane::new();
         shape.material.reflective = 0.5;
-        shape.transform = crate::transformations::translation(0.0, -1.0, 0.0);
+        shape
+            .set_transform(crate::transformations::translation(0.0, -1.0, 0.0))
+            .unwrap();
         w.planes.push(shape);
         //     And r ← ray(point(0, 0, -3), vector(0, -√2/2, √2/2))
         let r = ray(
@@ -672,7 +2208,7 @@ mod tests {
         //     And color ← shade_hit(w, comps)
         let color = w.shade_hit(comps);
         //   Then color = color(0.87677, 0.92436, 0.82918)
-        assert_eq!(color, Color::new(0.87677, 0.92436, 0.82918));
+        crate::assert_color_eq!(color, Color::new(0.87677, 0.92436, 0.82918));
     }
 
     // Scenario: color_at() with mutually reflective surfaces
@@ -690,15 +2226,20 @@ mod tests {
     //   Then color_at(w, r) should terminate successfully
     #[test]
     fn color_at_with_mutually_reflective_surfaces() {
-        let mut w = World::with_light(point_light(point(0.0, 0.0, 0.0), Color::new(1.0, 1.0, 1.0)));
+        let mut w =
+            WorldBuilder::with_light(point_light(point(0.0, 0.0, 0.0), Color::new(1.0, 1.0, 1.0)));
         let mut lower = Plane::new();
         lower.material.reflective = 1.0;
-        lower.transform = crate::transformations::translation(0.0, -1.0, 0.0);
+        lower
+            .set_transform(crate::transformations::translation(0.0, -1.0, 0.0))
+            .unwrap();
         w.planes.push(lower);
 
         let mut upper = Plane::new();
         upper.material.reflective = 1.0;
-        upper.transform = crate::transformations::translation(0.0, 1.0, 0.0);
+        upper
+            .set_transform(crate::transformations::translation(0.0, 1.0, 0.0))
+            .unwrap();
         w.planes.push(upper);
 
         let r = ray(point(0.0, 0.0, 0.0), vector(0.0, 1.0, 0.0));
@@ -722,7 +2263,9 @@ mod tests {
         let mut w = default_world();
         let mut shape = Plane::new();
         shape.material.reflective = 0.5;
-        shape.transform = crate::transformations::translation(0.0, -1.0, 0.0);
+        shape
+            .set_transform(crate::transformations::translation(0.0, -1.0, 0.0))
+            .unwrap();
         w.planes.push(shape);
         let r = ray(
             point(0.0, 0.0, -3.0),
@@ -731,7 +2274,7 @@ mod tests {
         let i = Intersection::new(SQRT_2, &w.planes[0]);
         let comps = i.prepare_computations(r, None);
         RECURSION_DEPTH.with(|depth| {
-            depth.set(MAX_RECURSION_DEPTH);
+            depth.set(crate::options::DEFAULT_MAX_RECURSION_DEPTH);
             let color = w.reflected_color(&comps);
             assert_eq!(color, Color::new(0.0, 0.0, 0.0));
         });
@@ -785,7 +2328,7 @@ mod tests {
         ];
         let comps = xs[0].prepare_computations(r, Some(xs.clone()));
         RECURSION_DEPTH.with(|depth| {
-            depth.set(MAX_RECURSION_DEPTH);
+            depth.set(crate::options::DEFAULT_MAX_RECURSION_DEPTH);
             let c = w.refracted_color(&comps);
             assert_eq!(c, Color::new(0.0, 0.0, 0.0));
         });
@@ -869,6 +2412,106 @@ mod tests {
         check_colors!(c, expected);
     }
 
+    // A glass pane facing the camera with a fine checkerboard a little way
+    // behind it. Nothing but the checkers adds light.
+    fn frosted_pane_world(blur: Float, blur_samples: usize) -> WorldBuilder {
+        use crate::{patterns::checkers_pattern, transformations::translation};
+        let mut w = WorldBuilder::with_light(point_light(
+            point(0.0, 0.0, -10.0),
+            Color::new(1.0, 1.0, 1.0),
+        ));
+        w.options = RenderOptions::new().with_blur_samples(blur_samples);
+        let facing_camera = crate::transformations::rotation_x(-PI / 2.0);
+        let mut pane = Plane::new();
+        pane.set_transform(facing_camera).unwrap();
+        pane.material.ambient = 0.0;
+        pane.material.diffuse = 0.0;
+        pane.material.specular = 0.0;
+        pane.material.transparency = 1.0;
+        pane.material.refractive_index = 1.5;
+        pane.material.translucency_blur = blur;
+        w.planes.push(pane);
+        let mut board = Plane::new();
+        board
+            .set_transform(translation(0.0, 0.0, 1.0) * facing_camera)
+            .unwrap();
+        let mut checkers = checkers_pattern(Color::new(1.0, 1.0, 1.0), COLOR_BLACK);
+        checkers.transform = scaling(0.2, 0.2, 0.2);
+        board.material.pattern = Some(Arc::new(checkers));
+        board.material.ambient = 1.0;
+        board.material.diffuse = 0.0;
+        board.material.specular = 0.0;
+        w.planes.push(board);
+        w
+    }
+
+    fn frosted_profile(w: &WorldBuilder) -> Vec<Float> {
+        (0..40)
+            .map(|k| {
+                let r = ray(
+                    point(k as Float * 0.025 - 0.5, 0.1, -1.0),
+                    vector(0.0, 0.0, 1.0),
+                );
+                let xs = vec![Intersection::new(1.0, &w.planes[0])];
+                w.refracted_color(&xs[0].prepare_computations(r, Some(xs.clone())))
+                    .red
+            })
+            .collect()
+    }
+
+    #[test]
+    fn frosted_glass_blurs_what_is_behind_it() {
+        let sharp = frosted_profile(&frosted_pane_world(0.0, 1));
+        let reference = frosted_profile(&frosted_pane_world(0.2, 256));
+        let partial = |p: &[Float]| p.iter().filter(|&&v| v > 0.05 && v < 0.95).count();
+        assert_eq!(partial(&sharp), 0);
+        assert!(partial(&reference) > 5);
+
+        let error = |samples| {
+            let p = frosted_profile(&frosted_pane_world(0.2, samples));
+            p.iter()
+                .zip(&reference)
+                .map(|(a, b)| (a - b).powi(2))
+                .sum::<Float>()
+        };
+        assert!(error(64) < error(4));
+    }
+
+    #[test]
+    fn frosted_samples_lost_to_total_internal_reflection_reflect_instead() {
+        let mut w = default_world();
+        w.objects[0].material.transparency = 1.0;
+        w.objects[0].material.refractive_index = 1.5;
+        w.objects[0].material.translucency_blur = 0.2;
+        // Every bounce inside frosted glass branches again, so keep it shallow.
+        w.options = RenderOptions::new()
+            .with_blur_samples(4)
+            .with_max_recursion_depth(2);
+        let r = ray(point(0.0, 0.0, SQRT_2 / 2.0), vector(0.0, 1.0, 0.0));
+        let xs = vec![
+            Intersection::new(-SQRT_2 / 2.0, &w.objects[0]),
+            Intersection::new(SQRT_2 / 2.0, &w.objects[0]),
+        ];
+        let comps = xs[1].prepare_computations(r, Some(xs.clone()));
+        assert_ne!(w.refracted_color(&comps), COLOR_BLACK);
+    }
+
+    #[test]
+    fn schlick_blend_of_frosted_glass_keeps_its_total() {
+        use crate::check_colors;
+        let shade = |blur: Float| {
+            let mut w = frosted_pane_world(blur, 32);
+            w.planes.truncate(1);
+            w.options.background = Color::new(0.5, 0.5, 0.5).into();
+            w.planes[0].material.reflective = 1.0;
+            let r = ray(point(0.3, 0.1, -1.0), vector(0.0, 0.0, 1.0));
+            let xs = vec![Intersection::new(1.0, &w.planes[0])];
+            w.shade_hit(xs[0].prepare_computations(r, Some(xs.clone())))
+        };
+        check_colors!(shade(0.3), shade(0.0));
+        check_colors!(shade(0.0), Color::new(0.5, 0.5, 0.5));
+    }
+
     // Scenario: shade_hit() with a transparent material
     //   Given w ← default_world()
     //     And floor ← plane() with:
@@ -891,7 +2534,9 @@ mod tests {
     fn shade_hit_with_a_transparent_material() {
         let mut w = default_world();
         let mut floor = Plane::new();
-        floor.transform = crate::transformations::translation(0.0, -1.0, 0.0);
+        floor
+            .set_transform(crate::transformations::translation(0.0, -1.0, 0.0))
+            .unwrap();
         floor.material.transparency = 0.5;
         floor.material.refractive_index = 1.5;
         w.planes.push(floor);
@@ -899,7 +2544,8 @@ mod tests {
         let mut ball = Sphere::new();
         ball.material.color = Color::new(1.0, 0.0, 0.0);
         ball.material.ambient = 0.5;
-        ball.transform = crate::transformations::translation(0.0, -3.5, -0.5);
+        ball.set_transform(crate::transformations::translation(0.0, -3.5, -0.5))
+            .unwrap();
         w.objects.push(ball);
 
         let r = ray(
@@ -909,7 +2555,7 @@ mod tests {
         let xs = vec![Intersection::new(SQRT_2, &w.planes[0])];
         let comps = xs[0].prepare_computations(r, Some(xs.clone()));
         let color = w.shade_hit(comps);
-        assert_eq!(color, Color::new(0.93642, 0.68642, 0.68642));
+        crate::assert_color_eq!(color, Color::new(0.93642, 0.68642, 0.68642));
     }
 
     // Scenario: shade_hit() with a reflective, transparent material
@@ -938,7 +2584,9 @@ mod tests {
             vector(0.0, -SQRT_2 / 2.0, SQRT_2 / 2.0),
         );
         let mut floor = Plane::new();
-        floor.transform = crate::transformations::translation(0.0, -1.0, 0.0);
+        floor
+            .set_transform(crate::transformations::translation(0.0, -1.0, 0.0))
+            .unwrap();
         floor.material.reflective = 0.5;
         floor.material.transparency = 0.5;
         floor.material.refractive_index = 1.5;
@@ -947,12 +2595,1686 @@ mod tests {
         let mut ball = Sphere::new();
         ball.material.color = Color::new(1.0, 0.0, 0.0);
         ball.material.ambient = 0.5;
-        ball.transform = crate::transformations::translation(0.0, -3.5, -0.5);
+        ball.set_transform(crate::transformations::translation(0.0, -3.5, -0.5))
+            .unwrap();
         w.objects.push(ball);
 
         let xs = vec![Intersection::new(SQRT_2, &w.planes[0])];
         let comps = xs[0].prepare_computations(r, Some(xs.clone()));
         let color = w.shade_hit(comps);
-        assert_eq!(color, Color::new(0.93391, 0.69643, 0.69243));
+        crate::assert_color_eq!(color, Color::new(0.93391, 0.69643, 0.69243));
+    }
+
+    #[derive(Debug, Clone)]
+    struct InheritingSphere {
+        sphere: Sphere,
+        // Set only when the shape was given a material of its own, which
+        // always wins over the inherited one.
+        own: Option<Material>,
+        inherited: Option<Material>,
+    }
+
+    impl crate::shapes::ShapeFunctions for InheritingSphere {
+        fn local_normal_at(&self, local_point: &Tuple4) -> Tuple4 {
+            self.sphere.local_normal_at(local_point)
+        }
+        fn material(&self) -> &Material {
+            self.own.as_ref().unwrap_or(&self.sphere.material)
+        }
+        fn id(&self) -> u64 {
+            self.sphere.id
+        }
+        fn tags(&self) -> &std::collections::HashSet<String> {
+            &self.sphere.tags
+        }
+        fn transform(&self) -> crate::matrices::Matrix4 {
+            self.sphere.transform()
+        }
+        fn transform_inverse(&self) -> crate::matrices::Matrix4 {
+            self.sphere.transform_inverse()
+        }
+        fn resolved_material(&self) -> &Material {
+            self.own
+                .as_ref()
+                .or(self.inherited.as_ref())
+                .unwrap_or(&self.sphere.material)
+        }
+    }
+
+    impl Intersectable<InheritingSphere> for InheritingSphere {
+        fn local_intersect<'a>(&'a self, local_ray: Ray) -> Vec<Intersection<'a>> {
+            self.sphere
+                .local_intersect(local_ray)
+                .iter()
+                .map(|i| Intersection::new(i.t, self))
+                .collect()
+        }
+    }
+
+    #[test]
+    fn shade_hit_uses_the_resolved_material() {
+        let w = default_world();
+        let r = ray(point(0.0, 0.0, -5.0), vector(0.0, 0.0, 1.0));
+        let glowing = |color: Color| {
+            let mut m = Material::new();
+            m.color = color;
+            m.ambient = 1.0;
+            m.diffuse = 0.0;
+            m.specular = 0.0;
+            m
+        };
+        let red = Color::new(1.0, 0.0, 0.0);
+        let blue = Color::new(0.0, 0.0, 1.0);
+        let shade = |shape: &InheritingSphere| {
+            let i = Intersection::new(4.0, shape);
+            w.shade_hit(i.prepare_computations(r, None))
+        };
+
+        let shape = InheritingSphere {
+            sphere: Sphere::new(),
+            own: None,
+            inherited: Some(glowing(red)),
+        };
+        assert_eq!(shade(&shape), red);
+
+        // An explicit material on the shape itself wins.
+        let shape = InheritingSphere {
+            own: Some(glowing(blue)),
+            ..shape
+        };
+        assert_eq!(shade(&shape), blue);
+
+        let shape = InheritingSphere {
+            own: None,
+            inherited: None,
+            ..shape
+        };
+        assert_ne!(shade(&shape), red);
+    }
+
+    #[test]
+    fn default_render_options_reproduce_render() {
+        let mut c = crate::camera::Camera::new(11, 11, PI / 2.0);
+        c.set_transform(crate::transformations::view_transform(
+            point(0.0, 0.0, -5.0),
+            point(0.0, 0.0, 0.0),
+            vector(0.0, 1.0, 0.0),
+        ));
+        let mut c2 = crate::camera::Camera::new(11, 11, PI / 2.0);
+        c2.set_transform(c.transform());
+        let a = render(c, default_world());
+        let b = render_with_options(c2, default_world(), &RenderOptions::default()).unwrap();
+        for y in 0..11 {
+            for x in 0..11 {
+                let (pa, pb) = (a.pixel_at(x, y), b.pixel_at(x, y));
+                assert_eq!((pa.red, pa.green, pa.blue), (pb.red, pb.green, pb.blue));
+            }
+        }
+    }
+
+    #[test]
+    fn the_background_option_colors_missed_rays() {
+        let mut w = default_world();
+        w.options = RenderOptions::new().with_background(Color::new(0.2, 0.3, 0.4));
+        let r = ray(point(0.0, 0.0, -5.0), vector(0.0, 1.0, 0.0));
+        assert_eq!(w.color_at(r), Color::new(0.2, 0.3, 0.4));
+    }
+
+    #[test]
+    fn a_mirror_sphere_reflects_the_environment_without_lights() {
+        use crate::options::Environment;
+        let mut sky = Canvas::new(16, 8);
+        for y in 0..8 {
+            for x in 0..16 {
+                sky.write_pixel(x, y, Color::new(0.2, 0.4, 0.1 * y as Float));
+            }
+        }
+        let mut w = WorldBuilder::new();
+        let mut mirror = Sphere::new();
+        mirror.material.color = COLOR_BLACK;
+        mirror.material.reflective = 1.0;
+        w.objects = vec![mirror];
+        w.options.background = Environment::Image {
+            canvas: Arc::new(sky),
+            rotation: 0.0,
+        };
+        let mut c = crate::camera::Camera::new(11, 11, PI / 3.0);
+        c.set_transform(crate::transformations::view_transform(
+            point(0.0, 0.0, -5.0),
+            point(0.0, 0.0, 0.0),
+            vector(0.0, 1.0, 0.0),
+        ));
+        let image = render_pixels(&c, &w);
+        assert_eq!(image.alpha_at(5, 5), 1.0);
+        let on_sphere = image.pixel_at(5, 5);
+        assert!(on_sphere.green > 0.1, "{on_sphere:?}");
+        // The sphere reflects what is behind the camera, seen from the front.
+        let direct = w.options.background.color_toward(vector(0.0, 0.0, -1.0));
+        crate::check_colors!(on_sphere, direct);
+    }
+
+    #[test]
+    fn warnings_are_counted_in_the_options_of_each_render() {
+        let c = || crate::camera::Camera::new(5, 5, PI / 2.0);
+        let mut dark = default_world();
+        dark.light = None;
+        let first = RenderOptions::new();
+        render_with_options(c(), dark, &first).unwrap();
+        let second = RenderOptions::new();
+        render_with_options(c(), default_world(), &second).unwrap();
+        assert!(first.warnings.count(Warning::NoLight) > 0);
+        assert_eq!(second.warnings.report(), None);
+    }
+
+    #[test]
+    fn render_keeps_the_worlds_own_options() {
+        let c = crate::camera::Camera::new(5, 5, PI / 2.0);
+        let mut w = WorldBuilder::new();
+        let sky = Color::new(0.2, 0.4, 0.8);
+        w.options = w.options.with_background(sky);
+        assert_eq!(render(c, w).pixel_at(2, 2), sky);
+    }
+
+    #[test]
+    fn rendering_with_invalid_options_is_an_error() {
+        let c = crate::camera::Camera::new(5, 5, PI / 2.0);
+        let options = RenderOptions::new().with_aa_samples(0);
+        let Err(err) = render_with_options(c, default_world(), &options) else {
+            panic!("rendered with aa_samples 0");
+        };
+        assert_eq!(err.field, "aa_samples");
+    }
+
+    #[test]
+    fn bounded_and_unbounded_intersection_choose_the_same_hit() {
+        let w = default_world();
+        for i in -10..=10 {
+            for j in -10..=10 {
+                let origin = point(0.0, 0.0, -5.0);
+                let target = point(i as Float * 0.15, j as Float * 0.15, 0.0);
+                let r = ray(origin, (target - origin).normalize());
+                let all = w.intersect(r);
+                let bounded = match hit(&all) {
+                    Some(h) => w.intersect_bounded(r, h.t + 1.0),
+                    None => w.intersect_bounded(r, 1.0),
+                };
+                assert_eq!(hit(&all).map(|h| h.t), hit(&bounded).map(|h| h.t));
+            }
+        }
+    }
+
+    #[test]
+    fn bounded_intersection_drops_hits_beyond_t_max() {
+        let w = default_world();
+        let r = ray(point(0.0, 0.0, -5.0), vector(0.0, 0.0, 1.0));
+        let ts: Vec<_> = w.intersect_bounded(r, 5.0).iter().map(|i| i.t).collect();
+        assert_eq!(ts, vec![4.0, 4.5]);
+    }
+
+    #[test]
+    fn rendering_writes_coverage_to_the_alpha_channel() {
+        let mut w = WorldBuilder::with_light(point_light(
+            point(-10.0, 10.0, -10.0),
+            Color::new(1.0, 1.0, 1.0),
+        ));
+        w.objects.push(Sphere::new());
+        let mut c = crate::camera::Camera::new(11, 11, PI / 3.0);
+        c.set_transform(crate::transformations::view_transform(
+            point(0.0, 0.0, -5.0),
+            point(0.0, 0.0, 0.0),
+            vector(0.0, 1.0, 0.0),
+        ));
+        let image = render_with_options(c, w, &RenderOptions::new().with_aa_samples(4)).unwrap();
+        assert_eq!(image.alpha_at(5, 5), 1.0);
+        assert_eq!(image.alpha_at(0, 0), 0.0);
+        let edge = (0..11).map(|x| image.alpha_at(x, 5));
+        assert!(edge.into_iter().any(|a| a > 0.0 && a < 1.0));
+    }
+
+    #[test]
+    fn a_high_priority_decal_wins_over_a_coplanar_floor() {
+        use crate::rectangles::Rectangle;
+        let mut decal = Rectangle::new(1.0, 1.0);
+        decal.material.color = Color::new(1.0, 0.0, 0.0);
+        decal.material.priority = 1;
+        let mut floor = Rectangle::new(10.0, 10.0);
+        floor.material.color = Color::new(0.0, 0.0, 1.0);
+        let mut w = WorldBuilder::with_light(point_light(
+            point(0.0, 10.0, 0.0),
+            Color::new(1.0, 1.0, 1.0),
+        ));
+        w.shapes.push(Box::new(decal));
+        w.shapes.push(Box::new(floor));
+        for i in 0..10 {
+            let x = i as Float * 0.05 - 0.25;
+            let c = w.color_at(ray(point(x, 5.0, 0.1), vector(0.0, -1.0, 0.0)));
+            assert!(c.red > c.blue + 0.5, "{c}");
+        }
+    }
+
+    #[test]
+    fn repeated_renders_are_identical() {
+        let scene = || {
+            let mut w = default_world();
+            w.planes.push(Plane::new());
+            let mut decal = crate::rectangles::Rectangle::new(2.0, 2.0);
+            decal.material.color = Color::new(1.0, 0.0, 0.0);
+            w.shapes.push(Box::new(decal));
+            w
+        };
+        let mut c = crate::camera::Camera::new(15, 15, PI / 2.0);
+        c.set_transform(crate::transformations::view_transform(
+            point(0.0, 3.0, -3.0),
+            point(0.0, 0.0, 0.0),
+            vector(0.0, 1.0, 0.0),
+        ));
+        let first = render(c, scene());
+        let mut c = crate::camera::Camera::new(15, 15, PI / 2.0);
+        c.set_transform(crate::transformations::view_transform(
+            point(0.0, 3.0, -3.0),
+            point(0.0, 0.0, 0.0),
+            vector(0.0, 1.0, 0.0),
+        ));
+        let second = render(c, scene());
+        assert_eq!(first.to_ppm(), second.to_ppm());
+    }
+
+    #[test]
+    fn accumulated_passes_converge_on_a_supersampled_render() {
+        let camera = || {
+            let mut c = crate::camera::Camera::new(12, 12, PI / 3.0);
+            c.set_transform(crate::transformations::view_transform(
+                point(0.0, 0.0, -5.0),
+                point(0.0, 0.0, 0.0),
+                vector(0.0, 1.0, 0.0),
+            ));
+            c
+        };
+        let reference = render_with_options(
+            camera(),
+            default_world(),
+            &RenderOptions::new().with_aa_samples(6),
+        )
+        .unwrap();
+        let error = |image: &Canvas| {
+            let mut total = 0.0;
+            for y in 0..image.height {
+                for x in 0..image.width {
+                    let d = image.pixel_at(x, y) - reference.pixel_at(x, y);
+                    total += d.red * d.red + d.green * d.green + d.blue * d.blue;
+                }
+            }
+            total
+        };
+        let mut errors = vec![];
+        let last = render_accumulating(camera(), default_world(), 16, |_, average| {
+            errors.push(error(average))
+        });
+        assert_eq!(errors.len(), 16);
+        assert!(errors[15] < errors[0] / 2.0, "{errors:?}");
+        assert_eq!(error(&last), errors[15]);
+    }
+
+    #[test]
+    fn progressive_levels_sharpen_to_a_direct_render() {
+        let mut c = crate::camera::Camera::new(20, 12, PI / 3.0);
+        c.set_transform(crate::transformations::view_transform(
+            point(0.0, 0.0, -5.0),
+            point(0.0, 0.0, 0.0),
+            vector(0.0, 1.0, 0.0),
+        ));
+        let mut levels = vec![];
+        let last = render_progressive(c.clone(), default_world(), 4, |level, image| {
+            assert_eq!((image.width, image.height), (20, 12));
+            levels.push((level, image.to_ppm()));
+        });
+        let order: Vec<u32> = levels.iter().map(|(level, _)| *level).collect();
+        assert_eq!(order, vec![0, 1, 2, 3]);
+        let direct = render(c, default_world()).to_ppm();
+        assert_eq!(last.to_ppm(), direct);
+        assert_eq!(levels[3].1, direct);
+        assert_ne!(levels[0].1, direct);
+    }
+
+    #[test]
+    fn objects_can_be_found_by_id_and_tag_and_removed() {
+        let mut w = WorldBuilder::with_light(point_light(
+            point(-10.0, 10.0, -10.0),
+            Color::new(1.0, 1.0, 1.0),
+        ));
+        for (x, tag) in [(-3.0, "red"), (0.0, "blue"), (3.0, "red")] {
+            let mut s = Sphere::with_transform(translation(x, 0.0, 0.0)).unwrap();
+            s.tags.insert(tag.to_string());
+            w.objects.push(s);
+        }
+        let mut floor = Plane::new();
+        floor.set_transform(translation(0.0, -2.0, 0.0)).unwrap();
+        floor.tags.insert("red".to_string());
+        w.planes.push(floor);
+        let mut lamp = Sphere::with_transform(translation(0.0, 3.0, 0.0)).unwrap();
+        lamp.tags.insert("blue".to_string());
+        w.shapes.push(Box::new(lamp));
+        let ids: Vec<u64> = w.objects.iter().map(|s| s.id).collect();
+        let (floor_id, lamp_id) = (w.planes[0].id, w.shapes[0].id());
+
+        let red = w.objects_with_tag("red");
+        assert_eq!(red.len(), 3);
+        assert!(is_same_shape(red[0], &w.objects[0]));
+        assert!(is_same_shape(red[1], &w.objects[2]));
+        assert!(is_same_shape(red[2], &w.planes[0]));
+        let blue = w.objects_with_tag("blue");
+        assert_eq!(blue.len(), 2);
+        assert!(is_same_shape(blue[1], w.shapes[0].as_ref()));
+        assert!(w.objects_with_tag("green").is_empty());
+
+        assert_eq!(
+            w.object(ids[1]).unwrap().transform(),
+            translation(0.0, 0.0, 0.0)
+        );
+        assert_eq!(
+            w.object(floor_id).unwrap().transform(),
+            translation(0.0, -2.0, 0.0)
+        );
+        assert_eq!(
+            w.object(lamp_id).unwrap().transform(),
+            translation(0.0, 3.0, 0.0)
+        );
+        for id in [ids[1], floor_id, lamp_id] {
+            assert_eq!(w.object_mut(id).map(|s| s.id()), Some(id));
+        }
+
+        let toward_middle = Ray::new(point(0.0, 0.0, -5.0), vector(0.0, 0.0, 1.0));
+        assert_eq!(w.cast_ray(toward_middle).unwrap().object_id, ids[1]);
+        for id in [ids[1], floor_id, lamp_id] {
+            assert!(w.remove_object(id));
+            assert!(!w.remove_object(id));
+            assert!(w.object(id).is_none());
+        }
+        assert_eq!(w.objects.len(), 2);
+        assert!(w.planes.is_empty() && w.shapes.is_empty());
+        assert!(w.cast_ray(toward_middle).is_none());
+        assert!(w.objects_with_tag("blue").is_empty());
+        assert_eq!(w.objects_with_tag("red").len(), 2);
+    }
+
+    #[test]
+    fn light_coverage_shows_range_and_shadows() {
+        let light = point_light(point(0.0, 4.0, 0.0), Color::new(1.0, 1.0, 1.0));
+        // Reaches the floor out to 3 units from under the light.
+        let mut w = WorldBuilder::with_light(light.with_max_range(5.0));
+        w.planes.push(Plane::new());
+        let mut ball =
+            Sphere::with_transform(translation(-1.0, 1.0, 0.0) * scaling(0.5, 0.5, 0.5)).unwrap();
+        ball.material.color = Color::new(0.2, 0.2, 0.2);
+        w.objects.push(ball);
+        let mut c = crate::camera::Camera::new(81, 81, PI / 2.0);
+        c.set_transform(crate::transformations::view_transform(
+            point(0.0, 10.0, 0.0),
+            point(0.0, 0.0, 0.0),
+            vector(0.0, 0.0, 1.0),
+        ));
+        let coverage = render_light_coverage(&c, &w.build());
+        let at = |p: Tuple4| {
+            let (x, y) = c.project_point(p).unwrap();
+            coverage.pixel_at(x, y).red
+        };
+        let outward: Vec<Float> = (0..10)
+            .map(|k| at(point(0.0, 0.0, k as Float * 0.5)))
+            .collect();
+        assert!(outward.windows(2).all(|w| w[0] >= w[1]), "{outward:?}");
+        assert_eq!((outward[0], outward[9]), (1.0, 0.0));
+        // In the ball's shadow, though in range.
+        assert_eq!(at(point(-1.8, 0.0, 0.0)), 0.0);
+        // The top of the ball is lit, whatever its color.
+        assert_eq!(at(point(-1.0, 1.5, 0.0)), 1.0);
+    }
+
+    #[test]
+    fn a_single_pass_at_the_pixel_center_matches_render() {
+        assert_eq!(halton(1, 2), 0.5);
+        let c = crate::camera::Camera::new(5, 5, PI / 2.0);
+        let image = render_pixels_jittered(&c, &default_world(), (0.5, 0.5));
+        let c = crate::camera::Camera::new(5, 5, PI / 2.0);
+        assert_eq!(image.to_ppm(), render(c, default_world()).to_ppm());
+    }
+
+    #[test]
+    fn skipping_bounded_misses_leaves_the_intersections_unchanged() {
+        use crate::{
+            rectangles::Rectangle,
+            transformations::{rotation_z, shearing, translation},
+        };
+        let mut w = default_world();
+        w.planes.push(Plane::new());
+        for i in 0..30 {
+            let x = i as Float;
+            w.objects.push(
+                Sphere::with_transform(
+                    translation(x * 0.3 - 4.5, (x * 0.7).sin(), 2.0 + x * 0.1)
+                        * shearing(0.5, 0.0, 0.0, x * 0.05, 0.0, 0.0)
+                        * scaling(0.2, 0.4 + x * 0.01, 0.1),
+                )
+                .unwrap(),
+            );
+        }
+        let mut panel = Rectangle::new(2.0, 1.0);
+        panel
+            .set_transform(translation(1.0, 1.0, 1.0) * rotation_z(0.4))
+            .unwrap();
+        w.shapes.push(Box::new(panel));
+
+        let brute_force = |r: Ray| {
+            let mut ts: Vec<Float> = w
+                .objects
+                .iter()
+                .flat_map(|o| o.intersect(r))
+                .map(|i| i.t)
+                .collect();
+            ts.extend(w.planes.iter().flat_map(|p| p.intersect(r)).map(|i| i.t));
+            ts.extend(
+                w.shapes
+                    .iter()
+                    .flat_map(|s| s.intersect_ray(r))
+                    .map(|i| i.t),
+            );
+            ts.sort_by(|a, b| a.partial_cmp(b).unwrap());
+            ts
+        };
+        for i in 0..40 {
+            for j in 0..20 {
+                let target = point(i as Float * 0.25 - 5.0, j as Float * 0.2 - 2.0, 2.0);
+                let origin = point(0.0, 0.5, -5.0);
+                let r = ray(origin, (target - origin).normalize());
+                let ts: Vec<Float> = w.intersect(r).iter().map(|i| i.t).collect();
+                assert_eq!(ts, brute_force(r));
+            }
+        }
+    }
+
+    fn red_glass_over_a_floor() -> WorldBuilder {
+        let mut w = WorldBuilder::with_light(point_light(
+            point(0.0, 10.0, 0.0),
+            Color::new(1.0, 1.0, 1.0),
+        ));
+        w.options = RenderOptions::new().with_colored_shadows(true);
+        let mut ball = crate::shapes::prefab::glass_sphere();
+        ball.set_transform(crate::transformations::translation(0.0, 1.0, 0.0))
+            .unwrap();
+        ball.material.color = Color::new(1.0, 0.2, 0.2);
+        ball.material.transparency = 0.9;
+        w.objects.push(ball);
+        w.planes.push(Plane::new());
+        w
+    }
+
+    #[test]
+    fn a_red_glass_sphere_casts_a_red_shadow() {
+        let floor_color = |w: &WorldBuilder| {
+            let r = ray(point(0.0, 0.5, -1.0), vector(0.0, -0.5, 1.0).normalize());
+            let xs = w.planes[0].intersect(r);
+            w.shade_hit(xs[0].prepare_computations(r, Some(xs.clone())))
+        };
+        let shadowed = floor_color(&red_glass_over_a_floor());
+        let mut without_ball = red_glass_over_a_floor();
+        without_ball.objects.clear();
+        let lit = floor_color(&without_ball);
+        assert!(shadowed.red < lit.red);
+        assert!(shadowed.green / lit.green < shadowed.red / lit.red);
+        assert!(shadowed.blue / lit.blue < shadowed.red / lit.red);
+    }
+
+    #[test]
+    fn light_is_filtered_once_per_transparent_occluder() {
+        let w = red_glass_over_a_floor();
+        let light = w.light.as_ref().unwrap();
+        let transmitted = w.shadow_attenuation(point(0.0, 0.01, 0.0), light);
+        crate::assert_color_eq!(transmitted, Color::new(0.9, 0.18, 0.18));
+    }
+
+    #[test]
+    fn transparent_occluders_block_light_unless_colored_shadows_are_on() {
+        let mut w = red_glass_over_a_floor();
+        w.options.colored_shadows = false;
+        let light = w.light.as_ref().unwrap();
+        assert_eq!(
+            w.shadow_attenuation(point(0.0, 0.01, 0.0), light),
+            COLOR_BLACK
+        );
+    }
+
+    #[test]
+    fn opaque_occluders_attenuate_exactly_like_is_shadowed() {
+        let mut w = default_world();
+        w.options.colored_shadows = true;
+        let light = w.light.as_ref().unwrap();
+        for p in [
+            point(0.0, 10.0, 0.0),
+            point(10.0, -10.0, 10.0),
+            point(-20.0, 20.0, -20.0),
+            point(-2.0, 2.0, -2.0),
+        ] {
+            let transmitted = w.shadow_attenuation(p, light);
+            let expected = if w.is_shadowed(p) {
+                COLOR_BLACK
+            } else {
+                COLOR_WHITE
+            };
+            assert_eq!(transmitted.red, expected.red);
+            assert_eq!(transmitted.green, expected.green);
+            assert_eq!(transmitted.blue, expected.blue);
+        }
+    }
+
+    #[test]
+    fn shadow_queries_name_the_nearest_occluder() {
+        let w = default_world();
+        let light = w.light.as_ref().unwrap();
+        let blocked = w.shadow_query(point(10.0, -10.0, 10.0), light);
+        assert!(blocked.occluded);
+        assert_eq!(blocked.occluder_id, Some(w.objects[0].id));
+        // The outer sphere's near side, one unit short of its center.
+        crate::check_floats!(blocked.occluder_t.unwrap(), (300.0 as Float).sqrt() - 1.0);
+        assert_eq!(blocked.attenuation, COLOR_BLACK);
+
+        let clear = w.shadow_query(point(-2.0, 2.0, -2.0), light);
+        assert!(!clear.occluded);
+        assert!(clear.occluder.is_none() && clear.occluder_t.is_none());
+        assert_eq!(clear.occluder_id, None);
+        assert_eq!(clear.attenuation, COLOR_WHITE);
+    }
+
+    #[test]
+    fn a_shadow_only_plane_darkens_the_floor_without_being_seen() {
+        let floor_world = |with_catcher: bool| {
+            let mut w = WorldBuilder::with_light(point_light(
+                point(0.0, 10.0, 0.0),
+                Color::new(1.0, 1.0, 1.0),
+            ));
+            w.planes.push(Plane::new());
+            if with_catcher {
+                let mut catcher = Plane::new();
+                catcher
+                    .set_transform(crate::transformations::translation(0.0, 2.0, 0.0))
+                    .unwrap();
+                catcher.material.visible_to_camera = false;
+                catcher.material.visible_in_reflections = false;
+                w.planes.push(catcher);
+            }
+            w
+        };
+        let r = ray(point(0.0, 5.0, -5.0), vector(0.0, -1.0, 1.0).normalize());
+        let w = floor_world(true);
+        let xs = w.intersect(r);
+        assert_eq!(xs.len(), 1);
+        crate::assert_same_object!(xs[0].object, &w.planes[0]);
+        let lit = floor_world(false).color_at(r);
+        let shadowed = w.color_at(r);
+        assert!(shadowed.red < lit.red);
+        crate::assert_color_eq!(shadowed, Color::new(0.1, 0.1, 0.1));
+    }
+
+    #[test]
+    fn a_camera_invisible_object_still_shows_in_a_mirror() {
+        let mirror_world = |visible_in_reflections: bool| {
+            let mut w = default_world();
+            w.objects.clear();
+            let mut ball = Sphere::new();
+            ball.material.color = Color::new(1.0, 0.0, 0.0);
+            ball.material.visible_to_camera = false;
+            ball.material.visible_in_reflections = visible_in_reflections;
+            w.objects.push(ball);
+            let mut mirror = Plane::new();
+            mirror
+                .set_transform(
+                    crate::transformations::translation(0.0, 0.0, 3.0)
+                        * crate::transformations::rotation_x(PI / 2.0),
+                )
+                .unwrap();
+            mirror.material = Material {
+                color: COLOR_BLACK,
+                ambient: 0.0,
+                diffuse: 0.0,
+                specular: 0.0,
+                reflective: 1.0,
+                ..Material::new()
+            };
+            w.planes.push(mirror);
+            w
+        };
+        let r = ray(point(0.0, 0.0, -5.0), vector(0.0, 0.0, 1.0));
+        let w = mirror_world(true);
+        assert!(w.intersect(r).iter().all(|i| i.t > 7.0));
+        let reflected = w.color_at(r);
+        assert!(reflected.red > 0.0);
+        assert_eq!(reflected.green, 0.0);
+        assert_eq!(mirror_world(false).color_at(r), COLOR_BLACK);
+    }
+
+    #[test]
+    fn shapes_that_cast_no_shadow_are_skipped_by_shadow_rays() {
+        let mut w = default_world();
+        let p = point(10.0, -10.0, 10.0);
+        assert!(w.is_shadowed(p));
+        for s in w.objects.iter_mut() {
+            s.material.casts_shadow = false;
+        }
+        assert!(!w.is_shadowed(p));
+        assert_eq!(
+            w.intersect(ray(p, vector(-1.0, 1.0, -1.0).normalize()))
+                .len(),
+            4
+        );
+    }
+
+    // A plane at z = 0 whose front faces -z, showing only that side.
+    fn one_sided_wall(sidedness: Sidedness) -> Plane {
+        let mut wall = Plane::new();
+        wall.set_transform(crate::transformations::rotation_x(-PI / 2.0))
+            .unwrap();
+        wall.material.sidedness = sidedness;
+        wall
+    }
+
+    #[test]
+    fn a_front_only_surface_hides_what_is_behind_it_from_the_front_only() {
+        let mut w = WorldBuilder::new();
+        w.planes.push(one_sided_wall(Sidedness::FrontOnly));
+        w.objects
+            .push(Sphere::with_transform(translation(2.0, 0.0, 3.0)).unwrap());
+        w.objects
+            .push(Sphere::with_transform(translation(-2.0, 0.0, -3.0)).unwrap());
+        let looking_at = |from: Tuple4, to: Tuple4| ray(from, (to - from).normalize());
+
+        let from_front = looking_at(point(0.0, 0.0, -10.0), point(2.0, 0.0, 3.0));
+        let h = hit(&w.intersect(from_front)).unwrap();
+        crate::assert_same_object!(h.object, &w.planes[0]);
+
+        // From behind, the ray passes through the wall to the sphere beyond.
+        let from_behind = looking_at(point(0.0, 0.0, 10.0), point(-2.0, 0.0, -3.0));
+        let h = hit(&w.intersect(from_behind)).unwrap();
+        crate::assert_same_object!(h.object, &w.objects[1]);
+    }
+
+    #[test]
+    fn one_sided_surfaces_only_shadow_what_the_light_sees_them_from() {
+        let mut w = WorldBuilder::with_light(point_light(
+            point(0.0, 0.0, -10.0),
+            Color::new(1.0, 1.0, 1.0),
+        ));
+        w.planes.push(one_sided_wall(Sidedness::FrontOnly));
+        // The light faces the wall's front, so it casts a shadow behind it.
+        assert!(w.is_shadowed(point(0.0, 0.0, 5.0)));
+
+        w.light = Some(point_light(
+            point(0.0, 0.0, 10.0),
+            Color::new(1.0, 1.0, 1.0),
+        ));
+        assert!(!w.is_shadowed(point(0.0, 0.0, -5.0)));
+
+        w.planes[0].material.sidedness = Sidedness::BackOnly;
+        assert!(w.is_shadowed(point(0.0, 0.0, -5.0)));
+        assert_eq!(
+            w.shadow_attenuation(point(0.0, 0.0, -5.0), w.light.as_ref().unwrap()),
+            COLOR_BLACK
+        );
+    }
+
+    #[test]
+    fn the_color_policy_decides_whether_negative_colors_are_clamped() {
+        use crate::options::ColorPolicy;
+        let shade = |policy: ColorPolicy, color: Color, intensity: Float| {
+            let mut w = default_world();
+            w.light = Some(point_light(
+                point(-10.0, 10.0, -10.0),
+                Color::new(1.0, 1.0, 1.0) * intensity,
+            ));
+            w.objects[0].material.color = color;
+            w.options = RenderOptions::new().with_color_policy(policy);
+            w.color_at(ray(point(0.0, 0.0, -5.0), vector(0.0, 0.0, 1.0)))
+        };
+        let green = Color::new(0.8, 1.0, 0.6);
+        // Bright lights stay bright either way, for the tone mapper.
+        let bright = Color::new(0.38066, 0.47583, 0.2855) * 5.0;
+        crate::assert_color_eq!(shade(ColorPolicy::AllowHdr, green, 5.0), bright);
+        crate::assert_color_eq!(shade(ColorPolicy::Clamp, green, 5.0), bright);
+
+        let negative = Color::new(-1.0, 1.0, 0.6);
+        let hdr = shade(ColorPolicy::AllowHdr, negative, 1.0);
+        assert!(hdr.red < 0.0, "{hdr}");
+        let clamped = shade(ColorPolicy::Clamp, negative, 1.0);
+        assert_eq!(clamped.red, 0.0);
+        assert_eq!((clamped.green, clamped.blue), (hdr.green, hdr.blue));
+    }
+
+    #[test]
+    fn a_point_in_open_space_is_unoccluded() {
+        let mut w = default_world();
+        w.planes.push(Plane::new());
+        let ao = AmbientOcclusion {
+            samples: 32,
+            max_distance: 5.0,
+        };
+        let open = w.ambient_occlusion(point(20.0, 0.001, 0.0), vector(0.0, 1.0, 0.0), ao);
+        assert_eq!(open, 1.0);
+    }
+
+    #[test]
+    fn the_bottom_of_a_narrow_well_is_almost_fully_occluded() {
+        use crate::transformations::{rotation_x, rotation_z, translation};
+        let mut w = WorldBuilder::new();
+        w.planes.push(Plane::new());
+        for (x, z) in [(0.2, 0.0), (-0.2, 0.0)] {
+            let mut wall = Plane::new();
+            wall.set_transform(translation(x, 0.0, z) * rotation_z(PI / 2.0))
+                .unwrap();
+            w.planes.push(wall);
+        }
+        for z in [0.2, -0.2] {
+            let mut wall = Plane::new();
+            wall.set_transform(translation(0.0, 0.0, z) * rotation_x(PI / 2.0))
+                .unwrap();
+            w.planes.push(wall);
+        }
+        let ao = AmbientOcclusion {
+            samples: 64,
+            max_distance: 100.0,
+        };
+        let open = w.ambient_occlusion(point(0.0, 0.001, 0.0), vector(0.0, 1.0, 0.0), ao);
+        assert!(open < 0.05, "{open}");
+    }
+
+    #[test]
+    fn occlusion_dims_only_the_ambient_term() {
+        let shade = |options: RenderOptions| {
+            let mut w = default_world();
+            w.options = options;
+            let mut floor = Plane::new();
+            floor
+                .set_transform(crate::transformations::translation(0.0, -1.0, 0.0))
+                .unwrap();
+            w.planes.push(floor);
+            let r = ray(point(0.0, 0.0, -5.0), vector(0.0, 0.0, 1.0));
+            w.color_at(r)
+        };
+        let plain = shade(RenderOptions::new());
+        crate::assert_color_eq!(plain, Color::new(0.38066, 0.47583, 0.2855));
+        let occluded = shade(RenderOptions::new().with_ambient_occlusion(16, 10.0));
+        assert!(occluded.red < plain.red);
+        assert!(occluded.red > plain.red - 0.1 * 0.8);
+    }
+
+    #[test]
+    fn describing_the_default_world() {
+        let w = default_world();
+        let text = w.describe();
+        assert!(text.starts_with("World: 2 spheres, 0 planes, 0 other shapes"));
+        assert!(text.contains("light at point(-10, 10, -10)"));
+        assert!(text.contains("  objects[0]: Sphere(identity; "));
+        assert!(text.contains("  objects[1]: Sphere(scale (0.5, 0.5, 0.5); "));
+        assert!(!text.contains("id="));
+        assert!(text.contains("color (0.8, 1, 0.6), ambient 0.1, diffuse 0.7"));
+        assert_eq!(text, w.describe());
+        assert_eq!(text, default_world().describe());
+    }
+
+    #[test]
+    fn describing_planes_and_other_shapes() {
+        let mut w = WorldBuilder::new();
+        let mut floor = Plane::new();
+        floor
+            .set_transform(crate::transformations::translation(0.0, -1.0, 0.0))
+            .unwrap();
+        floor.material.reflective = 0.5;
+        w.planes.push(floor);
+        w.shapes
+            .push(Box::new(crate::rectangles::Rectangle::new(2.0, 1.0)));
+        let text = w.describe();
+        assert!(text.contains("  no light"));
+        assert!(text.contains("  planes[0]: Plane(translate (0, -1, 0); color (1, 1, 1)"));
+        assert!(text.contains("reflective 0.5"));
+        assert!(text.contains("  shapes[0]: Rectangle(identity; "));
+    }
+
+    // A glass window made of one rectangle, with a checkered wall behind it
+    // that shines by its own ambient light. The window neither shades nor
+    // shadows, so only what it lets through or reflects shows.
+    fn window_world(thin: bool, with_window: bool) -> WorldBuilder {
+        use crate::{
+            patterns::checkers_pattern, rectangles::Rectangle, transformations::translation,
+        };
+        let mut w = WorldBuilder::with_light(point_light(
+            point(0.0, 0.0, -10.0),
+            Color::new(1.0, 1.0, 1.0),
+        ));
+        let facing_camera = crate::transformations::rotation_x(-PI / 2.0);
+        let mut wall = Plane::new();
+        wall.set_transform(translation(0.0, 0.0, 5.0) * facing_camera)
+            .unwrap();
+        let mut checkers = checkers_pattern(Color::new(1.0, 1.0, 1.0), Color::new(0.5, 0.5, 0.5));
+        // Keeps the wall's surface away from a checker boundary.
+        checkers.transform = translation(0.0, 0.5, 0.0);
+        wall.material.pattern = Some(Arc::new(checkers));
+        wall.material.ambient = 1.0;
+        wall.material.diffuse = 0.0;
+        wall.material.specular = 0.0;
+        w.planes.push(wall);
+        if with_window {
+            let mut window = Rectangle::new(10.0, 10.0);
+            window.set_transform(facing_camera).unwrap();
+            window.material.ambient = 0.0;
+            window.material.diffuse = 0.0;
+            window.material.specular = 0.0;
+            window.material.transparency = 0.9;
+            window.material.reflective = 0.1;
+            window.material.refractive_index = 1.5;
+            window.material.casts_shadow = false;
+            window.material.thin_surface = thin;
+            w.shapes.push(Box::new(window));
+        }
+        w
+    }
+
+    fn window_ray() -> Ray {
+        ray(point(0.3, 0.5, -5.0), vector(0.5, 0.0, 1.0).normalize())
+    }
+
+    fn refracted_through_window(w: &WorldBuilder) -> Color {
+        let r = window_ray();
+        let xs = w.intersect(r);
+        let comps = xs[0].prepare_computations(r, Some(xs.clone()));
+        w.refracted_color(&comps)
+    }
+
+    #[test]
+    fn a_thin_window_shows_the_wall_undistorted() {
+        use crate::check_colors;
+        let wall = window_world(true, false).color_at(window_ray());
+        let w = window_world(true, true);
+        check_colors!(refracted_through_window(&w), wall * 0.9);
+
+        // Schlick sends some of the light into the (black) reflection.
+        let seen = w.color_at(window_ray());
+        assert!(seen.red < wall.red * 0.9 && seen.red > wall.red * 0.8);
+    }
+
+    #[test]
+    fn a_solid_window_still_bends_the_view() {
+        let wall = window_world(false, false).color_at(window_ray());
+        let w = window_world(false, true);
+        assert_ne!(refracted_through_window(&w), wall * 0.9);
+    }
+
+    #[test]
+    fn light_through_absorbing_glass_falls_off_with_distance() {
+        use crate::check_colors;
+        let mut w = WorldBuilder::with_light(point_light(
+            point(0.0, 0.0, -10.0),
+            Color::new(1.0, 1.0, 1.0),
+        ));
+        w.options.background = COLOR_WHITE.into();
+        // Index 1 keeps the ray straight, so it crosses the full diameter.
+        let mut glass = Sphere::new();
+        glass.material.ambient = 0.0;
+        glass.material.diffuse = 0.0;
+        glass.material.specular = 0.0;
+        glass.material.transparency = 1.0;
+        glass.material.refractive_index = 1.0;
+        glass.material.absorbance = Color::new(0.1, 0.2, 0.5);
+        glass.material.casts_shadow = false;
+        w.objects.push(glass);
+
+        let r = ray(point(0.0, 0.0, -5.0), vector(0.0, 0.0, 1.0));
+        let xs = w.intersect(r);
+        let comps = xs[0].prepare_computations(r, Some(xs.clone()));
+        assert!(comps.entering());
+        let expected = Color::new(
+            (-0.2 as Float).exp(),
+            (-0.4 as Float).exp(),
+            (-1.0 as Float).exp(),
+        );
+        check_colors!(w.refracted_color(&comps), expected);
+
+        let leaving = xs[1].prepare_computations(r, Some(xs.clone()));
+        assert!(!leaving.entering());
+        check_colors!(w.refracted_color(&leaving), COLOR_WHITE);
+    }
+
+    #[test]
+    fn absorbing_glass_dims_only_up_to_the_object_inside_it() {
+        use crate::check_colors;
+        let mut w = WorldBuilder::with_light(point_light(
+            point(0.0, 0.0, -10.0),
+            Color::new(1.0, 1.0, 1.0),
+        ));
+        let mut glass = Sphere::with_transform(scaling(2.0, 2.0, 2.0)).unwrap();
+        glass.material.ambient = 0.0;
+        glass.material.diffuse = 0.0;
+        glass.material.specular = 0.0;
+        glass.material.transparency = 1.0;
+        glass.material.refractive_index = 1.0;
+        glass.material.absorbance = Color::new(0.1, 0.2, 0.5);
+        w.objects.push(glass);
+        let mut core = Sphere::with_transform(scaling(0.5, 0.5, 0.5)).unwrap();
+        core.material.ambient = 1.0;
+        core.material.diffuse = 0.0;
+        core.material.specular = 0.0;
+        w.objects.push(core);
+
+        // The ray goes in at z = -2 and meets the core at z = -0.5.
+        let r = ray(point(0.0, 0.0, -5.0), vector(0.0, 0.0, 1.0));
+        let xs = w.intersect(r);
+        let comps = xs[0].prepare_computations(r, Some(xs.clone()));
+        let expected = Color::new(
+            (-0.15 as Float).exp(),
+            (-0.3 as Float).exp(),
+            (-0.75 as Float).exp(),
+        );
+        check_colors!(w.refracted_color(&comps), expected);
+    }
+
+    #[test]
+    fn segments_of_nested_spheres_nest() {
+        let mut w = WorldBuilder::new();
+        w.objects
+            .push(Sphere::with_transform(scaling(2.0, 2.0, 2.0)).unwrap());
+        w.objects.push(Sphere::new());
+        let segments = w.ray_segments(ray(point(0.0, 0.0, -5.0), vector(0.0, 0.0, 1.0)));
+        assert_eq!(segments.len(), 2);
+        let (outer, inner) = (segments[0], segments[1]);
+        crate::assert_same_object!(outer.object, &w.objects[0]);
+        crate::assert_same_object!(inner.object, &w.objects[1]);
+        crate::check_floats!(outer.t_enter, 3.0);
+        crate::check_floats!(outer.t_exit, 7.0);
+        crate::check_floats!(inner.t_enter, 4.0);
+        crate::check_floats!(inner.t_exit, 6.0);
+        crate::check_floats!(inner.length(), 2.0);
+    }
+
+    #[test]
+    fn a_segment_left_open_runs_to_infinity() {
+        let mut w = WorldBuilder::new();
+        w.planes.push(Plane::new());
+        let segments = w.ray_segments(ray(point(0.0, 1.0, 0.0), vector(0.0, -1.0, 0.0)));
+        assert_eq!(segments.len(), 1);
+        crate::check_floats!(segments[0].t_enter, 1.0);
+        assert_eq!(segments[0].t_exit, Float::INFINITY);
+    }
+
+    // A floor and a ball lit from 5 degrees above the horizon, seen from
+    // straight above.
+    fn low_sun_world() -> WorldBuilder {
+        let elevation = (5.0 as Float).to_radians();
+        let mut w = WorldBuilder::with_light(point_light(
+            point(100.0 * elevation.cos(), 100.0 * elevation.sin(), 0.0),
+            COLOR_WHITE,
+        ));
+        w.planes.push(Plane::new());
+        w.objects.push(
+            Sphere::with_transform(crate::transformations::translation(0.0, 1.0, 0.0)).unwrap(),
+        );
+        w
+    }
+
+    // A floor so far below the origin that heights on it are only stored to
+    // the nearest 1/256, lit from 5 degrees above the horizon, and how many
+    // points on a 20 x 20 grid of it are wrongly in shadow. A bias of
+    // EPSILON is under half that step, so the offset rounds away and shadow
+    // rays start on the floor itself.
+    fn distant_floor_acne(options: RenderOptions) -> usize {
+        let depth = 3.0 * (2.0 as Float).powi(Float::MANTISSA_DIGITS as i32 - 10);
+        let elevation = (5.0 as Float).to_radians();
+        let mut w = WorldBuilder::with_light(point_light(
+            point(
+                100.0 * elevation.cos(),
+                100.0 * elevation.sin() - depth,
+                0.0,
+            ),
+            COLOR_WHITE,
+        ));
+        w.options = options;
+        w.planes.push(Plane::new());
+        w.planes[0]
+            .set_transform(crate::transformations::translation(0.0, -depth, 0.0))
+            .unwrap();
+        let ambient_only = Color::new(0.1, 0.1, 0.1);
+        (0..400)
+            .filter(|k| {
+                let (x, z) = ((k / 20) as Float * 0.1, (k % 20) as Float * 0.1);
+                let c = w.color_at(ray(point(x, 5.0 - depth, z), vector(0.0, -1.0, 0.0)));
+                c.red < ambient_only.red * 1.5
+            })
+            .count()
+    }
+
+    #[test]
+    fn a_floor_lit_at_a_grazing_angle_has_no_acne() {
+        // A fixed bias leaves every point in its own shadow.
+        let fixed = RenderOptions::new().with_max_shadow_bias(EPSILON);
+        assert_eq!(distant_floor_acne(fixed), 400);
+        assert_eq!(distant_floor_acne(RenderOptions::new()), 0);
+
+        let w = low_sun_world();
+        let ambient_only = Color::new(0.1, 0.1, 0.1);
+        for i in 0..40 {
+            for j in 0..40 {
+                let (x, z) = (1.5 + i as Float * 0.1, j as Float * 0.1 - 2.0);
+                let c = w.color_at(ray(point(x, 5.0, z), vector(0.0, -1.0, 0.0)));
+                assert!(c.red > ambient_only.red * 1.5, "acne at ({x}, {z}): {c}");
+            }
+        }
+    }
+
+    #[test]
+    fn contact_shadows_stay_tight_with_the_adaptive_bias() {
+        use crate::check_colors;
+        let w = low_sun_world();
+        // Start each ray under the ball, just above the floor.
+        for (x, above) in [(-0.1, 0.003), (-0.3, 0.02), (-2.0, 0.5)] {
+            let c = w.color_at(ray(point(x, above, 0.0), vector(0.0, -1.0, 0.0)));
+            check_colors!(c, Color::new(0.1, 0.1, 0.1));
+        }
+    }
+
+    #[test]
+    fn stereo_with_no_separation_matches_the_mono_render() {
+        let mut c = crate::camera::Camera::new(11, 11, PI / 2.0);
+        c.set_transform(crate::transformations::view_transform(
+            point(0.0, 0.0, -5.0),
+            point(0.0, 0.0, 0.0),
+            vector(0.0, 1.0, 0.0),
+        ));
+        let w = default_world();
+        let mono = render_pixels(&c, &w).to_ppm();
+        let (left, right) = render_stereo(&c, &w, 0.0, 5.0);
+        assert_eq!(left.to_ppm(), mono);
+        assert_eq!(right.to_ppm(), mono);
+    }
+
+    #[test]
+    fn stereo_shifts_a_foreground_sphere_in_opposite_directions() {
+        let mut c = crate::camera::Camera::new(41, 21, PI / 3.0);
+        c.set_transform(crate::transformations::view_transform(
+            point(0.0, 0.0, -5.0),
+            point(0.0, 0.0, 0.0),
+            vector(0.0, 1.0, 0.0),
+        ));
+        let mut w = WorldBuilder::with_light(point_light(point(0.0, 0.0, -10.0), COLOR_WHITE));
+        w.objects
+            .push(Sphere::with_transform(scaling(0.5, 0.5, 0.5)).unwrap());
+        // The mean column of the pixels the sphere covers.
+        let center = |canvas: &Canvas| {
+            let xs: Vec<usize> = (0..canvas.height)
+                .flat_map(|y| (0..canvas.width).map(move |x| (x, y)))
+                .filter(|&(x, y)| canvas.pixel_at(x, y) != COLOR_BLACK)
+                .map(|(x, _)| x)
+                .collect();
+            xs.iter().sum::<usize>() as Float / xs.len() as Float
+        };
+        let mono = center(&render_pixels(&c, &w));
+        // Converging behind the sphere brings it forward of the screen.
+        let (left, right) = render_stereo(&c, &w, 0.5, 20.0);
+        let (l, r) = (center(&left), center(&right));
+        assert!(l > mono + 0.5 && r < mono - 0.5, "{l} {mono} {r}");
+    }
+
+    #[test]
+    fn the_bounds_of_a_world_leave_out_planes() {
+        let mut w = default_world();
+        w.objects.push(
+            Sphere::with_transform(crate::transformations::translation(5.0, 0.0, 0.0)).unwrap(),
+        );
+        w.planes.push(Plane::new());
+        let (b, unbounded) = w.bounds();
+        assert_eq!(b.min, point(-1.0, -1.0, -1.0));
+        assert_eq!(b.max, point(6.0, 1.0, 1.0));
+        assert_eq!(unbounded, 1);
+        let (b, unbounded) = WorldBuilder::new().bounds();
+        assert!(b.is_empty());
+        assert_eq!(unbounded, 0);
+    }
+
+    #[test]
+    fn framing_a_unit_sphere_fills_most_of_the_image() {
+        let mut w = WorldBuilder::with_light(point_light(point(-10.0, 10.0, -10.0), COLOR_WHITE));
+        w.objects.push(Sphere::new());
+        let mut c = crate::camera::Camera::new(50, 50, PI / 2.0);
+        c.frame(&w.bounds().0, vector(0.0, 0.0, 1.0));
+        let eye = c.transform().inverse() * point(0.0, 0.0, 0.0);
+        // The near face of the box is 1 unit in front of the center and must
+        // be 1 / tan(pi / 4) away to fit, plus the margin.
+        assert!(eye.z < -2.0 && eye.z > -2.2, "{eye}");
+
+        let image = render_pixels(&c, &w);
+        let covered: Vec<(usize, usize)> = (0..50)
+            .flat_map(|y| (0..50).map(move |x| (x, y)))
+            .filter(|&(x, y)| image.pixel_at(x, y) != COLOR_BLACK)
+            .collect();
+        let xs = covered.iter().map(|p| p.0);
+        let ys = covered.iter().map(|p| p.1);
+        let (x0, x1) = (xs.clone().min().unwrap(), xs.max().unwrap());
+        let (y0, y1) = (ys.clone().min().unwrap(), ys.max().unwrap());
+        assert!(x0 > 0 && y0 > 0 && x1 < 49 && y1 < 49);
+        assert!(x1 - x0 > 25 && y1 - y0 > 25);
+    }
+
+    #[test]
+    fn a_subtle_reflection_still_shows() {
+        let mut w = default_world();
+        let mut shape = Plane::new();
+        shape.material.reflective = 0.001;
+        shape
+            .set_transform(crate::transformations::translation(0.0, -1.0, 0.0))
+            .unwrap();
+        w.planes.push(shape);
+        let r = ray(
+            point(0.0, 0.0, -3.0),
+            vector(0.0, -SQRT_2 / 2.0, SQRT_2 / 2.0),
+        );
+        let comps = Intersection::new(SQRT_2, &w.planes[0]).prepare_computations(r, None);
+        let color = w.reflected_color(&comps);
+        // The book's 0.5-reflective floor, scaled down.
+        let expected = Color::new(0.19032, 0.2379, 0.14274) * 0.002;
+        crate::check_floats!(color.green, expected.green);
+        assert!(color.green > 0.0);
+    }
+
+    // Two facing mirrors with a camera ray bouncing between them forever.
+    fn hall_of_mirrors(options: RenderOptions) -> Color {
+        let mut w = WorldBuilder::with_light(point_light(point(0.0, 0.0, -5.0), COLOR_WHITE));
+        w.options = options;
+        for y in [-1.0, 1.0] {
+            let mut mirror = Plane::new();
+            mirror
+                .set_transform(crate::transformations::translation(0.0, y, 0.0))
+                .unwrap();
+            mirror.material.reflective = 0.1;
+            w.planes.push(mirror);
+        }
+        w.color_at(ray(point(0.0, 0.0, 0.0), vector(0.0, 1.0, 1.0).normalize()))
+    }
+
+    #[test]
+    fn faint_reflections_are_culled_before_the_depth_limit() {
+        use crate::check_colors;
+        let culled = hall_of_mirrors(
+            RenderOptions::new()
+                .with_max_recursion_depth(20)
+                .with_min_throughput(0.002),
+        );
+        // 0.1 * 0.1 * 0.1 falls below 0.002, so the third bounce is never
+        // traced, just as if the depth limit were 3.
+        let shallow = hall_of_mirrors(
+            RenderOptions::new()
+                .with_max_recursion_depth(3)
+                .with_min_throughput(0.0),
+        );
+        check_colors!(culled, shallow);
+        let full = hall_of_mirrors(
+            RenderOptions::new()
+                .with_max_recursion_depth(20)
+                .with_min_throughput(0.0),
+        );
+        for (a, b) in [
+            (culled.red, full.red),
+            (culled.green, full.green),
+            (culled.blue, full.blue),
+        ] {
+            assert!((a - b).abs() < 0.002, "{culled} vs {full}");
+        }
+    }
+
+    #[test]
+    fn intersect_any_agrees_with_the_nearest_hit() {
+        let mut w = default_world();
+        w.planes.push(one_sided_wall(Sidedness::FrontOnly));
+        let mut floor = Plane::new();
+        floor.set_transform(translation(0.0, -1.5, 0.0)).unwrap();
+        w.planes.push(floor);
+        let mut ghost = Sphere::with_transform(translation(2.0, 0.5, 1.0)).unwrap();
+        ghost.material.casts_shadow = false;
+        w.objects.push(ghost);
+        let mut panel = crate::rectangles::Rectangle::new(1.0, 2.0);
+        panel.set_transform(translation(-1.5, 0.0, 0.5)).unwrap();
+        w.shapes.push(Box::new(panel));
+
+        let mut checked = 0;
+        for kind in [RayKind::Camera, RayKind::Shadow] {
+            for ox in [-3.0, -0.5, 0.0, 0.7, 2.5] {
+                for oz in [-4.0, -0.2, 0.0, 3.0] {
+                    for (dx, dy, dz) in [
+                        (1.0, 0.1, 0.3),
+                        (-0.4, -1.0, 0.2),
+                        (0.0, 0.3, 1.0),
+                        (0.2, 0.5, -1.0),
+                    ] {
+                        let r = Ray::new(point(ox, 0.2, oz), vector(dx, dy, dz).normalize())
+                            .with_kind(kind);
+                        for t_max in [0.5, 2.0, 6.0, Float::INFINITY] {
+                            let expected = hit_for(&w.intersect_bounded(r, t_max), kind).is_some();
+                            assert_eq!(w.intersect_any(r, t_max), expected, "{r:?} to {t_max}");
+                            checked += expected as usize;
+                        }
+                    }
+                }
+            }
+        }
+        assert!(checked > 0);
+    }
+
+    #[test]
+    fn the_aov_pass_traces_each_primary_ray_once() {
+        let mut c = crate::camera::Camera::new(12, 8, PI / 2.0);
+        c.set_transform(crate::transformations::view_transform(
+            point(0.0, 0.0, -5.0),
+            point(0.0, 0.0, 0.0),
+            vector(0.0, 1.0, 0.0),
+        ));
+        let d = default_world();
+        let counting = Arc::new(crate::metrics::CountingMetrics::new());
+        let mut w = d.clone();
+        w.options = w.options.with_metrics(counting.clone());
+
+        let aovs = render_aovs(&c, &w.build());
+        // Opaque, unreflective shapes only send shadow rays besides.
+        assert_eq!(counting.get(MetricKey::rays(RayKind::Camera)), 12 * 8);
+        assert_eq!(aovs.color.to_ppm(), render_pixels(&c, &d).to_ppm());
+
+        let (x, y) = (6, 4);
+        let n = aovs.normal.pixel_at(x, y);
+        assert!(n.blue < -0.8, "{n}");
+        assert_eq!(aovs.albedo.pixel_at(x, y), d.objects[0].material.color);
+        assert_eq!(aovs.albedo.pixel_at(0, 0), COLOR_BLACK);
+    }
+
+    #[test]
+    fn sample_offsets_cover_the_pixel_row_by_row() {
+        let centers: Vec<_> = sample_offsets(2, (0.5, 0.5)).collect();
+        assert_eq!(
+            centers,
+            vec![(0.25, 0.25), (0.75, 0.25), (0.25, 0.75), (0.75, 0.75)]
+        );
+        let corners: Vec<_> = sample_offsets(2, (0.0, 0.0)).collect();
+        assert_eq!(corners[3], (0.5, 0.5));
+        assert_eq!(
+            sample_offsets(1, (0.3, 0.7)).collect::<Vec<_>>(),
+            vec![(0.3, 0.7)]
+        );
+    }
+
+    #[test]
+    fn set_time_poses_animated_patterns_and_leaves_static_ones() {
+        use crate::patterns::{AnimatedPattern, Pattern, stripe_pattern};
+        let white = Color::new(1.0, 1.0, 1.0);
+        let black = Color::new(0.0, 0.0, 0.0);
+        let scrolling: Arc<dyn Pattern> = Arc::new(AnimatedPattern::new(
+            Arc::new(stripe_pattern(white, black)),
+            |t| translation(t, 0.0, 0.0),
+        ));
+        let still: Arc<dyn Pattern> = Arc::new(stripe_pattern(white, black));
+
+        let mut w = WorldBuilder::new();
+        let shared = Material {
+            pattern: Some(scrolling),
+            ..Material::new()
+        }
+        .shared();
+        for _ in 0..2 {
+            let mut s = Sphere::new();
+            s.shared_material = Some(shared.clone());
+            w.objects.push(s);
+        }
+        let mut floor = Plane::new();
+        floor.material.pattern = Some(still.clone());
+        w.planes.push(floor);
+
+        let p = point(0.25, 0.0, 0.0);
+        let color = |shape: &dyn Shape| {
+            let m = shape.material();
+            m.pattern.as_ref().unwrap().pattern_at_shape(shape, p)
+        };
+        assert_eq!(color(&w.objects[0]), white);
+        w.set_time(0.5);
+        assert_eq!(color(&w.objects[0]), black);
+        let (a, b) = (&w.objects[0], &w.objects[1]);
+        assert!(Arc::ptr_eq(
+            a.shared_material.as_ref().unwrap(),
+            b.shared_material.as_ref().unwrap()
+        ));
+        assert!(Arc::ptr_eq(
+            w.planes[0].material.pattern.as_ref().unwrap(),
+            &still
+        ));
+    }
+
+    #[test]
+    fn render_animation_renders_a_frame_per_time() {
+        let mut c = crate::camera::Camera::new(5, 5, PI / 2.0);
+        c.set_transform(crate::transformations::view_transform(
+            point(0.0, 0.0, -5.0),
+            point(0.0, 0.0, 0.0),
+            vector(0.0, 1.0, 0.0),
+        ));
+        let mut w = default_world();
+        let mut times = vec![];
+        render_animation(&c, &mut w, [0.0, 0.25, 0.5], |t, frame| {
+            assert_eq!((frame.width, frame.height), (5, 5));
+            times.push(t);
+        });
+        assert_eq!(times, [0.0, 0.25, 0.5]);
+    }
+
+    #[test]
+    fn object_masks_follow_the_object() {
+        let mut c = crate::camera::Camera::new(41, 41, PI / 3.0);
+        c.set_transform(crate::transformations::view_transform(
+            point(0.0, 0.0, -5.0),
+            point(0.0, 0.0, 0.0),
+            vector(0.0, 1.0, 0.0),
+        ));
+        let mut w = default_world();
+        let (outer, inner) = (w.objects[0].id, w.objects[1].id);
+        let mask_of = |w: &WorldBuilder, id: u64| {
+            let scene = w.clone().build();
+            let shape = scene.object(id).expect("the shape is in the scene");
+            mask_bounds(&render_object_mask(&c, &scene, shape))
+        };
+        let before = mask_of(&w, outer).expect("the outer sphere is in view");
+        assert!(before.area() > 0);
+        let center = |r: crate::camera::PixelRect| ((r.x0 + r.x1) / 2, (r.y0 + r.y1) / 2);
+        let (cx, cy) = center(before);
+        assert!(cx.abs_diff(20) <= 1 && cy.abs_diff(20) <= 1, "{before:?}");
+        // The inner sphere is hidden inside the outer one.
+        assert_eq!(mask_of(&w, inner), None);
+
+        w.objects[0]
+            .set_transform(translation(1.0, 0.0, 0.0))
+            .unwrap();
+        let after = mask_of(&w, outer).unwrap();
+        assert!(center(after).0 > cx + 2, "{before:?} -> {after:?}");
+        assert!(center(after).1.abs_diff(cy) <= 1);
+    }
+
+    #[test]
+    fn casting_a_ray_through_the_center_pixel() {
+        let w = default_world();
+        let mut c = crate::camera::Camera::new(11, 11, PI / 2.0);
+        c.set_transform(crate::transformations::view_transform(
+            point(0.0, 0.0, -5.0),
+            point(0.0, 0.0, 0.0),
+            vector(0.0, 1.0, 0.0),
+        ));
+        let hit = w.cast_ray(c.ray_for_pixel(5, 5)).unwrap();
+        assert_eq!(hit.object_id, w.objects[0].id);
+        crate::check_floats!(hit.t, 4.0);
+        assert_eq!(hit.point, point(0.0, 0.0, -1.0));
+        assert_eq!(hit.normal, vector(0.0, 0.0, -1.0));
+        assert!(!hit.inside);
+        assert_eq!(hit.material.color, w.objects[0].material.color);
+        assert!(w.cast_ray(c.ray_for_pixel(0, 0)).is_none());
+    }
+
+    #[test]
+    fn casting_a_ray_from_inside_flips_the_normal() {
+        let w = default_world();
+        let hit = w
+            .cast_ray(Ray::new(point(0.0, 0.0, 0.0), vector(0.0, 0.0, 1.0)))
+            .unwrap();
+        assert_eq!(hit.object_id, w.objects[1].id);
+        assert!(hit.inside);
+        assert_eq!(hit.normal, vector(0.0, 0.0, -1.0));
+    }
+
+    #[test]
+    fn custom_metrics_see_the_same_totals_as_counting_metrics() {
+        use crate::metrics::{CountingMetrics, Metrics};
+        use std::{collections::HashMap, sync::Mutex};
+
+        #[derive(Default)]
+        struct MapMetrics(Mutex<HashMap<MetricKey, u64>>);
+
+        impl Metrics for MapMetrics {
+            fn incr(&self, key: MetricKey, by: u64) {
+                *self.0.lock().unwrap().entry(key).or_default() += by;
+            }
+
+            fn timing(&self, key: MetricKey, nanos: u64) {
+                self.incr(key, nanos);
+            }
+        }
+
+        let mut c = crate::camera::Camera::new(11, 11, PI / 2.0);
+        c.set_transform(crate::transformations::view_transform(
+            point(0.0, 0.0, -5.0),
+            point(0.0, 0.0, 0.0),
+            vector(0.0, 1.0, 0.0),
+        ));
+        let map = Arc::new(MapMetrics::default());
+        let counting = Arc::new(CountingMetrics::new());
+        let mut w = default_world();
+        w.options = w.options.with_metrics(map.clone());
+        render_pixels(&c, &w);
+        w.options = w.options.with_metrics(counting.clone());
+        render_pixels(&c, &w);
+
+        let map = map.0.lock().unwrap();
+        assert_eq!(map[&MetricKey::PrimaryRays], 121);
+        assert!(map[&MetricKey::ShadowRays] > 0);
+        assert!(map[&MetricKey::PixelTimeNs] > 0);
+        for key in [
+            MetricKey::PrimaryRays,
+            MetricKey::ShadowRays,
+            MetricKey::ReflectRays,
+            MetricKey::RefractRays,
+            MetricKey::IntersectionTests,
+        ] {
+            let seen = map.get(&key).copied().unwrap_or(0);
+            assert_eq!(seen, counting.get(key), "{key:?}");
+        }
+    }
+
+    #[test]
+    fn disabled_metrics_are_never_called() {
+        use crate::metrics::Metrics;
+
+        struct Disabled;
+
+        impl Metrics for Disabled {
+            fn incr(&self, key: MetricKey, _by: u64) {
+                panic!("counted {key:?}");
+            }
+
+            fn timing(&self, key: MetricKey, _nanos: u64) {
+                panic!("timed {key:?}");
+            }
+
+            fn enabled(&self) -> bool {
+                false
+            }
+        }
+
+        let c = crate::camera::Camera::new(5, 5, PI / 2.0);
+        let mut w = default_world();
+        w.options = w.options.with_metrics(Arc::new(Disabled));
+        render_pixels(&c, &w);
+        assert!(!crate::metrics::NullMetrics.enabled());
+    }
+
+    #[test]
+    fn matte_scenes_never_work_out_a_reflection_vector() {
+        use crate::metrics::CountingMetrics;
+        let mut c = crate::camera::Camera::new(11, 11, PI / 2.0);
+        c.set_transform(crate::transformations::view_transform(
+            point(0.0, 0.0, -5.0),
+            point(0.0, 0.0, 0.0),
+            vector(0.0, 1.0, 0.0),
+        ));
+        let counting = Arc::new(CountingMetrics::new());
+        let mut w = default_world();
+        w.options = w.options.with_metrics(counting.clone());
+        let image = render_pixels(&c, &w);
+        crate::assert_color_eq!(image.pixel_at(5, 5), Color::new(0.38066, 0.47583, 0.2855));
+        assert_eq!(counting.get(MetricKey::ReflectVectors), 0);
+
+        w.objects[0].material.reflective = 0.5;
+        render_pixels(&c, &w);
+        assert!(counting.get(MetricKey::ReflectVectors) > 0);
+    }
+
+    #[test]
+    fn the_reflection_vector_is_worked_out_only_for_reflective_hits() {
+        let mut w = default_world();
+        let r = ray(point(0.0, 0.0, -5.0), vector(0.0, 0.0, 1.0));
+        let xs = w.intersect(r);
+        let comps = xs[0].prepare_computations(r, Some(xs.clone()));
+        assert!(comps.reflectv.get().is_none());
+        w.reflected_color(&comps);
+        assert!(comps.reflectv.get().is_none());
+
+        w.objects[0].material.reflective = 0.5;
+        let xs = w.intersect(r);
+        let comps = xs[0].prepare_computations(r, Some(xs.clone()));
+        assert!(comps.reflectv.get().is_none());
+        w.reflected_color(&comps);
+        assert!(comps.reflectv.get().is_some());
+    }
+
+    #[test]
+    fn points_beyond_a_lights_range_get_ambient_light_and_no_shadow_rays() {
+        use crate::metrics::CountingMetrics;
+        let mut c = crate::camera::Camera::new(11, 11, PI / 2.0);
+        c.set_transform(crate::transformations::view_transform(
+            point(0.0, 0.0, -5.0),
+            point(0.0, 0.0, 0.0),
+            vector(0.0, 1.0, 0.0),
+        ));
+        let counting = Arc::new(CountingMetrics::new());
+        let mut w = default_world();
+        w.options = w.options.with_metrics(counting.clone());
+        let full = render_pixels(&c, &w);
+
+        // The light is about 17 units from the sphere, so 100 is no limit.
+        let light = w.light.take().unwrap();
+        w.light = Some(light.clone().with_max_range(100.0));
+        assert_eq!(render_pixels(&c, &w).to_ppm(), full.to_ppm());
+
+        counting.reset();
+        w.light = Some(light.with_max_range(10.0));
+        let dark = render_pixels(&c, &w);
+        assert_eq!(counting.get(MetricKey::ShadowRays), 0);
+        let ambient = w.objects[0].material.color * w.objects[0].material.ambient;
+        crate::check_colors!(dark.pixel_at(5, 5), ambient);
+        assert!(w.is_shadowed(point(0.0, 0.0, -1.0)));
+    }
+
+    // A matte floor lit from straight above, seen straight down at `x`.
+    fn floor_brightness(normal_map: Option<Arc<dyn crate::patterns::Pattern>>, x: Float) -> Float {
+        let mut w = WorldBuilder::with_light(point_light(
+            point(0.0, 10.0, 0.0),
+            Color::new(1.0, 1.0, 1.0),
+        ));
+        let mut floor = Plane::new();
+        floor.material.specular = 0.0;
+        floor.material.normal_map = normal_map;
+        w.planes.push(floor);
+        w.color_at(Ray::new(point(x, 1.0, 0.0), vector(0.0, -1.0, 0.0)))
+            .red
+    }
+
+    #[test]
+    fn normal_maps_bend_the_shading_normal() {
+        // Stripes alternate between a tilted normal and an untouched one.
+        let bumps =
+            crate::patterns::stripe_pattern(Color::new(0.9, 0.5, 0.6), Color::new(0.5, 0.5, 1.0));
+        let bumps: Arc<dyn crate::patterns::Pattern> = Arc::new(bumps);
+        let flat = (floor_brightness(None, 0.25), floor_brightness(None, 1.25));
+        let mapped = (
+            floor_brightness(Some(bumps.clone()), 0.25),
+            floor_brightness(Some(bumps), 1.25),
+        );
+        assert!((flat.0 - flat.1).abs() < 0.01);
+        assert!(mapped.0 < mapped.1 - 0.1);
+        assert_eq!(mapped.1, flat.1);
+    }
+
+    #[test]
+    fn a_normal_map_pointing_straight_out_changes_nothing() {
+        let mut c = crate::camera::Camera::new(11, 11, PI / 2.0);
+        c.set_transform(crate::transformations::view_transform(
+            point(0.0, 0.0, -5.0),
+            point(0.0, 0.0, 0.0),
+            vector(0.0, 1.0, 0.0),
+        ));
+        let mut w = default_world();
+        let mut floor = Plane::new();
+        floor.set_transform(translation(0.0, -1.0, 0.0)).unwrap();
+        w.planes.push(floor);
+        let plain = render_pixels(&c, &w);
+        let out = Color::new(0.5, 0.5, 1.0);
+        let map: Arc<dyn crate::patterns::Pattern> =
+            Arc::new(crate::patterns::stripe_pattern(out, out));
+        w.objects[0].material.normal_map = Some(map.clone());
+        w.planes[0].material.normal_map = Some(map);
+        assert_eq!(render_pixels(&c, &w).to_ppm(), plain.to_ppm());
+    }
+
+    #[test]
+    fn shadow_rays_leave_from_above_a_normal_mapped_surface() {
+        let mut w = WorldBuilder::with_light(point_light(
+            point(0.0, 10.0, 0.0),
+            Color::new(1.0, 1.0, 1.0),
+        ));
+        // Bent past the tangent, so the shading normal points into the floor.
+        let steep = Color::new(1.0, 0.5, 0.4);
+        let mut floor = Plane::new();
+        floor.material.normal_map = Some(Arc::new(crate::patterns::stripe_pattern(steep, steep)));
+        w.planes.push(floor);
+        let r = Ray::new(point(0.0, 1.0, 0.0), vector(0.0, -1.0, 0.0));
+        let xs = w.intersect(r);
+        let comps = xs[0].prepare_computations(r, Some(xs.clone()));
+        assert!(comps.normalv.y < 0.0);
+        assert_eq!(comps.geometric_normalv, vector(0.0, 1.0, 0.0));
+        let (over_point, transmitted) = w.light_reaching(&comps, w.light.as_ref().unwrap());
+        assert!(over_point.y > 0.0);
+        assert_eq!(transmitted, Color::new(1.0, 1.0, 1.0));
+    }
+
+    fn default_world_camera() -> crate::camera::Camera {
+        let mut c = crate::camera::Camera::new(11, 11, PI / 2.0);
+        c.set_transform(crate::transformations::view_transform(
+            point(0.0, 0.0, -5.0),
+            point(0.0, 0.0, 0.0),
+            vector(0.0, 1.0, 0.0),
+        ));
+        c
+    }
+
+    #[test]
+    fn a_near_clip_sees_out_of_a_sphere_around_the_camera() {
+        let mut c = default_world_camera();
+        let mut w = default_world();
+        let plain = render_pixels(&c, &w);
+        let dome =
+            Sphere::with_transform(translation(0.0, 0.0, -5.0) * scaling(3.0, 3.0, 3.0)).unwrap();
+        w.objects.push(dome);
+        assert_ne!(render_pixels(&c, &w).pixel_at(5, 5), plain.pixel_at(5, 5));
+        // Every ray leaves the dome 3 units out.
+        c.near = 3.5;
+        assert_eq!(render_pixels(&c, &w).to_ppm(), plain.to_ppm());
+    }
+
+    #[test]
+    fn a_far_clip_shows_the_background_beyond_it() {
+        let mut c = default_world_camera();
+        let mut w = default_world();
+        let sky = Color::new(0.2, 0.4, 0.8);
+        w.options.background = sky.into();
+        // The outer sphere's near side is 4 units from the camera.
+        c.far = Some(3.0);
+        assert_eq!(render_pixels(&c, &w).pixel_at(5, 5), sky);
+        c.far = Some(4.5);
+        crate::check_colors!(
+            render_pixels(&c, &w).pixel_at(5, 5),
+            Color::new(0.38066, 0.47583, 0.2855)
+        );
     }
 }
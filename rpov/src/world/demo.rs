@@ -0,0 +1,180 @@
+use std::sync::Arc;
+
+use crate::{
+    camera::Camera,
+    canvas::Canvas,
+    colors::{COLOR_RED, COLOR_WHITE, Color},
+    floats::{Float, PI},
+    intersections::hit,
+    lighting::{ShadingContext, point_light, shade_surface},
+    matrices::{Matrix4, TransformError},
+    patterns::{PatternSpace, StripePattern},
+    planes::Plane,
+    rays::rays_toward_plane,
+    shapes::{
+        Intersectable,
+        prefab::{glass_sphere, matte_plane, matte_sphere, mirror_sphere},
+    },
+    spheres::Sphere,
+    transformations::{rotation_x, rotation_y, rotation_z, scaling, translation, view_transform},
+    tuples::{Tuple4, point, vector},
+    world::WorldBuilder,
+};
+
+pub const CORNELL_RED: Color = Color {
+    red: 0.63,
+    green: 0.06,
+    blue: 0.05,
+};
+pub const CORNELL_GREEN: Color = Color {
+    red: 0.14,
+    green: 0.45,
+    blue: 0.09,
+};
+pub const CORNELL_WHITE: Color = Color {
+    red: 0.73,
+    green: 0.73,
+    blue: 0.73,
+};
+
+// `plane` moved into place by `transform`, which must be invertible.
+fn placed(mut plane: Plane, transform: Matrix4) -> Plane {
+    plane
+        .set_transform(transform)
+        .expect("scene transforms are invertible");
+    plane
+}
+
+// `sphere` centered on `center` with the given radius, which must not be 0.
+fn moved_to(mut sphere: Sphere, center: Tuple4, radius: Float) -> Sphere {
+    let transform = translation(center.x, center.y, center.z) * scaling(radius, radius, radius);
+    sphere
+        .set_transform(transform)
+        .expect("scene transforms are invertible");
+    sphere
+}
+
+// The five walls of a 2x2x2 room standing on the floor at y = 0, centered on
+// the y axis and open towards -z: floor, ceiling, back wall, then the walls at
+// x = -1 and x = 1.
+pub fn room(left: Color, right: Color, rest: Color) -> Vec<Plane> {
+    let floor = matte_plane(rest);
+    let ceiling = placed(matte_plane(rest), translation(0.0, 2.0, 0.0));
+    let back = placed(
+        matte_plane(rest),
+        translation(0.0, 0.0, 1.0) * rotation_x(PI / 2.0),
+    );
+    let left_wall = placed(
+        matte_plane(left),
+        translation(-1.0, 0.0, 0.0) * rotation_z(PI / 2.0),
+    );
+    let right_wall = placed(
+        matte_plane(right),
+        translation(1.0, 0.0, 0.0) * rotation_z(PI / 2.0),
+    );
+    vec![floor, ceiling, back, left_wall, right_wall]
+}
+
+// A room after the Cornell box: red wall on the left, green on the right, two
+// white spheres on the floor and a light just below the ceiling. The camera
+// looks in through the open side and renders `size` pixels square.
+pub fn cornell_box(size: usize) -> (WorldBuilder, Camera) {
+    let mut w =
+        WorldBuilder::with_light(point_light(point(0.0, 1.9, 0.0), Color::new(1.0, 1.0, 1.0)));
+    w.planes = room(CORNELL_RED, CORNELL_GREEN, CORNELL_WHITE);
+
+    let mut tall = moved_to(Sphere::new(), point(-0.4, 0.45, 0.4), 0.45);
+    tall.material.color = CORNELL_WHITE;
+    let mut short = moved_to(Sphere::new(), point(0.45, 0.3, -0.4), 0.3);
+    short.material.color = CORNELL_WHITE;
+    w.objects = vec![tall, short];
+
+    let mut c = Camera::new(size, size, PI / 4.0);
+    c.set_transform(view_transform(
+        point(0.0, 1.0, -3.4),
+        point(0.0, 1.0, 0.0),
+        vector(0.0, 1.0, 0.0),
+    ));
+    (w, c)
+}
+
+// Chapter 5's closing exercise: the silhouette of a unit sphere under
+// `transform`, in red, cast onto a wall `pixels` square. Fails when the
+// transform can't be inverted.
+pub fn silhouette(transform: Matrix4, pixels: usize) -> Result<Canvas, TransformError> {
+    let mut c = Canvas::new(pixels, pixels);
+    let shape = Sphere::with_transform(transform)?;
+    for (x, y, r) in rays_toward_plane(point(0.0, 0.0, -5.0), -10.0, 7.0, pixels) {
+        if !shape.intersect(r).is_empty() {
+            c.write_pixel(x, y, COLOR_RED);
+        }
+    }
+    Ok(c)
+}
+
+// Chapter 6's closing exercise: a striped unit sphere lit with the Phong
+// model, drawn without a camera or shadows onto a wall `pixels` square.
+pub fn phong_sphere(pixels: usize) -> Canvas {
+    let mut c = Canvas::new(pixels, pixels);
+    let mut shape = Sphere::new();
+    shape.material.color = Color::new(1.0, 0.2, 1.0);
+    shape.material.pattern = Some(Arc::new(StripePattern {
+        a: Color::new(1.0, 0.2, 1.0),
+        b: Color::new(0.2, 1.0, 1.0),
+        transform: rotation_y(0.6),
+        space: PatternSpace::Object,
+    }));
+    let light = point_light(point(-10.0, 10.0, -10.0), COLOR_WHITE);
+
+    for (x, y, r) in rays_toward_plane(point(0.0, 0.0, -5.0), 10.0, 7.0, pixels) {
+        let xs = shape.intersect(r);
+        let Some(hit) = hit(&xs) else {
+            continue;
+        };
+        let point = r.position(hit.t);
+        let ctx = ShadingContext {
+            point,
+            eyev: -r.direction,
+            normalv: hit.object.normal_at(&point),
+            light: &light,
+            attenuation: COLOR_WHITE,
+            object: &shape,
+        };
+        c.write_pixel(x, y, shade_surface(&ctx, hit.object.material()));
+    }
+    c
+}
+
+// Chapter 7's closing scene: three spheres in the corner of two walls and a
+// floor, seen by a camera of `hsize` x `vsize` pixels. The book's spheres
+// are all matte; here the small ones are a mirror and glass.
+pub fn chapter7_scene(hsize: usize, vsize: usize) -> (WorldBuilder, Camera) {
+    let wall_color = Color::new(1.0, 0.9, 0.9);
+    let floor = matte_plane(wall_color);
+
+    let wall = |angle| {
+        let transform = translation(0.0, 0.0, 5.0) * rotation_y(angle) * rotation_x(PI / 2.0);
+        placed(matte_plane(wall_color), transform)
+    };
+    let (left_wall, right_wall) = (wall(-PI / 4.0), wall(PI / 4.0));
+
+    let middle = moved_to(
+        matte_sphere(Color::new(0.1, 1.0, 0.5)),
+        point(-0.5, 1.0, 0.5),
+        1.0,
+    );
+    let right = moved_to(mirror_sphere(), point(1.5, 0.5, -0.5), 0.5);
+    let left = moved_to(glass_sphere(), point(-1.5, 0.33, -0.75), 0.33);
+
+    let mut w = WorldBuilder::with_light(point_light(point(-10.0, 10.0, -10.0), COLOR_WHITE));
+    w.objects = vec![middle, right, left];
+    w.planes = vec![floor, left_wall, right_wall];
+
+    let mut c = Camera::new(hsize, vsize, PI / 3.0);
+    c.set_transform(view_transform(
+        point(0.0, 1.5, -5.0),
+        point(0.0, 1.0, 0.0),
+        vector(0.0, 1.0, 0.0),
+    ));
+    (w, c)
+}
@@ -0,0 +1,143 @@
+use std::sync::Arc;
+
+use crate::{
+    camera::Camera,
+    colors::Color,
+    floats::{Float, PI},
+    lighting::point_light,
+    materials::Material,
+    patterns::{checkers_pattern, gradient_pattern, ring_pattern, stripe_pattern},
+    planes::Plane,
+    spheres::Sphere,
+    transformations::{scaling, translation, view_transform},
+    tuples::{point, vector},
+    world::WorldBuilder,
+};
+
+// One tile of the gallery: a material and the name shown in its legend.
+#[derive(Debug, Clone)]
+pub struct Swatch {
+    pub name: String,
+    pub material: Material,
+}
+
+impl Swatch {
+    pub fn new(name: &str, material: Material) -> Self {
+        Self {
+            name: name.to_string(),
+            material,
+        }
+    }
+
+    // A sphere wearing this swatch's material, resting on a neutral grey plane.
+    pub fn world(&self) -> WorldBuilder {
+        let mut floor = Plane::new();
+        floor.material.color = Color::new(0.5, 0.5, 0.5);
+        floor.material.specular = 0.0;
+
+        let mut ball = Sphere::new();
+        ball.set_transform(translation(0.0, 1.0, 0.0))
+            .expect("scene transforms are invertible");
+        ball.material = self.material.clone();
+
+        let mut w = WorldBuilder::with_light(point_light(
+            point(-5.0, 8.0, -6.0),
+            Color::new(1.0, 1.0, 1.0),
+        ));
+        w.objects.push(ball);
+        w.planes.push(floor);
+        w
+    }
+}
+
+// A camera framing the sphere in Swatch::world.
+pub fn swatch_camera(size: usize) -> Camera {
+    let mut c = Camera::new(size, size, PI / 4.0);
+    c.set_transform(view_transform(
+        point(0.0, 2.0, -4.5),
+        point(0.0, 0.9, 0.0),
+        vector(0.0, 1.0, 0.0),
+    ));
+    c
+}
+
+pub fn material_swatches() -> Vec<Swatch> {
+    let red = Color::new(0.9, 0.2, 0.2);
+    let white = Color::new(0.9, 0.9, 0.9);
+    let small = scaling(0.25, 0.25, 0.25);
+
+    let mut swatches = vec![Swatch::new("default", Material::new())];
+
+    let mut stripes = stripe_pattern(red, white);
+    stripes.transform = small;
+    let mut gradient = gradient_pattern(red, white);
+    gradient.transform = translation(-1.0, 0.0, 0.0) * scaling(2.0, 1.0, 1.0);
+    let mut rings = ring_pattern(red, white);
+    rings.transform = small;
+    let mut checkers = checkers_pattern(red, white);
+    checkers.transform = small;
+    let patterned: [(&str, Arc<dyn crate::patterns::Pattern>); 4] = [
+        ("stripes", Arc::new(stripes)),
+        ("gradient", Arc::new(gradient)),
+        ("rings", Arc::new(rings)),
+        ("checkers", Arc::new(checkers)),
+    ];
+    for (name, pattern) in patterned {
+        swatches.push(Swatch::new(
+            name,
+            Material {
+                pattern: Some(pattern),
+                ..Material::new()
+            },
+        ));
+    }
+
+    for step in 1..=4 {
+        let reflective = step as Float * 0.25;
+        swatches.push(Swatch::new(
+            &format!("reflective {reflective}"),
+            Material {
+                color: Color::new(0.2, 0.2, 0.3),
+                reflective,
+                ..Material::new()
+            },
+        ));
+    }
+
+    swatches.push(Swatch::new(
+        "glass",
+        Material {
+            color: Color::new(0.1, 0.1, 0.1),
+            diffuse: 0.1,
+            reflective: 0.9,
+            transparency: 0.9,
+            refractive_index: 1.5,
+            ..Material::new()
+        },
+    ));
+    swatches
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn swatch_names_are_unique() {
+        let swatches = material_swatches();
+        let mut names: Vec<_> = swatches.iter().map(|s| s.name.clone()).collect();
+        names.sort();
+        names.dedup();
+        assert_eq!(names.len(), swatches.len());
+    }
+
+    #[test]
+    fn the_swatch_camera_sees_the_sphere() {
+        let swatch = &material_swatches()[0];
+        let w = swatch.world();
+        let c = swatch_camera(11);
+        let center = w.color_at(c.ray_for_pixel(5, 5));
+        let corner = w.color_at(c.ray_for_pixel(0, 0));
+        assert_ne!(center, corner);
+    }
+}
@@ -1,89 +1,18 @@
 mod test {
-
-    use std::sync::Arc;
-
-    use indicatif::{ProgressBar, ProgressStyle};
-    use num_traits::ToPrimitive;
     use rpov::{
-        canvas::Canvas,
-        intersections::hit,
-        lighting::{lighting, point_light},
-        shapes::Intersectable,
-        spheres::Sphere,
-        tuples::{Tuple4, point},
+        colors::{COLOR_BLACK, Color},
+        world::demo::phong_sphere,
     };
 
-    fn run_example(name: &str, canvas_pixels: usize) {
-        let ray_origin = point(0.0, 0.0, -5.0);
-        let wall_z = 10.0;
-        let wall_size = 7.0;
-        let pixel_size = wall_size / (canvas_pixels.to_f32().unwrap());
-        let half = wall_size / 2.0;
-        let mut c = Canvas::new(canvas_pixels, canvas_pixels);
-        let mut shape = Sphere::new();
-        shape.material.color = rpov::colors::Color::new(1.0, 0.2, 1.0);
-        shape.material.pattern = Some(Arc::new(rpov::patterns::StripePattern {
-            a: rpov::colors::Color::new(1.0, 0.2, 1.0),
-            b: rpov::colors::Color::new(0.2, 1.0, 1.0),
-            transform: rpov::transformations::rotation_y(0.6),
-        }));
-
-        let light_position = point(-10.0, 10.0, -10.0);
-        let light_color = rpov::colors::Color::new(1.0, 1.0, 1.0);
-        let light = point_light(light_position, light_color);
-
-        let path = format!("tests/out-ch6-{name}-{canvas_pixels}x{canvas_pixels}.ppm");
-        let mut found = 0;
-        let bar = ProgressBar::new(canvas_pixels as u64);
-        bar.set_style(ProgressStyle::with_template("{spinner:.green} [{elapsed_precise}] [{wide_bar:.cyan/blue}] {pos}/{len} ({eta}) {msg}")
-            .unwrap()
-            .progress_chars("#>- "));
-        bar.set_message(format!("Rendering {path}"));
-        for y in 0..canvas_pixels {
-            bar.inc(1);
-            let world_y = half - pixel_size * y.to_f32().unwrap();
-            for x in 0..canvas_pixels {
-                let world_x = -half + pixel_size * x.to_f32().unwrap();
-                let position: Tuple4 = point(world_x.into(), world_y.into(), wall_z);
-                let r = rpov::rays::ray(ray_origin, (position - ray_origin).normalize());
-                let intersections = shape.intersect(r);
-
-                let i = hit(&intersections);
-                if i.is_none() {
-                    continue;
-                }
-                found += 1;
-                let hit = i.unwrap();
-                let point = r.position(hit.t);
-                let normal = hit.object.normal_at(&point);
-                let eye = -r.direction;
-                let in_shadow = false;
-                let color = lighting(
-                    hit.object.material(),
-                    &shape,
-                    &light,
-                    point,
-                    eye,
-                    normal,
-                    in_shadow,
-                );
-                c.write_pixel(x, y, color);
-            }
-        }
-        bar.finish_with_message(format!("Rendering {path} complete!"));
-
-        std::fs::write(&path, c.to_ppm()).expect("Unable to write file");
-        assert!(found > 0, "No intersections found");
-    }
-
+    // The full-size render is `cargo run --example ch6_phong_sphere`.
     #[test]
     fn ch6_putting_it_together() {
-        run_example("identity", 50);
-    }
-
-    #[test]
-    #[cfg(not(debug_assertions))]
-    fn release_generation() {
-        run_example("release_", 1600);
+        let c = phong_sphere(20);
+        assert_eq!(c.pixel_at(0, 0), COLOR_BLACK);
+        // Lit from the upper left, so brighter there than lower right.
+        let lit = c.pixel_at(7, 7);
+        let dim = c.pixel_at(13, 13);
+        assert!(lit.red + lit.green + lit.blue > dim.red + dim.green + dim.blue);
+        rpov::check_colors!(c.pixel_at(10, 10), Color::new(0.53043, 0.10609, 0.53043));
     }
 }
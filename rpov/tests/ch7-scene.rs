@@ -1,89 +1,38 @@
-use rpov::camera::Camera;
+use rpov::canvas::Canvas;
 use rpov::colors::Color;
-use rpov::floats::consts::PI;
-use rpov::lighting::point_light;
 use rpov::materials::Material;
-use rpov::spheres::Sphere;
-use rpov::transformations::{rotation_x, rotation_y, scaling, translation, view_transform};
-use rpov::tuples::{point, vector};
-use rpov::world::{World, render};
+use rpov::world::{demo::chapter7_scene, render};
 
 mod tests {
     use super::*;
 
-    fn render_scenario_7(hs: usize, vs: usize) {
-        let mut floor = Sphere::new();
-        floor.transform = scaling(10.0, 0.01, 10.0);
-        let mut floor_material = Material::new();
-        floor_material.color = Color::new(1.0, 0.9, 0.9);
-        floor_material.specular = 0.0;
-        floor.material = floor_material.clone();
-
-        let mut left_wall = Sphere::new();
-        left_wall.transform = translation(0.0, 0.0, 5.0)
-            * rotation_y(-PI / 4.0)
-            * rotation_x(PI / 2.0)
-            * scaling(10.0, 0.01, 10.0);
-        left_wall.material = floor_material.clone();
-
-        let mut right_wall = Sphere::new();
-        right_wall.transform = translation(0.0, 0.0, 5.0)
-            * rotation_y(PI / 4.0)
-            * rotation_x(PI / 2.0)
-            * scaling(10.0, 0.01, 10.0);
-        right_wall.material = floor_material.clone();
-
-        let mut middle = Sphere::new();
-        middle.transform = translation(-0.5, 1.0, 0.5);
-        let mut middle_material = Material::new();
-        middle_material.color = Color::new(0.1, 1.0, 0.5);
-        middle_material.diffuse = 0.7;
-        middle_material.specular = 0.3;
-        middle.material = middle_material;
-
-        let mut right = Sphere::new();
-        right.transform = translation(1.5, 0.5, -0.5) * scaling(0.5, 0.5, 0.5);
-        let mut right_material = Material::new();
-        right_material.color = Color::new(0.5, 1.0, 0.1);
-        right_material.diffuse = 0.7;
-        right_material.specular = 0.3;
-        right.material = right_material;
-
-        let mut left = Sphere::new();
-        left.transform = translation(-1.5, 0.33, -0.75) * scaling(0.33, 0.33, 0.33);
-        let mut left_material = Material::new();
-        left_material.color = Color::new(1.0, 0.8, 0.1);
-        left_material.diffuse = 0.7;
-        left_material.specular = 0.3;
-        left.material = left_material;
-
-        let mut world = World::new();
-        world.objects = vec![floor, left_wall, right_wall, middle, right, left];
-        world.light = Some(point_light(
-            point(-10.0, 10.0, -10.0),
-            Color::new(1.0, 1.0, 1.0),
-        ));
-
-        let mut camera = Camera::new(hs, vs, PI / 3.0);
-        camera.transform = view_transform(
-            point(0.0, 1.5, -5.0),
-            point(0.0, 1.0, 0.0),
-            vector(0.0, 1.0, 0.0),
-        );
-
-        let canvas = render(camera, world);
-        let ppm = canvas.to_ppm();
-        let path = format!("tests/out-ch7-scene-{hs}x{vs}.ppm");
-        std::fs::write(path, ppm).unwrap();
+    // With share_walls, the floor and both walls use one shared material
+    // instead of a copy each.
+    fn render_scenario_7_canvas(hs: usize, vs: usize, share_walls: bool) -> Canvas {
+        let (mut world, camera) = chapter7_scene(hs, vs);
+        if share_walls {
+            let shared = world.planes[0].material.clone().shared();
+            for wall in world.planes.iter_mut() {
+                wall.material = Material::new();
+                wall.shared_material = Some(shared.clone());
+            }
+        }
+        render(camera, world)
     }
+
+    // The full-size render is `cargo run --release --example ch7_scene`.
     #[test]
     fn scenario_7() {
-        render_scenario_7(100, 50);
+        let c = render_scenario_7_canvas(20, 10, false);
+        // The lit side of the middle sphere, with the default diffuse of 0.9.
+        rpov::check_colors!(c.pixel_at(6, 3), Color::new(0.09779, 0.97792, 0.48896));
     }
 
     #[test]
-    #[cfg(not(debug_assertions))]
-    fn release_generation() {
-        render_scenario_7(3200, 1600);
+    fn sharing_the_wall_material_renders_the_same() {
+        assert_eq!(
+            render_scenario_7_canvas(40, 20, true).to_ppm(),
+            render_scenario_7_canvas(40, 20, false).to_ppm()
+        );
     }
 }
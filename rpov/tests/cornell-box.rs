@@ -0,0 +1,46 @@
+use rpov::colors::Color;
+use rpov::tuples::point;
+use rpov::world::demo::cornell_box;
+use rpov::world::render;
+
+mod tests {
+    use super::*;
+
+    fn is_mostly_red(c: Color) -> bool {
+        c.red > 2.0 * c.green && c.red > 2.0 * c.blue
+    }
+
+    fn is_mostly_green(c: Color) -> bool {
+        c.green > 2.0 * c.red && c.green > 2.0 * c.blue
+    }
+
+    fn is_grey(c: Color) -> bool {
+        (c.red - c.green).abs() < 0.01 && (c.green - c.blue).abs() < 0.01
+    }
+
+    #[test]
+    fn the_cornell_box_has_colored_walls_and_shadows() {
+        let (w, c) = cornell_box(80);
+        let image = render(c.clone(), w);
+        let at = |x, y, z| {
+            let (px, py) = c.project_point(point(x, y, z)).unwrap();
+            image.pixel_at(px, py)
+        };
+
+        // The red wall is on the left of the image and the green one on the
+        // right, with the back wall, floor and ceiling white between them.
+        let left = at(-1.0, 1.2, 0.0);
+        let right = at(1.0, 1.2, 0.0);
+        assert!(is_mostly_red(left), "{left}");
+        assert!(is_mostly_green(right), "{right}");
+        for white in [at(0.0, 1.0, 1.0), at(0.0, 0.0, -0.8), at(0.0, 2.0, 0.5)] {
+            assert!(is_grey(white), "{white}");
+        }
+
+        // The short sphere throws its shadow towards the camera, onto floor
+        // that is otherwise as well lit as the same spot mirrored across x.
+        let shadowed = at(0.53, 0.0, -0.48);
+        let lit = at(-0.53, 0.0, -0.48);
+        assert!(shadowed.red < lit.red * 0.5, "{shadowed} {lit}");
+    }
+}
@@ -0,0 +1,142 @@
+use std::collections::HashSet;
+
+use rpov::camera::Camera;
+use rpov::colors::Color;
+use rpov::floats::{Float, consts::PI};
+use rpov::intersections::Intersection;
+use rpov::lighting::point_light;
+use rpov::materials::Material;
+use rpov::matrices::Matrix4;
+use rpov::patterns::stripe_pattern;
+use rpov::planes::{Plane, WavyPlane};
+use rpov::rays::Ray;
+use rpov::shapes::{Intersectable, ShapeFunctions, next_shape_id, prefab::matte_plane};
+use rpov::spheres::Sphere;
+use rpov::transformations::{scaling, translation, view_transform};
+use rpov::tuples::{Tuple4, point, vector};
+use rpov::warnings::Warning;
+use rpov::world::{WorldBuilder, render_light_coverage, render_pixels};
+
+mod tests {
+    use super::*;
+
+    fn camera() -> Camera {
+        let mut c = Camera::new(24, 16, PI / 3.0);
+        c.set_transform(view_transform(
+            point(0.0, 1.5, -5.0),
+            point(0.0, 0.5, 0.0),
+            vector(0.0, 1.0, 0.0),
+        ));
+        c
+    }
+
+    // A shape whose intersection routine has gone wrong: every ray meets it
+    // at a NaN t.
+    #[derive(Debug, Clone)]
+    struct NanShape {
+        id: u64,
+        material: Material,
+        tags: HashSet<String>,
+    }
+
+    impl ShapeFunctions for NanShape {
+        fn id(&self) -> u64 {
+            self.id
+        }
+
+        fn tags(&self) -> &HashSet<String> {
+            &self.tags
+        }
+
+        fn transform(&self) -> Matrix4 {
+            Matrix4::identity()
+        }
+
+        fn transform_inverse(&self) -> Matrix4 {
+            Matrix4::identity()
+        }
+
+        fn material(&self) -> &Material {
+            &self.material
+        }
+
+        fn local_normal_at(&self, _local_point: &Tuple4) -> Tuple4 {
+            vector(0.0, 1.0, 0.0)
+        }
+    }
+
+    impl Intersectable<NanShape> for NanShape {
+        fn local_intersect<'a>(&'a self, _local_ray: Ray) -> Vec<Intersection<'a>> {
+            vec![Intersection::new(Float::NAN, self)]
+        }
+    }
+
+    // A floor with a stripe pattern squashed flat, a shape that is only ever
+    // hit at NaN, and a light sitting on the surface of an ordinary sphere.
+    fn broken_world() -> WorldBuilder {
+        let mut w =
+            WorldBuilder::with_light(point_light(point(1.0, 0.5, 0.0), Color::new(1.0, 1.0, 1.0)));
+
+        let mut floor = matte_plane(Color::new(0.8, 0.8, 0.8));
+        let mut stripes = stripe_pattern(Color::new(1.0, 1.0, 1.0), Color::new(0.0, 0.0, 0.0));
+        stripes.transform = scaling(0.0, 1.0, 1.0);
+        floor.material.pattern = Some(std::sync::Arc::new(stripes));
+        w.planes.push(floor);
+
+        // No built-in shape takes a NaN transform.
+        let nan_transform = Matrix4::from([[Float::NAN; 4]; 4]);
+        assert!(Plane::new().set_transform(nan_transform).is_err());
+        assert!(WavyPlane::new(vec![]).set_transform(nan_transform).is_err());
+        assert!(Sphere::with_transform(nan_transform).is_err());
+        w.shapes.push(Box::new(NanShape {
+            id: next_shape_id(),
+            material: Material::new(),
+            tags: HashSet::new(),
+        }));
+
+        w.objects.push(
+            Sphere::with_transform(translation(0.0, 0.5, 0.0) * scaling(0.5, 0.5, 0.5)).unwrap(),
+        );
+        w.objects
+            .push(Sphere::with_transform(translation(-1.5, 1.0, 0.5)).unwrap());
+        w
+    }
+
+    #[test]
+    fn a_pathological_scene_renders_to_completion() {
+        let w = broken_world();
+        let image = render_pixels(&camera(), &w);
+        assert_eq!((image.width, image.height), (24, 16));
+        assert!(w.options.warnings.count(Warning::SingularPatternTransform) > 0);
+        assert!(w.options.warnings.count(Warning::NanIntersection) > 0);
+        assert_eq!(w.options.warnings.count(Warning::NoLight), 0);
+        // Another world's render starts from nothing.
+        assert_eq!(WorldBuilder::new().options.warnings.report(), None);
+    }
+
+    #[test]
+    fn the_light_coverage_pass_counts_its_warnings() {
+        let scene = broken_world().build();
+        render_light_coverage(&camera(), &scene);
+        assert!(scene.options.warnings.count(Warning::NanIntersection) > 0);
+    }
+
+    #[test]
+    fn a_world_without_a_light_renders_its_ambient_color() {
+        let mut w = broken_world();
+        w.light = None;
+        let image = render_pixels(&camera(), &w);
+        assert!(w.options.warnings.count(Warning::NoLight) > 0);
+        let (x, y) = camera().project_point(point(-1.5, 1.0, -0.5)).unwrap();
+        // The default material's ambient term under a white light.
+        assert_eq!(image.pixel_at(x, y), Color::new(0.1, 0.1, 0.1));
+    }
+
+    #[test]
+    fn rays_without_a_direction_are_never_made() {
+        assert!(Ray::try_new(point(0.0, 0.0, 0.0), vector(0.0, 0.0, 0.0)).is_none());
+        assert!(Ray::try_new(point(0.0, 0.0, 0.0), vector(Float::NAN, 0.0, 1.0)).is_none());
+        let r = Ray::try_new(point(0.0, 0.0, 0.0), vector(0.0, 0.0, 2.0)).unwrap();
+        assert_eq!(r.direction, vector(0.0, 0.0, 1.0));
+    }
+}
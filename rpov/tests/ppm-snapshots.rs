@@ -0,0 +1,108 @@
+// The PPM writer's line wrapping, rounding and trailing newline, checked
+// against files in tests/snapshots. After changing the format on purpose,
+// run the tests with UPDATE_SNAPSHOTS=1 to rewrite the snapshots, then
+// review the diff before committing them.
+use std::{env, fs, path::PathBuf};
+
+use rpov::camera::Camera;
+use rpov::canvas::Canvas;
+use rpov::colors::Color;
+use rpov::floats::{Float, PI};
+use rpov::patterns::{Pattern, checkers_pattern};
+use rpov::transformations::view_transform;
+use rpov::tuples::{point, vector};
+use rpov::world::{default_world, render_pixels};
+use sha2::{Digest, Sha256};
+
+fn snapshot_path(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/snapshots")
+        .join(name)
+}
+
+// Compares `actual` with the named snapshot, or rewrites the snapshot when
+// UPDATE_SNAPSHOTS is set.
+fn assert_snapshot(name: &str, actual: &str) {
+    let path = snapshot_path(name);
+    if env::var_os("UPDATE_SNAPSHOTS").is_some() {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, actual).unwrap();
+        return;
+    }
+    let expected = fs::read_to_string(&path).unwrap_or_else(|e| {
+        panic!(
+            "can't read snapshot {}: {e}\n\
+             Run with UPDATE_SNAPSHOTS=1 to create it.",
+            path.display()
+        )
+    });
+    if actual != expected {
+        let line = actual
+            .lines()
+            .zip(expected.lines())
+            .position(|(a, e)| a != e)
+            .unwrap_or_else(|| actual.lines().count().min(expected.lines().count()));
+        panic!(
+            "output differs from snapshot {} from line {}.\n\
+             If the change is intended, rerun with UPDATE_SNAPSHOTS=1 and \
+             review the snapshot diff before committing it.",
+            path.display(),
+            line + 1
+        );
+    }
+}
+
+// Canvas::to_ppm scenario: Constructing the PPM pixel data.
+#[test]
+fn book_example_ppm() {
+    let mut c = Canvas::new(5, 3);
+    c.write_pixel(0, 0, Color::new(1.5, 0.0, 0.0));
+    c.write_pixel(2, 1, Color::new(0.0, 0.5, 0.0));
+    c.write_pixel(4, 2, Color::new(-0.5, 0.0, 1.0));
+    assert_snapshot("book_example_5x3.ppm", &c.to_ppm());
+}
+
+// Rows long enough to wrap, with values that land between the steps of 255.
+#[test]
+fn gradient_ppm() {
+    let (width, height) = (17, 4);
+    let mut c = Canvas::new(width, height);
+    for y in 0..height {
+        for x in 0..width {
+            let u = x as Float / (width - 1) as Float;
+            let v = y as Float / (height - 1) as Float;
+            c.write_pixel(x, y, Color::new(u, v, 1.0 - u * v));
+        }
+    }
+    assert_snapshot("gradient_17x4.ppm", &c.to_ppm());
+}
+
+#[test]
+fn checkerboard_ppm() {
+    let checkers = checkers_pattern(Color::new(1.0, 1.0, 1.0), Color::new(0.1, 0.2, 0.3));
+    let mut c = Canvas::new(8, 8);
+    for y in 0..8 {
+        for x in 0..8 {
+            let p = point(x as Float * 0.5, 0.0, y as Float * 0.5);
+            c.write_pixel(x, y, checkers.pattern_at(p));
+        }
+    }
+    assert_snapshot("checkerboard_8x8.ppm", &c.to_ppm());
+}
+
+// A whole render, kept as a hash rather than 50x25 pixels of text.
+#[test]
+fn default_world_render_hash() {
+    let mut camera = Camera::new(50, 25, PI / 2.0);
+    camera.set_transform(view_transform(
+        point(0.0, 0.0, -5.0),
+        point(0.0, 0.0, 0.0),
+        vector(0.0, 1.0, 0.0),
+    ));
+    let ppm = render_pixels(&camera, &default_world()).to_ppm();
+    let hash: String = Sha256::digest(ppm.as_bytes())
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect();
+    assert_snapshot("default_world_50x25.sha256", &format!("{hash}\n"));
+}
@@ -64,7 +64,7 @@ mod test {
             tick += 1;
         }
         c.write_pixel(c.width / 2, c.height / 2, COLOR_WHITE);
-        let path = format!("tests/out-projectile-{tick}.ppm");
-        std::fs::write(&path, c.to_ppm()).expect("Unable to write file");
+        assert_eq!(tick, 197);
+        assert_eq!(c.pixel_at(5, c.height - 12), red);
     }
 }
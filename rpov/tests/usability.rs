@@ -34,6 +34,14 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_display_round_trips_through_parse() {
+        let p = point(1.0, -2.0, 0.5);
+        assert_eq!(format!("{p}").parse::<rpov::tuples::Tuple4>().unwrap(), p);
+        let c = rpov::colors::Color::new(-0.5, 0.4, 1.7);
+        assert_eq!(format!("{c}").parse::<rpov::colors::Color>().unwrap(), c);
+    }
+
     #[test]
     fn test_matrix_identity_display() {
         let m = rpov::matrices::Matrix3::identity();